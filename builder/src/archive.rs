@@ -0,0 +1,250 @@
+use crate::backend::BuildSpec;
+use crate::build::{de_duplicate, ADDITIONAL_PACKAGE_BUILD_ARGS};
+use flate2::write::GzEncoder;
+use flate2::{Compression, GzBuilder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use utility::{Error, Result};
+
+/// modification time of every entry of a context archive, so the same files give the same archive
+pub const ARCHIVE_MTIME: u64 = 0;
+
+/// BuildManifest goes next to the context archive of a function and holds what the CLI would
+/// have passed to the build engine, for a remote builder to build the same image
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct BuildManifest {
+    pub function: String,
+    pub image: String,
+    pub language: String,
+    /// file name of the context archive, next to the manifest
+    pub context: String,
+    pub context_sha256: String,
+    pub build_args: BTreeMap<String, String>,
+    pub build_options: Vec<String>,
+    pub labels: BTreeMap<String, String>,
+    pub platforms: Vec<String>,
+}
+
+impl BuildManifest {
+    /// the packages of the build options end up in the ADDITIONAL_PACKAGE build arg, as in the
+    /// flags of the build engine. The proxies of the local environment are left out
+    pub fn new(
+        function: &str,
+        language: &str,
+        build_options: &[String],
+        spec: &BuildSpec,
+    ) -> BuildManifest {
+        let mut build_args: BTreeMap<String, String> = spec
+            .build_arg_map
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        if !spec.build_opt_packages.is_empty() {
+            build_args.insert(
+                ADDITIONAL_PACKAGE_BUILD_ARGS.to_string(),
+                de_duplicate(spec.build_opt_packages).join(" "),
+            );
+        }
+        BuildManifest {
+            function: function.to_string(),
+            image: spec.image.to_string(),
+            language: language.to_string(),
+            context: String::new(),
+            context_sha256: String::new(),
+            build_args,
+            build_options: build_options.to_vec(),
+            labels: spec
+                .build_label_map
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            platforms: spec
+                .platforms
+                .split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+        }
+    }
+}
+
+/// writes the build context as `<function>.tar.gz` and the manifest as `<function>.json` into the
+/// output folder, returns the path of the archive
+pub fn archive_build_context(
+    context: &str,
+    out_dir: &str,
+    mut manifest: BuildManifest,
+) -> Result<PathBuf> {
+    std::fs::create_dir_all(out_dir)?;
+    let archive = Path::new(out_dir).join(format!("{}.tar.gz", manifest.function));
+    manifest.context_sha256 = write_context_archive(Path::new(context), &archive)?;
+    manifest.context = format!("{}.tar.gz", manifest.function);
+
+    let content = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| Error::Custom(format!("can't serialize the build manifest: {}", e)))?;
+    std::fs::write(
+        Path::new(out_dir).join(format!("{}.json", manifest.function)),
+        content + "\n",
+    )?;
+    Ok(archive)
+}
+
+/// writes the folder as a reproducible tar.gz: sorted entries, fixed mtimes, owner root and
+/// modes reduced to 0755 or 0644. Returns the sha256 of the archive
+pub fn write_context_archive(context: &Path, archive: &Path) -> Result<String> {
+    let mut entries = Vec::new();
+    collect_entries(context, context, &mut entries).map_err(|e| {
+        Error::Custom(format!(
+            "can't read the build context {}: {}",
+            context.to_string_lossy(),
+            e
+        ))
+    })?;
+    entries.sort();
+
+    let encoder: GzEncoder<File> = GzBuilder::new()
+        .mtime(ARCHIVE_MTIME as u32)
+        .write(File::create(archive)?, Compression::default());
+    let mut tar = tar::Builder::new(encoder);
+    for (relative, path) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_mtime(ARCHIVE_MTIME);
+        header.set_uid(0);
+        header.set_gid(0);
+        if path.is_dir() {
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+            tar.append_data(&mut header, relative + "/", std::io::empty())?;
+        } else {
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_mode(if is_executable(&path) { 0o755 } else { 0o644 });
+            header.set_size(std::fs::metadata(&path)?.len());
+            tar.append_data(&mut header, relative, File::open(&path)?)?;
+        }
+    }
+    tar.into_inner()?.finish()?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(std::fs::read(archive)?);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn collect_entries(root: &Path, dir: &Path, entries: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        entries.push((relative.to_string_lossy().replace('\\', "/"), path.clone()));
+        if path.is_dir() {
+            collect_entries(root, &path, entries)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::Read;
+    use tempdir::TempDir;
+
+    fn write_context(root: &Path) {
+        std::fs::create_dir_all(root.join("function/lib")).unwrap();
+        std::fs::write(root.join("Dockerfile"), "FROM scratch\n").unwrap();
+        std::fs::write(root.join("function/lib/a.js"), "a").unwrap();
+        std::fs::write(root.join("function/handler.js"), "handler").unwrap();
+    }
+
+    #[test]
+    fn test_write_context_archive_is_reproducible() {
+        let dir = TempDir::new("context-archive").unwrap();
+        let first = dir.path().join("first");
+        write_context(&first);
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        let second = dir.path().join("second");
+        write_context(&second);
+
+        let first_sha = write_context_archive(&first, &dir.path().join("first.tar.gz")).unwrap();
+        let second_sha = write_context_archive(&second, &dir.path().join("second.tar.gz")).unwrap();
+        assert_eq!(first_sha, second_sha);
+
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(
+            File::open(dir.path().join("first.tar.gz")).unwrap(),
+        ));
+        let mut names = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            assert_eq!(entry.header().mtime().unwrap(), ARCHIVE_MTIME);
+            assert_eq!(entry.header().uid().unwrap(), 0);
+            let name = entry.path().unwrap().to_string_lossy().to_string();
+            if name == "function/handler.js" {
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+                assert_eq!(content, "handler");
+            }
+            names.push(name);
+        }
+        assert_eq!(
+            names,
+            vec![
+                "Dockerfile",
+                "function/",
+                "function/handler.js",
+                "function/lib/",
+                "function/lib/a.js"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_manifest() {
+        let mut build_args = HashMap::new();
+        build_args.insert("NPM_VERSION".to_string(), "0.2.2".to_string());
+        let mut labels = HashMap::new();
+        labels.insert("team".to_string(), "payments".to_string());
+        let packages = vec!["make".to_string(), "gcc".to_string(), "make".to_string()];
+        let spec = BuildSpec {
+            image: "user/fn:latest",
+            version: "",
+            no_cache: false,
+            squash: false,
+            http_proxy: "http://proxy:3128",
+            https_proxy: "",
+            build_arg_map: &build_args,
+            build_opt_packages: &packages,
+            build_label_map: &labels,
+            platforms: "linux/amd64, linux/arm64",
+            extra_tags: &vec![],
+        };
+
+        let manifest = BuildManifest::new("fn", "node14", &["dev".to_string()], &spec);
+
+        assert_eq!(manifest.image, "user/fn:latest");
+        assert_eq!(manifest.build_args.len(), 2);
+        assert_eq!(manifest.build_args["NPM_VERSION"], "0.2.2");
+        assert_eq!(
+            manifest.build_args[ADDITIONAL_PACKAGE_BUILD_ARGS],
+            de_duplicate(&packages).join(" ")
+        );
+        assert_eq!(manifest.build_options, vec!["dev".to_string()]);
+        assert_eq!(manifest.labels["team"], "payments");
+        assert_eq!(manifest.platforms, vec!["linux/amd64", "linux/arm64"]);
+    }
+}
@@ -0,0 +1,691 @@
+use crate::build::{build_flag_slice, de_duplicate, ADDITIONAL_PACKAGE_BUILD_ARGS};
+use std::collections::HashMap;
+use utility::{Error, Result};
+
+/// DEFAULT_ENGINE build engine used when none is set by --engine or the config file
+pub const DEFAULT_ENGINE: &str = "docker";
+
+/// a program and its arguments, run in the build context
+pub type EngineCommand = (&'static str, Vec<String>);
+
+/// BuildSpec holds what a build backend needs to build or publish the image of a function
+#[derive(Debug)]
+pub struct BuildSpec<'s> {
+    pub image: &'s str,
+    pub version: &'s str,
+    pub no_cache: bool,
+    pub squash: bool,
+    pub http_proxy: &'s str,
+    pub https_proxy: &'s str,
+    pub build_arg_map: &'s HashMap<String, String>,
+    pub build_opt_packages: &'s Vec<String>,
+    pub build_label_map: &'s HashMap<String, String>,
+
+    /// Platforms for use with the publish command, comma separated
+    pub platforms: &'s str,
+
+    /// ExtraTags for published images like :latest
+    pub extra_tags: &'s Vec<String>,
+}
+
+/// BuildBackend turns a BuildSpec into the commands of a container build tool
+pub trait BuildBackend: Send + Sync {
+    /// name of the engine as accepted by --engine
+    fn name(&self) -> &'static str;
+
+    /// commands building the image in the build context
+    fn build_commands(&self, spec: &BuildSpec) -> Result<Vec<EngineCommand>>;
+
+    /// commands building the image for all the platforms of the spec and pushing it
+    fn publish_commands(&self, spec: &BuildSpec) -> Result<Vec<EngineCommand>>;
+
+    /// command pushing an image built before, None when the engine has no local image to push
+    fn push_command(&self, image: &str) -> Option<EngineCommand>;
+
+    /// command succeeding when the image is in the local image store of the engine, None when the
+    /// engine has no local image store
+    fn inspect_command(&self, image: &str) -> Option<EngineCommand>;
+
+    /// commands pushing the image of the spec by building it again from the cache of the engine,
+    /// for the engines without a local image store. Empty when push_command pushes the image
+    fn rebuild_push_commands(&self, _spec: &BuildSpec) -> Result<Vec<EngineCommand>> {
+        Ok(vec![])
+    }
+
+    /// command run once before publishing, i.e. to create a buildx builder
+    fn publish_setup(&self) -> Option<EngineCommand> {
+        None
+    }
+
+    /// commands removing what an earlier publish of the spec left behind, run before
+    /// publish_commands and allowed to fail when there is nothing to remove
+    fn publish_cleanup(&self, _spec: &BuildSpec) -> Vec<EngineCommand> {
+        vec![]
+    }
+}
+
+/// Docker builds with `docker build` and publishes with `docker buildx`
+pub struct Docker;
+/// Podman builds with `podman build` and publishes a manifest list
+pub struct Podman;
+/// Buildah builds with `buildah bud` and publishes a manifest list
+pub struct Buildah;
+/// Buildctl builds with BuildKit's `buildctl`, BuildKit has no local image store so a build only
+/// stays in its cache and is pushed by running it again
+pub struct Buildctl;
+
+static DOCKER: Docker = Docker;
+static PODMAN: Podman = Podman;
+static BUILDAH: Buildah = Buildah;
+static BUILDCTL: Buildctl = Buildctl;
+
+/// returns the backend of the engine name, an empty name is the default engine
+pub fn get_build_backend(engine: &str) -> Result<&'static dyn BuildBackend> {
+    match engine.to_ascii_lowercase().as_str() {
+        "" | "docker" => Ok(&DOCKER),
+        "podman" => Ok(&PODMAN),
+        "buildah" => Ok(&BUILDAH),
+        "buildctl" | "buildkit" => Ok(&BUILDCTL),
+        other => Err(Error::Custom(format!(
+            "unknown build engine {}, use one of docker, podman, buildah or buildctl",
+            other
+        ))),
+    }
+}
+
+impl BuildBackend for Docker {
+    fn name(&self) -> &'static str {
+        "docker"
+    }
+
+    fn build_commands(&self, spec: &BuildSpec) -> Result<Vec<EngineCommand>> {
+        let mut args = vec!["build".to_string()];
+        args.append(&mut docker_style_flags(spec));
+        if !spec.platforms.is_empty() {
+            args.push(format!("--platform={}", spec.platforms));
+        }
+        args.push("--tag".to_string());
+        args.push(spec.image.to_string());
+        args.push(".".to_string());
+
+        Ok(vec![("docker", args)])
+    }
+
+    fn publish_commands(&self, spec: &BuildSpec) -> Result<Vec<EngineCommand>> {
+        /// pushOnly defined at https://github.com/docker/buildx
+        const PUSH_ONLY: &str = "--output=type=registry,push=true";
+        let mut args = vec![
+            "buildx".to_string(),
+            "build".to_string(),
+            "--progress=plain".to_string(),
+            "--platform=".to_string() + spec.platforms,
+            PUSH_ONLY.to_string(),
+        ];
+
+        args.append(&mut docker_style_flags(spec));
+        args.push("--tag".to_string());
+        args.push(spec.image.to_string());
+        args.push(".".to_string());
+
+        for tag in extra_tag_images(spec.image, spec.extra_tags) {
+            args.push("--tag".to_string());
+            args.push(tag);
+        }
+
+        Ok(vec![("docker", args)])
+    }
+
+    fn push_command(&self, image: &str) -> Option<EngineCommand> {
+        Some(("docker", vec!["push".to_string(), image.to_string()]))
+    }
+
+    fn inspect_command(&self, image: &str) -> Option<EngineCommand> {
+        Some(("docker", image_inspect(image)))
+    }
+
+    fn publish_setup(&self) -> Option<EngineCommand> {
+        let args = [
+            "buildx",
+            "create",
+            "--use",
+            "--name=multiarch",
+            "--node=multiarch",
+        ];
+        Some(("docker", args.iter().map(|a| a.to_string()).collect()))
+    }
+}
+
+impl BuildBackend for Podman {
+    fn name(&self) -> &'static str {
+        "podman"
+    }
+
+    fn build_commands(&self, spec: &BuildSpec) -> Result<Vec<EngineCommand>> {
+        Ok(vec![(
+            "podman",
+            manifest_style_build(&["build"], spec, None),
+        )])
+    }
+
+    fn publish_commands(&self, spec: &BuildSpec) -> Result<Vec<EngineCommand>> {
+        let mut commands = vec![
+            ("podman", manifest_command("create", spec.image)),
+            (
+                "podman",
+                manifest_style_build(&["build"], spec, Some(spec.image)),
+            ),
+        ];
+        for destination in published_images(spec) {
+            commands.push((
+                "podman",
+                vec![
+                    "manifest".to_string(),
+                    "push".to_string(),
+                    "--all".to_string(),
+                    spec.image.to_string(),
+                    format!("docker://{}", destination),
+                ],
+            ));
+        }
+        Ok(commands)
+    }
+
+    fn publish_cleanup(&self, spec: &BuildSpec) -> Vec<EngineCommand> {
+        vec![("podman", manifest_command("rm", spec.image))]
+    }
+
+    fn push_command(&self, image: &str) -> Option<EngineCommand> {
+        Some(("podman", vec!["push".to_string(), image.to_string()]))
+    }
+
+    fn inspect_command(&self, image: &str) -> Option<EngineCommand> {
+        Some(("podman", image_inspect(image)))
+    }
+}
+
+impl BuildBackend for Buildah {
+    fn name(&self) -> &'static str {
+        "buildah"
+    }
+
+    fn build_commands(&self, spec: &BuildSpec) -> Result<Vec<EngineCommand>> {
+        Ok(vec![(
+            "buildah",
+            manifest_style_build(&["bud"], spec, None),
+        )])
+    }
+
+    fn publish_commands(&self, spec: &BuildSpec) -> Result<Vec<EngineCommand>> {
+        let mut commands = vec![
+            ("buildah", manifest_command("create", spec.image)),
+            (
+                "buildah",
+                manifest_style_build(&["bud"], spec, Some(spec.image)),
+            ),
+        ];
+        for destination in published_images(spec) {
+            commands.push((
+                "buildah",
+                vec![
+                    "manifest".to_string(),
+                    "push".to_string(),
+                    "--all".to_string(),
+                    spec.image.to_string(),
+                    format!("docker://{}", destination),
+                ],
+            ));
+        }
+        Ok(commands)
+    }
+
+    fn publish_cleanup(&self, spec: &BuildSpec) -> Vec<EngineCommand> {
+        vec![("buildah", manifest_command("rm", spec.image))]
+    }
+
+    fn push_command(&self, image: &str) -> Option<EngineCommand> {
+        Some((
+            "buildah",
+            vec![
+                "push".to_string(),
+                image.to_string(),
+                format!("docker://{}", image),
+            ],
+        ))
+    }
+
+    fn inspect_command(&self, image: &str) -> Option<EngineCommand> {
+        Some((
+            "buildah",
+            vec![
+                "inspect".to_string(),
+                "--type".to_string(),
+                "image".to_string(),
+                image.to_string(),
+            ],
+        ))
+    }
+}
+
+impl BuildBackend for Buildctl {
+    fn name(&self) -> &'static str {
+        "buildctl"
+    }
+
+    fn build_commands(&self, spec: &BuildSpec) -> Result<Vec<EngineCommand>> {
+        Ok(vec![(
+            "buildctl",
+            buildctl_args(spec, &[spec.image], false)?,
+        )])
+    }
+
+    fn publish_commands(&self, spec: &BuildSpec) -> Result<Vec<EngineCommand>> {
+        let images = published_images(spec);
+        let images: Vec<&str> = images.iter().map(|i| i.as_str()).collect();
+        Ok(vec![("buildctl", buildctl_args(spec, &images, true)?)])
+    }
+
+    fn push_command(&self, _image: &str) -> Option<EngineCommand> {
+        None
+    }
+
+    fn inspect_command(&self, _image: &str) -> Option<EngineCommand> {
+        None
+    }
+
+    fn rebuild_push_commands(&self, spec: &BuildSpec) -> Result<Vec<EngineCommand>> {
+        Ok(vec![(
+            "buildctl",
+            buildctl_args(spec, &[spec.image], true)?,
+        )])
+    }
+}
+
+/// flags shared by the docker compatible CLIs
+fn docker_style_flags(spec: &BuildSpec) -> Vec<String> {
+    build_flag_slice(
+        spec.no_cache,
+        spec.squash,
+        spec.http_proxy,
+        spec.https_proxy,
+        spec.build_arg_map,
+        spec.build_opt_packages,
+        spec.build_label_map,
+    )
+}
+
+fn image_inspect(image: &str) -> Vec<String> {
+    vec![
+        "image".to_string(),
+        "inspect".to_string(),
+        image.to_string(),
+    ]
+}
+
+/// a podman or buildah manifest command on the manifest list of the image
+fn manifest_command(action: &str, image: &str) -> Vec<String> {
+    vec![
+        "manifest".to_string(),
+        action.to_string(),
+        image.to_string(),
+    ]
+}
+
+/// a podman or buildah build, into a manifest list when `manifest` is set
+fn manifest_style_build(
+    subcommand: &[&str],
+    spec: &BuildSpec,
+    manifest: Option<&str>,
+) -> Vec<String> {
+    let mut args: Vec<String> = subcommand.iter().map(|s| s.to_string()).collect();
+    args.append(&mut docker_style_flags(spec));
+    if !spec.platforms.is_empty() {
+        args.push(format!("--platform={}", spec.platforms));
+    }
+    match manifest {
+        Some(manifest) => {
+            args.push("--manifest".to_string());
+            args.push(manifest.to_string());
+        }
+        None => {
+            args.push("--tag".to_string());
+            args.push(spec.image.to_string());
+        }
+    }
+    args.push(".".to_string());
+    args
+}
+
+/// a buildctl build of the images, pushed to the registry when push is set and only kept in the
+/// BuildKit cache otherwise
+fn buildctl_args(spec: &BuildSpec, images: &[&str], push: bool) -> Result<Vec<String>> {
+    if spec.squash {
+        return Err(Error::Custom(
+            "--squash is not supported by the buildctl engine".to_string(),
+        ));
+    }
+    let mut args: Vec<String> = vec![
+        "build",
+        "--frontend",
+        "dockerfile.v0",
+        "--local",
+        "context=.",
+        "--local",
+        "dockerfile=.",
+    ]
+    .into_iter()
+    .map(|a| a.to_string())
+    .collect();
+
+    let mut build_args = build_arg_pairs(spec);
+    build_args.sort();
+    for (k, v) in build_args {
+        args.push("--opt".to_string());
+        args.push(format!("build-arg:{}={}", k, v));
+    }
+    let mut labels: Vec<(&String, &String)> = spec.build_label_map.iter().collect();
+    labels.sort();
+    for (k, v) in labels {
+        args.push("--opt".to_string());
+        args.push(format!("label:{}={}", k, v));
+    }
+    if !spec.platforms.is_empty() {
+        args.push("--opt".to_string());
+        args.push(format!("platform={}", spec.platforms));
+    }
+    if spec.no_cache {
+        args.push("--no-cache".to_string());
+    }
+
+    args.push("--output".to_string());
+    args.push(format!(
+        "type=image,\"name={}\",push={}",
+        images.join(","),
+        push
+    ));
+    Ok(args)
+}
+
+/// build args as key value pairs, the packages of build options are merged into
+/// ADDITIONAL_PACKAGE
+fn build_arg_pairs(spec: &BuildSpec) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    if !spec.http_proxy.is_empty() {
+        pairs.push(("http_proxy".to_string(), spec.http_proxy.to_string()));
+    }
+    if !spec.https_proxy.is_empty() {
+        pairs.push(("https_proxy".to_string(), spec.https_proxy.to_string()));
+    }
+
+    let mut packages: Vec<String> = Vec::new();
+    for (k, v) in spec.build_arg_map {
+        if k.as_str() == ADDITIONAL_PACKAGE_BUILD_ARGS {
+            packages.extend(
+                v.split(' ')
+                    .filter(|p| !p.is_empty())
+                    .map(|p| p.to_string()),
+            );
+        } else {
+            pairs.push((k.to_string(), v.to_string()));
+        }
+    }
+    packages.extend(spec.build_opt_packages.iter().cloned());
+    if !packages.is_empty() {
+        pairs.push((
+            ADDITIONAL_PACKAGE_BUILD_ARGS.to_string(),
+            de_duplicate(&packages).join(" "),
+        ));
+    }
+    pairs
+}
+
+/// the image followed by its extra tags
+fn published_images(spec: &BuildSpec) -> Vec<String> {
+    let mut images = vec![spec.image.to_string()];
+    images.append(&mut extra_tag_images(spec.image, spec.extra_tags));
+    images
+}
+
+/// returns the image with each of the extra tags instead of its own tag
+pub(crate) fn extra_tag_images(image: &str, extra_tags: &[String]) -> Vec<String> {
+    // a colon before the last slash belongs to the registry port
+    let name_start = image.rfind('/').map(|i| i + 1).unwrap_or(0);
+    let repository = match image[name_start..].rfind(':') {
+        Some(index) => &image[..name_start + index],
+        None => image,
+    };
+    extra_tags
+        .iter()
+        .map(|tag| format!("{}:{}", repository, tag))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::{extra_tag_images, get_build_backend, BuildSpec};
+    use std::collections::HashMap;
+
+    fn joined(commands: Vec<(&'static str, Vec<String>)>) -> Vec<String> {
+        commands
+            .into_iter()
+            .map(|(program, args)| format!("{} {}", program, args.join(" ")))
+            .collect()
+    }
+
+    #[test]
+    fn test_get_build_backend() {
+        assert_eq!(get_build_backend("").unwrap().name(), "docker");
+        assert_eq!(get_build_backend("Podman").unwrap().name(), "podman");
+        assert_eq!(get_build_backend("buildah").unwrap().name(), "buildah");
+        assert_eq!(get_build_backend("buildkit").unwrap().name(), "buildctl");
+        assert!(get_build_backend("kaniko").is_err());
+    }
+
+    #[test]
+    fn test_inspect_command() {
+        assert_eq!(
+            joined(
+                get_build_backend("podman")
+                    .unwrap()
+                    .inspect_command("fn:0.1")
+                    .into_iter()
+                    .collect()
+            ),
+            vec!["podman image inspect fn:0.1".to_string()]
+        );
+        assert_eq!(
+            joined(
+                get_build_backend("buildah")
+                    .unwrap()
+                    .inspect_command("fn:0.1")
+                    .into_iter()
+                    .collect()
+            ),
+            vec!["buildah inspect --type image fn:0.1".to_string()]
+        );
+        assert!(get_build_backend("buildctl")
+            .unwrap()
+            .inspect_command("fn:0.1")
+            .is_none());
+    }
+
+    #[test]
+    fn test_extra_tag_images() {
+        let tags = vec!["latest".to_string()];
+        assert_eq!(
+            extra_tag_images("localhost:5000/fn:0.1", &tags),
+            vec!["localhost:5000/fn:latest"]
+        );
+        assert_eq!(
+            extra_tag_images("localhost:5000/fn", &tags),
+            vec!["localhost:5000/fn:latest"]
+        );
+        assert_eq!(extra_tag_images("fn", &tags), vec!["fn:latest"]);
+    }
+
+    #[test]
+    fn test_build_commands() {
+        let mut build_args = HashMap::new();
+        build_args.insert("GO111MODULE".to_string(), "on".to_string());
+        let mut labels = HashMap::new();
+        labels.insert("team".to_string(), "core".to_string());
+        let spec = BuildSpec {
+            image: "fn:0.1",
+            version: "",
+            no_cache: true,
+            squash: false,
+            http_proxy: "",
+            https_proxy: "",
+            build_arg_map: &build_args,
+            build_opt_packages: &vec!["git".to_string()],
+            build_label_map: &labels,
+            platforms: "",
+            extra_tags: &vec![],
+        };
+
+        let flags = "--no-cache --build-arg GO111MODULE=on --build-arg ADDITIONAL_PACKAGE=git --label team=core";
+        assert_eq!(
+            joined(
+                get_build_backend("docker")
+                    .unwrap()
+                    .build_commands(&spec)
+                    .unwrap()
+            ),
+            vec![format!("docker build {} --tag fn:0.1 .", flags)]
+        );
+        assert_eq!(
+            joined(
+                get_build_backend("podman")
+                    .unwrap()
+                    .build_commands(&spec)
+                    .unwrap()
+            ),
+            vec![format!("podman build {} --tag fn:0.1 .", flags)]
+        );
+        assert_eq!(
+            joined(
+                get_build_backend("buildah")
+                    .unwrap()
+                    .build_commands(&spec)
+                    .unwrap()
+            ),
+            vec![format!("buildah bud {} --tag fn:0.1 .", flags)]
+        );
+        assert_eq!(
+            joined(
+                get_build_backend("buildctl")
+                    .unwrap()
+                    .build_commands(&spec)
+                    .unwrap()
+            ),
+            vec![
+                "buildctl build --frontend dockerfile.v0 --local context=. --local dockerfile=. \
+            --opt build-arg:ADDITIONAL_PACKAGE=git --opt build-arg:GO111MODULE=on \
+            --opt label:team=core --no-cache --output type=image,\"name=fn:0.1\",push=false"
+                    .to_string()
+            ]
+        );
+        // the image built by buildctl is pushed by building it again from the BuildKit cache
+        assert_eq!(
+            joined(
+                get_build_backend("buildctl")
+                    .unwrap()
+                    .rebuild_push_commands(&spec)
+                    .unwrap()
+            ),
+            vec![
+                "buildctl build --frontend dockerfile.v0 --local context=. --local dockerfile=. \
+            --opt build-arg:ADDITIONAL_PACKAGE=git --opt build-arg:GO111MODULE=on \
+            --opt label:team=core --no-cache --output type=image,\"name=fn:0.1\",push=true"
+                    .to_string()
+            ]
+        );
+        assert!(get_build_backend("docker")
+            .unwrap()
+            .rebuild_push_commands(&spec)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_publish_commands() {
+        let spec = BuildSpec {
+            image: "registry.test/fn:0.1",
+            version: "",
+            no_cache: false,
+            squash: false,
+            http_proxy: "",
+            https_proxy: "",
+            build_arg_map: &Default::default(),
+            build_opt_packages: &vec![],
+            build_label_map: &Default::default(),
+            platforms: "linux/amd64,linux/arm64",
+            extra_tags: &vec!["latest".to_string()],
+        };
+
+        assert_eq!(
+            joined(get_build_backend("docker").unwrap().publish_commands(&spec).unwrap()),
+            vec!["docker buildx build --progress=plain --platform=linux/amd64,linux/arm64 \
+            --output=type=registry,push=true --tag registry.test/fn:0.1 . --tag registry.test/fn:latest"
+                .to_string()]
+        );
+        assert_eq!(
+            joined(
+                get_build_backend("podman")
+                    .unwrap()
+                    .publish_commands(&spec)
+                    .unwrap()
+            ),
+            vec![
+                "podman manifest create registry.test/fn:0.1".to_string(),
+                "podman build --platform=linux/amd64,linux/arm64 --manifest registry.test/fn:0.1 ."
+                    .to_string(),
+                "podman manifest push --all registry.test/fn:0.1 docker://registry.test/fn:0.1"
+                    .to_string(),
+                "podman manifest push --all registry.test/fn:0.1 docker://registry.test/fn:latest"
+                    .to_string(),
+            ]
+        );
+        assert_eq!(
+            joined(get_build_backend("buildah").unwrap().publish_cleanup(&spec)),
+            vec!["buildah manifest rm registry.test/fn:0.1".to_string()]
+        );
+        assert!(get_build_backend("docker")
+            .unwrap()
+            .publish_cleanup(&spec)
+            .is_empty());
+        assert_eq!(
+            joined(
+                get_build_backend("buildctl")
+                    .unwrap()
+                    .publish_commands(&spec)
+                    .unwrap()
+            ),
+            vec![
+                "buildctl build --frontend dockerfile.v0 --local context=. --local dockerfile=. \
+            --opt platform=linux/amd64,linux/arm64 \
+            --output type=image,\"name=registry.test/fn:0.1,registry.test/fn:latest\",push=true"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_buildctl_rejects_squash() {
+        let spec = BuildSpec {
+            image: "fn:0.1",
+            version: "",
+            no_cache: false,
+            squash: true,
+            http_proxy: "",
+            https_proxy: "",
+            build_arg_map: &Default::default(),
+            build_opt_packages: &vec![],
+            build_label_map: &Default::default(),
+            platforms: "",
+            extra_tags: &vec![],
+        };
+        let backend = get_build_backend("buildctl").unwrap();
+        assert!(backend.build_commands(&spec).is_err());
+        assert!(backend.push_command("fn:0.1").is_none());
+    }
+}
@@ -1,11 +1,18 @@
 #![allow(dead_code)]
-use crate::copy_files;
-use exec::command_exec;
+use crate::archive::{archive_build_context, BuildManifest};
+use crate::backend::{BuildBackend, BuildSpec, EngineCommand};
+use crate::cache::{build_hash, BuildCache, HashInput};
+use crate::ignore_file::IgnoreRules;
+use crate::provenance::{build_time, Provenance};
+use crate::{copy_files, copy_files_ignoring};
+use exec::stream::{command_stream, StreamOptions};
 use path_clean::PathClean;
 
+use chrono::{DateTime, Utc};
 use path_abs::PathInfo;
 use schema::image::{
-    build_image_name, BuildFormat, BRANCH_AND_SHA_FORMAT, DESCRIBE_FORMAT, SHA_FORMAT,
+    build_image_name, image_with_tag, render_tag_template, tag_safe, BuildFormat,
+    BRANCH_AND_SHA_FORMAT, DESCRIBE_FORMAT, SHA_FORMAT,
 };
 use stack::language_template::{is_valid_template, parse_yaml_for_language_template};
 use stack::schema::BuildOption;
@@ -14,12 +21,14 @@ use std::io::ErrorKind;
 use std::path::Path;
 use std::sync::atomic::{AtomicU32, Ordering};
 use utility::{Error, Result};
-use versioncontol::git::{get_git_branch, get_git_describe, get_git_sha};
+use versioncontol::git::{get_git_branch, get_git_describe, get_git_full_sha, get_git_sha};
 
 /// AdditionalPackageBuildArg holds the special build-arg keyname for use with build-opts.
 /// Can also be passed as a build arg hence needs to be accessed from commands
 pub const ADDITIONAL_PACKAGE_BUILD_ARGS: &str = "ADDITIONAL_PACKAGE";
-const DEFAULT_HANDLER_FOLDER: &str = "function";
+/// DefaultHandlerFolder is where the handler is copied in the build context when the template
+/// sets no handler_folder
+pub const DEFAULT_HANDLER_FOLDER: &str = "function";
 
 static DEFAULT_DIR_PERMISSION: AtomicU32 = AtomicU32::new(0700);
 
@@ -34,13 +43,37 @@ pub struct BuildImage<'s> {
     pub build_arg_map: &'s HashMap<String, String>,
     pub build_options: &'s Vec<String>,
     pub tag_mode: BuildFormat,
+    /// renders the image tag when not empty, see schema::image::render_tag_template
+    pub tag_template: &'s str,
     pub build_label_map: &'s HashMap<String, String>,
     pub quiet_build: bool,
     pub copy_extra_paths: &'s Vec<String>,
+    pub engine: &'static dyn BuildBackend,
+    pub force: bool,
+    /// prints the files of the build context of a shrinkwrap
+    pub list_context: bool,
+    /// adds the OCI provenance labels of the git repository and the template to the image
+    pub provenance: bool,
+    /// folder receiving the build context of a shrinkwrap as a tar.gz with its build manifest,
+    /// nothing is archived when empty
+    pub archive: &'s str,
+    /// folder of the stack YAML file, its .faasignore applies to the handlers
+    pub project_root: &'s str,
+    /// platforms of the function, listed in the build manifest of a shrinkwrap as an image is
+    /// built for the platform of the engine
+    pub platforms: &'s str,
 }
 
-// BuildImage construct Docker image from function parameters
-pub async fn build_image(build: &BuildImage<'_>) -> Result<()> {
+/// BuildOutcome tells whether build_image built the image or had nothing to do
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuildOutcome {
+    Built,
+    UpToDate,
+    ShrinkWrapped,
+}
+
+// BuildImage construct the image of a function with its build engine, returns the image name
+pub async fn build_image(build: &BuildImage<'_>) -> Result<(String, BuildOutcome)> {
     if is_valid_template(build.language).await {
         let path_to_template_yaml = format!("./template/{}/template.yml", build.language);
         if let Err(e) = std::fs::metadata(&path_to_template_yaml) {
@@ -50,13 +83,12 @@ pub async fn build_image(build: &BuildImage<'_>) -> Result<()> {
         }
         let lang_temp = parse_yaml_for_language_template(path_to_template_yaml.as_str()).await?;
 
-        let (branch, version) = get_image_tag_values(build.tag_mode)?;
-        let image_name = build_image_name(
+        let image_name = get_image_name(
             build.tag_mode,
+            build.tag_template,
             build.image,
-            version.as_str(),
-            branch.as_str(),
-        );
+            build.handler,
+        )?;
         ensure_handler_path(build.handler).map_err(|_| {
             Error::Custom(format!(
                 "building {}, {} is an invalid path",
@@ -64,6 +96,26 @@ pub async fn build_image(build: &BuildImage<'_>) -> Result<()> {
             ))
         })?;
 
+        let cache = BuildCache::default();
+        let hash = if build.shrinkwrap {
+            None
+        } else {
+            build_cache_hash(build)
+        };
+        if let Some(hash) = &hash {
+            if !build.force
+                && !build.nocache
+                && cache.is_fresh(build.function_name, &image_name, hash)
+                && has_local_image(build.engine, &image_name)
+            {
+                println!(
+                    "Skipping build of: {}, {} is up to date. Use --force to rebuild.\n",
+                    build.function_name, image_name
+                );
+                return Ok((image_name, BuildOutcome::UpToDate));
+            }
+        }
+
         let temp_path = create_build_context(
             build.function_name,
             build.handler,
@@ -71,17 +123,13 @@ pub async fn build_image(build: &BuildImage<'_>) -> Result<()> {
             is_language_template(build.language),
             lang_temp.handler_folder.as_str(),
             build.copy_extra_paths,
+            build.project_root,
         )?;
         println!(
             "Building: {} with {} template. Please wait..\n",
             image_name, build.language
         );
 
-        if build.shrinkwrap {
-            println!("{} shrink-wrapped to {}\n", build.shrinkwrap, temp_path);
-            return Ok(());
-        }
-
         let build_opt_options = get_build_option_packages(
             build.build_options,
             build.language,
@@ -90,8 +138,14 @@ pub async fn build_image(build: &BuildImage<'_>) -> Result<()> {
 
         let http_proxy = std::env::var("http_proxy").unwrap_or_default();
         let https_proxy = std::env::var("https_proxy").unwrap_or_default();
+        let build_label_map = if build.provenance {
+            Provenance::current(build.language, lang_temp.version.as_str())
+                .with_labels(build.build_label_map)
+        } else {
+            build.build_label_map.clone()
+        };
 
-        let docker_build_val = DockerBuild {
+        let spec = BuildSpec {
             image: image_name.as_str(),
             version: "",
             no_cache: build.nocache,
@@ -100,26 +154,59 @@ pub async fn build_image(build: &BuildImage<'_>) -> Result<()> {
             https_proxy: https_proxy.as_str(),
             build_arg_map: build.build_arg_map,
             build_opt_packages: &build_opt_options,
-            build_label_map: build.build_label_map,
-
-            platforms: "",
+            build_label_map: &build_label_map,
+            platforms: if build.shrinkwrap {
+                build.platforms
+            } else {
+                ""
+            },
             extra_tags: &vec![],
         };
 
-        let (command, args) = get_docker_build_command(&docker_build_val);
-        let cmd_args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
-
-        let res = command_exec(temp_path.as_str(), &command, &cmd_args)?;
+        if build.shrinkwrap {
+            println!("{} shrink-wrapped to {}\n", build.function_name, temp_path);
+            if build.list_context {
+                for file in list_build_context(temp_path.as_str())? {
+                    println!("{}", file);
+                }
+            }
+            if !build.archive.is_empty() {
+                let manifest = BuildManifest::new(
+                    build.function_name,
+                    build.language,
+                    build.build_options,
+                    &spec,
+                );
+                let archive = archive_build_context(&temp_path, build.archive, manifest)?;
+                println!(
+                    "{} archived to {}\n",
+                    build.function_name,
+                    archive.to_string_lossy()
+                );
+            }
+            return Ok((image_name, BuildOutcome::ShrinkWrapped));
+        }
 
-        if !res.success() {
-            Err(Error::Custom(format!(
-                "[{}] received non-zero exit code from build, error: {:?}",
-                build.function_name, res
-            )))
-        } else {
-            println!("Image: {} built.\n", image_name);
-            Ok(())
+        let commands = build.engine.build_commands(&spec)?;
+        run_engine_commands(
+            temp_path.as_str(),
+            build.function_name,
+            &commands,
+            build.quiet_build,
+        )?;
+        println!("Image: {} built.\n", image_name);
+        if let Some(hash) = &hash {
+            let recorded = cache
+                .record_build(build.function_name, &image_name, hash)
+                .and_then(|_| cache.record_build_spec(build.function_name, &image_name, &spec));
+            if let Err(e) = recorded {
+                println!(
+                    "Unable to update the build cache of {}: {}\n",
+                    build.function_name, e
+                );
+            }
         }
+        Ok((image_name, BuildOutcome::Built))
     } else {
         Err(Error::Custom(format!(
             "language template: {} not supported, build a custom Dockerfile",
@@ -128,44 +215,90 @@ pub async fn build_image(build: &BuildImage<'_>) -> Result<()> {
     }
 }
 
-fn get_docker_build_command(build: &DockerBuild) -> (&'static str, Vec<String>) {
-    let mut flag_slice = build_flag_slice(
-        build.no_cache,
-        build.squash,
-        build.http_proxy,
-        build.https_proxy,
-        build.build_arg_map,
-        build.build_opt_packages,
-        build.build_label_map,
-    );
-
-    let mut args = vec!["build".to_string()];
-    args.append(&mut flag_slice);
-    args.push("--tag".to_string());
-    args.push(build.image.to_string());
-    args.push(".".to_string());
+/// returns the build cache hash of the function, None when its files can't be read in which case
+/// the function is always built
+fn build_cache_hash(build: &BuildImage) -> Option<String> {
+    let template_path = format!("./template/{}", build.language);
+    let rules = match IgnoreRules::load(build.handler, build.project_root) {
+        Ok(rules) => rules,
+        Err(e) => {
+            println!(
+                "Building {} without the build cache: {}\n",
+                build.function_name, e
+            );
+            return None;
+        }
+    };
+    let revision = if build.provenance {
+        get_git_full_sha().unwrap_or_default()
+    } else {
+        String::new()
+    };
+    let input = HashInput {
+        handler: build.handler,
+        language: build.language,
+        template_path: template_path.as_str(),
+        copy_extra_paths: build.copy_extra_paths,
+        build_arg_map: build.build_arg_map,
+        build_options: build.build_options,
+        build_label_map: build.build_label_map,
+        provenance: build.provenance,
+        revision: revision.as_str(),
+        squash: build.squash,
+        engine: build.engine.name(),
+        ignore_rules: &rules,
+    };
+    match build_hash(&input) {
+        Ok(hash) => Some(hash),
+        Err(e) => {
+            println!(
+                "Building {} without the build cache: {}\n",
+                build.function_name, e
+            );
+            None
+        }
+    }
+}
 
-    ("docker", args)
+/// true when the engine still has the image, a removed image has to be built again even when
+/// the build cache has it. The engines without a local image store rely on the build cache
+fn has_local_image(engine: &dyn BuildBackend, image: &str) -> bool {
+    match engine.inspect_command(image) {
+        Some((command, args)) => std::process::Command::new(command)
+            .args(&args)
+            .output()
+            .map(|output| output.status.success())
+            .unwrap_or(false),
+        None => true,
+    }
 }
 
-#[derive(Debug)]
-pub(crate) struct DockerBuild<'s> {
-    pub(crate) image: &'s str,
-    pub(crate) version: &'s str,
-    pub(crate) no_cache: bool,
-    pub(crate) squash: bool,
-    pub(crate) http_proxy: &'s str,
-    pub(crate) https_proxy: &'s str,
-    pub(crate) build_arg_map: &'s HashMap<String, String>,
-    pub(crate) build_opt_packages: &'s Vec<String>,
-    pub(crate) build_label_map: &'s HashMap<String, String>,
-
-    /// Platforms for use with buildx and publish command
-    pub(crate) platforms: &'s str,
-
-    /// ExtraTags for published images like :latest
-    pub(crate) extra_tags: &'s Vec<String>,
+/// runs the commands of a build engine one after the other in the build context, their output is
+/// streamed with the function name as prefix and its last lines are part of the error
+pub(crate) fn run_engine_commands(
+    temp_path: &str,
+    function_name: &str,
+    commands: &[EngineCommand],
+    quiet: bool,
+) -> Result<()> {
+    let options = StreamOptions::new(function_name, quiet);
+    for (command, args) in commands {
+        let cmd_args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+        let res = command_stream(temp_path, command, &cmd_args, &options)?;
+
+        if !res.success() {
+            return Err(Error::Custom(format!(
+                "[{}] received non-zero exit code from {} ({}), last lines of output:\n{}",
+                function_name,
+                command,
+                res.status,
+                res.tail.join("\n")
+            )));
+        }
+    }
+    Ok(())
 }
+
 pub(crate) fn get_build_option_packages(
     requested_build_options: &Vec<String>,
     language: &str,
@@ -220,15 +353,19 @@ pub(crate) fn create_build_context(
     use_function: bool,
     handler_folder: &str,
     copy_extra_paths: &Vec<String>,
+    project_root: &str,
 ) -> Result<String> {
     let temp_path = format!("./build/{}/", function_name);
     println!("Clearing temporary build folder: {}\n", temp_path);
-    std::fs::remove_dir_all(temp_path.as_str()).map_err(|_| {
-        Error::Custom(format!(
-            "Error clearing temporary build folder: {}\n",
-            temp_path
-        ))
-    })?;
+    match std::fs::remove_dir_all(temp_path.as_str()) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            return Err(Error::Custom(format!(
+                "Error clearing temporary build folder: {}\n",
+                temp_path
+            )))
+        }
+        _ => {}
+    }
 
     let mut function_path = std::path::Path::new(temp_path.as_str()).to_owned();
 
@@ -287,6 +424,8 @@ pub(crate) fn create_build_context(
             .map_err(|e| Error::Custom(format!("Error copying template directory: {}.\n", e)))?;
     }
 
+    let rules = IgnoreRules::load(handler, project_root)?;
+
     let infos = std::fs::read_dir(handler)
         .map_err(|e| Error::Custom(format!("Error reading the handler: {} - {}.\n", handler, e)))?;
 
@@ -301,7 +440,7 @@ pub(crate) fn create_build_context(
                 let src = src.to_string_lossy().to_string();
                 let dest = function_path.join(file_name.as_str()).clean();
                 let dest = dest.to_string_lossy().to_string();
-                copy_files(src.as_str(), dest.as_str())?;
+                copy_files_ignoring(src.as_str(), dest.as_str(), &rules)?;
             }
         }
     }
@@ -316,12 +455,33 @@ pub(crate) fn create_build_context(
             .clean()
             .to_string_lossy()
             .to_string();
-        copy_files(abs.as_str(), dest.as_str())?;
+        copy_files_ignoring(abs.as_str(), dest.as_str(), &rules)?;
     }
 
     return Ok(temp_path);
 }
 
+/// returns the files of a build context relative to it, sorted
+pub fn list_build_context(temp_path: &str) -> Result<Vec<String>> {
+    fn walk(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                walk(root, &path, files)?;
+            } else {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                files.push(relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(Path::new(temp_path), Path::new(temp_path), &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
 /// pathInScope returns the absolute path to `path` and ensures that it is located within the
 /// provided scope. An error will be returned, if the path is outside of the provided scope.
 fn path_in_scope(path: &str, scope: &str) -> Result<String> {
@@ -405,6 +565,122 @@ pub fn get_image_tag_values(tag_type: BuildFormat) -> Result<(String, String)> {
     Ok((branch, version))
 }
 
+/// returns the image name of a function, its tag is rendered from the tag template when there is
+/// one, otherwise it follows the tag format
+pub fn get_image_name(
+    tag_mode: BuildFormat,
+    tag_template: &str,
+    image: &str,
+    handler: &str,
+) -> Result<String> {
+    if tag_template.is_empty() {
+        let (branch, version) = get_image_tag_values(tag_mode)?;
+        return Ok(build_image_name(
+            tag_mode,
+            image,
+            version.as_str(),
+            branch.as_str(),
+        ));
+    }
+
+    let now = build_time();
+    let tag = render_tag_template(tag_template, |name, arg| {
+        tag_template_value(name, arg, handler, &now)
+    })
+    .map_err(|e| Error::Custom(format!("tag template of {}: {}", image, e)))?;
+    Ok(image_with_tag(image, tag.as_str()))
+}
+
+/// returns the image name of a function built before. A tag template with `{timestamp}` or
+/// `{date}` renders differently on every run, so the image of the last build of the function is
+/// used when the build cache has one for the same repository
+pub fn get_built_image_name(
+    cache: &BuildCache,
+    function_name: &str,
+    tag_mode: BuildFormat,
+    tag_template: &str,
+    image: &str,
+    handler: &str,
+) -> Result<String> {
+    if tag_template.contains("{timestamp") || tag_template.contains("{date") {
+        if let Some(entry) = cache.lookup(function_name) {
+            if image_with_tag(&entry.image, "") == image_with_tag(image, "") {
+                return Ok(entry.image);
+            }
+        }
+    }
+    get_image_name(tag_mode, tag_template, image, handler)
+}
+
+fn tag_template_value(
+    name: &str,
+    arg: Option<&str>,
+    handler: &str,
+    now: &DateTime<Utc>,
+) -> Result<String> {
+    let from_git = |value: Result<String>| -> Result<String> {
+        match value {
+            Ok(value) if !value.is_empty() => Ok(value),
+            _ => Err(Error::Custom(format!(
+                "cannot render {{{}}} as this is not a Git repository",
+                name
+            ))),
+        }
+    };
+    match (name, arg) {
+        // branches like feature/login are not valid tags
+        ("branch", None) => Ok(tag_safe(from_git(get_git_branch())?.as_str())),
+        ("sha", None) => from_git(get_git_full_sha()),
+        ("short_sha", None) => from_git(get_git_sha()),
+        ("describe", None) => from_git(get_git_describe()),
+        ("timestamp", None) => Ok(now.timestamp().to_string()),
+        ("date", None) => Ok(now.format("%Y%m%d").to_string()),
+        ("version", None) => handler_version(handler),
+        ("env", Some(variable)) => std::env::var(variable).map_err(|_| {
+            Error::Custom(format!(
+                "environment variable {} of {{env:{}}} is not set",
+                variable, variable
+            ))
+        }),
+        _ => Err(Error::Custom(format!(
+            "unknown placeholder {{{}}}, use branch, sha, short_sha, describe, timestamp, date, \
+             version or env:NAME",
+            name
+        ))),
+    }
+}
+
+/// returns the version of the package.json or the Cargo.toml of the handler
+pub(crate) fn handler_version(handler: &str) -> Result<String> {
+    let package_json = Path::new(handler).join("package.json");
+    if let Ok(content) = std::fs::read_to_string(&package_json) {
+        let package: serde_json::Value = serde_json::from_str(content.as_str())
+            .map_err(|e| Error::Custom(format!("can't parse {:?}: {}", package_json, e)))?;
+        if let Some(version) = package["version"].as_str() {
+            return Ok(version.to_string());
+        }
+    }
+
+    let cargo_toml = Path::new(handler).join("Cargo.toml");
+    if let Ok(content) = std::fs::read_to_string(&cargo_toml) {
+        let mut in_package = false;
+        for line in content.lines().map(|l| l.trim()) {
+            if line.starts_with('[') {
+                in_package = line == "[package]";
+            } else if in_package && line.starts_with("version") {
+                if let Some((_, version)) = line.split_once('=') {
+                    return Ok(version.trim().trim_matches('"').to_string());
+                }
+            }
+        }
+    }
+
+    Err(Error::Custom(format!(
+        "cannot render {{version}}, no version in the package.json or Cargo.toml of {}",
+        handler
+    )))
+}
+
 pub(crate) fn build_flag_slice(
     nocache: bool,
     squash: bool,
@@ -461,7 +737,7 @@ pub(crate) fn build_flag_slice(
     return space_safe_build_flags;
 }
 
-fn de_duplicate(build_opt_packages: &Vec<String>) -> Vec<String> {
+pub(crate) fn de_duplicate(build_opt_packages: &Vec<String>) -> Vec<String> {
     let mut seen_packages: HashMap<String, bool> = HashMap::new();
     let mut ret_packages = Vec::new();
 
@@ -493,15 +769,98 @@ pub(crate) fn is_language_template(language: &str) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use crate::backend::{get_build_backend, BuildSpec};
     use crate::build::{
-        build_flag_slice, de_duplicate, get_docker_build_command, get_packages,
-        is_language_template, path_in_scope, DockerBuild,
+        build_flag_slice, de_duplicate, get_built_image_name, get_image_name, get_packages,
+        handler_version, is_language_template, path_in_scope,
     };
+    use crate::cache::BuildCache;
+    use crate::provenance::build_time;
     use path_clean::PathClean;
+    use schema::image::TEMPLATE_FORMAT;
     use stack::schema::BuildOption;
     use std::collections::HashMap;
     use std::path::PathBuf;
 
+    #[test]
+    fn test_handler_version() {
+        let dir = tempdir::TempDir::new("handler-version").unwrap();
+        let handler = dir.path().to_string_lossy().to_string();
+        assert!(handler_version(handler.as_str()).is_err());
+
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[dependencies]\nversion = \"9\"\n\n[package]\nname = \"handler\"\nversion = \"0.3.1\"\n",
+        )
+        .unwrap();
+        assert_eq!(handler_version(handler.as_str()).unwrap(), "0.3.1");
+
+        std::fs::write(
+            dir.path().join("package.json"),
+            r#"{"name": "handler", "version": "1.2.0"}"#,
+        )
+        .unwrap();
+        assert_eq!(handler_version(handler.as_str()).unwrap(), "1.2.0");
+    }
+
+    #[test]
+    fn test_get_built_image_name() {
+        let dir = tempdir::TempDir::new("built-image-name").unwrap();
+        let cache = BuildCache::new(dir.path().join(".cache"));
+        let handler = dir.path().to_string_lossy().to_string();
+
+        // push renders the image of the last build, not the time of its own run
+        cache
+            .record_build("fn", "user/fn:1622541600", "abc")
+            .unwrap();
+        let pushed = |template: &str, image: &str| {
+            get_built_image_name(&cache, "fn", TEMPLATE_FORMAT, template, image, &handler).unwrap()
+        };
+        assert_eq!(pushed("{timestamp}", "user/fn"), "user/fn:1622541600");
+        assert_eq!(
+            pushed("{timestamp}", "user/other"),
+            format!("user/other:{}", build_time().timestamp())
+        );
+        assert_eq!(pushed("v1", "user/fn"), "user/fn:v1");
+
+        let built =
+            get_image_name(TEMPLATE_FORMAT, "{date}-{timestamp}", "user/fn", &handler).unwrap();
+        cache.record_build("fn", &built, "abc").unwrap();
+        assert_eq!(pushed("{date}-{timestamp}", "user/fn"), built);
+    }
+
+    #[test]
+    fn test_get_image_name_with_tag_template() {
+        let dir = tempdir::TempDir::new("tag-template").unwrap();
+        std::fs::write(dir.path().join("package.json"), r#"{"version": "1.2.0"}"#).unwrap();
+        let handler = dir.path().to_string_lossy().to_string();
+        std::env::set_var("FAAS_TAG_TEMPLATE_TEST", "build42");
+
+        let got = get_image_name(
+            TEMPLATE_FORMAT,
+            "{version}-{env:FAAS_TAG_TEMPLATE_TEST:5}-{date}",
+            "user/fn:latest",
+            handler.as_str(),
+        )
+        .unwrap();
+        let date = build_time().format("%Y%m%d").to_string();
+        assert_eq!(got, format!("user/fn:1.2.0-build-{}", date));
+
+        assert!(get_image_name(TEMPLATE_FORMAT, "{nope}", "user/fn", handler.as_str()).is_err());
+        // the functions of a run share the build time
+        assert_eq!(
+            get_image_name(TEMPLATE_FORMAT, "{timestamp}", "user/fn", handler.as_str()).unwrap(),
+            format!("user/fn:{}", build_time().timestamp())
+        );
+        assert!(get_image_name(
+            TEMPLATE_FORMAT,
+            "{env:FAAS_TAG_TEMPLATE_UNSET}",
+            "user/fn",
+            handler.as_str()
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_is_language_template_dockerfile() {
         let language = "Dockerfile";
@@ -518,9 +877,17 @@ mod tests {
         let got = is_language_template(language);
         assert_eq!(got, want);
     }
+    fn get_docker_build_command(build: &BuildSpec) -> (&'static str, Vec<String>) {
+        let mut commands = get_build_backend("docker")
+            .unwrap()
+            .build_commands(build)
+            .unwrap();
+        commands.remove(0)
+    }
+
     #[test]
     fn test_get_docker_build_command_no_opts() {
-        let docker_build_val = DockerBuild {
+        let docker_build_val = BuildSpec {
             image: "imagename:latest",
             version: "",
             no_cache: false,
@@ -546,7 +913,7 @@ mod tests {
 
     #[test]
     fn test_get_docker_build_command_no_cache() {
-        let docker_build_val = DockerBuild {
+        let docker_build_val = BuildSpec {
             image: "imagename:latest",
             version: "",
             no_cache: true,
@@ -572,7 +939,7 @@ mod tests {
 
     #[test]
     fn test_get_docker_build_command_with_proxies() {
-        let docker_build_val = DockerBuild {
+        let docker_build_val = BuildSpec {
             image: "imagename:latest",
             version: "",
             no_cache: false,
@@ -601,7 +968,7 @@ mod tests {
         map.insert("USERNAME".to_string(), "admin".to_string());
         map.insert("PASSWORD".to_string(), "1234".to_string());
 
-        let docker_build_val = DockerBuild {
+        let docker_build_val = BuildSpec {
             image: "imagename:latest",
             version: "",
             no_cache: false,
@@ -0,0 +1,468 @@
+use crate::backend::BuildSpec;
+use crate::ignore_file::IgnoreRules;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use utility::{Error, Result};
+
+/// folder of the build cache, next to the build contexts of the functions
+pub const BUILD_CACHE_DIR: &str = "./build/.cache";
+
+/// CacheEntry is the last successful build of a function
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct CacheEntry {
+    pub image: String,
+    pub hash: String,
+    #[serde(default)]
+    pub pushed: bool,
+    /// inputs of the build, for the engines pushing by building again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_spec: Option<CachedBuildSpec>,
+}
+
+/// CachedBuildSpec holds the inputs of the BuildSpec of a build. The commands pushing the image
+/// are made again from them by the engine, the cache is in the workspace and must not be able to
+/// run any program
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct CachedBuildSpec {
+    pub version: String,
+    pub no_cache: bool,
+    pub squash: bool,
+    pub http_proxy: String,
+    pub https_proxy: String,
+    pub build_arg_map: HashMap<String, String>,
+    pub build_opt_packages: Vec<String>,
+    pub build_label_map: HashMap<String, String>,
+    pub platforms: String,
+    pub extra_tags: Vec<String>,
+}
+
+impl CachedBuildSpec {
+    pub fn new(spec: &BuildSpec) -> CachedBuildSpec {
+        CachedBuildSpec {
+            version: spec.version.to_string(),
+            no_cache: spec.no_cache,
+            squash: spec.squash,
+            http_proxy: spec.http_proxy.to_string(),
+            https_proxy: spec.https_proxy.to_string(),
+            build_arg_map: spec.build_arg_map.clone(),
+            build_opt_packages: spec.build_opt_packages.clone(),
+            build_label_map: spec.build_label_map.clone(),
+            platforms: spec.platforms.to_string(),
+            extra_tags: spec.extra_tags.clone(),
+        }
+    }
+
+    /// returns the BuildSpec of the image built with these inputs
+    pub fn spec<'s>(&'s self, image: &'s str) -> BuildSpec<'s> {
+        BuildSpec {
+            image,
+            version: self.version.as_str(),
+            no_cache: self.no_cache,
+            squash: self.squash,
+            http_proxy: self.http_proxy.as_str(),
+            https_proxy: self.https_proxy.as_str(),
+            build_arg_map: &self.build_arg_map,
+            build_opt_packages: &self.build_opt_packages,
+            build_label_map: &self.build_label_map,
+            platforms: self.platforms.as_str(),
+            extra_tags: &self.extra_tags,
+        }
+    }
+}
+
+/// HashInput holds everything that ends up in the image of a function
+pub struct HashInput<'s> {
+    pub handler: &'s str,
+    pub language: &'s str,
+    pub template_path: &'s str,
+    pub copy_extra_paths: &'s Vec<String>,
+    pub build_arg_map: &'s HashMap<String, String>,
+    pub build_options: &'s Vec<String>,
+    pub build_label_map: &'s HashMap<String, String>,
+    /// the provenance labels change with every build, only whether they are added is hashed
+    pub provenance: bool,
+    /// the commit of the revision label, empty without the provenance labels
+    pub revision: &'s str,
+    pub squash: bool,
+    pub engine: &'s str,
+    /// files left out of the build context are left out of the hash
+    pub ignore_rules: &'s IgnoreRules,
+}
+
+/// BuildCache keeps one entry per function under its folder
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildCache {
+    dir: PathBuf,
+}
+
+impl Default for BuildCache {
+    fn default() -> Self {
+        BuildCache::new(BUILD_CACHE_DIR)
+    }
+}
+
+impl BuildCache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> BuildCache {
+        BuildCache {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    fn entry_path(&self, function_name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", function_name))
+    }
+
+    /// returns the entry of the function, None when it was never built or the entry is unreadable
+    pub fn lookup(&self, function_name: &str) -> Option<CacheEntry> {
+        let content = std::fs::read_to_string(self.entry_path(function_name)).ok()?;
+        serde_json::from_str(content.as_str()).ok()
+    }
+
+    /// true when the last build of the function produced the same image from the same hash
+    pub fn is_fresh(&self, function_name: &str, image: &str, hash: &str) -> bool {
+        matches!(self.lookup(function_name), Some(entry) if entry.image == image && entry.hash == hash)
+    }
+
+    /// true when the image of the last build of the function was already pushed
+    pub fn is_pushed(&self, function_name: &str, image: &str) -> bool {
+        matches!(self.lookup(function_name), Some(entry) if entry.image == image && entry.pushed)
+    }
+
+    /// records a successful build, the new image still has to be pushed
+    pub fn record_build(&self, function_name: &str, image: &str, hash: &str) -> Result<()> {
+        self.save(
+            function_name,
+            &CacheEntry {
+                image: image.to_string(),
+                hash: hash.to_string(),
+                pushed: false,
+                ..CacheEntry::default()
+            },
+        )
+    }
+
+    /// records the inputs of the last build, to push its image by building it again
+    pub fn record_build_spec(
+        &self,
+        function_name: &str,
+        image: &str,
+        spec: &BuildSpec,
+    ) -> Result<()> {
+        match self.lookup(function_name) {
+            Some(mut entry) if entry.image == image => {
+                entry.build_spec = Some(CachedBuildSpec::new(spec));
+                self.save(function_name, &entry)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// records a successful push of the image, images built without the cache are not recorded
+    pub fn record_push(&self, function_name: &str, image: &str) -> Result<()> {
+        match self.lookup(function_name) {
+            Some(mut entry) if entry.image == image => {
+                entry.pushed = true;
+                self.save(function_name, &entry)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn save(&self, function_name: &str, entry: &CacheEntry) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let content = serde_json::to_string_pretty(entry)
+            .map_err(|e| Error::Custom(format!("can't serialize the build cache: {}", e)))?;
+        std::fs::write(self.entry_path(function_name), content)?;
+        Ok(())
+    }
+}
+
+/// returns the sha256 of the handler, the template, the extra paths and the build settings of a
+/// function
+pub fn build_hash(input: &HashInput) -> Result<String> {
+    let mut hasher = Sha256::new();
+
+    hash_field(&mut hasher, "language", input.language);
+    hash_field(&mut hasher, "engine", input.engine);
+    hash_field(&mut hasher, "squash", &input.squash.to_string());
+    hash_field(&mut hasher, "provenance", &input.provenance.to_string());
+    hash_field(&mut hasher, "revision", input.revision);
+
+    let build_args: BTreeMap<_, _> = input.build_arg_map.iter().collect();
+    for (key, value) in build_args {
+        hash_field(&mut hasher, "build-arg", &format!("{}={}", key, value));
+    }
+    let build_labels: BTreeMap<_, _> = input.build_label_map.iter().collect();
+    for (key, value) in build_labels {
+        hash_field(&mut hasher, "build-label", &format!("{}={}", key, value));
+    }
+    let mut build_options = input.build_options.clone();
+    build_options.sort();
+    for option in build_options {
+        hash_field(&mut hasher, "build-option", option.as_str());
+    }
+
+    // the build and template folders of the handler are not copied into the build context
+    let no_rules = IgnoreRules::default();
+    hash_path(
+        &mut hasher,
+        Path::new(input.handler),
+        &["build", "template"],
+        input.ignore_rules,
+    )?;
+    if Path::new(input.template_path).exists() {
+        hash_path(&mut hasher, Path::new(input.template_path), &[], &no_rules)?;
+    }
+    let mut extra_paths = input.copy_extra_paths.clone();
+    extra_paths.sort();
+    for path in extra_paths {
+        hash_path(
+            &mut hasher,
+            Path::new(path.as_str()),
+            &[],
+            input.ignore_rules,
+        )?;
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn hash_field(hasher: &mut Sha256, name: &str, value: &str) {
+    hasher.update(name.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(value.as_bytes());
+    hasher.update([0u8]);
+}
+
+/// returns the sha256 of the relative names and the contents of the files of a folder, the same
+/// files give the same hash wherever the folder is
+pub fn hash_dir(dir: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hash_files(&mut hasher, dir, &[], &IgnoreRules::default())?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// hashes the path, then the relative names and the contents of the files under it
+fn hash_path(hasher: &mut Sha256, root: &Path, skip: &[&str], rules: &IgnoreRules) -> Result<()> {
+    hash_field(hasher, "path", &root.to_string_lossy());
+    hash_files(hasher, root, skip, rules)
+}
+
+/// hashes the relative names and the contents of the files under the path in a stable order
+fn hash_files(hasher: &mut Sha256, root: &Path, skip: &[&str], rules: &IgnoreRules) -> Result<()> {
+    let mut files = Vec::new();
+    collect_files(root, root, skip, rules, &mut files)
+        .map_err(|e| Error::Custom(format!("can't hash {}: {}", root.to_string_lossy(), e)))?;
+    files.sort();
+
+    for (relative, path) in files {
+        hash_field(hasher, "file", relative.as_str());
+        if std::fs::symlink_metadata(&path)?.file_type().is_symlink() {
+            hash_field(
+                hasher,
+                "link",
+                &std::fs::read_link(&path)?.to_string_lossy(),
+            );
+        } else {
+            hasher.update(std::fs::read(path)?);
+            hasher.update([0u8]);
+        }
+    }
+    Ok(())
+}
+
+/// symbolic links under the root are not followed, their target path is hashed instead
+fn collect_files(
+    root: &Path,
+    path: &Path,
+    skip: &[&str],
+    rules: &IgnoreRules,
+    files: &mut Vec<(String, PathBuf)>,
+) -> Result<()> {
+    if rules.is_ignored(path) {
+        return Ok(());
+    }
+    let is_link = path != root && std::fs::symlink_metadata(path)?.file_type().is_symlink();
+    if is_link || path.is_file() {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        files.push((
+            relative.to_string_lossy().replace('\\', "/"),
+            path.to_path_buf(),
+        ));
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if path == root && skip.contains(&file_name.as_str()) {
+            continue;
+        }
+        collect_files(root, &entry.path(), skip, rules, files)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn input<'s>(
+        handler: &'s str,
+        extra: &'s Vec<String>,
+        args: &'s HashMap<String, String>,
+        options: &'s Vec<String>,
+        rules: &'s IgnoreRules,
+    ) -> HashInput<'s> {
+        HashInput {
+            handler,
+            language: "python3",
+            template_path: "",
+            copy_extra_paths: extra,
+            build_arg_map: args,
+            build_options: options,
+            build_label_map: args,
+            provenance: true,
+            revision: "",
+            squash: false,
+            engine: "docker",
+            ignore_rules: rules,
+        }
+    }
+
+    #[test]
+    fn test_build_hash_changes_with_handler_and_args() {
+        let dir = TempDir::new("build-hash").unwrap();
+        let handler = dir.path().join("fn");
+        std::fs::create_dir_all(handler.join("build")).unwrap();
+        std::fs::write(handler.join("handler.py"), "def handle(req): return req").unwrap();
+        let handler = handler.to_string_lossy().to_string();
+        let extra = vec![];
+        let options = vec![];
+        let mut args = HashMap::new();
+        let rules = IgnoreRules::default();
+
+        let first = build_hash(&input(&handler, &extra, &args, &options, &rules)).unwrap();
+        assert_eq!(
+            first,
+            build_hash(&input(&handler, &extra, &args, &options, &rules)).unwrap()
+        );
+
+        // a new commit changes the revision label of the image
+        let committed = HashInput {
+            revision: "3c5c2a1e0d9b7f6a5c4b3a2918d7e6f5a4b3c2d1",
+            ..input(&handler, &extra, &args, &options, &rules)
+        };
+        assert_ne!(first, build_hash(&committed).unwrap());
+
+        // the build folder of the handler is skipped
+        std::fs::write(Path::new(&handler).join("build").join("out.txt"), "ignored").unwrap();
+        assert_eq!(
+            first,
+            build_hash(&input(&handler, &extra, &args, &options, &rules)).unwrap()
+        );
+
+        args.insert("NPM_VERSION".to_string(), "0.2.2".to_string());
+        let with_args = build_hash(&input(&handler, &extra, &args, &options, &rules)).unwrap();
+        assert_ne!(first, with_args);
+
+        std::fs::write(
+            Path::new(&handler).join("handler.py"),
+            "def handle(req): return 'changed'",
+        )
+        .unwrap();
+        assert_ne!(
+            with_args,
+            build_hash(&input(&handler, &extra, &args, &options, &rules)).unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_hash_does_not_follow_links() {
+        let dir = TempDir::new("build-hash-links").unwrap();
+        let handler = dir.path().join("fn");
+        std::fs::create_dir_all(&handler).unwrap();
+        std::fs::write(handler.join("handler.py"), "def handle(req): return req").unwrap();
+        std::fs::write(dir.path().join("outside.txt"), "outside").unwrap();
+        // a link back to its own folder would never end when followed
+        std::os::unix::fs::symlink(".", handler.join("loop")).unwrap();
+        std::os::unix::fs::symlink("../outside.txt", handler.join("data")).unwrap();
+        let handler = handler.to_string_lossy().to_string();
+        let extra = vec![];
+        let options = vec![];
+        let args = HashMap::new();
+        let rules = IgnoreRules::default();
+
+        let first = build_hash(&input(&handler, &extra, &args, &options, &rules)).unwrap();
+        // the content behind a link is not hashed, only where it points to
+        std::fs::write(dir.path().join("outside.txt"), "changed").unwrap();
+        assert_eq!(
+            first,
+            build_hash(&input(&handler, &extra, &args, &options, &rules)).unwrap()
+        );
+        std::fs::remove_file(Path::new(&handler).join("data")).unwrap();
+        std::os::unix::fs::symlink("../other.txt", Path::new(&handler).join("data")).unwrap();
+        assert_ne!(
+            first,
+            build_hash(&input(&handler, &extra, &args, &options, &rules)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_build_cache_entries() {
+        let dir = TempDir::new("build-cache").unwrap();
+        let cache = BuildCache::new(dir.path().join(".cache"));
+
+        assert!(!cache.is_fresh("fn", "user/fn:latest", "abc"));
+        cache.record_build("fn", "user/fn:latest", "abc").unwrap();
+        assert!(cache.is_fresh("fn", "user/fn:latest", "abc"));
+        assert!(!cache.is_fresh("fn", "user/fn:0.2", "abc"));
+        assert!(!cache.is_fresh("fn", "user/fn:latest", "def"));
+
+        assert!(!cache.is_pushed("fn", "user/fn:latest"));
+        cache.record_push("fn", "user/fn:latest").unwrap();
+        assert!(cache.is_pushed("fn", "user/fn:latest"));
+
+        // a new build has to be pushed again
+        cache.record_build("fn", "user/fn:latest", "def").unwrap();
+        assert!(!cache.is_pushed("fn", "user/fn:latest"));
+
+        cache.record_push("other", "user/other:latest").unwrap();
+        assert_eq!(cache.lookup("other"), None);
+
+        let build_arg_map: HashMap<String, String> =
+            [("GO111MODULE".to_string(), "on".to_string())]
+                .iter()
+                .cloned()
+                .collect();
+        let no_packages = vec![];
+        let no_labels = HashMap::new();
+        let no_tags = vec![];
+        let spec = BuildSpec {
+            image: "user/fn:latest",
+            version: "",
+            no_cache: true,
+            squash: false,
+            http_proxy: "",
+            https_proxy: "",
+            build_arg_map: &build_arg_map,
+            build_opt_packages: &no_packages,
+            build_label_map: &no_labels,
+            platforms: "",
+            extra_tags: &no_tags,
+        };
+        cache.record_build_spec("fn", "user/fn:0.2", &spec).unwrap();
+        assert_eq!(cache.lookup("fn").unwrap().build_spec, None);
+        cache
+            .record_build_spec("fn", "user/fn:latest", &spec)
+            .unwrap();
+        let entry = cache.lookup("fn").unwrap();
+        let cached = entry.build_spec.unwrap();
+        let cached_spec = cached.spec("user/fn:latest");
+        assert_eq!(cached_spec.build_arg_map, &build_arg_map);
+        assert!(cached_spec.no_cache);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::ignore_file::IgnoreRules;
 use std::fs;
 
 // package builder
@@ -31,12 +32,25 @@ use std::fs;
 //CopyFiles copies files from src to destination.
 
 pub fn copy_files(src: &str, dest: &str) -> Result<(), utility::Error> {
+    copy_files_ignoring(src, dest, &IgnoreRules::default())
+}
+
+/// copies files from src to destination, leaving out the paths excluded by the ignore rules
+pub fn copy_files_ignoring(
+    src: &str,
+    dest: &str,
+    rules: &IgnoreRules,
+) -> Result<(), utility::Error> {
     debug_print(format!("after path \n{}\n{}", src, dest));
+    if rules.is_ignored(std::path::Path::new(src)) {
+        debug_print(format!("Ignoring: {}", src));
+        return Ok(());
+    }
     let info = std::fs::metadata(src)?;
 
     if info.is_dir() {
         debug_print(format!("Creating directory: {} at {}", src, dest));
-        return copy_dir(src, dest);
+        return copy_dir(src, dest, rules);
     }
 
     debug_print(format!("cp - {} to {}", src, dest));
@@ -72,7 +86,7 @@ fn ensure_base_dir(path: &str) -> Result<(), utility::Error> {
     fs::create_dir_all(base_path).map_err(|e| utility::Error::Io(e))
 }
 
-fn copy_dir(src: &str, dest: &str) -> Result<(), utility::Error> {
+fn copy_dir(src: &str, dest: &str, rules: &IgnoreRules) -> Result<(), utility::Error> {
     //   let info = std::fs::metadata(src)?;
     fs::create_dir_all(dest)?;
     let infos = fs::read_dir(src)?;
@@ -88,7 +102,7 @@ fn copy_dir(src: &str, dest: &str) -> Result<(), utility::Error> {
         let dest = dest.to_str().unwrap();
         let src = info.path();
         let src = src.to_str().unwrap();
-        copy_files(src, dest)?
+        copy_files_ignoring(src, dest, rules)?
     }
     Ok(())
 }
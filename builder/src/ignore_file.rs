@@ -0,0 +1,132 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+use utility::{Error, Result};
+
+/// ignore file of a function handler
+pub const DOCKER_IGNORE_FILE: &str = ".dockerignore";
+/// ignore file of the project, next to the stack.yml
+pub const FAAS_IGNORE_FILE: &str = ".faasignore";
+
+/// IgnoreRules holds the gitignore-style rules of the handler .dockerignore and the project
+/// .faasignore, the paths left out of the build context
+#[derive(Default)]
+pub struct IgnoreRules {
+    matchers: Vec<(PathBuf, Gitignore)>,
+}
+
+impl IgnoreRules {
+    /// loads the .dockerignore of the handler and the .faasignore of the project root, missing
+    /// files have no rules
+    pub fn load(handler: &str, project_root: &str) -> Result<IgnoreRules> {
+        let mut rules = IgnoreRules::default();
+        rules.add(handler, DOCKER_IGNORE_FILE)?;
+        rules.add(project_root, FAAS_IGNORE_FILE)?;
+        Ok(rules)
+    }
+
+    fn add(&mut self, dir: &str, file_name: &str) -> Result<()> {
+        let file = Path::new(dir).join(file_name);
+        if !file.is_file() {
+            return Ok(());
+        }
+        let root = std::fs::canonicalize(dir)?;
+        let mut builder = GitignoreBuilder::new(&root);
+        if let Some(e) = builder.add(&file) {
+            return Err(Error::Custom(format!("can't read {:?}: {}", file, e)));
+        }
+        let matcher = builder
+            .build()
+            .map_err(|e| Error::Custom(format!("invalid pattern in {:?}: {}", file, e)))?;
+        self.matchers.push((root, matcher));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matchers.is_empty()
+    }
+
+    /// true when a rule excludes the path, a path outside of the folder of an ignore file is not
+    /// matched by its rules
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        if self.matchers.is_empty() {
+            return false;
+        }
+        let path = match std::fs::canonicalize(path) {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+        let is_dir = path.is_dir();
+        self.matchers.iter().any(|(root, matcher)| {
+            path.starts_with(root)
+                && path != *root
+                && matcher
+                    .matched_path_or_any_parents(&path, is_dir)
+                    .is_ignore()
+        })
+    }
+}
+
+/// returns the project root holding the .faasignore, the folder of the stack YAML file or the
+/// current folder when the file is remote or in the current folder
+pub fn project_root(yaml_file: &str) -> String {
+    if yaml_file.starts_with("http://") || yaml_file.starts_with("https://") {
+        return ".".to_string();
+    }
+    match Path::new(yaml_file).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy().to_string(),
+        _ => ".".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ignore_file::{project_root, IgnoreRules};
+    use std::path::Path;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_ignore_rules() {
+        let project = TempDir::new("faasignore").unwrap();
+        let root = project.path();
+        let handler = root.join("fn");
+        for dir in &["node_modules/lib", "src", ".git"] {
+            std::fs::create_dir_all(handler.join(dir)).unwrap();
+        }
+        for file in &[
+            "handler.js",
+            ".env",
+            "src/a.js",
+            "src/keep.log",
+            "debug.log",
+        ] {
+            std::fs::write(handler.join(file), "x").unwrap();
+        }
+        std::fs::write(
+            handler.join(".dockerignore"),
+            "node_modules/\n*.log\n!keep.log\n",
+        )
+        .unwrap();
+        std::fs::write(root.join(".faasignore"), ".env\n.git\n").unwrap();
+
+        let rules = IgnoreRules::load(handler.to_str().unwrap(), root.to_str().unwrap()).unwrap();
+
+        assert!(rules.is_ignored(&handler.join("node_modules")));
+        assert!(rules.is_ignored(&handler.join("node_modules/lib")));
+        assert!(rules.is_ignored(&handler.join("debug.log")));
+        assert!(!rules.is_ignored(&handler.join("src/keep.log")));
+        assert!(rules.is_ignored(&handler.join(".env")));
+        assert!(rules.is_ignored(&handler.join(".git")));
+        assert!(!rules.is_ignored(&handler.join("handler.js")));
+        assert!(!rules.is_ignored(&handler.join("src/a.js")));
+        assert!(!rules.is_ignored(&handler));
+        assert!(!rules.is_ignored(Path::new("/")));
+    }
+
+    #[test]
+    fn test_project_root() {
+        assert_eq!(project_root("stack.yml"), ".");
+        assert_eq!(project_root(""), ".");
+        assert_eq!(project_root("functions/stack.yml"), "functions");
+        assert_eq!(project_root("https://domain/path/stack.yml"), ".");
+    }
+}
@@ -1,5 +1,10 @@
+pub mod archive;
+pub mod backend;
 pub mod build;
+pub mod cache;
 pub mod copy;
+pub mod ignore_file;
+pub mod provenance;
 pub mod publish;
 
 pub use copy::*;
@@ -1,9 +1,11 @@
+use crate::archive::{archive_build_context, BuildManifest};
+use crate::backend::{BuildBackend, BuildSpec};
 use crate::build::{
-    build_flag_slice, create_build_context, ensure_handler_path, get_build_option_packages,
-    get_image_tag_values, is_language_template, DockerBuild,
+    create_build_context, ensure_handler_path, get_build_option_packages, get_image_name,
+    is_language_template, run_engine_commands,
 };
-use exec::command_exec;
-use schema::image::{build_image_name, BuildFormat};
+use crate::provenance::Provenance;
+use schema::image::BuildFormat;
 use stack::language_template::{is_valid_template, parse_yaml_for_language_template};
 use std::collections::HashMap;
 use std::io::ErrorKind;
@@ -20,11 +22,21 @@ pub struct PublishImage<'s> {
     pub build_arg_map: &'s HashMap<String, String>,
     pub build_options: &'s Vec<String>,
     pub tag_mode: BuildFormat,
+    /// renders the image tag when not empty, see schema::image::render_tag_template
+    pub tag_template: &'s str,
     pub build_label_map: &'s HashMap<String, String>,
     pub quiet_build: bool,
     pub copy_extra_paths: &'s Vec<String>,
     pub platforms: &'s str,
     pub extra_tags: &'s Vec<String>,
+    pub engine: &'static dyn BuildBackend,
+    /// adds the OCI provenance labels of the git repository and the template to the image
+    pub provenance: bool,
+    /// folder receiving the build context of a shrinkwrap as a tar.gz with its build manifest,
+    /// nothing is archived when empty
+    pub archive: &'s str,
+    /// folder of the stack YAML file, its .faasignore applies to the handlers
+    pub project_root: &'s str,
 }
 
 pub async fn publish_image(publish: &PublishImage<'_>) -> Result<()> {
@@ -39,14 +51,12 @@ pub async fn publish_image(publish: &PublishImage<'_>) -> Result<()> {
             .await
             .map_err(|e| Error::Custom(format!("error reading language template: {}", e)))?;
 
-        let (branch, version) = get_image_tag_values(publish.tag_mode)?;
-
-        let image_name = build_image_name(
+        let image_name = get_image_name(
             publish.tag_mode,
+            publish.tag_template,
             publish.image,
-            version.as_str(),
-            branch.as_str(),
-        );
+            publish.handler,
+        )?;
         ensure_handler_path(publish.handler).map_err(|_e| {
             Error::Custom(format!(
                 "building {}, {} is an invalid path",
@@ -61,6 +71,7 @@ pub async fn publish_image(publish: &PublishImage<'_>) -> Result<()> {
             is_language_template(publish.language),
             lang_template.handler_folder.as_str(),
             publish.copy_extra_paths,
+            publish.project_root,
         )?;
 
         println!(
@@ -68,14 +79,6 @@ pub async fn publish_image(publish: &PublishImage<'_>) -> Result<()> {
             image_name, publish.language
         );
 
-        if publish.shrinkwrap {
-            format!(
-                "{} shrink-wrapped to {}\n",
-                publish.function_name, temp_path
-            );
-            return Ok(());
-        }
-
         let build_opt_packages = get_build_option_packages(
             publish.build_options,
             publish.language,
@@ -84,7 +87,13 @@ pub async fn publish_image(publish: &PublishImage<'_>) -> Result<()> {
 
         let http_proxy = std::env::var("http_proxy").unwrap_or_default();
         let https_proxy = std::env::var("https_proxy").unwrap_or_default();
-        let docker_build_val = DockerBuild {
+        let build_label_map = if publish.provenance {
+            Provenance::current(publish.language, lang_template.version.as_str())
+                .with_labels(publish.build_label_map)
+        } else {
+            publish.build_label_map.clone()
+        };
+        let spec = BuildSpec {
             image: image_name.as_str(),
             version: "",
             no_cache: publish.nocache,
@@ -93,27 +102,52 @@ pub async fn publish_image(publish: &PublishImage<'_>) -> Result<()> {
             https_proxy: https_proxy.as_str(),
             build_arg_map: publish.build_arg_map,
             build_opt_packages: &build_opt_packages,
-            build_label_map: publish.build_label_map,
+            build_label_map: &build_label_map,
             platforms: publish.platforms,
             extra_tags: publish.extra_tags,
         };
 
-        let (command, args) = get_docker_buildx_command(docker_build_val);
-        println!("Publishing with command: {} {:?}\n", command, args);
-
-        let cmd_args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
-
-        let res = command_exec(temp_path.as_str(), &command, &cmd_args)?;
+        if publish.shrinkwrap {
+            println!(
+                "{} shrink-wrapped to {}\n",
+                publish.function_name, temp_path
+            );
+            if !publish.archive.is_empty() {
+                let manifest = BuildManifest::new(
+                    publish.function_name,
+                    publish.language,
+                    publish.build_options,
+                    &spec,
+                );
+                let archive = archive_build_context(&temp_path, publish.archive, manifest)?;
+                println!(
+                    "{} archived to {}\n",
+                    publish.function_name,
+                    archive.to_string_lossy()
+                );
+            }
+            return Ok(());
+        }
 
-        if !res.success() {
-            Err(Error::Custom(format!(
-                "[{}] received non-zero exit code from build, error: {:?}",
-                publish.function_name, res
-            )))
-        } else {
-            println!("Image: {} published.\n", image_name);
-            Ok(())
+        // a manifest list left by an earlier publish would keep the images of that build
+        for (command, args) in publish.engine.publish_cleanup(&spec) {
+            let _ = std::process::Command::new(command)
+                .args(&args)
+                .current_dir(&temp_path)
+                .output();
+        }
+        let commands = publish.engine.publish_commands(&spec)?;
+        for (command, args) in &commands {
+            println!("Publishing with command: {} {:?}\n", command, args);
         }
+        run_engine_commands(
+            temp_path.as_str(),
+            publish.function_name,
+            &commands,
+            publish.quiet_build,
+        )?;
+        println!("Image: {} published.\n", image_name);
+        Ok(())
     } else {
         Err(Error::Custom(format!(
             "language template: {} not supported, build a custom Dockerfile",
@@ -121,48 +155,3 @@ pub async fn publish_image(publish: &PublishImage<'_>) -> Result<()> {
         )))
     }
 }
-
-fn get_docker_buildx_command(build: DockerBuild) -> (&'static str, Vec<String>) {
-    let mut flag_slice = build_flag_slice(
-        build.no_cache,
-        build.squash,
-        build.http_proxy,
-        build.https_proxy,
-        build.build_arg_map,
-        build.build_opt_packages,
-        build.build_label_map,
-    );
-
-    /// pushOnly defined at https://github.com/docker/buildx
-    const PUSH_ONLY: &str = "--output=type=registry,push=true";
-    let mut args = vec![
-        "buildx".to_string(),
-        "build".to_string(),
-        "--progress=plain".to_string(),
-        "--platform=".to_string() + build.platforms,
-        PUSH_ONLY.to_string(),
-    ];
-
-    args.append(&mut flag_slice);
-    args.push("--tag".to_string());
-    args.push(build.image.to_string());
-    args.push(".".to_string());
-
-    for t in build.extra_tags {
-        let tag = if let Some(index) = build.image.rfind(':') {
-            apply_tag(index, build.image, t)
-        } else {
-            apply_tag(build.image.len() - 1, build.image, t)
-        };
-        args.push("--tag".to_string());
-        args.push(tag);
-    }
-
-    ("docker", args)
-}
-
-fn apply_tag(index: usize, base_image: &str, tag: &str) -> String {
-    // return fmt.Sprintf("%s:%s", baseImage[:index], tag);
-    let (arr, _) = base_image.split_at(index);
-    format!("{}:{}", arr, tag)
-}
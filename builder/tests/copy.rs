@@ -51,3 +51,25 @@ fn setup_source_folder(files_count: i32, _mode: i32) -> Result<TempDir, Error> {
 
     Ok(tmp_dir)
 }
+
+#[test]
+fn test_copy_files_ignoring() {
+    let src_dir = TempDir::new("00openfaas-test-ignore-source-").unwrap();
+    let dest_dir = TempDir::new("00openfaas-test-ignore-destination-").unwrap();
+    let src = src_dir.path();
+    std::fs::create_dir_all(src.join("node_modules/lib")).unwrap();
+    std::fs::write(src.join("node_modules/lib/index.js"), "lib").unwrap();
+    std::fs::write(src.join("handler.js"), "handler").unwrap();
+    std::fs::write(src.join(".env"), "SECRET=1").unwrap();
+    std::fs::write(src.join(".dockerignore"), "node_modules\n.env\n").unwrap();
+
+    let rules =
+        builder::ignore_file::IgnoreRules::load(src.to_str().unwrap(), "/nonexistent").unwrap();
+    let dest = dest_dir.path().join("function");
+    builder::copy_files_ignoring(src.to_str().unwrap(), dest.to_str().unwrap(), &rules).unwrap();
+
+    assert!(dest.join("handler.js").is_file());
+    assert!(dest.join(".dockerignore").is_file());
+    assert!(!dest.join(".env").exists());
+    assert!(!dest.join("node_modules").exists());
+}
@@ -3,9 +3,17 @@ use crate::faas::DEFAULT_GATEWAY;
 use crate::{CommandAppend, State};
 use chrono::Timelike;
 use clap::{App, ArgMatches, SubCommand};
-use config::config_file::{update_auth_config, OAUTH_2AUTH_TYPE};
+use config::config_file::{
+    save_auth_config, set_credential_helper, update_auth_config, AuthConfig, OAuth2Config,
+    OAUTH_2AUTH_TYPE,
+};
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Request, Response, Server};
+use proxy::oauth2::{
+    client_credentials_token, exchange_code, now_unix, poll_device_token, random_string,
+    request_device_code, Pkce, TokenResponse, AUTHORIZATION_CODE_GRANT, CLIENT_CREDENTIALS_GRANT,
+    DEVICE_CODE_GRANT,
+};
 use reqwest::{Method, StatusCode, Url};
 use std::collections::HashMap;
 use std::convert::Infallible;
@@ -19,6 +27,18 @@ use utility::{Error, Result};
 struct Data {
     gateway: String,
     tx: tokio::sync::mpsc::Sender<()>,
+    code_flow: Option<CodeFlow>,
+}
+
+/// what the local listener needs to exchange the code of the authorization code grant
+#[derive(Debug, Clone)]
+struct CodeFlow {
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    state: String,
+    pkce: Pkce,
 }
 
 static mut GATEWAY: String = String::new();
@@ -41,26 +61,16 @@ const BUILD_CAPTURE_FRAGMENT: &str = r#"<html>
 </body>
 </html>"#;
 
-pub(crate) struct Auth;
-
-#[derive(serde::Serialize, Debug)]
-struct ClientCredentialsReq<'s> {
-    pub client_id: &'s str,     // `json:"client_id"`
-    pub client_secret: &'s str, // `json:"client_secret"`
-    pub audience: &'s str,      // `json:"audience"`
-    pub grant_type: &'s str,    // `json:"grant_type"`
-}
+const AUTHORIZATION_COMPLETE: &str = r#"<html>
+<head>
+<title>OpenFaaS CLI Authorization flow</title>
+</head>
+<body>
+ Authorization flow complete. Please close this browser window.
+</body>
+</html>"#;
 
-#[derive(serde::Deserialize, Debug)]
-struct ClientCredentialsToken<'s> {
-    #[serde(default)]
-    pub access_token: &'s str, // `json:"access_token"`
-    #[serde(default)]
-    pub scope: &'s str, // `json:"scope"`
-    pub expires_in: Option<i32>, //`json:"expires_in"`
-    #[serde(default)]
-    pub token_type: &'s str, // `json:"token_type"`
-}
+pub(crate) struct Auth;
 
 impl CommandAppend for Auth {
     #[inline(always)]
@@ -69,15 +79,17 @@ impl CommandAppend for Auth {
             SubCommand::with_name("auth")
                 .about(r#"Generate and save the registry authentication file"#)
                 .args_from_usage("
-                    --auth-url         [auth-url]                      'OAuth2 Authorize URL i.e. http://idp/oauth/authorize'
+                    --auth-url         [auth-url]                      'OAuth2 Authorize URL i.e. http://idp/oauth/authorize, or the device authorization URL for the device_code grant'
+                    --token-url        [token-url]                     'OAuth2 Token URL i.e. http://idp/oauth/token, required by the authorization_code and device_code grants'
                     --client-id        [client-id]                      'OAuth2 client_id'
                     --listen-port      [listen-port]                     'OAuth2 local port for receiving cookie'
                     --audience         [audience]                        'OAuth2 audience'
                     --launch-browse                                      'Launch browser for OAuth2 redirect'
                     --redirect-host    [redirect-host]                           'Host for OAuth2 redirection in the implicit flow including URL scheme'
                     --scope            [scope]                          'scope for OAuth2 flow - i.e. \"openid profile\"'
-                    --grant            [grant]                          'grant for OAuth2 flow - either implicit, implicit-id or client_credentials'
-                    --client-secret    [client-secret]                 'OAuth2 client_secret, for use with client_credentials grant'
+                    --grant            [grant]                          'grant for OAuth2 flow - either authorization_code, device_code, implicit, implicit-id or client_credentials'
+                    --client-secret    [client-secret]                 'OAuth2 client_secret, for use with client_credentials grant or a confidential client of the authorization_code and device_code grants, it is only saved by a --credential-helper, set OPENFAAS_CLIENT_SECRET to renew the token without one'
+                    --credential-helper [credential-helper]            'Keep the token in a docker-credential-<helper> store, i.e. pass, secretservice, osxkeychain or wincred'
              ",
                 )
         );
@@ -103,6 +115,7 @@ impl Auth {
             let scope = a_args.value_of("scope").unwrap_or("openid profile");
             let grant = a_args.value_of("grant").unwrap_or("implicit");
             let client_secret = a_args.value_of("client-secret").unwrap_or_default();
+            let token_url = a_args.value_of("token-url").unwrap_or_default();
             //todo check this
             let launch_browser = true; //a_args.is_present("launch-browser");
             let listen_port = listen_port.parse().map_err(|_| {
@@ -110,6 +123,9 @@ impl Auth {
             })?;
 
             check_values(auth_url, client_id)?;
+            if let Some(helper) = a_args.value_of("credential-helper") {
+                set_credential_helper(gateway, helper)?;
+            }
             let mut data = AuthData {
                 gateway,
                 grant,
@@ -119,6 +135,8 @@ impl Auth {
                 scope,
                 audience,
                 client_id,
+                client_secret,
+                token_url,
                 //token: "",
                 launch_browser,
             };
@@ -129,20 +147,20 @@ impl Auth {
             } else if grant == "implicit-id" {
                 data.grant = "id_token";
                 auth_implicit(&data).await?;
+            } else if grant == AUTHORIZATION_CODE_GRANT {
+                check_token_url(token_url)?;
+                data.grant = "code";
+                auth_implicit(&data).await?;
+            } else if grant == "device_code" {
+                check_token_url(token_url)?;
+                auth_device_code(&data).await?;
             } else if grant == "client_credentials" {
-                auth_client_credentials(
-                    client_id,
-                    client_secret,
-                    audience,
-                    grant,
-                    auth_url,
-                    gateway,
-                )
-                .await?;
+                auth_client_credentials(client_id, client_secret, audience, auth_url, gateway)
+                    .await?;
             } else {
                 return Err(State::Custom(format!(
-                    "invalid grant {} please enter one of these grants 'implicit-id' ,\
-                 'implicit-id', 'client_credentials or implicit'",
+                    "invalid grant {} please enter one of these grants 'authorization_code', \
+                 'device_code', 'implicit-id', 'client_credentials or implicit'",
                     grant
                 )));
             }
@@ -178,6 +196,15 @@ fn check_values(auth_url: &str, client_id: &str) -> Result<()> {
     Ok(())
 }
 
+fn check_token_url(token_url: &str) -> Result<()> {
+    if token_url.is_empty() || url::Url::parse(token_url).is_err() {
+        return Err(Error::Custom(
+            "--token-url is required and must be a valid URL for this grant".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 struct AuthData<'s> {
     gateway: &'s str,
     grant: &'s str,
@@ -187,9 +214,13 @@ struct AuthData<'s> {
     scope: &'s str,
     audience: &'s str,
     client_id: &'s str,
+    client_secret: &'s str,
+    token_url: &'s str,
     //token: &'s str,
     launch_browser: bool,
 }
+/// runs a grant needing the browser, the implicit ones or the authorization code one with PKCE,
+/// the redirect is received by a local listener
 async fn auth_implicit(auth_data: &AuthData<'_>) -> Result<()> {
     // We'll bind to 127.0.0.1:3000
     //http://127.0.0.1/
@@ -200,9 +231,24 @@ async fn auth_implicit(auth_data: &AuthData<'_>) -> Result<()> {
 
     let (tx, mut rx) = tokio::sync::mpsc::channel::<()>(1);
 
+    let redirect_uri = make_redirect_url(auth_data.redirect_host, auth_data.listen_port)?;
+    let code_flow = if auth_data.grant == "code" {
+        Some(CodeFlow {
+            token_url: auth_data.token_url.to_string(),
+            client_id: auth_data.client_id.to_string(),
+            client_secret: auth_data.client_secret.to_string(),
+            redirect_uri: redirect_uri.to_string(),
+            state: random_string(32),
+            pkce: Pkce::new(),
+        })
+    } else {
+        None
+    };
+
     let data = Data {
         gateway: auth_data.gateway.to_string(),
         tx: tx.clone(),
+        code_flow: code_flow.clone(),
     };
     let data = Arc::new(data);
 
@@ -250,20 +296,30 @@ async fn auth_implicit(auth_data: &AuthData<'_>) -> Result<()> {
         let mut q = auth_url_val.query_pairs_mut();
 
         // let req = client.request(Method::GET,)
-        q.append_pair("response_mode", "fragment");
+        if code_flow.is_none() {
+            q.append_pair("response_mode", "fragment");
+        }
         q.append_pair("audience", auth_data.audience);
         q.append_pair("client_id", auth_data.client_id);
 
         let now = chrono::Local::now().timestamp_nanos().to_string();
         q.append_pair("nonce", now.as_str());
-        let uri = make_redirect_url(auth_data.redirect_host, auth_data.listen_port)?;
-        println!("redirect {}\n", uri);
-        q.append_pair("redirect_uri", uri.as_str());
+        println!("redirect {}\n", redirect_uri);
+        q.append_pair("redirect_uri", redirect_uri.as_str());
         q.append_pair("response_type", auth_data.grant);
 
         q.append_pair("scope", auth_data.scope);
         // let state = chrono::Local::now().timestamp_nanos().to_string();
-        q.append_pair("state", now.as_str());
+        match &code_flow {
+            Some(code_flow) => {
+                q.append_pair("state", code_flow.state.as_str());
+                q.append_pair("code_challenge", code_flow.pkce.challenge.as_str());
+                q.append_pair("code_challenge_method", "S256");
+            }
+            None => {
+                q.append_pair("state", now.as_str());
+            }
+        }
     }
 
     //let launch_uri = format!("https://abdelmonem.us.auth0.com/authorize?%26response_mode=fragment&audience=https%3A%2F%2Fabdelmonem.us.auth0.com%2Fapi%2Fv2%2F&client_id=0dFGCpXdzAzAKWejwYywI8hmVP7wTqsv&nonce=1625617096342424200&redirect_uri=http%3A%2F%2F127.0.0.1%3A31111%2Foauth%2Fcallback&response_type=id_token&scope=oidc+profile&state=1625617096342424200");
@@ -302,6 +358,21 @@ async fn request_handler(req: Request<Body>, data: Arc<Data>) -> Result<Response
     })?;
     let pairs: HashMap<_, _> = url.query_pairs().into_owned().collect();
 
+    if let Some(code_flow) = &data.code_flow {
+        if pairs.contains_key("code") || pairs.contains_key("error") {
+            let res = handle_code_redirect(gateway, code_flow, &pairs).await;
+            if let Err(e) = &res {
+                println!("{}", e);
+            }
+            data.tx.send(()).await.map_err(|e| {
+                println!("{:?}", e);
+                Error::Custom(e.to_string())
+            })?;
+            res?;
+            return Ok(Response::new(AUTHORIZATION_COMPLETE.into()));
+        }
+    }
+
     if let Some(v) = pairs.get("fragment") {
         //fmt.Println("v: ",v)
         let url = "http://example.com?".to_string() + v;
@@ -385,65 +456,127 @@ faas-cli list --gateway "{}""#,
     );
 }
 
+/// checks the state of the authorization code redirect, then exchanges its code for a token
+async fn handle_code_redirect(
+    gateway: &str,
+    code_flow: &CodeFlow,
+    pairs: &HashMap<String, String>,
+) -> Result<()> {
+    if let Some(error) = pairs.get("error") {
+        return Err(Error::Custom(format!(
+            "authorization failed: {} {}",
+            error,
+            pairs
+                .get("error_description")
+                .map(|d| d.as_str())
+                .unwrap_or_default()
+        )));
+    }
+    if pairs.get("state") != Some(&code_flow.state) {
+        return Err(Error::Custom(
+            "the state of the redirect does not match the one sent to the IdP".to_string(),
+        ));
+    }
+    let code = pairs.get("code").map(|c| c.as_str()).unwrap_or_default();
+
+    let token = exchange_code(
+        code_flow.token_url.as_str(),
+        code,
+        code_flow.redirect_uri.as_str(),
+        code_flow.client_id.as_str(),
+        code_flow.client_secret.as_str(),
+        code_flow.pkce.verifier.as_str(),
+    )
+    .await?;
+
+    let oauth2 = OAuth2Config {
+        token_url: code_flow.token_url.clone(),
+        grant: AUTHORIZATION_CODE_GRANT.to_string(),
+        client_id: code_flow.client_id.clone(),
+        client_secret: code_flow.client_secret.clone(),
+        ..Default::default()
+    };
+    save_oauth2_token(gateway, &token, oauth2)
+}
+
+/// runs the device authorization grant, the user approves the printed code on another device
+async fn auth_device_code(auth_data: &AuthData<'_>) -> Result<()> {
+    let device = request_device_code(
+        auth_data.auth_url,
+        auth_data.client_id,
+        auth_data.scope,
+        auth_data.audience,
+    )
+    .await?;
+
+    println!(
+        "To sign in, open {} and enter the code: {}",
+        device.verification_uri, device.user_code
+    );
+    if !device.verification_uri_complete.is_empty() {
+        println!("or open {}", device.verification_uri_complete);
+    }
+    println!("Waiting for the authorization to be approved...");
+
+    let token = poll_device_token(
+        auth_data.token_url,
+        auth_data.client_id,
+        auth_data.client_secret,
+        &device,
+        device.poll_interval(),
+    )
+    .await?;
+
+    let oauth2 = OAuth2Config {
+        token_url: auth_data.token_url.to_string(),
+        grant: DEVICE_CODE_GRANT.to_string(),
+        client_id: auth_data.client_id.to_string(),
+        client_secret: auth_data.client_secret.to_string(),
+        audience: auth_data.audience.to_string(),
+        ..Default::default()
+    };
+    save_oauth2_token(auth_data.gateway, &token, oauth2)
+}
+
+/// saves an oauth2 token of the gateway with its expiry and refresh token
+fn save_oauth2_token(gateway: &str, token: &TokenResponse, oauth2: OAuth2Config) -> Result<()> {
+    let oauth2 = OAuth2Config {
+        expiry: token.expiry(now_unix()),
+        refresh_token: token.refresh_token.clone(),
+        ..oauth2
+    };
+    save_auth_config(AuthConfig {
+        gateway: gateway.to_string(),
+        auth: OAUTH_2AUTH_TYPE.into(),
+        token: token.access_token.clone(),
+        oauth2: Some(oauth2),
+    })?;
+
+    colour::green!("credentials saved for {}\n", gateway);
+    print_example_token_usage(gateway, token.access_token.as_str());
+    Ok(())
+}
+
+/// runs the client_credentials grant and saves the token together with what is needed
+/// to run the grant again once the token expires
 async fn auth_client_credentials(
     client_id: &str,
     client_secret: &str,
     audience: &str,
-    grant_type: &str,
     auth_url: &str,
     gateway: &str,
 ) -> Result<()> {
-    let body = ClientCredentialsReq {
-        client_id,
-        client_secret,
-        audience,
-        grant_type,
+    let token = client_credentials_token(auth_url, client_id, client_secret, audience).await?;
+
+    let oauth2 = OAuth2Config {
+        token_url: auth_url.to_string(),
+        grant: CLIENT_CREDENTIALS_GRANT.to_string(),
+        client_id: client_id.to_string(),
+        client_secret: client_secret.to_string(),
+        audience: audience.to_string(),
+        ..Default::default()
     };
-    let body_bytes = serde_json::to_string(&body)
-        .map_err(|e| Error::Custom(format!("unable to serialize {:?} ,with error {}", body, e)))?;
-    let url = reqwest::Url::parse(auth_url).map_err(|e| Error::Custom(e.to_string()))?;
-
-    let client = reqwest::Client::new();
-    let req = client
-        .request(Method::POST, url)
-        .header("Content-Type", "application/json")
-        .body(body_bytes)
-        .build()
-        .map_err(|e| Error::Custom(format!("can't format a request: {:?}", e)))?;
-
-    let res = client
-        .execute(req)
-        .await
-        .map_err(|e| Error::Custom(format!("cannot POST to {} and the error {}", auth_url, e)))?;
-
-    let status_code = res.status();
-    let token_data = res
-        .text()
-        .await
-        .map_err(|e| Error::Custom(format!("cannot read body and the error {}", e)))?;
-
-    if status_code == StatusCode::OK {
-        if !token_data.is_empty() {
-            let token: ClientCredentialsToken =
-                serde_json::from_str(token_data.as_str()).map_err(|e| {
-                    Error::Custom(format!(
-                        "can't deserialize {} ClientCredentialsToken to  and the error {}",
-                        token_data, e
-                    ))
-                })?;
-
-            update_auth_config(gateway, token.access_token, OAUTH_2AUTH_TYPE.into())?;
-
-            colour::green!("credentials saved for {}", gateway);
-            print_example_token_usage(gateway, token.access_token);
-        }
-        Ok(())
-    } else {
-        Err(Error::Custom(format!(
-            "cannot authenticate, code: {}.\nResponse: {}",
-            status_code, token_data
-        )))
-    }
+    save_oauth2_token(gateway, &token, oauth2)
 }
 
 fn make_redirect_url(host: &str, port: u16) -> Result<Url> {
@@ -462,3 +595,45 @@ fn make_redirect_url(host: &str, port: u16) -> Result<Url> {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::{handle_code_redirect, CodeFlow};
+    use proxy::oauth2::Pkce;
+    use std::collections::HashMap;
+
+    fn code_flow() -> CodeFlow {
+        CodeFlow {
+            token_url: "http://idp.test/oauth/token".into(),
+            client_id: "client".into(),
+            client_secret: String::new(),
+            redirect_uri: "http://127.0.0.1:31111/oauth/callback".into(),
+            state: "expected-state".into(),
+            pkce: Pkce::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_code_redirect_rejects_state() {
+        let pairs: HashMap<String, String> = vec![
+            ("code".to_string(), "the-code".to_string()),
+            ("state".to_string(), "other-state".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let res = handle_code_redirect("http://openfaas.test", &code_flow(), &pairs).await;
+        assert!(format!("{:?}", res).contains("state of the redirect does not match"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_code_redirect_error() {
+        let pairs: HashMap<String, String> = vec![
+            ("error".to_string(), "access_denied".to_string()),
+            ("error_description".to_string(), "user denied".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let res = handle_code_redirect("http://openfaas.test", &code_flow(), &pairs).await;
+        assert!(format!("{:?}", res).contains("authorization failed: access_denied user denied"));
+    }
+}
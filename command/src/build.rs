@@ -1,21 +1,26 @@
 use crate::deploy::{merge_map, merge_slice, parse_map};
 use crate::faas::check_and_set_default_yaml;
-use crate::fetch_template::{fetch_templates, DEFAULT_TEMPLATE_REPOSITORY};
-use crate::priority::{get_template_url, TEMPLATE_URL_ENVIRONMENT};
+use crate::fetch_template::{fetch_templates, DEFAULT_TEMPLATE_REPOSITORY, TEMPLATE_DIRECTORY};
+use crate::priority::{get_build_engine, get_template_url, TEMPLATE_URL_ENVIRONMENT};
+use crate::stack_summary::{StackSummary, StepResult, StepStatus};
+use crate::template_lock::{check_frozen_templates, LockMode, TEMPLATE_LOCK_FILE};
 use crate::template_pull_stack::filter_existing_templates;
 use crate::template_pull_stack::pull_stack_templates;
+use crate::template_source::parse_template_location;
 use crate::validate::validate_language_flag;
 use crate::{CommandAppend, State};
+use builder::backend::BuildBackend;
 use builder::build::ADDITIONAL_PACKAGE_BUILD_ARGS;
-use builder::build::{build_image, BuildImage};
+use builder::build::{build_image, BuildImage, BuildOutcome};
+use builder::ignore_file::project_root;
 use clap::{App, Arg, ArgMatches, SubCommand};
-use schema::image::{BuildFormat, TBuildFormat};
+use schema::image::{select_tag_template, tag_template_of, BuildFormat, TBuildFormat};
 use stack::schema::{Function, Services};
 use stack::stack::parse_yaml_file;
 use std::collections::HashMap;
+use std::path::Path;
 use std::time::Instant;
 use utility::{Error, Result};
-use versioncontol::parse::parse_panned_remote;
 
 pub(crate) fn generate_build_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
     app.args_from_usage(
@@ -28,9 +33,17 @@ pub(crate) fn generate_build_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
                           --squash                          'Use Docker's squash flag for smaller images [experimental]'
                           --parallel [parallel]             'Build in parallel to depth specified.'
                           --shrinkwrap                      'Just write files to ./build/ folder for shrink-wrapping'
+                          --list-context                    'With --shrinkwrap, list the files of each build context'
+                          --archive [archive]               'With --shrinkwrap, write each build context as a reproducible tar.gz and a JSON build manifest to this folder'
                          --quiet                          'Perform a quiet build, without showing output from Docker'
                          --disable-stack-pull              'Disables the template configuration in the stack.yml'
-                         --tag [tag]                         'Override latest tag on function Docker image, accepts latest, sha, branch, or describe'
+                         --frozen                          'Fail when the templates don't match template.lock instead of updating it'
+                         --offline                         'Pull the templates from the template cache only, without fetching their repositories'
+                         --tag [tag]                         'Override latest tag on function Docker image, accepts latest, sha, branch, describe or template:{branch}-{sha:7}'
+                         --engine [engine]                   'Container build engine: docker, podman, buildah or buildctl'
+                         --force                             'Rebuild functions even when the build cache has them up to date'
+                         --keep-going                        'Carry on with the other functions when one of them fails'
+                         --no-provenance                     'Do not add the OCI provenance labels (revision, source, created, version and template) to the images'
             ")
         .arg(
             Arg::with_name("build-arg")
@@ -76,13 +89,20 @@ impl CommandAppend for Build {
         let subcommand = SubCommand::with_name("build").about(
             r#"Builds OpenFaaS function containers either via the supplied YAML config using
 the "--yaml" flag (which may contain multiple function definitions), or directly
-via flags.`,
+via flags. Files matched by a .dockerignore in the handler or a .faasignore next to
+the YAML file are left out of the build context.`,
 	Example: `  faas-cli build -f https://domain/path/myfunctions.yml
   faas-cli build -f ./stack.yml --no-cache --build-arg NPM_VERSION=0.2.2
   faas-cli build -f ./stack.yml --build-option dev
   faas-cli build -f ./stack.yml --tag sha
   faas-cli build -f ./stack.yml --tag branch
   faas-cli build -f ./stack.yml --tag describe
+  faas-cli build -f ./stack.yml --tag 'template:{branch}-{sha:7}-{timestamp}'
+  faas-cli build -f ./stack.yml --shrinkwrap --list-context
+  faas-cli build -f ./stack.yml --shrinkwrap --archive out/
+  faas-cli build -f ./stack.yml --no-provenance
+  faas-cli build -f ./stack.yml --frozen
+  faas-cli build -f ./stack.yml --offline
   faas-cli build -f ./stack.yml --filter "*gif*"
   faas-cli build -f ./stack.yml --regex "fn[0-9]_.*"
   faas-cli build --image=my_image --lang=python --handler=/path/to/fn/
@@ -99,7 +119,7 @@ impl Build {
     #[inline(always)]
     pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
         if let Some(b_args) = args.subcommand_matches("build") {
-            build_from_args(b_args).await?;
+            build_from_args(b_args, &mut Vec::new()).await?;
             Err(State::Matched)
             //pushStack(&services, parallel, tagFormat)
         } else {
@@ -108,7 +128,12 @@ impl Build {
     }
 }
 
-pub(crate) async fn build_from_args(b_args: &ArgMatches<'_>) -> utility::Result<()> {
+/// builds the functions of the arguments, the functions of the stack failing to build are added
+/// to failed
+pub(crate) async fn build_from_args(
+    b_args: &ArgMatches<'_>,
+    failed: &mut Vec<String>,
+) -> utility::Result<()> {
     let regex = b_args.value_of("regex").unwrap_or("");
     let filter = b_args.value_of("filter").unwrap_or("");
     let envsubst = true; //b_args.is_present("envsubst");
@@ -123,6 +148,15 @@ pub(crate) async fn build_from_args(b_args: &ArgMatches<'_>) -> utility::Result<
     let shrinkwrap = b_args.is_present("shrinkwrap");
     let quiet_build = b_args.is_present("quiet");
     let disable_stack_pull = b_args.is_present("disable-stack-pull");
+    let lock_mode = LockMode::from_flags(b_args.is_present("frozen"), false)?;
+    let offline = b_args.is_present("offline");
+    let engine = get_build_engine(b_args)?;
+    let force = b_args.is_present("force");
+    let keep_going = b_args.is_present("keep-going");
+    let list_context = b_args.is_present("list-context");
+    let provenance = !b_args.is_present("no-provenance");
+    let archive = b_args.value_of("archive").unwrap_or_default();
+    let project_root = project_root(yaml_file);
 
     let language = b_args.value_of("lang").unwrap_or_default();
     //  let network = b_args.value_of("network").unwrap_or(DEFAULT_NETWORK);
@@ -178,6 +212,17 @@ pub(crate) async fn build_from_args(b_args: &ArgMatches<'_>) -> utility::Result<
     let mapped = parse_build_args(&build_args)?;
     let build_label_map = parse_map(&build_label, "build-label")?;
 
+    if list_context && !shrinkwrap {
+        return Err(Error::Custom(
+            "the --list-context flag requires --shrinkwrap".to_string(),
+        ));
+    }
+    if !archive.is_empty() && !shrinkwrap {
+        return Err(Error::Custom(
+            "the --archive flag requires --shrinkwrap".to_string(),
+        ));
+    }
+
     if parallel < 1 {
         return Err(Error::Custom(
             "the --parallel flag must be great than 0".to_string(),
@@ -192,10 +237,18 @@ pub(crate) async fn build_from_args(b_args: &ArgMatches<'_>) -> utility::Result<
     let temp_url = std::env::var(TEMPLATE_URL_ENVIRONMENT).unwrap_or_default();
     let template_address = get_template_url("", temp_url.as_str(), DEFAULT_TEMPLATE_REPOSITORY);
 
-    pull_templates(template_address.as_str())
+    pull_templates(template_address.as_str(), lock_mode, offline)
+        .await
         .map_err(|e| Error::Custom(format!("could not pull templates for OpenFaaS: {}", e)))?;
 
     if services.functions.is_empty() {
+        if lock_mode == LockMode::Frozen && language != "dockerfile" {
+            check_frozen_templates(
+                Path::new(TEMPLATE_LOCK_FILE),
+                Path::new(TEMPLATE_DIRECTORY),
+                &[language.to_string()],
+            )?;
+        }
         if image.is_empty() {
             return Err(Error::Custom(
                 "please provide a valid --image name for your Docker image".to_string(),
@@ -222,9 +275,17 @@ pub(crate) async fn build_from_args(b_args: &ArgMatches<'_>) -> utility::Result<
             build_arg_map: &mapped,
             build_options: &build_options,
             tag_mode: tag_format.unwrap(),
+            tag_template: tag_template_of(tag).unwrap_or_default(),
             build_label_map: &build_label_map,
             quiet_build,
             copy_extra_paths: &copy_extra_paths,
+            engine,
+            force,
+            list_context,
+            provenance,
+            archive,
+            project_root: project_root.as_str(),
+            platforms: "",
         };
 
         build_image(&image_builder).await?;
@@ -241,7 +302,17 @@ pub(crate) async fn build_from_args(b_args: &ArgMatches<'_>) -> utility::Result<
                 ))
             })?;
 
-            pull_stack_templates(new_temp_infos, "", yaml_file, false, false).map_err(|e| {
+            pull_stack_templates(
+                new_temp_infos,
+                "",
+                yaml_file,
+                false,
+                false,
+                lock_mode,
+                offline,
+            )
+            .await
+            .map_err(|e| {
                 Error::Custom(format!(
                     "could not pull templates from function yaml file: {}",
                     e
@@ -249,7 +320,11 @@ pub(crate) async fn build_from_args(b_args: &ArgMatches<'_>) -> utility::Result<
             })?;
         }
 
-        let errors = build(
+        if lock_mode == LockMode::Frozen {
+            check_frozen_functions(&services)?;
+        }
+
+        let summary = build(
             services,
             parallel,
             build_options,
@@ -261,18 +336,19 @@ pub(crate) async fn build_from_args(b_args: &ArgMatches<'_>) -> utility::Result<
             shrinkwrap,
             quiet_build,
             tag_format.unwrap_or_default(),
+            tag,
+            engine,
+            force,
+            keep_going,
+            list_context,
+            provenance,
+            archive,
+            project_root.as_str(),
         )
         .await?;
 
-        if !errors.is_empty() {
-            let mut error_summary = "Errors received during build:\n".to_string();
-            for err in errors {
-                error_summary.push('-');
-                error_summary.push_str((err + "\n").as_str());
-            }
-
-            return Err(Error::Custom(error_summary));
-        }
+        failed.extend(summary.failed_functions());
+        summary.finish()?;
     }
 
     Ok(())
@@ -314,15 +390,39 @@ pub(crate) fn parse_build_args(args: &Vec<&str>) -> utility::Result<HashMap<Stri
     Ok(mapped)
 }
 
+/// with --frozen, the templates of the functions built must match template.lock
+pub(crate) fn check_frozen_functions(services: &Services) -> Result<()> {
+    let mut languages: Vec<String> = services
+        .functions
+        .values()
+        .filter(|f| !f.skip_build && f.language != "dockerfile")
+        .map(|f| f.language.to_lowercase())
+        .collect();
+    languages.sort();
+    languages.dedup();
+    check_frozen_templates(
+        Path::new(TEMPLATE_LOCK_FILE),
+        Path::new(TEMPLATE_DIRECTORY),
+        &languages,
+    )
+}
+
 /// PullTemplates pulls templates from specified git remote. templateURL may be a pinned repository.
-pub(crate) fn pull_templates(template_url: &str) -> Result<()> {
+pub(crate) async fn pull_templates(
+    template_url: &str,
+    lock_mode: LockMode,
+    offline: bool,
+) -> Result<()> {
     if std::fs::metadata("./template").is_err() {
         colour::yellow!("No templates found in current directory.\n");
-        let (template_url, ref_name) = parse_panned_remote(template_url);
+        let location = parse_template_location(template_url, "")?;
 
         //todo check this
-        fetch_templates(template_url.as_str(), ref_name.as_str(), false, false)
-            .map_err(|_e| Error::Custom("Unable to download templates from Github.".to_string()))?;
+        fetch_templates(&location, false, false, lock_mode, offline)
+            .await
+            .map_err(|e| {
+                Error::Custom(format!("Unable to download templates from Github: {}", e))
+            })?;
     }
     Ok(())
 }
@@ -339,8 +439,16 @@ async fn build<'s>(
     shrinkwrap: bool,
     quiet_build: bool,
     tag_mode: i32,
-) -> Result<Vec<String>> {
-    let mut errors: Vec<String> = Vec::new();
+    flag_tag: &str,
+    engine: &'static dyn BuildBackend,
+    force: bool,
+    keep_going: bool,
+    list_context: bool,
+    provenance: bool,
+    archive: &str,
+    project_root: &str,
+) -> Result<StackSummary> {
+    let mut summary = StackSummary::new("build");
 
     let start = Instant::now();
 
@@ -348,75 +456,85 @@ async fn build<'s>(
 
     let extra_path = services.stack_configuration.copy_extra_paths;
     let mut handles = vec![];
-    for (k, mut function) in services.functions {
+    let mut functions: Vec<(String, Function)> = services.functions.into_iter().collect();
+    functions.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut functions = functions.into_iter();
+    while let Some((k, mut function)) = functions.next() {
+        function.name = k;
+        function.tag_template =
+            select_tag_template(flag_tag, function.tag_template.as_str()).to_string();
         let extra = extra_path.clone();
 
-        if queue_depth > 1 {
-            if function.skip_build {
-                println!("Skipping build of: {}.\n", function.name)
-            } else {
-                function.name = k;
-                let build_label_map = build_label_map.clone();
-                let build_arg_map = build_arg_map.clone();
-                let copy_extra = copy_extra.clone();
-                let build_options = build_options.clone();
-
-                let h = tokio::spawn(image_builder(
-                    function,
-                    extra,
-                    build_options,
-                    build_arg_map,
-                    build_label_map,
-                    copy_extra,
-                    index,
-                    nocache,
-                    squash,
-                    shrinkwrap,
-                    quiet_build,
-                    tag_mode,
-                ));
-                handles.push(h);
-            }
+        if function.skip_build {
+            println!("Skipping build of: {}.\n", function.name);
+            summary.push(StepResult::new(
+                function.name.as_str(),
+                function.image.as_str(),
+                Default::default(),
+                StepStatus::Skipped("skip_build is set".to_string()),
+            ));
         } else {
+            // a failure stops the functions not started yet unless --keep-going is set
+            if queue_depth > 1
+                && summary.wait_for_slot(&mut handles, queue_depth).await?
+                && !keep_going
+            {
+                summary.not_started(
+                    std::iter::once((function.name, function.image))
+                        .chain(functions.map(|(k, f)| (k, f.image)))
+                        .collect(),
+                );
+                break;
+            }
             let build_label_map = build_label_map.clone();
             let build_arg_map = build_arg_map.clone();
             let copy_extra = copy_extra.clone();
             let build_options = build_options.clone();
 
-            if function.skip_build {
-                println!("Skipping build of: {}.\n", function.name)
+            let task = image_builder(
+                function,
+                extra,
+                build_options,
+                build_arg_map,
+                build_label_map,
+                copy_extra,
+                index,
+                nocache,
+                squash,
+                shrinkwrap,
+                quiet_build,
+                tag_mode,
+                engine,
+                force,
+                list_context,
+                provenance,
+                archive.to_string(),
+                project_root.to_string(),
+            );
+            if queue_depth > 1 {
+                handles.push(tokio::spawn(task));
             } else {
-                function.name = k;
-                let mut list = image_builder(
-                    function,
-                    extra,
-                    build_options,
-                    build_arg_map,
-                    build_label_map,
-                    copy_extra,
-                    index,
-                    nocache,
-                    squash,
-                    shrinkwrap,
-                    quiet_build,
-                    tag_mode,
-                )
-                .await;
-                errors.append(&mut list);
+                let result = task.await;
+                let failed = result.failed();
+                summary.push(result);
+                if failed && !keep_going {
+                    summary.not_started(functions.map(|(k, f)| (k, f.image)).collect());
+                    break;
+                }
             }
         }
         index += 1;
     }
     for handle in handles {
-        let mut list = handle.await.map_err(|e| Error::Custom(e.to_string()))?;
-        errors.append(&mut list);
+        let result = handle.await.map_err(|e| Error::Custom(e.to_string()))?;
+        summary.push(result);
     }
     colour::green!("[{}] Worker done.\n", index);
 
     let duration = start.elapsed();
     colour::green!("\nTotal build time: {}\n", duration.as_secs());
 
-    return Ok(errors);
+    Ok(summary)
 }
 
 pub(crate) fn combine_build_opts(
@@ -439,12 +557,19 @@ async fn image_builder(
     shrinkwrap: bool,
     quiet_build: bool,
     tag_mode: BuildFormat,
-) -> Vec<String> {
+    engine: &'static dyn BuildBackend,
+    force: bool,
+    list_context: bool,
+    provenance: bool,
+    archive: String,
+    project_root: String,
+) -> StepResult {
     let inner_start = Instant::now();
-    let mut errors: Vec<String> = Vec::new();
+    let mut image = function.image.clone();
     colour::blue!("[{}] > Building {}.\n", index, function.name);
-    if function.language.is_empty() {
+    let status = if function.language.is_empty() {
         colour::yellow!("Please provide a valid language for your function.\n");
+        StepStatus::Failed("no language given for the function".to_string())
     } else {
         let combined_build_options =
             combine_build_opts(function.build_options.clone(), build_options);
@@ -463,21 +588,38 @@ async fn image_builder(
             build_arg_map: &combined_build_arg_map,
             build_options: &combined_build_options,
             tag_mode,
+            tag_template: function.tag_template.as_str(),
             build_label_map: &build_label_map,
             quiet_build,
             copy_extra_paths: &combined_extra_paths,
+            engine,
+            force,
+            list_context,
+            provenance,
+            archive: archive.as_str(),
+            project_root: project_root.as_str(),
+            platforms: function.platforms.as_str(),
         };
-        if let Err(e) = build_image(&image_builder).await {
-            println!("error pr : {:?}", e);
-            errors.push(e.to_string());
+        match build_image(&image_builder).await {
+            Ok((built, outcome)) => {
+                image = built;
+                match outcome {
+                    BuildOutcome::UpToDate => StepStatus::Skipped("up to date".to_string()),
+                    _ => StepStatus::Ok,
+                }
+            }
+            Err(e) => {
+                colour::red!("[{}] Build of {} failed: {}\n", index, function.name, e);
+                StepStatus::Failed(e.to_string())
+            }
         }
-    }
+    };
     let duration = inner_start.elapsed();
     colour::green!(
-        "[{}] < Building {:#?} done in {}s.\n",
+        "[{}] < Building {} done in {}s.\n",
         index,
-        function,
+        function.name,
         duration.as_secs()
     );
-    errors
+    StepResult::new(function.name.as_str(), image.as_str(), duration, status)
 }
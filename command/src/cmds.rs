@@ -1,12 +1,14 @@
 use crate::auth::Auth;
 use crate::build::Build;
 use crate::cloud::Cloud;
+use crate::context::Context;
 use crate::deploy::Deploy;
 use crate::describe::Describe;
 use crate::faas::Faas;
 use crate::generate::Generate;
 use crate::invoke::Invoke;
 use crate::list::List;
+use crate::local_run::LocalRun;
 use crate::login::Login;
 use crate::logout::Logout;
 use crate::logs::Logs;
@@ -14,16 +16,19 @@ use crate::namespaces::Namespaces;
 use crate::new_function::NewFunction;
 use crate::publish::Publish;
 use crate::push::Push;
+use crate::ready::Ready;
 use crate::registry_login::RegistryLogin;
 use crate::remove::Remove;
+use crate::scale::Scale;
 use crate::secret::Secret;
 use crate::store::Store;
 use crate::template::Template;
+use crate::test::Test;
 use crate::up::Up;
 use crate::version::Version;
 use crate::{CommandAppend, State};
 use clap::{App, AppSettings, ArgMatches};
-use utility::{Error, Result};
+use utility::Error;
 
 const WELCOME_MSG: &str = r#"  ___                   _____           ____
  / _ \ _ __   ___ _ __ |  ___|_ _  __ _/ ___|
@@ -47,10 +52,15 @@ pub fn build_cli() -> App<'static, 'static> {
 
     let app = Describe::append_subcommand(app);
     let app = Remove::append_subcommand(app);
+    let app = Scale::append_subcommand(app);
+    let app = Ready::append_subcommand(app);
     let app = NewFunction::append_subcommand(app);
     let app = Up::append_subcommand(app);
+    let app = LocalRun::append_subcommand(app);
+    let app = Test::append_subcommand(app);
     let app = Publish::append_subcommand(app);
     let app = Secret::append_subcommand(app);
+    let app = Context::append_subcommand(app);
     let app = Store::append_subcommand(app);
     let app = Version::append_subcommand(app);
     let app = Cloud::append_subcommand(app);
@@ -96,25 +106,29 @@ pub fn build_cli() -> App<'static, 'static> {
     //                     .help("Service name"))
     //                 .setting(AppSettings::TrailingVarArg)
 }
-pub async fn run(args: &ArgMatches<'_>) -> Result<()> {
+/// runs the command, a State::Exit is passed on for the caller to exit with its code
+pub async fn run(args: &ArgMatches<'_>) -> crate::Result {
     match dispatch_command(args).await {
-        Ok(_) => Err(Error::Custom(
+        Ok(_) => Err(State::Error(Error::Custom(
             "command not matched please contact the development team".to_string(),
-        )),
-        Err(s) => match s {
-            State::Matched => Ok(()),
-            state => Err(Error::Custom(state.to_string())),
-        },
+        ))),
+        Err(State::Matched) => Ok(()),
+        Err(state) => Err(state),
     }
 }
 
 pub async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
     Describe::dispatch_command(args).await?;
     Remove::dispatch_command(args).await?;
+    Scale::dispatch_command(args).await?;
+    Ready::dispatch_command(args).await?;
     NewFunction::dispatch_command(args).await?;
     Up::dispatch_command(args).await?;
+    LocalRun::dispatch_command(args).await?;
+    Test::dispatch_command(args).await?;
     Publish::dispatch_command(args).await?;
     Secret::dispatch_command(args).await?;
+    Context::dispatch_command(args).await?;
     Store::dispatch_command(args).await?;
     Version::dispatch_command(args).await?;
     Cloud::dispatch_command(args).await?;
@@ -0,0 +1,53 @@
+use crate::context_add::ContextAdd;
+use crate::context_list::ContextList;
+use crate::context_remove::ContextRemove;
+use crate::context_use::ContextUse;
+use crate::{CommandAppend, State, SubCommandAppend};
+use clap::{App, ArgMatches, SubCommand};
+
+pub(crate) struct Context;
+
+impl CommandAppend for Context {
+    #[inline(always)]
+    fn append_subcommand(app: App<'static, 'static>) -> App<'static, 'static> {
+        let app = app.subcommand(
+            SubCommand::with_name("context")
+                .about(r#"Manage named contexts, each one bundles a gateway, a default namespace, TLS settings
+and a template store URL. The gateway of the current context is used when neither --gateway
+nor the YAML file set one, before the OPENFAAS_URL environment variable."#)
+                .subcommand(ContextList::append_subcommand())
+                .subcommand(ContextUse::append_subcommand())
+                .subcommand(ContextAdd::append_subcommand())
+                .subcommand(ContextRemove::append_subcommand()),
+        );
+        app
+    }
+}
+
+impl Context {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if let Some(c_args) = args.subcommand_matches("context") {
+            ContextList::dispatch_command(c_args).await?;
+            ContextUse::dispatch_command(c_args).await?;
+            ContextAdd::dispatch_command(c_args).await?;
+            ContextRemove::dispatch_command(c_args).await?;
+
+            let usage = c_args.usage();
+
+            Err(State::Custom(format!(
+                "context command must followed by sub command\n\
+                {}\n
+                for example:\
+  Example: `faas-cli context list | use | add | remove
+faas-cli context add staging --gateway https://staging.example.com --namespace staging-fn
+faas-cli context use staging
+enter :faas-cli context --help
+",
+                usage
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
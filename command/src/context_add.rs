@@ -0,0 +1,69 @@
+use crate::faas::DEFAULT_GATEWAY;
+use crate::{State, SubCommandAppend};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use config::config_file::{update_context, Context};
+
+pub(crate) struct ContextAdd;
+
+impl SubCommandAppend for ContextAdd {
+    #[inline(always)]
+    fn append_subcommand() -> App<'static, 'static> {
+        let app =
+            SubCommand::with_name("add")
+                .about(r#"Create or update a context, --gateway is required. The credentials are the ones
+stored by faas-cli login for the same gateway
+  faas-cli context add dev --gateway http://127.0.0.1:8080
+  faas-cli context add prod --gateway https://gw.example.com --namespace prod-fn --ca-file ./ca.pem"#)
+                .arg(
+                    Arg::with_name("CONTEXT-NAME")
+                        .index(1)
+                        .help("context name")
+                        .required(true),
+                )
+                .args_from_usage("
+                   --tls-no-verify                          'Disable TLS validation for this context'
+                   -n, --namespace  [namespace]             'Default namespace of the functions'
+                   --template-store-url [template-store-url] 'Template store URL of this context'
+                ");
+
+        app
+    }
+}
+
+impl ContextAdd {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if let Some(a_args) = args.subcommand_matches("add") {
+            let name = a_args
+                .value_of("CONTEXT-NAME")
+                .ok_or(State::Custom("context name is required".to_string()))?;
+            // --gateway has a default for the other commands, a context must not get it silently
+            if a_args.occurrences_of("gateway") == 0 {
+                return Err(State::Custom(format!(
+                    "the --gateway of context {} is required, e.g. --gateway {}",
+                    name, DEFAULT_GATEWAY
+                )));
+            }
+            let gateway = a_args.value_of("gateway").unwrap_or(DEFAULT_GATEWAY);
+
+            let context = Context {
+                name: name.to_string(),
+                gateway: gateway.trim_end_matches('/').to_string(),
+                namespace: a_args.value_of("namespace").unwrap_or_default().to_string(),
+                tls_insecure: a_args.is_present("tls-no-verify"),
+                ca_file: a_args.value_of("ca-file").unwrap_or_default().to_string(),
+                cert_file: a_args.value_of("tls-cert").unwrap_or_default().to_string(),
+                key_file: a_args.value_of("tls-key").unwrap_or_default().to_string(),
+                template_store_url: a_args
+                    .value_of("template-store-url")
+                    .unwrap_or_default()
+                    .to_string(),
+            };
+            update_context(context)?;
+            colour::green!("Context {} saved for {}\n", name, gateway);
+            Err(State::Matched)
+        } else {
+            Ok(())
+        }
+    }
+}
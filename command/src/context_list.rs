@@ -0,0 +1,103 @@
+use crate::{State, SubCommandAppend};
+use clap::{App, ArgMatches, SubCommand};
+use config::config_file::{list_contexts, Context};
+
+pub(crate) struct ContextList;
+
+impl SubCommandAppend for ContextList {
+    #[inline(always)]
+    fn append_subcommand() -> App<'static, 'static> {
+        let app = SubCommand::with_name("list")
+            .alias("ls")
+            .about(r#"faas-cli context list"#);
+
+        app
+    }
+}
+
+impl ContextList {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if let Some(_l_args) = args.subcommand_matches("list") {
+            let (contexts, current) = list_contexts()?;
+            if contexts.is_empty() {
+                colour::yellow!(
+                    "No contexts found, add one with: faas-cli context add NAME --gateway URL\n"
+                );
+            } else {
+                colour::green!("{}", render_context_list(&contexts, current.as_str()));
+            }
+            Err(State::Matched)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn render_context_list(contexts: &Vec<Context>, current: &str) -> String {
+    let name_width = contexts
+        .iter()
+        .map(|c| c.name.len())
+        .max()
+        .unwrap_or_default()
+        .max("NAME".len());
+    let gateway_width = contexts
+        .iter()
+        .map(|c| c.gateway.len())
+        .max()
+        .unwrap_or_default()
+        .max("GATEWAY".len());
+
+    let mut fmt = format!(
+        "CURRENT  {:name_width$}  {:gateway_width$}  NAMESPACE\n",
+        "NAME",
+        "GATEWAY",
+        name_width = name_width,
+        gateway_width = gateway_width
+    );
+    for context in contexts {
+        let marker = if context.name == current { "*" } else { "" };
+        fmt.push_str(
+            format!(
+                "{:7}  {:name_width$}  {:gateway_width$}  {}",
+                marker,
+                context.name,
+                context.gateway,
+                context.namespace,
+                name_width = name_width,
+                gateway_width = gateway_width
+            )
+            .trim_end(),
+        );
+        fmt.push('\n');
+    }
+    fmt
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context_list::render_context_list;
+    use config::config_file::Context;
+
+    #[test]
+    fn test_render_context_list() {
+        let contexts = vec![
+            Context {
+                name: "dev".into(),
+                gateway: "http://127.0.0.1:8080".into(),
+                ..Default::default()
+            },
+            Context {
+                name: "production".into(),
+                gateway: "https://gw.example.com".into(),
+                namespace: "prod-fn".into(),
+                ..Default::default()
+            },
+        ];
+        let expected = "CURRENT  NAME        GATEWAY                 NAMESPACE
+         dev         http://127.0.0.1:8080
+*        production  https://gw.example.com  prod-fn
+";
+        assert_eq!(render_context_list(&contexts, "production"), expected);
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{State, SubCommandAppend};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use config::config_file::remove_context;
+
+pub(crate) struct ContextRemove;
+
+impl SubCommandAppend for ContextRemove {
+    #[inline(always)]
+    fn append_subcommand() -> App<'static, 'static> {
+        let app = SubCommand::with_name("remove")
+            .alias("rm")
+            .about(r#"faas-cli context remove NAME"#)
+            .arg(
+                Arg::with_name("CONTEXT-NAME")
+                    .index(1)
+                    .help("context name")
+                    .required(true),
+            );
+
+        app
+    }
+}
+
+impl ContextRemove {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if let Some(r_args) = args.subcommand_matches("remove") {
+            let name = r_args
+                .value_of("CONTEXT-NAME")
+                .ok_or(State::Custom("context name is required".to_string()))?;
+            remove_context(name)?;
+            colour::green!("Removed context {}\n", name);
+            Err(State::Matched)
+        } else {
+            Ok(())
+        }
+    }
+}
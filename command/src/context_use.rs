@@ -0,0 +1,37 @@
+use crate::{State, SubCommandAppend};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use config::config_file::use_context;
+
+pub(crate) struct ContextUse;
+
+impl SubCommandAppend for ContextUse {
+    #[inline(always)]
+    fn append_subcommand() -> App<'static, 'static> {
+        let app = SubCommand::with_name("use")
+            .about(r#"faas-cli context use NAME"#)
+            .arg(
+                Arg::with_name("CONTEXT-NAME")
+                    .index(1)
+                    .help("context name")
+                    .required(true),
+            );
+
+        app
+    }
+}
+
+impl ContextUse {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if let Some(u_args) = args.subcommand_matches("use") {
+            let name = u_args
+                .value_of("CONTEXT-NAME")
+                .ok_or(State::Custom("context name is required".to_string()))?;
+            use_context(name)?;
+            colour::green!("Switched to context {}\n", name);
+            Err(State::Matched)
+        } else {
+            Ok(())
+        }
+    }
+}
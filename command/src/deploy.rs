@@ -1,20 +1,27 @@
 use crate::error::check_tls_insecure;
 use crate::faas::{check_and_set_default_yaml, DEFAULT_GATEWAY, DEFAULT_NETWORK};
-use crate::priority::{get_gateway_url, get_namespace, OPENFAAS_URL_ENVIRONMENT};
+use crate::priority::{
+    get_build_engine, get_gateway_url, get_namespace, get_tls_options, OPENFAAS_URL_ENVIRONMENT,
+};
+use crate::stack_summary::{StackSummary, StepResult, StepStatus};
 use crate::validate::validate_language_flag;
 use crate::{CommandAppend, State};
-use builder::build::get_image_tag_values;
+use builder::backend::BuildBackend;
+use builder::build::get_built_image_name;
+use builder::cache::BuildCache;
+use builder::provenance::Provenance;
 use clap::{App, Arg, ArgMatches, SubCommand};
 use proxy::auth::ClientAuthE;
 use proxy::client::Client;
 use proxy::deploy::{DeployFunctionSpec, FunctionResourceRequest};
 use reqwest::StatusCode;
-use schema::image::{build_image_name, BuildFormat, TBuildFormat};
+use schema::image::{select_tag_template, BuildFormat, TBuildFormat};
 use stack::language_template::parse_yaml_for_language_template;
 use stack::schema::{EnvironmentFile, Function};
 use stack::stack::parse_yaml_file;
 use std::collections::HashMap;
 use std::io::ErrorKind;
+use std::time::Instant;
 use utility::{Error, Result};
 //use crate::faas::check_and_set_default_yaml;
 
@@ -54,10 +61,13 @@ via flags. Note: --replace and --update are mutually exclusive.`,
                          --replace                          'Remove and re-create existing function(s)'
                          --update                           'Perform rolling update on existing function(s)'
                          --readonly                         'Force the root container filesystem to be read only'
-                         --tag [tag]                         'Override latest tag on function Docker image, accepts latest, sha, branch, or describe'
+                         --tag [tag]                         'Override latest tag on function Docker image, accepts latest, sha, branch, describe or template:{branch}-{sha:7}'
                          --tls-no-verify                      'Disable TLS validation'
                          -k ,--token  [token]                     'Pass a JWT token to use instead of basic auth'
                          --read-template                       'Read the function's template'
+                         --keep-going                          'Carry on with the other functions when one of them fails'
+                         --no-provenance                       'Do not add the OCI provenance labels (revision, source, created, version and template) of the images to the functions'
+                         --engine [engine]                     'Container engine to read the provenance labels of the images from: docker, podman, buildah or buildctl'
             ")
                 .arg(
                     Arg::with_name("env")
@@ -109,7 +119,7 @@ impl Deploy {
     #[inline(always)]
     pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
         if let Some(dp_args) = args.subcommand_matches("deploy") {
-            deploy_from_args(dp_args).await?;
+            deploy_from_args(dp_args, &[]).await?;
             Err(State::Matched)
             //pushStack(&services, parallel, tagFormat)
         } else {
@@ -118,7 +128,8 @@ impl Deploy {
     }
 }
 
-pub(crate) async fn deploy_from_args(dp_args: &ArgMatches<'_>) -> Result<()> {
+/// deploys the functions of the arguments, leaving out the functions of the stack in skip
+pub(crate) async fn deploy_from_args(dp_args: &ArgMatches<'_>, skip: &[String]) -> Result<()> {
     let gateway_arg = dp_args.value_of("gateway").unwrap_or(DEFAULT_GATEWAY);
     let regex = dp_args.value_of("regex").unwrap_or("");
     let filter = dp_args.value_of("filter").unwrap_or("");
@@ -130,6 +141,8 @@ pub(crate) async fn deploy_from_args(dp_args: &ArgMatches<'_>) -> Result<()> {
     let update = true; //dp_args.is_present("update");
     let read_template = true; //dp_args.is_present("read-template");
     let replace = dp_args.is_present("replace");
+    let keep_going = dp_args.is_present("keep-going");
+    let provenance = !dp_args.is_present("no-provenance");
     let read_only_root_filesystem = dp_args.is_present("readonly");
     let tls_insecure = dp_args.is_present("tls-no-verify");
 
@@ -184,6 +197,7 @@ pub(crate) async fn deploy_from_args(dp_args: &ArgMatches<'_>) -> Result<()> {
 
     if !yaml_file.is_empty() {
         let mut parsed_svc = parse_yaml_file(yaml_file, regex, filter, envsubst).await?;
+        parsed_svc.functions.retain(|name, _| !skip.contains(name));
 
         let openfass_url = std::env::var(OPENFAAS_URL_ENVIRONMENT).unwrap_or_default();
         parsed_svc.provider.gateway_url = get_gateway_url(
@@ -201,109 +215,148 @@ pub(crate) async fn deploy_from_args(dp_args: &ArgMatches<'_>) -> Result<()> {
     let mut failed_status_code: HashMap<String, u16> = HashMap::new();
 
     if !services.functions.is_empty() {
-        let cli_auth = ClientAuthE::new(token, services.provider.gateway_url.as_str())?;
+        let mut summary = StackSummary::new("deploy");
+        let cli_auth = ClientAuthE::new(token, services.provider.gateway_url.as_str()).await?;
 
-        let proxy_client = cli_auth.get_client(services.provider.gateway_url.as_str())?;
+        let tls = get_tls_options(dp_args, services.provider.gateway_url.as_str());
+        let proxy_client = cli_auth.get_client(services.provider.gateway_url.as_str(), &tls)?;
         // proxyClient, err := proxy.NewClient(cliAuth, services.Provider.GatewayURL, transport, &commandTimeout)
 
-        for (k, mut function) in services.functions {
-            let mut function_secrets = secrets.iter().map(|s| s.to_string()).collect();
-
+        let gateway_url = services.provider.gateway_url.clone();
+        let mut functions: Vec<(String, Function)> = services.functions.into_iter().collect();
+        functions.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut functions = functions.into_iter();
+        while let Some((k, mut function)) = functions.next() {
+            let start = Instant::now();
             function.name = k.clone();
-            println!("Deploying: {}.\n", function.name);
-            let function_constraints: Vec<String> = if !&function.constraints.is_empty() {
-                function.constraints.clone()
-            } else if !&constraints.is_empty() {
-                constraints.iter().map(|m| m.to_string()).collect()
-            } else {
-                Default::default()
-            };
+            let mut image = function.image.clone();
+            let deployed: Result<(String, u16)> = async {
+                let mut function_secrets = secrets.iter().map(|s| s.to_string()).collect();
+
+                println!("Deploying: {}.\n", function.name);
+                let function_constraints: Vec<String> = if !&function.constraints.is_empty() {
+                    function.constraints.clone()
+                } else if !&constraints.is_empty() {
+                    constraints.iter().map(|m| m.to_string()).collect()
+                } else {
+                    Default::default()
+                };
+
+                if !function.secrets.is_empty() {
+                    function_secrets = merge_slice(function.secrets.clone(), function_secrets);
+                }
 
-            if !function.secrets.is_empty() {
-                function_secrets = merge_slice(function.secrets.clone(), function_secrets);
-            }
+                // Check if there is a functionNamespace flag passed, if so, override the namespace value
+                // defined in the stack.yaml
+                function.namespace = get_namespace(function_namespace, function.namespace.as_str());
 
-            // Check if there is a functionNamespace flag passed, if so, override the namespace value
-            // defined in the stack.yaml
-            function.namespace = get_namespace(function_namespace, function.namespace.as_str());
+                let file_environment = read_files(&function.environment_file)?;
 
-            let file_environment = read_files(&function.environment_file)?;
+                let label_map = function.labels.clone();
+                let label_arg_map = parse_map(&label_opts, "label")
+                    .map_err(|e| Error::Custom(format!("error parsing labels: {}", e)))?;
 
-            let label_map = function.labels.clone();
-            let label_arg_map = parse_map(&label_opts, "label")
-                .map_err(|e| Error::Custom(format!("error parsing labels: {}", e)))?;
+                let mut all_labels = merge_map(label_map, label_arg_map);
+                let all_env = compile_environment(
+                    &envvar_opts,
+                    function.environment.as_ref().unwrap_or(&HashMap::new()),
+                    &file_environment,
+                )?;
 
-            let all_labels = merge_map(label_map, label_arg_map);
-            let all_env = compile_environment(
-                &envvar_opts,
-                function.environment.as_ref().unwrap_or(&HashMap::new()),
-                &file_environment,
-            )?;
+                if read_template {
+                    // Get FProcess to use from the ./template/template.yml, if a template is being used
 
-            if read_template {
-                // Get FProcess to use from the ./template/template.yml, if a template is being used
+                    if language_exists_not_dockerfile(function.language.as_str()) {
+                        function.fprocess =derive_fprocess(&function).await
+                            .map_err(|e| Error::Custom(format!(r#"template directory may be missing or invalid, please run "faas-cli template pull",
+                                                      Error: {} "#, e)))?;
+                    }
+                }
 
-                if language_exists_not_dockerfile(function.language.as_str()) {
-                    function.fprocess =derive_fprocess(&function).await
-                        .map_err(|e| Error::Custom(format!(r#"template directory may be missing or invalid, please run "faas-cli template pull",
-                                                  Error: {} "#, e)))?;
+                let function_resource_request = FunctionResourceRequest {
+                    limits: Some(function.limits.clone()),
+                    requests: Some(function.requests.clone()),
+                };
+
+                let annotations = function.annotations.clone();
+                let annotation_args = parse_map(&annotation_opts, "annotation")
+                    .map_err(|e| Error::Custom(format!("error parsing annotations: {}", e)))?;
+
+                let all_annotations = merge_map(annotations, annotation_args);
+                function.image = get_built_image_name(
+                    &BuildCache::default(),
+                    function.name.as_str(),
+                    tag_format.unwrap_or_default(),
+                    select_tag_template(tag, function.tag_template.as_str()),
+                    function.image.as_str(),
+                    function.handler.as_str(),
+                )?;
+                if provenance && !function.skip_build {
+                    all_labels = with_provenance_labels(
+                        get_build_engine(dp_args)?,
+                        function.image.as_str(),
+                        &all_labels,
+                    );
                 }
-            }
 
-            let function_resource_request = FunctionResourceRequest {
-                limits: Some(function.limits.clone()),
-                requests: Some(function.requests.clone()),
-            };
+                if read_only_root_filesystem {
+                    function.readonly_root_filesystem = read_only_root_filesystem;
+                }
 
-            let annotations = function.annotations.clone();
-            let annotation_args = parse_map(&annotation_opts, "annotation")
-                .map_err(|e| Error::Custom(format!("error parsing annotations: {}", e)))?;
-
-            let all_annotations = merge_map(annotations, annotation_args);
-            let (branch, sha) = get_image_tag_values(tag_format.unwrap_or_default())?;
-            function.image = build_image_name(
-                tag_format.unwrap_or_default(),
-                function.image.as_str(),
-                sha.as_str(),
-                branch.as_str(),
-            );
-
-            if read_only_root_filesystem {
-                function.readonly_root_filesystem = read_only_root_filesystem;
-            }
+                let spec = DeployFunctionSpec {
+                    fprocess: function.fprocess.unwrap_or_default().clone(),
+                    function_name: function.name.clone(),
+                    image: function.image.clone(),
+                    registry_auth: "".to_string(),
+                    language: function.language.clone(),
+                    replace,
+                    env_vars: all_env,
+                    network: "".to_string(),
+                    constraints: function_constraints,
+                    update,
+                    secrets: function_secrets,
+                    labels: all_labels,
+                    annotations: all_annotations,
+                    function_resource_request,
+                    read_only_root_filesystem: function.readonly_root_filesystem,
+                    tls_insecure,
+                    token: token.to_string(),
+                    namespace: function.namespace,
+                };
+                let msg = check_tls_insecure(gateway_url.as_str(), spec.tls_insecure);
+                if !msg.is_empty() {
+                    println!("{}", msg);
+                }
 
-            let spec = DeployFunctionSpec {
-                fprocess: function.fprocess.unwrap_or_default().clone(),
-                function_name: function.name.clone(),
-                image: function.image.clone(),
-                registry_auth: "".to_string(),
-                language: function.language.clone(),
-                replace,
-                env_vars: all_env,
-                network: "".to_string(),
-                constraints: function_constraints,
-                update,
-                secrets: function_secrets,
-                labels: all_labels,
-                annotations: all_annotations,
-                function_resource_request,
-                read_only_root_filesystem: function.readonly_root_filesystem,
-                tls_insecure,
-                token: token.to_string(),
-                namespace: function.namespace,
-            };
-            let msg = check_tls_insecure(services.provider.gateway_url.as_str(), spec.tls_insecure);
-            if !msg.is_empty() {
-                println!("{}", msg);
+                let (status_code, output) = proxy_client.deploy_function(&spec).await?;
+                println!("{}", output);
+                Ok((spec.image, status_code))
             }
-
-            let (status_code, output) = proxy_client.deploy_function(&spec).await?;
-            println!("{}", output);
-            //here
-            if bad_status_code(status_code) {
-                failed_status_code.insert(k, status_code);
+            .await;
+
+            let status = match deployed {
+                Ok((deployed_image, status_code)) => {
+                    image = deployed_image;
+                    if bad_status_code(status_code) {
+                        StepStatus::Failed(format!("status code: {}", status_code))
+                    } else {
+                        StepStatus::Ok
+                    }
+                }
+                Err(e) => {
+                    colour::red!("Deploy of {} failed: {}\n", k, e);
+                    StepStatus::Failed(e.to_string())
+                }
+            };
+            let result = StepResult::new(k.as_str(), image.as_str(), start.elapsed(), status);
+            let failed = result.failed();
+            summary.push(result);
+            if failed && !keep_going {
+                summary.not_started(functions.map(|(k, f)| (k, f.image)).collect());
+                break;
             }
         }
+        summary.finish()?;
     } else {
         if image.is_empty() || function_name.is_empty() {
             return Err(Error::Custom(
@@ -318,8 +371,9 @@ pub(crate) async fn deploy_from_args(dp_args: &ArgMatches<'_>) -> Result<()> {
             services.provider.gateway_url.as_str(),
             openfaas_url.as_str(),
         );
-        let cli_auth = ClientAuthE::new(token, gateway.as_str())?;
-        let proxy_client = cli_auth.get_client(gateway.as_str())?;
+        let cli_auth = ClientAuthE::new(token, gateway.as_str()).await?;
+        let tls = get_tls_options(dp_args, gateway.as_str());
+        let proxy_client = cli_auth.get_client(gateway.as_str(), &tls)?;
         // proxyClient, err := proxy.NewClient(cliAuth, gateway, transport, &commandTimeout)
 
         // default to a readable filesystem until we get more input about the expected behavior
@@ -345,7 +399,7 @@ pub(crate) async fn deploy_from_args(dp_args: &ArgMatches<'_>) -> Result<()> {
             tls_insecure,
             default_read_only_rfs,
             token.to_string(),
-            function_namespace.to_string(),
+            get_namespace(function_namespace, ""),
             language.to_string(),
             network.to_string(),
             gateway_arg,
@@ -530,11 +584,11 @@ pub(crate) fn compile_environment(
     Ok(merge_map(function_and_stack, envvar_argument))
 }
 
-fn language_exists_not_dockerfile(language: &str) -> bool {
-    !language.is_empty() && language.to_ascii_uppercase() != "dockerfile"
+pub(crate) fn language_exists_not_dockerfile(language: &str) -> bool {
+    !language.is_empty() && !language.eq_ignore_ascii_case("dockerfile")
 }
 
-async fn derive_fprocess(function: &Function) -> Result<Option<String>> {
+pub(crate) async fn derive_fprocess(function: &Function) -> Result<Option<String>> {
     let path_to_temp_yaml = format!("./template/{}/template.yml", function.language);
     if let Err(e) = std::fs::metadata(&path_to_temp_yaml) {
         if e.kind() == ErrorKind::NotFound {
@@ -546,6 +600,49 @@ async fn derive_fprocess(function: &Function) -> Result<Option<String>> {
     Ok(parsed_lang_temp.fprocess)
 }
 
+/// returns the labels of a function with the provenance labels of its image added, so that
+/// describe shows them whatever the provider copies from the image. They are read from the image
+/// the engine has, not from the working directory which may have moved on since the build, and
+/// left out when the engine doesn't have the image
+fn with_provenance_labels(
+    engine: &dyn BuildBackend,
+    image: &str,
+    labels: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    match Provenance::of_image(engine, image) {
+        Some(provenance) => provenance.with_labels(labels),
+        None => labels.clone(),
+    }
+}
+
 pub(crate) fn bad_status_code(status: u16) -> bool {
     status != StatusCode::ACCEPTED.as_u16() && status != StatusCode::OK.as_u16()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::backend::get_build_backend;
+    use builder::provenance::REVISION_LABEL;
+
+    #[test]
+    fn test_language_exists_not_dockerfile() {
+        assert!(language_exists_not_dockerfile("node14"));
+        assert!(!language_exists_not_dockerfile("dockerfile"));
+        assert!(!language_exists_not_dockerfile("Dockerfile"));
+        assert!(!language_exists_not_dockerfile(""));
+    }
+
+    #[test]
+    fn test_with_provenance_labels() {
+        let mut labels = HashMap::new();
+        labels.insert("team".to_string(), "payments".to_string());
+
+        // buildctl keeps no local image to read the labels from, they are left out
+        let engine = get_build_backend("buildctl").unwrap();
+        let labels = with_provenance_labels(engine, "fn:0.1", &labels);
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels["team"], "payments");
+        assert!(!labels.contains_key(REVISION_LABEL));
+    }
+}
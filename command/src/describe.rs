@@ -1,6 +1,7 @@
 use crate::faas::{check_and_set_default_yaml, DEFAULT_GATEWAY};
-use crate::priority::{get_gateway_url, OPENFAAS_URL_ENVIRONMENT};
+use crate::priority::{get_gateway_url, get_namespace, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
 use crate::{CommandAppend, State};
+use builder::provenance::PROVENANCE_LABELS;
 use clap::{App, ArgMatches, SubCommand};
 use proxy::auth::ClientAuthE;
 use schema::describe::FunctionDescription;
@@ -34,7 +35,9 @@ impl Describe {
         if let Some(r_args) = args.subcommand_matches("describe") {
             let gateway = r_args.value_of("gateway").unwrap_or(DEFAULT_GATEWAY);
             let token = r_args.value_of("token").unwrap_or_default();
-            let function_namespace = r_args.value_of("namespace").unwrap_or_default();
+            let function_namespace =
+                get_namespace(r_args.value_of("namespace").unwrap_or_default(), "");
+            let function_namespace = function_namespace.as_str();
             //let tls_no_verify = r_args.is_present("tls-no-verify");
             let envsubst = true;
 
@@ -68,8 +71,11 @@ impl Describe {
                 yaml_gateway.as_str(),
                 openfaas_url.as_str(),
             );
-            let client_auth = ClientAuthE::new(token, gateway_address.as_str())?;
-            let mut client = client_auth.get_client(gateway_address.as_str())?;
+            let client_auth = ClientAuthE::new(token, gateway_address.as_str()).await?;
+            let mut client = client_auth.get_client(
+                gateway_address.as_str(),
+                &get_tls_options(r_args, gateway_address.as_str()),
+            )?;
             // transport := GetDefaultCLITransport(tlsInsecure, &commandTimeout)
             // proxyclient, err := proxy.NewClient(cliAuth, gatewayAddress, transport, &commandTimeout)
             let function = client
@@ -166,6 +172,16 @@ fn print_function_description(func_desc: &FunctionDescription) {
     let str = format!("{:width$}", "\nAsync URL:", width = 30) + func_desc.async_url;
     fmt.push_str(str.as_str());
 
+    let provenance = provenance_of(func_desc);
+    if !provenance.is_empty() {
+        fmt.push_str("\nProvenance");
+
+        for (key, value) in provenance {
+            let str = format!("{:width$}", "\n", width = 30);
+            fmt = fmt + str.as_str() + key + " : " + value;
+        }
+    }
+
     if !func_desc.labels.is_empty() {
         // let str = format!("{:width$}", "\nLabels:", width = 30);
         fmt.push_str("\nLabels");
@@ -187,3 +203,57 @@ fn print_function_description(func_desc: &FunctionDescription) {
     }
     colour::green!("{}", fmt);
 }
+
+/// returns the provenance labels of the image of a function, found in the labels deploy sets or
+/// in the annotations of the providers exposing the labels of the image
+fn provenance_of<'s>(func_desc: &FunctionDescription<'s>) -> Vec<(&'static str, &'s str)> {
+    PROVENANCE_LABELS
+        .iter()
+        .filter_map(|key| {
+            func_desc
+                .labels
+                .get(*key)
+                .or_else(|| func_desc.annotations.get(*key))
+                .map(|value| (*key, value.as_str()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_provenance_of() {
+        let mut labels = HashMap::new();
+        labels.insert("com.openfaas.template".to_string(), "node14".to_string());
+        labels.insert("team".to_string(), "payments".to_string());
+        let mut annotations = HashMap::new();
+        annotations.insert(
+            "org.opencontainers.image.revision".to_string(),
+            "3c5c2a1".to_string(),
+        );
+        let func_desc = FunctionDescription {
+            name: "figlet",
+            status: "Ready",
+            replicas: 1,
+            available_replicas: 1,
+            invocation_count: 0,
+            image: "user/figlet:latest",
+            env_process: "",
+            url: "",
+            async_url: "",
+            labels: &labels,
+            annotations: &annotations,
+        };
+
+        assert_eq!(
+            provenance_of(&func_desc),
+            vec![
+                ("org.opencontainers.image.revision", "3c5c2a1"),
+                ("com.openfaas.template", "node14"),
+            ]
+        );
+    }
+}
@@ -50,6 +50,29 @@ impl CommandAppend for Faas {
                     .long("envsubst")
                     .global(true)
                     .help("Substitute environment variables in stack.yml file"),
+            )
+            .arg(
+                Arg::with_name("ca-file")
+                    .long("ca-file")
+                    .takes_value(true)
+                    .global(true)
+                    .help("PEM bundle of extra certificate authorities to trust for the gateway, can also be set with OPENFAAS_CA_FILE"),
+            )
+            .arg(
+                Arg::with_name("tls-cert")
+                    .long("tls-cert")
+                    .takes_value(true)
+                    .global(true)
+                    .requires("tls-key")
+                    .help("PEM client certificate for gateways requiring mutual TLS"),
+            )
+            .arg(
+                Arg::with_name("tls-key")
+                    .long("tls-key")
+                    .takes_value(true)
+                    .global(true)
+                    .requires("tls-cert")
+                    .help("PEM (PKCS#8) private key of the --tls-cert client certificate"),
             );
         app
     }
@@ -1,60 +1,139 @@
+use crate::repository_cache::RepositoryCache;
+use crate::template_archive::fetch_archive;
+use crate::template_lock::{
+    hash_templates, LockMode, LockedSource, TemplateLock, TEMPLATE_LOCK_FILE,
+};
+use crate::template_oci::fetch_oci;
+use crate::template_source::{parse_template_location, TemplateLocation};
 use builder::copy_files;
-use lazy_static::lazy_static;
 use std::collections::HashMap;
 use std::io::ErrorKind;
-use std::sync::{Arc, Mutex};
-use tempdir::TempDir;
+use std::path::{Path, PathBuf};
 use utility::{Error, Result};
-use versioncontol::git::{GIT_CHECKOUT_REF_NAME, GIT_CLONE, GIT_CLONE_DEFAULT};
-use versioncontol::parse::{is_git_remote, is_pinned_git_remote, parse_panned_remote};
+use versioncontol::git::{get_git_commit, GIT_CHECKOUT_REF_NAME};
 
 /// DefaultTemplateRepository contains the Git repo for the official templates
 pub(crate) const DEFAULT_TEMPLATE_REPOSITORY: &str = "https://github.com/openfaas/templates.git";
 pub(crate) const TEMPLATE_DIRECTORY: &str = "./template/";
 
-lazy_static! {
-    ///to make the life time of temp folder longer than function scope
-    static ref TEMP_FOLDERS:Arc<Mutex<Vec<TempDir>>> = Arc::new(Mutex::new(Vec::new()));
-}
-
-/// fetchTemplates fetch code templates using git clone.
-pub(crate) fn fetch_templates(
-    template_url: &str,
-    ref_name: &str,
+/// fetchTemplates fetch code templates into the template cache and copies them from there, git
+/// repositories are cloned, archives and OCI artifacts are downloaded and file:// folders are read
+/// in place. The commit, or the digest of an archive or artifact, and the templates pulled are
+/// recorded in template.lock, a locked source is pulled at its locked revision unless updating.
+/// Offline, only the revisions already cached are used
+pub(crate) async fn fetch_templates(
+    location: &TemplateLocation,
     overwrite: bool,
     pull_debug: bool,
+    lock_mode: LockMode,
+    offline: bool,
 ) -> Result<()> {
+    let (template_url, ref_name) = location.key();
     if template_url.is_empty() {
         return Err(Error::Custom("pass valid template_url".to_string()));
     }
-    let dir = tempdir::TempDir::new("openFaasTemplates")?;
-    let dir_path = dir.path().to_string_lossy().to_string();
-
+    let lock_path = Path::new(TEMPLATE_LOCK_FILE);
+    let lock = TemplateLock::load(lock_path)?;
+    let locked = match lock_mode {
+        LockMode::Update => None,
+        _ => lock.source(template_url, ref_name).cloned(),
+    };
+    if lock_mode == LockMode::Frozen && locked.is_none() {
+        return Err(Error::Custom(format!(
+            "{} is not locked in {}, pull it without --frozen first",
+            template_url, TEMPLATE_LOCK_FILE
+        )));
+    }
     colour::green!("Attempting to expand templates from {}\n", template_url);
+    let locked_revision = locked
+        .as_ref()
+        .map(|l| l.commit.as_str())
+        .filter(|commit| !commit.is_empty());
+    if let Some(revision) = locked_revision {
+        colour::green!("Using {} locked in {}\n", revision, TEMPLATE_LOCK_FILE);
+    }
+
+    let cache = RepositoryCache::open()?;
+    // held until the templates are copied out of the cache
+    let _lock = match location {
+        TemplateLocation::Directory { .. } => None,
+        _ => Some(cache.lock(template_url, ref_name)?),
+    };
+    let (dir, commit) = match location {
+        TemplateLocation::Git {
+            repository,
+            ref_name,
+        } => {
+            let dir = cache.checkout(repository, ref_name, locked_revision, offline)?;
+            let commit = get_git_commit(dir.to_string_lossy().as_ref())?;
+            (dir, commit)
+        }
+        TemplateLocation::Directory { path, .. } => (path.clone(), String::new()),
+        TemplateLocation::Archive { url, sha256 } => {
+            fetch_archive(&cache, url, sha256, locked_revision, offline).await?
+        }
+        TemplateLocation::Oci { source, reference } => {
+            fetch_oci(&cache, source, reference, locked_revision, offline).await?
+        }
+    };
+    let dir = locate_template_root(&dir)?;
     if pull_debug {
-        //don't clean up
-        let mut lock = TEMP_FOLDERS
-            .lock()
-            .map_err(|e| Error::Custom(format!("failed to access mutux:{:?}", e)))?;
-        lock.push(dir);
-        println!("Temp files in {:?}", dir_path);
+        println!("Templates read from {:?}", dir.to_string_lossy());
     }
 
-    let mut args: HashMap<String, String> = HashMap::new();
+    install_templates(
+        &dir,
+        Path::new("."),
+        template_url,
+        ref_name,
+        commit.as_str(),
+        overwrite,
+        lock_mode,
+    )
+}
 
-    args.insert("dir".to_string(), dir_path.clone());
-    args.insert("repo".to_string(), template_url.to_string());
+/// copies the templates fetched into source_dir to the template folder of the project and locks
+/// them. With --frozen, the templates fetched are checked against the lock before anything is
+/// copied and the templates left on disk are checked afterwards
+fn install_templates(
+    source_dir: &Path,
+    project_dir: &Path,
+    template_url: &str,
+    ref_name: &str,
+    commit: &str,
+    overwrite: bool,
+    lock_mode: LockMode,
+) -> Result<()> {
+    let lock_path = project_dir.join(TEMPLATE_LOCK_FILE);
+    let template_dir = project_dir.join(TEMPLATE_DIRECTORY);
+    let mut lock = TemplateLock::load(&lock_path)?;
+    let templates = hash_templates(&source_dir.join(TEMPLATE_DIRECTORY))?;
 
-    let cmd = if !ref_name.is_empty() {
-        args.insert("ref_name".to_string(), ref_name.to_string());
-        GIT_CLONE
-    } else {
-        GIT_CLONE_DEFAULT
+    let frozen = match lock_mode {
+        LockMode::Frozen => {
+            let locked = lock.source(template_url, ref_name).ok_or_else(|| {
+                Error::Custom(format!(
+                    "{} is not locked in {}, pull it without --frozen first",
+                    template_url, TEMPLATE_LOCK_FILE
+                ))
+            })?;
+            if templates != locked.templates {
+                return Err(Error::Custom(format!(
+                    "the templates don't match {}: {} was changed",
+                    TEMPLATE_LOCK_FILE,
+                    describe_revision(template_url, commit)
+                )));
+            }
+            Some(locked.templates.keys().cloned().collect::<Vec<String>>())
+        }
+        _ => None,
     };
 
-    cmd.invoke(".", args)?;
-
-    let (pre_existing_languages, fetched_languages) = move_templates(dir_path.as_str(), overwrite)?;
+    let (pre_existing_languages, fetched_languages) = move_templates(
+        source_dir.to_string_lossy().as_ref(),
+        &template_dir,
+        overwrite,
+    )?;
 
     if !pre_existing_languages.is_empty() {
         colour::yellow!(
@@ -71,10 +150,69 @@ pub(crate) fn fetch_templates(
         template_url
     );
 
+    match frozen {
+        Some(names) => {
+            let mismatches = lock.verify(&template_dir, &names)?;
+            if !mismatches.is_empty() {
+                return Err(Error::Custom(format!(
+                    "the templates don't match {}: {}",
+                    TEMPLATE_LOCK_FILE,
+                    mismatches.join(", ")
+                )));
+            }
+        }
+        None => {
+            lock.record(LockedSource {
+                repository: template_url.to_string(),
+                ref_name: ref_name.to_string(),
+                commit: commit.to_string(),
+                templates,
+            });
+            lock.save(&lock_path)?;
+            colour::green!(
+                "Locked {} in {}\n",
+                describe_revision(template_url, commit),
+                TEMPLATE_LOCK_FILE
+            );
+        }
+    }
+
     Ok(())
 }
 
-fn move_templates(repo_path: &str, overwrite: bool) -> Result<(Vec<String>, Vec<String>)> {
+fn describe_revision(source: &str, revision: &str) -> String {
+    if revision.is_empty() {
+        source.to_string()
+    } else {
+        format!("{} at {}", source, revision)
+    }
+}
+
+/// the folder holding the template folder: the folder itself, or the single folder it contains as
+/// archives of a repository usually do
+fn locate_template_root(dir: &Path) -> Result<PathBuf> {
+    let template_dir = TEMPLATE_DIRECTORY.trim_start_matches("./");
+    if dir.join(template_dir).is_dir() {
+        return Ok(dir.to_path_buf());
+    }
+    let entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| Error::Custom(format!("can't read {}: {}", dir.to_string_lossy(), e)))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .collect();
+    match entries.as_slice() {
+        [single] if single.join(template_dir).is_dir() => Ok(single.clone()),
+        _ => Err(Error::Custom(format!(
+            "can't find templates in: {}",
+            dir.to_string_lossy()
+        ))),
+    }
+}
+
+fn move_templates(
+    repo_path: &str,
+    template_dir: &Path,
+    overwrite: bool,
+) -> Result<(Vec<String>, Vec<String>)> {
     let mut existing_languages: Vec<String> = Vec::new();
     let mut fetched_languages: Vec<String> = Vec::new();
     let mut available_languages: HashMap<String, bool> = HashMap::new();
@@ -90,23 +228,24 @@ fn move_templates(repo_path: &str, overwrite: bool) -> Result<(Vec<String>, Vec<
 
     for file in templates {
         let file = file?.path();
-        if file.is_file() {
+        if file.is_dir() {
             let language = file.file_name().unwrap().to_string_lossy().to_string();
-            let can_write =
-                can_write_language(&mut available_languages, language.as_str(), overwrite)?;
+            let can_write = can_write_language(
+                &mut available_languages,
+                template_dir,
+                language.as_str(),
+                overwrite,
+            )?;
 
             if can_write {
                 fetched_languages.push(language.clone());
                 // Do cp here
                 let language_src = format!("{}/{}", temp_dir.trim_end_matches('/'), language);
-                let language_dest = format!(
-                    "{}/{}",
-                    TEMPLATE_DIRECTORY
-                        .trim_start_matches("./")
-                        .trim_end_matches('/'),
-                    language
-                );
-                copy_files(language_src.as_str(), language_dest.as_str())?;
+                let language_dest = template_dir.join(language.as_str());
+                copy_files(
+                    language_src.as_str(),
+                    language_dest.to_string_lossy().as_ref(),
+                )?;
             } else {
                 existing_languages.push(language);
                 // continue
@@ -122,26 +261,26 @@ fn move_templates(repo_path: &str, overwrite: bool) -> Result<(Vec<String>, Vec<
 /// overwrite flag will allow to force copy the language template
 fn can_write_language(
     available_languages: &mut HashMap<String, bool>,
+    template_dir: &Path,
     language: &str,
     overwrite: bool,
 ) -> Result<bool> {
     let mut can_write = false;
 
-    if !available_languages.is_empty() && !language.is_empty() {
+    if !language.is_empty() {
         if let Some(existed) = available_languages.get(language) {
             return Ok(*existed);
         }
 
-        can_write = template_folder_exists(language, overwrite)?;
+        can_write = template_folder_exists(template_dir, language, overwrite)?;
         available_languages.insert(language.to_string(), can_write);
     }
 
     Ok(can_write)
 }
 /// Takes a language input (e.g. "node"), tells whether or not it is OK to download
-fn template_folder_exists(language: &str, overwrite: bool) -> Result<bool> {
-    let dir = TEMPLATE_DIRECTORY.to_string() + language;
-    match std::fs::metadata(dir) {
+fn template_folder_exists(template_dir: &Path, language: &str, overwrite: bool) -> Result<bool> {
+    match std::fs::metadata(template_dir.join(language)) {
         Ok(_) => {
             if !overwrite {
                 Ok(false)
@@ -159,42 +298,153 @@ fn template_folder_exists(language: &str, overwrite: bool) -> Result<bool> {
     }
 }
 
-pub(crate) fn pull_template(repository: &str, overwrite: bool, pull_debug: bool) -> Result<()> {
-    if std::fs::metadata(repository).is_err() {
-        if !is_git_remote(repository) && !is_pinned_git_remote(repository) {
-            return Err(Error::Custom(
-                "The repository URL must be a valid git repo uri".to_string(),
-            ));
-        }
-    }
-
-    let (repository, ref_name) = parse_panned_remote(repository);
-
-    if !ref_name.is_empty() {
-        let map: HashMap<String, String> = [("refname".to_string(), ref_name.clone())]
-            .iter()
-            .cloned()
-            .collect();
-        GIT_CHECKOUT_REF_NAME.invoke("",map)
-            .map_err(|e|{
-                colour::red!("Invalid tag or branch name `{}`\n", ref_name);
-                colour::red!("See https://git-scm.com/docs/git-check-ref-format for more details of the rules Git enforces
-                 on branch and reference names.");
+pub(crate) async fn pull_template(
+    repository: &str,
+    sha256: &str,
+    overwrite: bool,
+    pull_debug: bool,
+    lock_mode: LockMode,
+    offline: bool,
+) -> Result<()> {
+    let location = parse_template_location(repository, sha256)?;
 
-                e
-            })?;
+    if let TemplateLocation::Git { ref_name, .. } = &location {
+        if !ref_name.is_empty() {
+            let map: HashMap<String, String> = [("refname".to_string(), ref_name.clone())]
+                .iter()
+                .cloned()
+                .collect();
+            GIT_CHECKOUT_REF_NAME.invoke(".",map)
+                .inspect_err(|_|{
+                    colour::red!("Invalid tag or branch name `{}`\n", ref_name);
+                    colour::red!("See https://git-scm.com/docs/git-check-ref-format for more details of the rules Git enforces
+                     on branch and reference names.");
+                })?;
+        }
     }
+    let (repository, ref_name) = location.key();
     colour::green!(
         "Fetch templates from repository: {} at {}\n",
         repository,
         ref_name
     );
 
-    fetch_templates(
-        repository.as_str(),
-        ref_name.as_str(),
-        overwrite,
-        pull_debug,
-    )
-    .map_err(|e| Error::Custom(format!("error while fetching templates : {}", e)))
+    fetch_templates(&location, overwrite, pull_debug, lock_mode, offline)
+        .await
+        .map_err(|e| Error::Custom(format!("error while fetching templates : {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    const REPOSITORY: &str = "https://github.com/openfaas/templates.git";
+
+    fn write_template(source_dir: &Path, name: &str, content: &str) {
+        let dir = source_dir.join("template").join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("template.yml"), content).unwrap();
+    }
+
+    fn install(
+        source_dir: &Path,
+        project_dir: &Path,
+        overwrite: bool,
+        mode: LockMode,
+    ) -> Result<()> {
+        install_templates(
+            source_dir,
+            project_dir,
+            REPOSITORY,
+            "",
+            "0".repeat(40).as_str(),
+            overwrite,
+            mode,
+        )
+    }
+
+    #[test]
+    fn test_move_templates() {
+        let source = TempDir::new("templates-source").unwrap();
+        let project = TempDir::new("templates-project").unwrap();
+        write_template(source.path(), "node14", "node14");
+        write_template(source.path(), "python3", "python3");
+        // files next to the template folders are not templates
+        std::fs::write(source.path().join("template/README.md"), "").unwrap();
+        let template_dir = project.path().join("template");
+        std::fs::create_dir_all(template_dir.join("python3")).unwrap();
+
+        let (mut existing, mut fetched) =
+            move_templates(source.path().to_str().unwrap(), &template_dir, false).unwrap();
+        existing.sort();
+        fetched.sort();
+        assert_eq!(existing, vec!["python3".to_string()]);
+        assert_eq!(fetched, vec!["node14".to_string()]);
+        assert!(template_dir.join("node14/template.yml").is_file());
+        assert!(!template_dir.join("python3/template.yml").exists());
+        assert!(!template_dir.join("README.md").exists());
+
+        let (existing, fetched) =
+            move_templates(source.path().to_str().unwrap(), &template_dir, true).unwrap();
+        assert!(existing.is_empty());
+        assert_eq!(fetched.len(), 2);
+        assert!(template_dir.join("python3/template.yml").is_file());
+    }
+
+    #[test]
+    fn test_can_write_language() {
+        let project = TempDir::new("templates-project").unwrap();
+        std::fs::create_dir_all(project.path().join("go")).unwrap();
+        let mut available = HashMap::new();
+
+        // the first language checked is written even though none was seen before
+        assert!(can_write_language(&mut available, project.path(), "node14", false).unwrap());
+        assert!(!can_write_language(&mut available, project.path(), "go", false).unwrap());
+        // the answer is kept for the rest of the pull
+        assert!(!can_write_language(&mut available, project.path(), "go", true).unwrap());
+        assert!(can_write_language(&mut HashMap::new(), project.path(), "go", true).unwrap());
+        assert!(!can_write_language(&mut available, project.path(), "", true).unwrap());
+    }
+
+    #[test]
+    fn test_install_templates_lock() {
+        let source = TempDir::new("templates-source").unwrap();
+        let project = TempDir::new("templates-project").unwrap();
+        write_template(source.path(), "node14", "node14");
+        let lock_path = project.path().join(TEMPLATE_LOCK_FILE);
+
+        // frozen needs the source locked first
+        assert!(install(source.path(), project.path(), false, LockMode::Frozen).is_err());
+        assert!(!project.path().join("template").exists());
+
+        install(source.path(), project.path(), false, LockMode::Locked).unwrap();
+        let lock = TemplateLock::load(&lock_path).unwrap();
+        let locked = lock.source(REPOSITORY, "").unwrap();
+        assert_eq!(locked.commit, "0".repeat(40));
+        assert_eq!(locked.templates.keys().collect::<Vec<_>>(), vec!["node14"]);
+
+        install(source.path(), project.path(), true, LockMode::Frozen).unwrap();
+
+        // fetched templates that don't match the lock are not copied
+        write_template(source.path(), "node14", "changed");
+        let err = install(source.path(), project.path(), true, LockMode::Frozen).unwrap_err();
+        assert!(err.to_string().contains("was changed"), "{}", err);
+        assert_eq!(
+            std::fs::read_to_string(project.path().join("template/node14/template.yml")).unwrap(),
+            "node14"
+        );
+        assert_eq!(TemplateLock::load(&lock_path).unwrap(), lock);
+
+        // templates changed on disk fail too
+        write_template(source.path(), "node14", "node14");
+        write_template(&project.path(), "node14", "edited");
+        assert!(install(source.path(), project.path(), false, LockMode::Frozen).is_err());
+
+        // updating locks the new content
+        write_template(source.path(), "node14", "changed");
+        install(source.path(), project.path(), true, LockMode::Update).unwrap();
+        assert_ne!(TemplateLock::load(&lock_path).unwrap(), lock);
+        install(source.path(), project.path(), false, LockMode::Frozen).unwrap();
+    }
 }
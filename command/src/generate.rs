@@ -2,10 +2,13 @@ use crate::deploy::{compile_environment, merge_map, parse_map, read_files};
 use crate::faas::check_and_set_default_yaml;
 use crate::store::DEFAULT_STORE;
 use crate::{CommandAppend, State};
-use builder::build::get_image_tag_values;
+use builder::build::{get_built_image_name, get_image_tag_values};
+use builder::cache::BuildCache;
 use clap::{App, Arg, ArgMatches, SubCommand};
 use proxy::function_store::function_store_list;
-use schema::image::{build_image_name, BuildFormat, TBuildFormat};
+use schema::image::{
+    build_image_name, select_tag_template, BuildFormat, TBuildFormat, TEMPLATE_FORMAT,
+};
 use schema::knative::v1::serving::{
     EnvPair, Secret, ServingServiceCRD, ServingSpecContainersContainerSpec, Volume, VolumeMount,
     API_VERSION_LATEST,
@@ -39,7 +42,7 @@ faas-cli generate --api=openfaas.com/v1 -f stack.yml --tag branch -n openfaas-fn
                 --from-store [from-store]       'generate using a store image'
                 --api [api]                     'CRD API version e.g openfaas.com/v1, serving.knative.dev/v1'
                 -n ,--namespace [namespace]     'Kubernetes namespace for functions'
-                --tag  [tag]                    'Override latest tag on function Docker image, accepts latest, sha, branch, describe'
+                --tag  [tag]                    'Override latest tag on function Docker image, accepts latest, sha, branch, describe or template:{branch}-{sha:7}'
                 --arch  [arch]                  'Desired image arch. (Default x86_64)'
                 -u ,--url   [url]               'Alternative Store URL starting with http(s)://'
             ")
@@ -144,6 +147,20 @@ impl Generate {
                 services = parsed_services?;
             }
 
+            for (name, function) in services.functions.iter_mut() {
+                let tag_template = select_tag_template(tag, function.tag_template.as_str());
+                if !tag_template.is_empty() {
+                    function.image = get_built_image_name(
+                        &BuildCache::default(),
+                        name.as_str(),
+                        TEMPLATE_FORMAT,
+                        tag_template,
+                        function.image.as_str(),
+                        function.handler.as_str(),
+                    )?;
+                }
+            }
+
             let (branch, version) = get_image_tag_values(tag_format.unwrap())?;
             //println!("{:?}\n{:?},{},{},{} {}",services,tag_format,function_namespace,branch,version,api);
 
@@ -1,5 +1,5 @@
 use crate::faas::{check_and_set_default_yaml, DEFAULT_GATEWAY};
-use crate::priority::{get_gateway_url, OPENFAAS_URL_ENVIRONMENT};
+use crate::priority::{get_gateway_url, get_namespace, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
 use crate::{CommandAppend, State};
 use clap::{App, Arg, ArgMatches, SubCommand};
 use stack::schema::Services;
@@ -93,14 +93,14 @@ impl Invoke {
             )))?;
 
             let key = l_args.value_of("key").unwrap_or("");
-            let namespace = l_args.value_of("namespace").unwrap_or("");
+            let namespace = get_namespace(l_args.value_of("namespace").unwrap_or(""), "");
+            let namespace = namespace.as_str();
             let method = l_args.value_of("method").unwrap_or("POST").to_uppercase();
             let sig_header = l_args.value_of("sign").unwrap_or("");
             let query: Vec<&str> = l_args.values_of("query").unwrap_or_default().collect();
             let mut headers: Vec<&str> = l_args.values_of("header").unwrap_or_default().collect();
             let content_type = l_args.value_of("content-type").unwrap_or("text/plain");
             let invoke_async = l_args.is_present("async");
-            let tls_insecure = l_args.is_present("tls-no-verify");
 
             let method = Method::from_bytes(method.as_ref())
                 .map_err(|_e| Error::Custom(format!("invalid method {}", method)))?;
@@ -159,7 +159,7 @@ impl Invoke {
                 &headers,
                 invoke_async,
                 method,
-                tls_insecure,
+                &get_tls_options(l_args, gateway_address.as_str()),
                 namespace,
             )
             .await?;
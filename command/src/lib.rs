@@ -1,5 +1,6 @@
 use crate::cmds::{build_cli, run};
 use clap::{App, ArgMatches};
+use std::process::ExitCode;
 
 use utility::Error;
 
@@ -12,6 +13,7 @@ pub(crate) mod fetch_template;
 pub(crate) mod generate;
 pub(crate) mod invoke;
 pub(crate) mod list;
+pub(crate) mod local_run;
 pub(crate) mod login;
 pub(crate) mod logout;
 pub(crate) mod logs;
@@ -24,33 +26,53 @@ pub(crate) mod validate;
 //command
 pub(crate) mod template;
 //subcommands
+pub(crate) mod template_archive;
+pub(crate) mod template_cache;
+pub(crate) mod template_cache_list;
+pub(crate) mod template_cache_prune;
+pub(crate) mod template_lock;
+pub(crate) mod template_oci;
 pub(crate) mod template_pull;
 pub(crate) mod template_pull_stack;
+pub(crate) mod template_source;
 
 pub(crate) mod auth;
 pub(crate) mod cloud;
+pub(crate) mod context;
+pub(crate) mod context_add;
+pub(crate) mod context_list;
+pub(crate) mod context_remove;
+pub(crate) mod context_use;
 pub(crate) mod registry_login;
 pub(crate) mod version;
 
 pub(crate) mod template_store_describe;
 pub(crate) mod template_store_list;
 pub(crate) mod template_store_pull;
+pub(crate) mod template_validate;
 
 pub(crate) mod describe;
 pub(crate) mod new_function;
 pub(crate) mod publish;
+pub(crate) mod ready;
 pub(crate) mod remove;
+pub(crate) mod repository_cache;
+pub(crate) mod scale;
 pub(crate) mod secret;
 pub(crate) mod secret_create;
 mod secret_list;
 pub(crate) mod secret_remove;
 pub(crate) mod secret_update;
+pub(crate) mod stack_summary;
 pub(crate) mod store;
 pub(crate) mod store_deploy;
 pub(crate) mod store_describe;
 pub(crate) mod store_list;
+pub(crate) mod test;
+pub(crate) mod test_report;
 pub(crate) mod up;
 pub(crate) mod update_gitignore;
+pub(crate) mod watch;
 
 type Result = std::result::Result<(), State>;
 // pub(crate) enum State{
@@ -68,6 +90,9 @@ pub enum State {
     Custom(String),
     #[error("{0}")]
     Error(#[from] Error),
+    /// print the message and terminate the CLI with the given exit code
+    #[error("{1}")]
+    Exit(i32, String),
 }
 
 //return OK(()) , or Err(State::Error(e))
@@ -95,14 +120,22 @@ pub fn extract_error(res: crate::Result) -> std::result::Result<(), State> {
 //         }
 //     }
 // }
-pub async fn exec() {
+pub async fn exec() -> ExitCode {
     // completions handling first
 
     // let name = args.subcommand_name().unwrap();
     let app = build_cli();
     let args: ArgMatches = app.get_matches();
-    if let Err(err) = run(&args).await {
-        println!("{}", err.to_string());
+    match run(&args).await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(State::Exit(code, msg)) => {
+            println!("{}", msg);
+            ExitCode::from(code as u8)
+        }
+        Err(err) => {
+            println!("{}", err.to_string());
+            ExitCode::FAILURE
+        }
     }
 }
 pub(crate) trait CommandAppend {
@@ -1,5 +1,5 @@
 use crate::faas::{check_and_set_default_yaml, DEFAULT_GATEWAY};
-use crate::priority::{get_gateway_url, OPENFAAS_URL_ENVIRONMENT};
+use crate::priority::{get_gateway_url, get_namespace, OPENFAAS_URL_ENVIRONMENT};
 use crate::{CommandAppend, State};
 use clap::{App, Arg, ArgMatches, SubCommand};
 use proxy::auth::ClientAuthE;
@@ -69,7 +69,7 @@ impl List {
                     .map_err(|e| State::Error(utility::Error::Custom(format!("{}", e))))?;
             }
             let token = largs.value_of("token").unwrap_or("").to_string();
-            let namespace = largs.value_of("namespace").unwrap_or("").to_string();
+            let namespace = get_namespace(largs.value_of("namespace").unwrap_or(""), "");
             let sort_by = largs.value_of("sort").unwrap_or("name").to_string();
             let quiet = largs.is_present("quiet");
             let verbose = largs.is_present("verbose");
@@ -83,6 +83,7 @@ impl List {
             );
 
             let cli_auth = ClientAuthE::new(token.as_str(), gateway_address.as_str())
+                .await
                 .map_err(|e| State::Error(e))?;
 
             // let transport = get_de
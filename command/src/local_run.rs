@@ -0,0 +1,428 @@
+use crate::deploy::{
+    compile_environment, derive_fprocess, language_exists_not_dockerfile, merge_slice, read_files,
+};
+use crate::faas::check_and_set_default_yaml;
+use crate::priority::get_build_engine;
+use crate::{CommandAppend, State};
+use builder::build::get_built_image_name;
+use builder::cache::BuildCache;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use exec::stream::{command_stream, StreamOptions};
+use schema::image::{select_tag_template, BuildFormat, TBuildFormat};
+use stack::schema::Function;
+use stack::stack::parse_yaml_file;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use utility::faas::types::model::FunctionResources;
+use utility::{Error, Result};
+
+/// folder of the secrets inside a function container, as mounted by the providers
+pub(crate) const SECRETS_MOUNT_PATH: &str = "/var/openfaas/secrets";
+/// port of the watchdog inside a function container
+const WATCHDOG_PORT: u16 = 8080;
+
+pub(crate) struct LocalRun;
+
+impl CommandAppend for LocalRun {
+    #[inline(always)]
+    fn append_subcommand(app: App<'static, 'static>) -> App<'static, 'static> {
+        let app = app.subcommand(
+            SubCommand::with_name("local-run")
+                .about(r#"Run a built function image on this machine with the environment the gateway would
+give it: environment and environment_file, secrets mounted from local files, fprocess,
+a read-only root filesystem and the memory and CPU limits of the YAML config. The logs of
+the function are streamed until it is stopped with Control+C.`,
+	Example: `  faas-cli local-run figlet
+  faas-cli local-run figlet --port 8081
+  faas-cli local-run -f ./stack.yml figlet --secrets-dir ./secrets --env debug=true`"#)
+                .args_from_usage("[FUNCTION-NAME] 'function to run, can be omitted when the YAML config has a single function'
+                   -p, --port [port]                        'Host port the function is exposed on'
+                   --secrets-dir [secrets-dir]              'Folder with one file per secret, named after the secret'
+                   --readonly                               'Force the root container filesystem to be read only'
+                   --tag [tag]                              'Override latest tag on function Docker image, accepts latest, sha, branch, describe or template:{branch}-{sha:7}'
+                   --engine [engine]                        'Container engine running the function: docker or podman'
+                ")
+                .arg(
+                    Arg::with_name("env")
+                        .help("Set one or more environment variables --env e1=v1 ")
+                        .long("env")
+                        .short("e")
+                        .takes_value(true)
+                        .multiple(true)
+                )
+                .arg(
+                    Arg::with_name("secret")
+                        .help("Give the function access to a secure secret")
+                        .long("secret")
+                        .takes_value(true)
+                        .multiple(true)
+                )
+        );
+
+        app
+    }
+}
+
+impl LocalRun {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if let Some(l_args) = args.subcommand_matches("local-run") {
+            local_run_from_args(l_args).await?;
+            Err(State::Matched)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+async fn local_run_from_args(l_args: &ArgMatches<'_>) -> Result<()> {
+    let yaml_file = l_args
+        .value_of("yaml")
+        .unwrap_or(check_and_set_default_yaml().unwrap_or_default());
+    if yaml_file.is_empty() {
+        return Err(Error::Custom(
+            "local-run needs a YAML file, use --yaml (-f) YAML_FILE".to_string(),
+        ));
+    }
+    let port: u16 = l_args
+        .value_of("port")
+        .unwrap_or("8080")
+        .parse()
+        .map_err(|_| Error::Custom("the --port flag must be a port number".to_string()))?;
+    let secrets_dir = l_args.value_of("secrets-dir").unwrap_or(".secrets");
+    let tag = l_args.value_of("tag").unwrap_or("");
+    let mut tag_format: Option<BuildFormat> = None;
+    tag_format.set(Some(tag.to_string()))?;
+    let engine = get_build_engine(l_args)?;
+    if !["docker", "podman"].contains(&engine.name()) {
+        return Err(Error::Custom(format!(
+            "local-run needs docker or podman to run the function, {} can only build images",
+            engine.name()
+        )));
+    }
+    let envvar_opts: Vec<String> = l_args
+        .values_of("env")
+        .unwrap_or_default()
+        .map(|m| m.to_string())
+        .collect();
+    let secrets: Vec<String> = l_args
+        .values_of("secret")
+        .unwrap_or_default()
+        .map(|m| m.to_string())
+        .collect();
+
+    let services = parse_yaml_file(yaml_file, "", "", true).await?;
+    let function_name = match l_args.value_of("FUNCTION-NAME") {
+        Some(name) => name.to_string(),
+        None if services.functions.len() == 1 => services.functions.keys().next().cloned().unwrap(),
+        None => {
+            return Err(Error::Custom(
+                "give the name of the function to run like: faas-cli local-run FUNCTION_NAME"
+                    .to_string(),
+            ))
+        }
+    };
+    let mut function = services
+        .functions
+        .get(&function_name)
+        .cloned()
+        .ok_or_else(|| {
+            Error::Custom(format!(
+                "no function {} found in {}",
+                function_name, yaml_file
+            ))
+        })?;
+    function.name = function_name;
+
+    // the same values deploy sends to the gateway
+    let file_environment = read_files(&function.environment_file)?;
+    let environment = compile_environment(
+        &envvar_opts,
+        function.environment.as_ref().unwrap_or(&HashMap::new()),
+        &file_environment,
+    )?;
+    if language_exists_not_dockerfile(function.language.as_str()) {
+        function.fprocess = derive_fprocess(&function).await.map_err(|e| {
+            Error::Custom(format!(
+                r#"template directory may be missing or invalid, please run "faas-cli template pull", Error: {}"#,
+                e
+            ))
+        })?;
+    }
+    let image = get_built_image_name(
+        &BuildCache::default(),
+        function.name.as_str(),
+        tag_format.unwrap_or_default(),
+        select_tag_template(tag, function.tag_template.as_str()),
+        function.image.as_str(),
+        function.handler.as_str(),
+    )?;
+
+    let run = LocalRunSpec::new(
+        &function,
+        image.as_str(),
+        environment,
+        merge_slice(function.secrets.clone(), secrets),
+        Path::new(secrets_dir),
+        l_args.is_present("readonly"),
+        port,
+    )?;
+    let args = run.run_args()?;
+
+    colour::green!(
+        "Running {} on http://127.0.0.1:{}, press Control+C to stop.\n",
+        run.name,
+        port
+    );
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+    let output = command_stream(
+        ".",
+        engine.name(),
+        &args,
+        &StreamOptions::new(run.name.as_str(), false),
+    )?;
+    if !output.success() {
+        return Err(Error::Custom(format!(
+            "{} exited with {}",
+            run.name, output.status
+        )));
+    }
+    Ok(())
+}
+
+/// LocalRunSpec is a function container as the providers would run it
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LocalRunSpec {
+    pub(crate) name: String,
+    pub(crate) image: String,
+    pub(crate) environment: BTreeMap<String, String>,
+    /// secret names with the local files mounted for them
+    pub(crate) secrets: Vec<(String, PathBuf)>,
+    pub(crate) read_only_root_filesystem: bool,
+    pub(crate) limits: FunctionResources,
+    pub(crate) port: u16,
+}
+
+impl LocalRunSpec {
+    /// the fprocess of the function is passed in the environment like the providers do, a secret
+    /// is read from the file named after it in the secrets folder
+    pub(crate) fn new(
+        function: &Function,
+        image: &str,
+        environment: HashMap<String, String>,
+        secrets: Vec<String>,
+        secrets_dir: &Path,
+        read_only_root_filesystem: bool,
+        port: u16,
+    ) -> Result<LocalRunSpec> {
+        let mut environment: BTreeMap<String, String> = environment.into_iter().collect();
+        if let Some(fprocess) = function.fprocess.as_ref().filter(|f| !f.is_empty()) {
+            environment.insert("fprocess".to_string(), fprocess.clone());
+        }
+
+        let mut secret_files = Vec::new();
+        for secret in secrets {
+            let file = secrets_dir.join(secret.as_str());
+            if !file.is_file() {
+                return Err(Error::Custom(format!(
+                    "secret {} of {} not found, create {} with its value or use --secrets-dir",
+                    secret,
+                    function.name,
+                    file.to_string_lossy()
+                )));
+            }
+            secret_files.push((secret, std::fs::canonicalize(file)?));
+        }
+
+        Ok(LocalRunSpec {
+            name: function.name.clone(),
+            image: image.to_string(),
+            environment,
+            secrets: secret_files,
+            read_only_root_filesystem: read_only_root_filesystem
+                || function.readonly_root_filesystem,
+            limits: function.limits.clone(),
+            port,
+        })
+    }
+
+    /// arguments of `docker run` or `podman run` for the function
+    pub(crate) fn run_args(&self) -> Result<Vec<String>> {
+        let mut args = vec![
+            "run".to_string(),
+            "--rm".to_string(),
+            "--name".to_string(),
+            self.name.clone(),
+            "--publish".to_string(),
+            // the function is only reachable from this machine, like the gateway would have it
+            format!("127.0.0.1:{}:{}", self.port, WATCHDOG_PORT),
+        ];
+        for (key, value) in &self.environment {
+            args.push("--env".to_string());
+            args.push(format!("{}={}", key, value));
+        }
+        for (secret, file) in &self.secrets {
+            args.push("--volume".to_string());
+            args.push(format!(
+                "{}:{}/{}:ro",
+                file.to_string_lossy(),
+                SECRETS_MOUNT_PATH,
+                secret
+            ));
+        }
+        if self.read_only_root_filesystem {
+            // the providers keep /tmp writable for a read-only function
+            args.push("--read-only".to_string());
+            args.push("--tmpfs".to_string());
+            args.push("/tmp".to_string());
+        }
+        if !self.limits.memory.is_empty() {
+            args.push("--memory".to_string());
+            args.push(engine_memory(self.limits.memory.as_str())?);
+        }
+        if !self.limits.cpu.is_empty() {
+            args.push("--cpus".to_string());
+            args.push(engine_cpus(self.limits.cpu.as_str())?);
+        }
+        args.push(self.image.clone());
+        Ok(args)
+    }
+}
+
+/// converts a Kubernetes memory quantity like 128Mi, 0.5Gi or 1G into the format of docker run
+fn engine_memory(memory: &str) -> Result<String> {
+    let invalid = || Error::Custom(format!("can't convert the memory limit {}", memory));
+    let split = memory
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(memory.len());
+    let (value, unit) = memory.split_at(split);
+    let value: f64 = value.parse().map_err(|_| invalid())?;
+    let bytes: u64 = match unit {
+        "" => 1,
+        "Ki" => 1 << 10,
+        "Mi" => 1 << 20,
+        "Gi" => 1 << 30,
+        "Ti" => 1 << 40,
+        "k" | "K" => 1_000,
+        "M" => 1_000_000,
+        "G" => 1_000_000_000,
+        _ => return Err(invalid()),
+    };
+    // docker run takes no fractions, a quantity like 0.5Gi is given in bytes
+    if value.fract() != 0.0 {
+        return Ok(((value * bytes as f64).round() as u64).to_string());
+    }
+    let value = value as u64;
+    Ok(match unit {
+        "Ki" => format!("{}k", value),
+        "Mi" => format!("{}m", value),
+        "Gi" => format!("{}g", value),
+        "Ti" => format!("{}g", value * 1024),
+        _ => (value * bytes).to_string(),
+    })
+}
+
+/// converts a Kubernetes CPU quantity like 500m or 2 into the number of CPUs of docker run
+fn engine_cpus(cpu: &str) -> Result<String> {
+    let invalid = || Error::Custom(format!("can't convert the CPU limit {}", cpu));
+    match cpu.strip_suffix('m') {
+        Some(milli) => {
+            let milli: u64 = milli.parse().map_err(|_| invalid())?;
+            Ok(format!("{}", milli as f64 / 1000.0))
+        }
+        None => {
+            cpu.parse::<f64>().map_err(|_| invalid())?;
+            Ok(cpu.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_engine_limits() {
+        assert_eq!(engine_memory("128Mi").unwrap(), "128m");
+        assert_eq!(engine_memory("1Gi").unwrap(), "1g");
+        assert_eq!(engine_memory("20M").unwrap(), "20000000");
+        assert_eq!(engine_memory("1048576").unwrap(), "1048576");
+        assert_eq!(engine_memory("0.5Gi").unwrap(), "536870912");
+        assert_eq!(engine_memory("1.5M").unwrap(), "1500000");
+        assert!(engine_memory("1.2.3Mi").is_err());
+        assert!(engine_memory("128Xi").is_err());
+        assert_eq!(engine_cpus("500m").unwrap(), "0.5");
+        assert_eq!(engine_cpus("2").unwrap(), "2");
+        assert!(engine_cpus("two").is_err());
+    }
+
+    #[test]
+    fn test_local_run_args() {
+        let dir = TempDir::new("local-run").unwrap();
+        std::fs::write(dir.path().join("api-key"), "s3cr3t").unwrap();
+        let function = Function {
+            name: "figlet".to_string(),
+            fprocess: Some("figlet".to_string()),
+            limits: FunctionResources {
+                memory: "128Mi".to_string(),
+                cpu: "250m".to_string(),
+            },
+            readonly_root_filesystem: true,
+            ..Default::default()
+        };
+        let mut environment = HashMap::new();
+        environment.insert("write_debug".to_string(), "true".to_string());
+
+        let missing = LocalRunSpec::new(
+            &function,
+            "user/figlet:latest",
+            environment.clone(),
+            vec!["db-password".to_string()],
+            dir.path(),
+            false,
+            8081,
+        );
+        assert!(missing.unwrap_err().to_string().contains("db-password"));
+
+        let spec = LocalRunSpec::new(
+            &function,
+            "user/figlet:latest",
+            environment,
+            vec!["api-key".to_string()],
+            dir.path(),
+            false,
+            8081,
+        )
+        .unwrap();
+        let secret = std::fs::canonicalize(dir.path().join("api-key")).unwrap();
+        assert_eq!(
+            spec.run_args().unwrap(),
+            vec![
+                "run".to_string(),
+                "--rm".to_string(),
+                "--name".to_string(),
+                "figlet".to_string(),
+                "--publish".to_string(),
+                "127.0.0.1:8081:8080".to_string(),
+                "--env".to_string(),
+                "fprocess=figlet".to_string(),
+                "--env".to_string(),
+                "write_debug=true".to_string(),
+                "--volume".to_string(),
+                format!(
+                    "{}:/var/openfaas/secrets/api-key:ro",
+                    secret.to_string_lossy()
+                ),
+                "--read-only".to_string(),
+                "--tmpfs".to_string(),
+                "/tmp".to_string(),
+                "--memory".to_string(),
+                "128m".to_string(),
+                "--cpus".to_string(),
+                "0.25".to_string(),
+                "user/figlet:latest".to_string(),
+            ]
+        );
+    }
+}
@@ -21,13 +21,14 @@
 
 use crate::error::{check_tls_insecure, NOT_TLS_WARN};
 use crate::faas::DEFAULT_GATEWAY;
-use crate::priority::{get_gateway_url, OPENFAAS_URL_ENVIRONMENT};
+use crate::priority::{get_gateway_url, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
 use crate::{CommandAppend, State};
 use clap::{App, Arg, ArgMatches, SubCommand};
 use config::config_file::{
-    decode_auth, encode_auth, lookup_auth_config, update_auth_config, BASIC_AUTH_TYPE,
+    decode_auth, encode_auth, lookup_auth_config, set_credential_helper, set_credentials_store,
+    update_auth_config, BASIC_AUTH_TYPE,
 };
-use proxy::proxy::make_http_client;
+use proxy::proxy::{make_http_client, TlsOptions};
 use reqwest::StatusCode;
 use reqwest::{Method, Url};
 use std::io::Read;
@@ -80,6 +81,8 @@ echo $PASSWORD | faas-cli login -s  --gateway https://openfaas.mydomain.com
                     "-p ,--password [password] 'Gateway password'
                         --tls-no-verify 'Disable TLS validation'
                         --password-stdin  'Reads the gateway password from stdin'
+                        --credential-helper [credential-helper] 'Keep the credentials in a docker-credential-<helper> store, i.e. pass, secretservice, osxkeychain or wincred'
+                        --credentials-store [credentials-store] 'Keep the credentials of every gateway without its own --credential-helper in a docker-credential-<helper> store'
             ",
                 )
         );
@@ -107,7 +110,6 @@ impl Login {
              --username VALUE"
             )))?;
             let password_stdin_presented = l_args.is_present("password-stdin");
-            let tls_insecure = l_args.is_present("tls-no-verify");
             let mut password = String::new();
 
             if let Some(pass) = l_args.value_of("password") {
@@ -157,10 +159,16 @@ impl Login {
                 username,
                 password.as_str(),
                 Some(timeout),
-                tls_insecure,
+                &get_tls_options(l_args, gateway.as_str()),
             )
             .await?;
 
+            if let Some(helper) = l_args.value_of("credentials-store") {
+                set_credentials_store(helper)?;
+            }
+            if let Some(helper) = l_args.value_of("credential-helper") {
+                set_credential_helper(gateway.as_str(), helper)?;
+            }
             let token = encode_auth(username, password.as_str());
 
             update_auth_config(gateway.as_str(), token.as_str(), BASIC_AUTH_TYPE.into())?;
@@ -183,13 +191,13 @@ async fn validate_login(
     user: &str,
     pass: &str,
     timeout: Option<Duration>,
-    insecure_tls: bool,
+    tls: &TlsOptions,
 ) -> crate::Result {
-    if !check_tls_insecure(gateway, insecure_tls).is_empty() {
+    if !check_tls_insecure(gateway, tls.insecure).is_empty() {
         println!("{}", NOT_TLS_WARN)
     }
 
-    let client = make_http_client(timeout, insecure_tls)?;
+    let client = make_http_client(timeout, tls)?;
 
     let url = format!("{}/system/functions", gateway.trim_end_matches('/'));
     let url = Url::parse(url.as_str()).map_err(|e| State::Custom(e.to_string()))?;
@@ -1,13 +1,24 @@
 use crate::error::check_tls_insecure;
-use crate::faas::DEFAULT_GATEWAY;
-use crate::logs_format::get_log_formatter;
-use crate::priority::{get_gateway_url, OPENFAAS_URL_ENVIRONMENT};
+use crate::faas::{check_and_set_default_yaml, DEFAULT_GATEWAY};
+use crate::logs_format::{get_log_formatter, LogFormatter};
+use crate::priority::{get_gateway_url, get_namespace, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
 use crate::{CommandAppend, State};
-use chrono::DateTime;
+use chrono::{DateTime, FixedOffset};
 use clap::{App, Arg, ArgMatches, SubCommand};
+use exec::stream::colour_by_key;
+use futures_util::future;
+use futures_util::stream::{self, Stream, StreamExt};
 use proxy::auth::ClientAuthE;
+use proxy::client::Client;
+use stack::stack::parse_yaml_file;
+use std::collections::VecDeque;
+use std::pin::Pin;
 use std::time::Duration;
-use utility::faas_provider::logs::Request;
+use tokio::time::Instant;
+use utility::faas_provider::logs::{Message, Request};
+
+/// how long a function without new messages holds back the merged logs of the others
+const MERGE_WINDOW: Duration = Duration::from_millis(250);
 
 pub(crate) struct Logs;
 
@@ -37,7 +48,9 @@ impl CommandAppend for Logs {
   faas-cli logs FN --output=json
   faas-cli logs FN --lines=5
   faas-cli logs FN --tail=false --since=10m
-  faas-cli logs FN --tail=false --since=2010-01-01T00:00:00Z"#)
+  faas-cli logs FN --tail=false --since=2010-01-01T00:00:00Z
+  faas-cli logs -f stack.yml
+  faas-cli logs -f stack.yml --filter 'api-*'"#)
                 // .arg_from_usage("<name> 'function name'")
                 //.arg_from_usage("-g, --gateway [gateway]")
 
@@ -67,7 +80,7 @@ impl CommandAppend for Logs {
              Example: --since 5sec   or --since '5 sec'  'will set timeout to 5 seconds'")
                 )
                 .args_from_usage(
-                    "<NAME> 'function name'
+                    "[NAME] 'function name, when omitted the logs of all the functions in the YAML config are merged'
              -k ,--token [token] 'Pass a JWT token to use instead of basic auth'
              -n ,--namespace [namespace]        'Namespace of the function'
              --since-time [since-time]          'include logs since the given timestamp (RFC3339)'
@@ -97,27 +110,91 @@ impl Logs {
             let include_name = l_args.is_present("name");
             let include_instance = l_args.is_present("instance");
 
+            let namespace = l_args.value_of("namespace").unwrap_or("");
+            let regex = l_args.value_of("regex").unwrap_or_default();
+            let filter = l_args.value_of("filter").unwrap_or_default();
+            let envsubst = true;
+
+            let yaml_file = l_args
+                .value_of("yaml")
+                .unwrap_or(check_and_set_default_yaml().unwrap_or_default());
+            let (services, yaml_gateway) = if !yaml_file.is_empty() && !l_args.is_present("NAME") {
+                let svcs = parse_yaml_file(yaml_file, regex, filter, envsubst).await?;
+                let yaml_gateway = svcs.provider.gateway_url.clone();
+                (Some(svcs), yaml_gateway)
+            } else {
+                (None, String::new())
+            };
+
             let openfaas_url = std::env::var(OPENFAAS_URL_ENVIRONMENT).unwrap_or(String::new());
-            let gateway = get_gateway_url(gateway, DEFAULT_GATEWAY, "", openfaas_url.as_str());
+            let gateway = get_gateway_url(
+                gateway,
+                DEFAULT_GATEWAY,
+                yaml_gateway.as_str(),
+                openfaas_url.as_str(),
+            );
             let msg = check_tls_insecure(gateway.as_str(), tls_insecure);
             if !msg.is_empty() {
                 println!("{}", msg);
             }
 
-            let log_request = log_request_from_flags(l_args)?;
-            let cli_auth = ClientAuthE::new(token, gateway.as_str())?;
-            let client = cli_auth.get_client(gateway.as_str())?;
-            let log_events = client.get_logs(log_request).await?;
-            //println!("log_events {:?}",log_events);
-
             let formatter = get_log_formatter(output_format);
+            let func_name = l_args.value_of("NAME").unwrap_or_default();
+            if !func_name.is_empty() {
+                let namespace = get_namespace(namespace, "");
+                let log_request = log_request_from_flags(l_args, func_name, namespace.as_str())?;
+                let cli_auth = ClientAuthE::new(token, gateway.as_str()).await?;
+                let client = cli_auth
+                    .get_client(gateway.as_str(), &get_tls_options(l_args, gateway.as_str()))?;
+
+                let log_events = client.get_logs_stream(log_request);
+                futures_util::pin_mut!(log_events);
+                while let Some(log_msg) = log_events.next().await {
+                    println!(
+                        "{}",
+                        formatter(&log_msg?, time_format, include_name, include_instance)?
+                    );
+                }
+                return Err(State::Matched);
+            }
 
-            for log_msg in log_events {
-                println!(
-                    "{}",
-                    formatter(&log_msg, time_format, include_name, include_instance)?
-                );
+            // (name, namespace) of every function to merge the logs of
+            let targets: Vec<(String, String)> = services
+                .map(|services| {
+                    services
+                        .functions
+                        .into_iter()
+                        .map(|(name, function)| {
+                            let namespace = get_namespace(namespace, function.namespace.as_str());
+                            (name, namespace)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            if targets.is_empty() {
+                return Err(State::Custom(
+                    "please provide a function name or a YAML file with --yaml".to_string(),
+                ));
             }
+            let requests = targets
+                .iter()
+                .map(|(name, namespace)| {
+                    log_request_from_flags(l_args, name.as_str(), namespace.as_str())
+                })
+                .collect::<utility::Result<Vec<Request>>>()?;
+
+            let cli_auth = ClientAuthE::new(token, gateway.as_str()).await?;
+            let client = cli_auth
+                .get_client(gateway.as_str(), &get_tls_options(l_args, gateway.as_str()))?;
+            print_merged_logs(
+                &client,
+                requests,
+                formatter,
+                time_format,
+                include_name,
+                include_instance,
+            )
+            .await?;
             Err(State::Matched)
         } else {
             //todo investigate the output
@@ -126,11 +203,11 @@ impl Logs {
     }
 }
 
-fn log_request_from_flags<'s>(args: &'s ArgMatches<'s>) -> utility::Result<Request<'s>> {
-    let namespace = args.value_of("namespace").unwrap_or("");
-    let func_name = args.value_of("NAME").ok_or(utility::Error::Custom(
-        "function name is required".to_string(),
-    ))?;
+fn log_request_from_flags<'s>(
+    args: &'s ArgMatches<'s>,
+    func_name: &'s str,
+    namespace: &'s str,
+) -> utility::Result<Request<'s>> {
     let tail = args.value_of("lines").unwrap_or("-1");
     let tail: isize = tail
         .parse()
@@ -175,3 +252,234 @@ fn since_value(t: Duration, d: Duration) -> i64 {
         0_i64
     }
 }
+
+/// prints the logs of several functions merged in timestamp order, every line is prefixed with
+/// the function name and instance in a colour that is stable per function. A function whose logs
+/// can't be read is reported and the logs of the others keep being printed
+async fn print_merged_logs(
+    client: &Client<'_>,
+    requests: Vec<Request<'_>>,
+    formatter: LogFormatter,
+    time_format: &str,
+    include_name: bool,
+    include_instance: bool,
+) -> utility::Result<()> {
+    let width = requests.iter().map(|r| r.name.len()).max().unwrap_or(0);
+    let names: Vec<String> = requests.iter().map(|r| r.name.to_string()).collect();
+    let streams: Vec<Pin<Box<dyn Stream<Item = utility::Result<Message>> + '_>>> = requests
+        .into_iter()
+        .map(|request| {
+            Box::pin(client.get_logs_stream(request))
+                as Pin<Box<dyn Stream<Item = utility::Result<Message>>>>
+        })
+        .collect();
+
+    let mut failed = Vec::new();
+    merge_logs(streams, MERGE_WINDOW, |index, msg| {
+        let name = names[index].as_str();
+        match msg {
+            Ok(msg) => {
+                let line = formatter(&msg, time_format, include_name, include_instance)?;
+                // the formatter already prints the name and the instance when they're asked for
+                let mut prefix = Vec::new();
+                if !include_name {
+                    prefix.push(format!("{:width$}", name, width = width));
+                }
+                if !include_instance && !msg.instance.is_empty() {
+                    prefix.push(msg.instance.clone());
+                }
+                if prefix.is_empty() {
+                    println!("{}", colour_by_key(name, line.as_str()));
+                } else {
+                    let prefix = format!("{} |", prefix.join(" "));
+                    println!("{} {}", colour_by_key(name, prefix.as_str()), line);
+                }
+            }
+            Err(e) => {
+                eprintln!("{} | {}", colour_by_key(name, name), e);
+                failed.push(name.to_string());
+            }
+        }
+        Ok(())
+    })
+    .await?;
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(utility::Error::Custom(format!(
+            "can't read the logs of {}",
+            failed.join(", ")
+        )))
+    }
+}
+
+/// merges the message streams in timestamp order and hands every message to emit with the index
+/// of its stream. Each stream is in order, so the next message is the oldest of the first messages
+/// once every open stream has one. A stream without messages holds the others back for the window
+/// at most, so a function that logs nothing doesn't stop the others from being followed. Errors
+/// are handed over as they arrive
+async fn merge_logs<S, F>(streams: Vec<S>, window: Duration, mut emit: F) -> utility::Result<()>
+where
+    S: Stream<Item = utility::Result<Message>> + Unpin,
+    F: FnMut(usize, utility::Result<Message>) -> utility::Result<()>,
+{
+    let mut pending: Vec<VecDeque<(Message, Instant)>> =
+        streams.iter().map(|_| VecDeque::new()).collect();
+    let mut open = vec![true; streams.len()];
+    // every stream ends with None so the merge knows it won't send anything older
+    let mut merged = stream::select_all(streams.into_iter().enumerate().map(|(index, s)| {
+        s.map(move |msg| (index, Some(msg)))
+            .chain(stream::once(future::ready((index, None))))
+    }));
+
+    loop {
+        emit_in_order(&mut pending, &open, false, &mut emit)?;
+        let oldest = pending
+            .iter()
+            .filter_map(|p| p.front().map(|(_, received)| *received))
+            .min();
+        let item = match oldest {
+            Some(received) => match tokio::time::timeout_at(received + window, merged.next()).await
+            {
+                Ok(item) => item,
+                Err(_) => {
+                    // the streams without messages are quiet, print what arrived
+                    emit_in_order(&mut pending, &open, true, &mut emit)?;
+                    continue;
+                }
+            },
+            None => merged.next().await,
+        };
+        match item {
+            Some((index, Some(Ok(msg)))) => pending[index].push_back((msg, Instant::now())),
+            Some((index, Some(Err(e)))) => emit(index, Err(e))?,
+            Some((index, None)) => open[index] = false,
+            None => return emit_in_order(&mut pending, &open, true, &mut emit),
+        }
+    }
+}
+
+/// hands over the messages that are next in order, all the pending ones when flush is set
+fn emit_in_order<F>(
+    pending: &mut [VecDeque<(Message, Instant)>],
+    open: &[bool],
+    flush: bool,
+    emit: &mut F,
+) -> utility::Result<()>
+where
+    F: FnMut(usize, utility::Result<Message>) -> utility::Result<()>,
+{
+    while let Some(index) = next_in_order(pending, open, flush) {
+        if let Some((msg, _)) = pending[index].pop_front() {
+            emit(index, Ok(msg))?;
+        }
+    }
+    Ok(())
+}
+
+/// the stream whose first message is the oldest, None while an open stream has no message to
+/// compare with unless flush is set
+fn next_in_order(
+    pending: &[VecDeque<(Message, Instant)>],
+    open: &[bool],
+    flush: bool,
+) -> Option<usize> {
+    if !flush
+        && pending
+            .iter()
+            .zip(open)
+            .any(|(p, open)| *open && p.is_empty())
+    {
+        return None;
+    }
+    pending
+        .iter()
+        .enumerate()
+        .filter_map(|(index, p)| p.front().map(|(msg, _)| (timestamp_key(msg), index)))
+        .min()
+        .map(|(_, index)| index)
+}
+
+/// orders messages by their RFC3339 timestamp, messages without a valid timestamp come last
+fn timestamp_key(msg: &Message) -> (u8, Option<DateTime<FixedOffset>>) {
+    DateTime::parse_from_rfc3339(msg.timestamp.as_str())
+        .map(|t| (0, Some(t)))
+        .unwrap_or((1, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logs::merge_logs;
+    use futures_util::stream::{self, StreamExt};
+    use std::time::Duration;
+    use utility::faas_provider::logs::Message;
+
+    fn message(name: &str, timestamp: &str) -> Message {
+        Message {
+            name: name.into(),
+            timestamp: timestamp.into(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_merge_logs_in_order() {
+        let api_a = stream::iter(vec![
+            Ok(message("api-a", "2021-06-01T10:00:00.5Z")),
+            Ok(message("api-a", "2021-06-01T10:00:03Z")),
+            Ok(message("api-a", "not a time")),
+        ]);
+        let api_b = stream::iter(vec![
+            Ok(message("api-b", "2021-06-01T12:00:01+02:00")),
+            Ok(message("api-b", "2021-06-01T10:00:02Z")),
+            Err(utility::Error::Custom("disconnected".to_string())),
+        ]);
+        let api_c = stream::iter(vec![Ok(message("api-c", "2021-06-01T10:00:04Z"))]);
+
+        let mut order = Vec::new();
+        let mut errors = Vec::new();
+        merge_logs(
+            vec![api_a, api_b, api_c],
+            Duration::from_secs(60),
+            |index, msg| {
+                match msg {
+                    Ok(msg) => order.push(msg.timestamp),
+                    Err(_) => errors.push(index),
+                }
+                Ok(())
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            order,
+            vec![
+                "2021-06-01T10:00:00.5Z",
+                "2021-06-01T12:00:01+02:00",
+                "2021-06-01T10:00:02Z",
+                "2021-06-01T10:00:03Z",
+                "2021-06-01T10:00:04Z",
+                "not a time"
+            ]
+        );
+        // the error of a stream doesn't stop the others
+        assert_eq!(errors, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_logs_quiet_stream() {
+        let api_a = stream::iter(vec![Ok(message("api-a", "2021-06-01T10:00:00Z"))]).boxed();
+        let quiet = stream::pending().boxed();
+
+        let mut order = Vec::new();
+        let merge = merge_logs(vec![api_a, quiet], Duration::from_millis(10), |_, msg| {
+            order.push(msg.unwrap().timestamp);
+            Ok(())
+        });
+        assert!(tokio::time::timeout(Duration::from_millis(200), merge)
+            .await
+            .is_err());
+        assert_eq!(order, vec!["2021-06-01T10:00:00Z"]);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::faas::DEFAULT_GATEWAY;
-use crate::priority::{get_gateway_url, OPENFAAS_URL_ENVIRONMENT};
+use crate::priority::{get_gateway_url, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
 use crate::{CommandAppend, State};
 use clap::{App, ArgMatches, SubCommand};
 use proxy::auth::ClientAuthE;
@@ -37,11 +37,14 @@ impl Namespaces {
             let gateway_address =
                 get_gateway_url(gateway, DEFAULT_GATEWAY, "", openfass_url.as_str());
 
-            let cli_auth = ClientAuthE::new(token, gateway_address.as_str())?;
+            let cli_auth = ClientAuthE::new(token, gateway_address.as_str()).await?;
 
             //transport := GetDefaultCLITransport(tlsInsecure, &commandTimeout)
             //client, err := proxy.NewClient(cliAuth, gatewayAddress, transport, &commandTimeout)
-            let mut client = cli_auth.get_client(gateway_address.as_str())?;
+            let mut client = cli_auth.get_client(
+                gateway_address.as_str(),
+                &get_tls_options(ns_args, gateway_address.as_str()),
+            )?;
             let namespaces = client.list_namesapces().await?;
             // namespaces, err := client.ListNamespaces(context.Background())
             print_namespaces(&namespaces);
@@ -4,6 +4,7 @@ use crate::fetch_template::{DEFAULT_TEMPLATE_REPOSITORY, TEMPLATE_DIRECTORY};
 use crate::priority::{
     get_gateway_url, get_template_url, OPENFAAS_URL_ENVIRONMENT, TEMPLATE_URL_ENVIRONMENT,
 };
+use crate::template_lock::LockMode;
 use crate::update_gitignore::update_gitignore;
 use crate::validate::validate_language_flag;
 use crate::version::print_logo;
@@ -32,6 +33,7 @@ language or type in --list for a list of languages available.`,
   faas-cli new chatbot --lang node --append stack.yml
   faas-cli new text-parser --lang python --quiet
   faas-cli new text-parser --lang python --gateway http://mydomain:8080
+  faas-cli new text-parser --lang python --offline
   faas-cli new --list`"#)
         ).args_from_usage("[FUNCTION-NAME]
             --lang               [lang]               'Language or template to use'
@@ -44,6 +46,7 @@ language or type in --list for a list of languages available.`,
             --list                                                  'List available languages'
             -a ,--append        [append]                             'Append to existing YAML file'
             -q, --quiet                                               'Skip template notes'
+            --offline                                               'Pull the templates from the template cache only, without fetching their repository'
             ");
         app
     }
@@ -71,6 +74,7 @@ impl NewFunction {
             let envsubst = n_args.is_present("envsubst");
             let quiet = n_args.is_present("quiet");
             let list = n_args.is_present("list");
+            let offline = n_args.is_present("offline");
 
             if !list {
                 let (lang, _) = validate_language_flag(language);
@@ -93,7 +97,7 @@ impl NewFunction {
                 let template_url = std::env::var(TEMPLATE_URL_ENVIRONMENT).unwrap_or("".into());
                 let template_address =
                     get_template_url("", template_url.as_str(), DEFAULT_TEMPLATE_REPOSITORY);
-                pull_templates(template_address.as_str())?;
+                pull_templates(template_address.as_str(), LockMode::Locked, offline).await?;
 
                 if !is_valid_template(language).await {
                     return Err(State::Custom(format!(
@@ -240,24 +244,11 @@ impl NewFunction {
                 }
 
                 let yaml_content = prepare_yaml_content(append_mode, gateway.as_str(), &function);
-                let mut file;
-                #[cfg(target_os = "windows")]
-                {
-                    let mut options = std::fs::File::options();
-                    options.write(true).append(true);
-                    file = options
-                        .open(format!("./{}", file_name))
-                        .map_err(|e| State::Custom(e.to_string()))?;
-                }
-                //todo fix this
-                #[cfg(target_os = "unix")]
-                {
-                    let mut options = std::fs::File::options();
-                    options.write(true).append(true);
-                    file = options
-                        .open(format!("./{}", file_name))
-                        .map_err(|e| State::Custom(e.to_string()))?;
-                }
+                let mut options = std::fs::File::options();
+                options.write(true).append(true);
+                let mut file = options
+                    .open(format!("./{}", file_name))
+                    .map_err(|e| State::Custom(e.to_string()))?;
 
                 file.write_all(yaml_content.as_bytes())
                     .map_err(|e| State::Custom(e.to_string()))?;
@@ -1,14 +1,43 @@
 #![allow(dead_code)]
+use builder::backend::{get_build_backend, BuildBackend};
+use clap::ArgMatches;
+use config::config_file::{lookup_build_engine, lookup_current_context, Context};
+use proxy::proxy::TlsOptions;
 
 pub(crate) const OPENFAAS_URL_ENVIRONMENT: &str = "OPENFAAS_URL";
 pub(crate) const TEMPLATE_URL_ENVIRONMENT: &str = "OPENFAAS_TEMPLATE_URL";
 pub(crate) const TEMPLATE_STORE_URL_ENVIRONMENT: &str = "OPENFAAS_TEMPLATE_STORE_URL";
+pub(crate) const CA_FILE_ENVIRONMENT: &str = "OPENFAAS_CA_FILE";
+
+/// returns the context selected with `faas context use`, None when there is none or the config
+/// file can't be read
+pub(crate) fn current_context() -> Option<Context> {
+    lookup_current_context().ok().flatten()
+}
 
 pub(crate) fn get_gateway_url(
     argument_url: &str,
     default_url: &str,
     yaml_url: &str,
     environment_url: &str,
+) -> String {
+    let context_url = current_context().map(|c| c.gateway).unwrap_or_default();
+    get_gateway_url_with_context(
+        argument_url,
+        default_url,
+        yaml_url,
+        context_url.as_str(),
+        environment_url,
+    )
+}
+
+/// the gateway priority is flag, stack.yml, current context, OPENFAAS_URL then the default
+pub(crate) fn get_gateway_url_with_context(
+    argument_url: &str,
+    default_url: &str,
+    yaml_url: &str,
+    context_url: &str,
+    environment_url: &str,
 ) -> String {
     let mut gateway_url: String;
 
@@ -16,6 +45,8 @@ pub(crate) fn get_gateway_url(
         gateway_url = argument_url.into();
     } else if !yaml_url.is_empty() && yaml_url != default_url {
         gateway_url = yaml_url.into();
+    } else if !context_url.is_empty() {
+        gateway_url = context_url.into();
     } else if !environment_url.is_empty() {
         gateway_url = environment_url.into();
     } else {
@@ -23,13 +54,54 @@ pub(crate) fn get_gateway_url(
     }
 
     gateway_url = gateway_url.trim_end_matches('/').to_ascii_lowercase();
-    if !gateway_url.starts_with("http://") {
+    if !gateway_url.starts_with("http://") && !gateway_url.starts_with("https://") {
         format!("http://{}", gateway_url)
     } else {
         gateway_url
     }
 }
 
+/// returns the TLS settings from the --tls-no-verify, --ca-file, --tls-cert and --tls-key flags,
+/// the CA file falls back to the OPENFAAS_CA_FILE environment variable, then unset values to the
+/// current context when the gateway is the one of the context
+pub(crate) fn get_tls_options(args: &ArgMatches<'_>, gateway: &str) -> TlsOptions {
+    get_tls_options_with_context(args, gateway, current_context().unwrap_or_default())
+}
+
+/// the TLS settings of a context are meant for its gateway, they're left out when another
+/// gateway is given with --gateway, stack.yml or OPENFAAS_URL
+pub(crate) fn get_tls_options_with_context(
+    args: &ArgMatches<'_>,
+    gateway: &str,
+    context: Context,
+) -> TlsOptions {
+    let context = if !context.gateway.is_empty()
+        && get_gateway_url_with_context(gateway, "", "", "", "")
+            == get_gateway_url_with_context(context.gateway.as_str(), "", "", "", "")
+    {
+        context
+    } else {
+        Context::default()
+    };
+    let mut ca_file = match args.value_of("ca-file") {
+        Some(ca_file) if !ca_file.is_empty() => ca_file.to_string(),
+        _ => std::env::var(CA_FILE_ENVIRONMENT).unwrap_or_default(),
+    };
+    if ca_file.is_empty() {
+        ca_file = context.ca_file;
+    }
+    let (cert_file, key_file) = match (args.value_of("tls-cert"), args.value_of("tls-key")) {
+        (Some(cert), Some(key)) => (cert.to_string(), key.to_string()),
+        _ => (context.cert_file, context.key_file),
+    };
+    TlsOptions {
+        insecure: args.is_present("tls-no-verify") || context.tls_insecure,
+        ca_file,
+        cert_file,
+        key_file,
+    }
+}
+
 pub(crate) fn get_template_url(
     argument_url: &str,
     environment_url: &str,
@@ -49,8 +121,29 @@ pub(crate) fn get_template_store_url(
     environment_url: &str,
     default_url: &str,
 ) -> String {
-    if argument_url != default_url {
+    let context_url = current_context()
+        .map(|c| c.template_store_url)
+        .unwrap_or_default();
+    get_template_store_url_with_context(
+        argument_url,
+        context_url.as_str(),
+        environment_url,
+        default_url,
+    )
+}
+
+/// the template store priority is flag, current context, OPENFAAS_TEMPLATE_STORE_URL then the
+/// default, the context comes before the environment like it does for the gateway
+pub(crate) fn get_template_store_url_with_context(
+    argument_url: &str,
+    context_url: &str,
+    environment_url: &str,
+    default_url: &str,
+) -> String {
+    if !argument_url.is_empty() && argument_url != default_url {
         argument_url.to_string()
+    } else if !context_url.is_empty() {
+        context_url.to_string()
     } else if !environment_url.is_empty() {
         environment_url.to_string()
     } else {
@@ -58,6 +151,18 @@ pub(crate) fn get_template_store_url(
     }
 }
 
+/// returns the build backend chosen by the --engine flag, then the build-engine of the config
+/// file, docker otherwise
+pub(crate) fn get_build_engine(
+    args: &ArgMatches<'_>,
+) -> utility::Result<&'static dyn BuildBackend> {
+    let engine = match args.value_of("engine") {
+        Some(engine) if !engine.is_empty() => engine.to_string(),
+        _ => lookup_build_engine()?,
+    };
+    get_build_backend(engine.as_str())
+}
+
 pub(crate) fn get_namespace(flag_namespace: &str, stack_namespace: &str) -> String {
     // If the namespace flag is passed use it
     if !flag_namespace.is_empty() {
@@ -66,7 +171,124 @@ pub(crate) fn get_namespace(flag_namespace: &str, stack_namespace: &str) -> Stri
     } else if !stack_namespace.is_empty() {
         stack_namespace.into()
     } else {
-        //return defaultFunctionNamespace
-        "".into()
+        // the default namespace of the current context, otherwise the provider default
+        current_context().map(|c| c.namespace).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::faas::DEFAULT_GATEWAY;
+    use crate::priority::{
+        get_gateway_url_with_context, get_template_store_url_with_context,
+        get_tls_options_with_context,
+    };
+    use clap::App;
+    use config::config_file::Context;
+
+    #[test]
+    fn test_get_gateway_url_keeps_scheme() {
+        assert_eq!(
+            get_gateway_url_with_context("https://gw.example.com/", DEFAULT_GATEWAY, "", "", ""),
+            "https://gw.example.com"
+        );
+        assert_eq!(
+            get_gateway_url_with_context("gw.example.com:8080", DEFAULT_GATEWAY, "", "", ""),
+            "http://gw.example.com:8080"
+        );
+        assert_eq!(
+            get_gateway_url_with_context("", DEFAULT_GATEWAY, "", "", "https://env.example.com"),
+            "https://env.example.com"
+        );
+    }
+
+    #[test]
+    fn test_get_gateway_url_context_priority() {
+        let context = "https://staging.example.com";
+        assert_eq!(
+            get_gateway_url_with_context(
+                DEFAULT_GATEWAY,
+                DEFAULT_GATEWAY,
+                "",
+                context,
+                "http://env.example.com"
+            ),
+            context
+        );
+        assert_eq!(
+            get_gateway_url_with_context(
+                DEFAULT_GATEWAY,
+                DEFAULT_GATEWAY,
+                "http://yaml.example.com",
+                context,
+                ""
+            ),
+            "http://yaml.example.com"
+        );
+        assert_eq!(
+            get_gateway_url_with_context(
+                "http://flag.example.com",
+                DEFAULT_GATEWAY,
+                "",
+                context,
+                ""
+            ),
+            "http://flag.example.com"
+        );
+    }
+
+    #[test]
+    fn test_get_tls_options_context_gateway() {
+        let context = Context {
+            name: "staging".to_string(),
+            gateway: "https://staging.example.com/".to_string(),
+            tls_insecure: true,
+            ca_file: "staging-ca.pem".to_string(),
+            cert_file: "staging.crt".to_string(),
+            key_file: "staging.key".to_string(),
+            ..Default::default()
+        };
+        let args = App::new("faas-cli")
+            .args_from_usage(
+                "--tls-no-verify 'Disable TLS validation'
+                 --ca-file [ca-file] 'CA file'
+                 --tls-cert [tls-cert] 'client certificate'
+                 --tls-key [tls-key] 'client key'",
+            )
+            .get_matches_from(vec!["faas-cli"]);
+
+        let tls =
+            get_tls_options_with_context(&args, "https://staging.example.com", context.clone());
+        assert!(tls.insecure);
+        assert_eq!(tls.cert_file, "staging.crt");
+
+        // a gateway given with --gateway or OPENFAAS_URL doesn't get the TLS settings of the context
+        let tls = get_tls_options_with_context(&args, "https://prod.example.com", context);
+        assert!(!tls.insecure);
+        assert!(tls.cert_file.is_empty());
+        assert!(tls.key_file.is_empty());
+    }
+
+    #[test]
+    fn test_get_template_store_url_context_priority() {
+        let default = "https://raw.githubusercontent.com/openfaas/store/master/templates.json";
+        let context = "https://store.staging.example.com/templates.json";
+        let env = "https://store.env.example.com/templates.json";
+        assert_eq!(
+            get_template_store_url_with_context(default, context, env, default),
+            context
+        );
+        assert_eq!(
+            get_template_store_url_with_context("https://flag", context, env, default),
+            "https://flag"
+        );
+        assert_eq!(
+            get_template_store_url_with_context(default, "", env, default),
+            env
+        );
+        assert_eq!(
+            get_template_store_url_with_context("", "", "", default),
+            default
+        );
     }
 }
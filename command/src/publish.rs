@@ -1,14 +1,17 @@
-use crate::build::{combine_build_opts, parse_build_args, pull_templates};
+use crate::build::{check_frozen_functions, combine_build_opts, parse_build_args, pull_templates};
 use crate::deploy::{merge_map, merge_slice, parse_map};
 use crate::fetch_template::DEFAULT_TEMPLATE_REPOSITORY;
-use crate::priority::{get_template_url, TEMPLATE_URL_ENVIRONMENT};
+use crate::priority::{get_build_engine, get_template_url, TEMPLATE_URL_ENVIRONMENT};
+use crate::template_lock::LockMode;
 use crate::template_pull_stack::{filter_existing_templates, pull_stack_templates};
 use crate::template_store_list::DEFAULT_TEMPLATE_STORE;
 use crate::validate::validate_language_flag;
 use crate::{CommandAppend, State};
+use builder::backend::BuildBackend;
+use builder::ignore_file::project_root;
 use builder::publish::{publish_image, PublishImage};
 use clap::{App, Arg, ArgMatches, SubCommand};
-use schema::image::{BuildFormat, TBuildFormat};
+use schema::image::{select_tag_template, BuildFormat, TBuildFormat};
 use stack::schema::{Function, Services};
 use stack::stack::parse_yaml_file;
 use std::collections::HashMap;
@@ -16,6 +19,9 @@ use std::process::Stdio;
 use std::time::Instant;
 use utility::{Error, Result};
 
+/// platform of the images published without --platforms or platforms in stack.yml
+const DEFAULT_PLATFORM: &str = "linux/amd64";
+
 pub(crate) struct Publish;
 
 impl CommandAppend for Publish {
@@ -39,9 +45,10 @@ impl CommandAppend for Publish {
         faas-cli publish -f go.yml --no-cache --build-arg NPM_VERSION=0.2.2
         faas-cli publish --build-option dev
         faas-cli publish --tag sha
+        faas-cli publish --shrinkwrap --archive out/
         `"#)
                 .args_from_usage(
-                    " --platforms [platforms]          'A set of platforms to publish'
+                    " --platforms [platforms]          'A set of platforms to publish, the platforms of the function or linux/amd64 when not given'
                           --handler [handler]             'Directory with handler for function, e.g. handler.js'
                           --image   [image]                'Docker image name to build'
                           --lang    [lang]                  'Programming language template'
@@ -50,9 +57,14 @@ impl CommandAppend for Publish {
                           --squash                          'Use Docker's squash flag for smaller images [experimental]'
                           --parallel [parallel]             'Build in parallel to depth specified.'
                           --shrinkwrap                      'Just write files to ./build/ folder for shrink-wrapping'
+                          --archive [archive]               'With --shrinkwrap, write each build context as a reproducible tar.gz and a JSON build manifest to this folder'
                          --quiet                          'Perform a quiet build, without showing output from Docker'
                          --disable-stack-pull              'Disables the template configuration in the stack.yml'
-                         --tag [tag]                         'Override latest tag on function Docker image, accepts latest, sha, branch, or describe'
+                         --frozen                          'Fail when the templates don't match template.lock instead of updating it'
+                         --offline                         'Pull the templates from the template cache only, without fetching their repositories'
+                         --tag [tag]                         'Override latest tag on function Docker image, accepts latest, sha, branch, describe or template:{branch}-{sha:7}'
+                         --engine [engine]                   'Container build engine: docker, podman, buildah or buildctl'
+                         --no-provenance                     'Do not add the OCI provenance labels (revision, source, created, version and template) to the images'
             ")
                 .arg(
                     Arg::with_name("build-arg")
@@ -131,9 +143,20 @@ pub(crate) async fn publish_from_args(p_args: &ArgMatches<'_>) -> utility::Resul
     let shrinkwrap = p_args.is_present("shrinkwrap");
     let quiet_build = p_args.is_present("quiet");
     let disable_stack_pull = p_args.is_present("disable-stack-pull");
+    let lock_mode = LockMode::from_flags(p_args.is_present("frozen"), false)?;
+    let offline = p_args.is_present("offline");
+    let engine = get_build_engine(p_args)?;
+    let provenance = !p_args.is_present("no-provenance");
+    let archive = p_args.value_of("archive").unwrap_or_default();
+    let project_root = project_root(yaml_file);
+    if !archive.is_empty() && !shrinkwrap {
+        return Err(Error::Custom(
+            "the --archive flag requires --shrinkwrap".to_string(),
+        ));
+    }
 
     let language = p_args.value_of("lang").unwrap_or_default();
-    let platforms = p_args.value_of("platforms").unwrap_or("linux/amd64");
+    let platforms = p_args.value_of("platforms").unwrap_or_default();
     // let image = b_args.value_of("image").unwrap_or_default();
     // let handler = b_args.value_of("handler").unwrap_or_default();
 
@@ -200,35 +223,32 @@ pub(crate) async fn publish_from_args(p_args: &ArgMatches<'_>) -> utility::Resul
     let temp_url = std::env::var(TEMPLATE_URL_ENVIRONMENT).unwrap_or_default();
     let template_address = get_template_url("", temp_url.as_str(), DEFAULT_TEMPLATE_REPOSITORY);
 
-    pull_templates(template_address.as_str())
+    pull_templates(template_address.as_str(), lock_mode, offline)
+        .await
         .map_err(|e| Error::Custom(format!("could not pull templates for OpenFaaS: {}", e)))?;
 
-    let mut task = std::process::Command::new("docker");
-    task.args(&[
-        "buildx",
-        "create",
-        "--use",
-        "--name=multiarch",
-        "--node=multiarch",
-    ])
-    .env("DOCKER_CLI_EXPERIMENTAL", "enabled");
-
-    if quiet_build {
-        task.stdout(Stdio::piped());
-    }
+    if let Some((program, setup_args)) = engine.publish_setup() {
+        let mut task = std::process::Command::new(program);
+        task.args(&setup_args)
+            .env("DOCKER_CLI_EXPERIMENTAL", "enabled");
 
-    let output = task.output().map_err(|e| Error::Custom(e.to_string()))?;
+        if quiet_build {
+            task.stdout(Stdio::piped());
+        }
 
-    if !output.status.success() {
-        return Err(Error::Custom(format!(
-            "non-zero exit code:{}",
-            output.status
-        )));
-    }
+        let output = task.output().map_err(|e| Error::Custom(e.to_string()))?;
+
+        if !output.status.success() {
+            return Err(Error::Custom(format!(
+                "non-zero exit code:{}",
+                output.status
+            )));
+        }
 
-    unsafe {
-        let out = std::str::from_utf8_unchecked(&output.stdout);
-        colour::green!("Created buildx node: {}\n", out)
+        unsafe {
+            let out = std::str::from_utf8_unchecked(&output.stdout);
+            colour::green!("Created buildx node: {}\n", out)
+        }
     }
 
     if !services.stack_configuration.template_configs.is_empty() && !disable_stack_pull {
@@ -249,7 +269,10 @@ pub(crate) async fn publish_from_args(p_args: &ArgMatches<'_>) -> utility::Resul
             yaml_file,
             false,
             false,
+            lock_mode,
+            offline,
         )
+        .await
         .map_err(|e| {
             Error::Custom(format!(
                 "could not pull templates from function yaml file:: {}",
@@ -258,6 +281,10 @@ pub(crate) async fn publish_from_args(p_args: &ArgMatches<'_>) -> utility::Resul
         })?;
     }
 
+    if lock_mode == LockMode::Frozen {
+        check_frozen_functions(&services)?;
+    }
+
     let errors = publish(
         services,
         parallel,
@@ -272,6 +299,11 @@ pub(crate) async fn publish_from_args(p_args: &ArgMatches<'_>) -> utility::Resul
         shrinkwrap,
         quiet_build,
         tag_format.unwrap_or_default(),
+        tag,
+        engine,
+        provenance,
+        archive,
+        project_root.as_str(),
     )
     .await?;
 
@@ -302,6 +334,11 @@ async fn publish(
     shrinkwrap: bool,
     quiet_build: bool,
     tag_mode: i32,
+    flag_tag: &str,
+    engine: &'static dyn BuildBackend,
+    provenance: bool,
+    archive: &str,
+    project_root: &str,
 ) -> Result<Vec<String>> {
     let mut errors: Vec<String> = Vec::new();
 
@@ -313,6 +350,8 @@ async fn publish(
     let mut handles = vec![];
     for (k, mut function) in services.functions {
         let extra = extra_path.clone();
+        function.tag_template =
+            select_tag_template(flag_tag, function.tag_template.as_str()).to_string();
 
         if queue_depth > 0 {
             if function.skip_build {
@@ -341,6 +380,10 @@ async fn publish(
                     shrinkwrap,
                     quiet_build,
                     tag_mode,
+                    engine,
+                    provenance,
+                    archive.to_string(),
+                    project_root.to_string(),
                 ));
                 handles.push(h);
             }
@@ -371,6 +414,10 @@ async fn publish(
                     shrinkwrap,
                     quiet_build,
                     tag_mode,
+                    engine,
+                    provenance,
+                    archive.to_string(),
+                    project_root.to_string(),
                 )
                 .await;
                 errors.append(&mut list);
@@ -405,6 +452,10 @@ async fn image_publisher(
     shrinkwrap: bool,
     quiet_build: bool,
     tag_mode: BuildFormat,
+    engine: &'static dyn BuildBackend,
+    provenance: bool,
+    archive: String,
+    project_root: String,
 ) -> Vec<String> {
     let inner_start = Instant::now();
     let mut errors: Vec<String> = Vec::new();
@@ -429,11 +480,16 @@ async fn image_publisher(
             build_arg_map: &combined_build_arg_map,
             build_options: &combined_build_options,
             tag_mode,
+            tag_template: function.tag_template.as_str(),
             build_label_map: &build_label_map,
             quiet_build,
             copy_extra_paths: &combined_extra_paths,
-            platforms: platforms.as_str(),
+            platforms: select_platforms(platforms.as_str(), function.platforms.as_str()),
             extra_tags: &tag_extra,
+            engine,
+            provenance,
+            archive: archive.as_str(),
+            project_root: project_root.as_str(),
         };
         if let Err(e) = publish_image(&image_data).await {
             errors.push(e.to_string());
@@ -448,3 +504,30 @@ async fn image_publisher(
     );
     errors
 }
+
+/// returns the platforms to publish a function for, the --platforms flag wins over the platforms
+/// of the function in stack.yml
+fn select_platforms<'s>(flag_platforms: &'s str, function_platforms: &'s str) -> &'s str {
+    if !flag_platforms.is_empty() {
+        flag_platforms
+    } else if !function_platforms.is_empty() {
+        function_platforms
+    } else {
+        DEFAULT_PLATFORM
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_platforms() {
+        assert_eq!(
+            select_platforms("linux/arm64", "linux/arm/7"),
+            "linux/arm64"
+        );
+        assert_eq!(select_platforms("", "linux/arm/7"), "linux/arm/7");
+        assert_eq!(select_platforms("", ""), DEFAULT_PLATFORM);
+    }
+}
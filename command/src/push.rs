@@ -1,13 +1,20 @@
+use crate::stack_summary::{StackSummary, StepResult, StepStatus};
 use crate::{CommandAppend, State};
 use clap::{App, ArgMatches, SubCommand};
 
 use crate::faas::check_and_set_default_yaml;
-use builder::build::get_image_tag_values;
+use crate::priority::get_build_engine;
+use builder::backend::BuildBackend;
+use builder::build::{get_built_image_name, get_image_tag_values};
+use builder::cache::{BuildCache, CacheEntry};
 use colour::yellow;
-use schema::image::{build_image_name, BuildFormat, TBuildFormat, DEFAULT_FORMAT};
+use schema::image::{
+    build_image_name, select_tag_template, BuildFormat, TBuildFormat, DEFAULT_FORMAT,
+};
 use stack::schema::{Function, Services};
 use stack::stack::parse_yaml_file;
 use std::collections::HashMap;
+use std::time::Instant;
 use utility::Error;
 
 pub(crate) struct Push;
@@ -32,7 +39,10 @@ These container images must already be present in your local image cache.`,
   faas-cli push -f ./stack.yml --tag describe' "#)
                 .args_from_usage(
                     "--parallel [parallel]  'Push images in parallel to depth specified.'
-                           --tag [tag]       'Override latest tag on function Docker image, accepts 'latest', 'sha', 'branch', 'describe'
+                           --tag [tag]       'Override latest tag on function Docker image, accepts latest, sha, branch, describe or template:{branch}-{sha:7}'
+                           --engine [engine] 'Container build engine: docker, podman, buildah or buildctl'
+                           --force           'Push images even when the build cache has them already pushed'
+                           --keep-going      'Carry on with the other functions when one of them fails'
             ")
         );
         app
@@ -43,7 +53,7 @@ impl Push {
     #[inline(always)]
     pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
         if let Some(ps_args) = args.subcommand_matches("push") {
-            push_from_args(ps_args).await?;
+            push_from_args(ps_args, &mut Vec::new()).await?;
             Err(State::Matched)
             //pushStack(&services, parallel, tagFormat)
         } else {
@@ -52,7 +62,12 @@ impl Push {
     }
 }
 
-pub(crate) async fn push_from_args(ps_args: &ArgMatches<'_>) -> utility::Result<()> {
+/// pushes the functions of the arguments, the functions in failed are left out and the ones
+/// failing to push are added to it
+pub(crate) async fn push_from_args(
+    ps_args: &ArgMatches<'_>,
+    failed: &mut Vec<String>,
+) -> utility::Result<()> {
     // let gateway = args.value_of("gateway").unwrap_or(DEFAULT_GATEWAY);
     let regex = ps_args.value_of("regex").unwrap_or("");
     let filter = ps_args.value_of("filter").unwrap_or("");
@@ -62,6 +77,9 @@ pub(crate) async fn push_from_args(ps_args: &ArgMatches<'_>) -> utility::Result<
     let envsubst = true; //ps_args.is_present("envsubst");
     let parallel = ps_args.value_of("parallel").unwrap_or("1");
     let tag = ps_args.value_of("tag").unwrap_or("");
+    let engine = get_build_engine(ps_args)?;
+    let force = ps_args.is_present("force");
+    let keep_going = ps_args.is_present("keep-going");
     let parallel: usize = parallel
         .parse()
         .map_err(|_e| Error::Custom(format!("{} not valid integer positive number", parallel)))?;
@@ -93,8 +111,20 @@ pub(crate) async fn push_from_args(ps_args: &ArgMatches<'_>) -> utility::Result<
 
                                       You must provide a username or registry prefix to the Function's image such as user1/function1",invalid_images)));
         }
-        push_stack(&services, parallel, tag_format.unwrap()).await?;
-        Ok(())
+        let mut services = services;
+        services.functions.retain(|name, _| !failed.contains(name));
+        let summary = push_stack(
+            &services,
+            parallel,
+            tag_format.unwrap(),
+            tag,
+            engine,
+            force,
+            keep_going,
+        )
+        .await?;
+        failed.extend(summary.failed_functions());
+        summary.finish()
     } else {
         Err(Error::Custom(
             "you must supply a valid YAML file".to_string(),
@@ -106,27 +136,54 @@ async fn push_stack(
     services: &Services,
     queue_depth: usize,
     tag_mode: BuildFormat,
-) -> utility::Result<()> {
+    flag_tag: &str,
+    engine: &'static dyn BuildBackend,
+    force: bool,
+    keep_going: bool,
+) -> utility::Result<StackSummary> {
     let mut tag_mode = tag_mode;
+    let mut summary = StackSummary::new("push");
 
     let mut index = -1;
     let mut handles = vec![];
 
-    let mut push_func = move |function: Function, index: i32| -> utility::Result<()> {
-        let res = get_image_tag_values(tag_mode.clone());
+    let mut push_func = move |function: Function, index: i32| -> StepResult {
+        let start = Instant::now();
+        let image_name = if function.tag_template.is_empty() {
+            let res = get_image_tag_values(tag_mode);
 
-        let (branch, sha) = if let Ok((b, v)) = res {
-            (b, v)
+            let (branch, sha) = if let Ok((b, v)) = res {
+                (b, v)
+            } else {
+                tag_mode = DEFAULT_FORMAT;
+                (String::new(), String::new())
+            };
+            build_image_name(
+                tag_mode,
+                function.image.as_str(),
+                sha.as_str(),
+                branch.as_str(),
+            )
         } else {
-            tag_mode = DEFAULT_FORMAT;
-            (String::new(), String::new())
+            match get_built_image_name(
+                &BuildCache::default(),
+                function.name.as_str(),
+                tag_mode,
+                function.tag_template.as_str(),
+                function.image.as_str(),
+                function.handler.as_str(),
+            ) {
+                Ok(image_name) => image_name,
+                Err(e) => {
+                    return StepResult::new(
+                        function.name.as_str(),
+                        function.image.as_str(),
+                        start.elapsed(),
+                        StepStatus::Failed(e.to_string()),
+                    )
+                }
+            }
         };
-        let image_name = build_image_name(
-            tag_mode,
-            function.image.as_str(),
-            sha.as_str(),
-            branch.as_str(),
-        );
 
         let str = format!(
             "[{}] > Pushing {} [{}].\n",
@@ -134,36 +191,79 @@ async fn push_stack(
         );
         yellow!("{}", str.as_str());
 
-        if function.image.is_empty() {
-            println!("Please provide a valid Image value in the YAML file.")
+        let status = if function.image.is_empty() {
+            println!("Please provide a valid Image value in the YAML file.");
+            StepStatus::Failed("no image given for the function".to_string())
         } else if function.skip_build {
             println!("Skipping {}\n", function.name);
+            StepStatus::Skipped("skip_build is set".to_string())
+        } else if !force && BuildCache::default().is_pushed(&function.name, &image_name) {
+            println!(
+                "Skipping push of: {}, {} is unchanged since the last push. Use --force to push it again.\n",
+                function.name, image_name
+            );
+            StepStatus::Skipped("already pushed".to_string())
+        } else if let Err(e) = push_image(engine, &function.name, image_name.as_str()) {
+            colour::red!("[{}] Push of {} failed: {}\n", index, function.name, e);
+            StepStatus::Failed(e.to_string())
         } else {
-            push_image(image_name.as_str())?;
+            if let Err(e) = BuildCache::default().record_push(&function.name, &image_name) {
+                println!(
+                    "Unable to update the build cache of {}: {}\n",
+                    function.name, e
+                );
+            }
             let str = format!(
                 "[{}] < Pushing {} [{}] done.\n",
                 index, function.name, image_name
             );
             yellow!("{}", str.as_str());
-        }
-        Ok(())
+            StepStatus::Ok
+        };
+        StepResult::new(
+            function.name.as_str(),
+            image_name.as_str(),
+            start.elapsed(),
+            status,
+        )
     };
-    for (_, function) in services.functions.clone() {
+    let mut functions: Vec<(String, Function)> = services.functions.clone().into_iter().collect();
+    functions.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut functions = functions.into_iter();
+    while let Some((name, mut function)) = functions.next() {
         index += 1;
-        if queue_depth != 0 {
+        function.name = name;
+        function.tag_template =
+            select_tag_template(flag_tag, function.tag_template.as_str()).to_string();
+        if queue_depth > 1 {
+            // a failure stops the functions not started yet unless --keep-going is set
+            if summary.wait_for_slot(&mut handles, queue_depth).await? && !keep_going {
+                summary.not_started(
+                    std::iter::once((function.name, function.image))
+                        .chain(functions.map(|(k, f)| (k, f.image)))
+                        .collect(),
+                );
+                break;
+            }
             let h = tokio::spawn(async move { push_func(function, index) });
             handles.push(h);
         } else {
-            push_func(function, index)?;
+            let result = push_func(function, index);
+            let failed = result.failed();
+            summary.push(result);
+            if failed && !keep_going {
+                summary.not_started(functions.map(|(k, f)| (k, f.image)).collect());
+                break;
+            }
         }
     }
     for handle in handles {
-        handle.await.map_err(|e| Error::Custom(e.to_string()))??;
+        summary.push(handle.await.map_err(|e| Error::Custom(e.to_string()))?);
     }
 
     let str = format!("[{}] Worker done.\n", index);
     yellow!("{}", str.as_str());
-    Ok(())
+    Ok(summary)
 }
 
 fn validate_images(functions: &HashMap<String, Function>) -> Vec<String> {
@@ -177,6 +277,41 @@ fn validate_images(functions: &HashMap<String, Function>) -> Vec<String> {
     images
 }
 
-fn push_image(image: &str) -> utility::Result<()> {
-    exec::command("./", vec!["docker", "push", image])
+/// pushes the image with the engine, the engines without a local image store push by running
+/// the build recorded in the build cache again
+fn push_image(engine: &dyn BuildBackend, function_name: &str, image: &str) -> utility::Result<()> {
+    match engine.push_command(image) {
+        Some((program, args)) => {
+            let mut command = vec![program];
+            command.extend(args.iter().map(|a| a.as_str()));
+            exec::command("./", command)
+        }
+        None => match BuildCache::default().lookup(function_name) {
+            Some(CacheEntry {
+                image: built_image,
+                build_spec: Some(build_spec),
+                ..
+            }) if built_image == image => {
+                let commands = engine.rebuild_push_commands(&build_spec.spec(image))?;
+                let context = format!("./build/{}/", function_name);
+                for (program, args) in &commands {
+                    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+                    let status = exec::command_exec(context.as_str(), program, &args)?;
+                    if !status.success() {
+                        return Err(Error::Custom(format!(
+                            "received non-zero exit code from {} ({})",
+                            program, status
+                        )));
+                    }
+                }
+                Ok(())
+            }
+            _ => Err(Error::Custom(format!(
+                "{} keeps no local image of {}, build it with {} first or use publish",
+                engine.name(),
+                image,
+                engine.name()
+            ))),
+        },
+    }
 }
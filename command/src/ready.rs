@@ -0,0 +1,370 @@
+use crate::error::check_tls_insecure;
+use crate::faas::{check_and_set_default_yaml, DEFAULT_GATEWAY};
+use crate::priority::{get_gateway_url, get_namespace, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
+use crate::{CommandAppend, State};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use proxy::auth::ClientAuthE;
+use proxy::client::Client;
+use stack::stack::parse_yaml_file;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use utility::{Error, Result};
+
+/// exit code of the CLI when the gateway or the functions did not become ready in time
+pub(crate) const READY_TIMEOUT_EXIT_CODE: i32 = 2;
+
+pub(crate) struct Ready;
+
+impl CommandAppend for Ready {
+    #[inline(always)]
+    fn append_subcommand(app: App<'static, 'static>) -> App<'static, 'static> {
+        let app = app.subcommand(
+            SubCommand::with_name("ready")
+                .about(r#"Block until the OpenFaaS gateway is healthy and the given function(s) have at least
+one available replica. Without a function name every function in the supplied YAML config
+is waited for, without either only the gateway is checked. Exits with code 2 on timeout, and
+at once with code 1 when the credentials are refused or a function doesn't exist.`,
+	Example: `  faas-cli ready
+  faas-cli ready figlet --timeout '2 min'
+  faas-cli ready figlet --namespace openfaas-fn --interval '500 ms'
+  faas-cli ready -f ./stack.yml
+  faas-cli ready -f ./stack.yml --filter "*gif*"`"#)
+                .args_from_usage("[FUNCTION-NAME]
+                   --tls-no-verify     'Disable TLS validation'
+                   -k, --token [token]                      'Pass a JWT token to use instead of basic auth'
+                   -n, --namespace  [namespace]             'Namespace of the function'
+                ")
+                .arg(
+                    Arg::with_name("timeout")
+                        .long("timeout")
+                        .default_value("60 sec")
+                        .takes_value(true)
+                        .help("How long to wait before giving up, in Duration using units defined \
+                        in 'https://www.freedesktop.org/software/systemd/man/systemd.time.html#Parsing%20Time%20Spans' \
+             Example: --timeout 30sec   or --timeout '2 min'"),
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .long("interval")
+                        .default_value("1 sec")
+                        .takes_value(true)
+                        .help("How long to sleep between two checks, in Duration \
+             Example: --interval 500ms   or --interval '2 sec'"),
+                ),
+        );
+
+        app
+    }
+}
+
+impl Ready {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if let Some(r_args) = args.subcommand_matches("ready") {
+            let gateway = r_args.value_of("gateway").unwrap_or(DEFAULT_GATEWAY);
+            let token = r_args.value_of("token").unwrap_or_default();
+            let function_namespace = r_args.value_of("namespace").unwrap_or_default();
+            let tls_no_verify = r_args.is_present("tls-no-verify");
+            let regex = r_args.value_of("regex").unwrap_or_default();
+            let filter = r_args.value_of("filter").unwrap_or_default();
+            let envsubst = true;
+
+            let function_name = r_args.value_of("FUNCTION-NAME").unwrap_or_default();
+
+            let timeout = r_args.value_of("timeout").unwrap_or("60 sec");
+            let timeout = parse_duration::parse(timeout).map_err(|e| {
+                State::Custom(format!(
+                    "{} , you must set timeout in duration like --timeout '60 sec'",
+                    e
+                ))
+            })?;
+            let interval = r_args.value_of("interval").unwrap_or("1 sec");
+            let interval = parse_duration::parse(interval).map_err(|e| {
+                State::Custom(format!(
+                    "{} , you must set interval in duration like --interval '1 sec'",
+                    e
+                ))
+            })?;
+
+            let yaml_file = r_args
+                .value_of("yaml")
+                .unwrap_or(check_and_set_default_yaml().unwrap_or_default());
+
+            let (services, yaml_gateway) = if !yaml_file.is_empty() && function_name.is_empty() {
+                let svcs = parse_yaml_file(yaml_file, regex, filter, envsubst).await?;
+                let yaml_gateway = svcs.provider.gateway_url.clone();
+                (Some(svcs), yaml_gateway)
+            } else {
+                (None, String::new())
+            };
+            let openfaas_url = std::env::var(OPENFAAS_URL_ENVIRONMENT).unwrap_or_default();
+            let gateway_address = get_gateway_url(
+                gateway,
+                DEFAULT_GATEWAY,
+                yaml_gateway.as_str(),
+                openfaas_url.as_str(),
+            );
+            let msg = check_tls_insecure(gateway_address.as_str(), tls_no_verify);
+            if !msg.is_empty() {
+                colour::yellow!("{}\n", msg);
+            }
+
+            let client_auth = ClientAuthE::new(token, gateway_address.as_str()).await?;
+            let client = client_auth.get_client(
+                gateway_address.as_str(),
+                &get_tls_options(r_args, gateway_address.as_str()),
+            )?;
+
+            // (name, namespace) of every function to wait for
+            let targets: Vec<(String, String)> = match services {
+                Some(services) => services
+                    .functions
+                    .into_iter()
+                    .map(|(name, function)| {
+                        let namespace =
+                            get_namespace(function_namespace, function.namespace.as_str());
+                        (name, namespace)
+                    })
+                    .collect(),
+                None if !function_name.is_empty() => {
+                    vec![(
+                        function_name.to_string(),
+                        get_namespace(function_namespace, ""),
+                    )]
+                }
+                None => Vec::new(),
+            };
+
+            // a single deadline is shared by the gateway and all the functions
+            let deadline = Instant::now() + timeout;
+
+            wait_for_gateway(&client, deadline, interval).await?;
+            colour::green!("Gateway {} is healthy\n", gateway_address);
+
+            for (name, namespace) in &targets {
+                wait_for_function(
+                    &client,
+                    name.as_str(),
+                    namespace.as_str(),
+                    deadline,
+                    interval,
+                )
+                .await?;
+                colour::green!("Function {}.{} is ready\n", name, namespace);
+            }
+
+            Err(State::Matched)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// polls the gateway healthz endpoint until it reports healthy, exits with
+/// READY_TIMEOUT_EXIT_CODE when the deadline is exceeded
+pub(crate) async fn wait_for_gateway(
+    client: &Client<'_>,
+    deadline: Instant,
+    interval: Duration,
+) -> crate::Result {
+    let err = loop {
+        let err = match poll(client.get_health(), deadline).await {
+            Ok(_) => return Ok(()),
+            Err(e) if is_permanent(&e) => return Err(State::Error(e)),
+            Err(e) => e,
+        };
+        if !sleep_before_deadline(interval, deadline).await {
+            break err;
+        }
+    };
+    Err(State::Exit(
+        READY_TIMEOUT_EXIT_CODE,
+        format!(
+            "timed out waiting for the gateway {} to become healthy, last error: {}",
+            client.gateway.as_str(),
+            err
+        ),
+    ))
+}
+
+/// polls the gateway until the function has at least one available replica, exits with
+/// READY_TIMEOUT_EXIT_CODE when the deadline is exceeded
+pub(crate) async fn wait_for_function(
+    client: &Client<'_>,
+    function_name: &str,
+    namespace: &str,
+    deadline: Instant,
+    interval: Duration,
+) -> crate::Result {
+    let reason = loop {
+        let reason = match poll(client.get_function_info(function_name, namespace), deadline).await
+        {
+            Ok(status) if status.available_replicas >= 1 => return Ok(()),
+            Ok(status) => format!(
+                "{} of {} replica(s) available",
+                status.available_replicas, status.replicas
+            ),
+            Err(e) if is_permanent(&e) => return Err(State::Error(e)),
+            Err(e) => e.to_string(),
+        };
+        if !sleep_before_deadline(interval, deadline).await {
+            break reason;
+        }
+    };
+    Err(State::Exit(
+        READY_TIMEOUT_EXIT_CODE,
+        format!(
+            "timed out waiting for function {} to become ready, last status: {}",
+            function_name, reason
+        ),
+    ))
+}
+
+/// sleeps between two polls, false when the deadline is exceeded. A poll at the deadline would
+/// have no time left to answer and hide the last status
+async fn sleep_before_deadline(interval: Duration, deadline: Instant) -> bool {
+    if Instant::now() >= deadline {
+        return false;
+    }
+    tokio::time::sleep(interval).await;
+    Instant::now() < deadline
+}
+
+/// runs a poll request, a gateway that hangs is given up on when the deadline is exceeded
+async fn poll<T>(request: impl Future<Output = Result<T>>, deadline: Instant) -> Result<T> {
+    let left = deadline.saturating_duration_since(Instant::now());
+    tokio::time::timeout(left, request)
+        .await
+        .map_err(|_| Error::Custom("the gateway did not answer in time".to_string()))?
+}
+
+/// tells whether waiting can't fix the error of a poll, such as refused credentials, a missing
+/// function or namespace. Server and connection errors are retried
+fn is_permanent(err: &Error) -> bool {
+    match err {
+        Error::Status(status, _) => (400..500).contains(status) && *status != 408 && *status != 429,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ready::{
+        is_permanent, wait_for_function, wait_for_gateway, READY_TIMEOUT_EXIT_CODE,
+    };
+    use crate::State;
+    use proxy::client::Client;
+    use proxy::utils::{FUNCTION_PATH, HEALTHZ_PATH};
+    use std::time::{Duration, Instant};
+    use utility::faas::types::model::FunctionStatus;
+    use utility::Error;
+
+    fn function_status(name: &str, available_replicas: u64) -> String {
+        let status = FunctionStatus {
+            name: name.into(),
+            replicas: 1,
+            available_replicas,
+            ..Default::default()
+        };
+        serde_json::to_string(&status).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_gateway() {
+        let _mok = mockito::mock("GET", HEALTHZ_PATH).with_status(200).create();
+        let auth = proxy::TestAuth {};
+        let add = format!("http://{}", mockito::server_address().to_string());
+        let client = Client::new(Box::new(&auth), add.as_str()).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let res = wait_for_gateway(&client, deadline, Duration::from_millis(10)).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_function_ready() {
+        let _mok = mockito::mock("GET", format!("{}/func-ready-ok", FUNCTION_PATH).as_str())
+            .with_status(200)
+            .with_body(function_status("func-ready-ok", 1))
+            .create();
+        let auth = proxy::TestAuth {};
+        let add = format!("http://{}", mockito::server_address().to_string());
+        let client = Client::new(Box::new(&auth), add.as_str()).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let res = wait_for_function(
+            &client,
+            "func-ready-ok",
+            "",
+            deadline,
+            Duration::from_millis(10),
+        )
+        .await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_function_timeout() {
+        let _mok = mockito::mock(
+            "GET",
+            format!("{}/func-ready-timeout", FUNCTION_PATH).as_str(),
+        )
+        .with_status(200)
+        .with_body(function_status("func-ready-timeout", 0))
+        .create();
+        let auth = proxy::TestAuth {};
+        let add = format!("http://{}", mockito::server_address().to_string());
+        let client = Client::new(Box::new(&auth), add.as_str()).unwrap();
+
+        let deadline = Instant::now() + Duration::from_millis(500);
+        let res = wait_for_function(
+            &client,
+            "func-ready-timeout",
+            "",
+            deadline,
+            Duration::from_millis(10),
+        )
+        .await;
+        // the last poll may be cut by the deadline, so its status isn't checked
+        assert!(
+            matches!(res, Err(State::Exit(READY_TIMEOUT_EXIT_CODE, ref msg)) if msg.contains("timed out waiting for function func-ready-timeout"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_function_missing() {
+        let _mok = mockito::mock(
+            "GET",
+            format!("{}/func-ready-missing", FUNCTION_PATH).as_str(),
+        )
+        .with_status(404)
+        .create();
+        let auth = proxy::TestAuth {};
+        let add = format!("http://{}", mockito::server_address().to_string());
+        let client = Client::new(Box::new(&auth), add.as_str()).unwrap();
+
+        // a missing function fails at once instead of waiting for the deadline
+        let start = Instant::now();
+        let deadline = start + Duration::from_secs(30);
+        let res = wait_for_function(
+            &client,
+            "func-ready-missing",
+            "",
+            deadline,
+            Duration::from_millis(10),
+        )
+        .await;
+        assert!(matches!(res, Err(State::Error(Error::Status(404, _)))));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_is_permanent() {
+        assert!(is_permanent(&Error::Status(401, String::new())));
+        assert!(is_permanent(&Error::Status(403, String::new())));
+        assert!(is_permanent(&Error::Status(404, String::new())));
+        assert!(!is_permanent(&Error::Status(429, String::new())));
+        assert!(!is_permanent(&Error::Status(503, String::new())));
+        assert!(!is_permanent(&Error::Custom("can't connect".to_string())));
+    }
+}
@@ -1,9 +1,16 @@
 use crate::faas::{check_and_set_default_yaml, DEFAULT_GATEWAY};
-use crate::priority::{get_gateway_url, get_namespace, OPENFAAS_URL_ENVIRONMENT};
+use crate::priority::{get_gateway_url, get_namespace, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
 use crate::{CommandAppend, State};
 use clap::{App, ArgMatches, SubCommand};
+use futures_util::stream::{self, StreamExt};
 use proxy::auth::ClientAuthE;
+use proxy::client::Client;
 use stack::stack::parse_yaml_file;
+use std::io::{BufRead, IsTerminal, Write};
+use utility::Result;
+
+/// how many functions of a stack are deleted at the same time
+const REMOVE_CONCURRENCY: usize = 8;
 
 pub(crate) struct Remove;
 
@@ -19,12 +26,16 @@ explicitly specifying a function name.`,
   faas-cli remove -f ./stack.yml
   faas-cli remove -f ./stack.yml --filter "*gif*"
   faas-cli remove -f ./stack.yml --regex "fn[0-9]_.*"
+  faas-cli remove -f ./stack.yml --filter "preview-*" --dry-run
+  faas-cli remove -f ./stack.yml --filter "preview-*" --yes
   faas-cli remove url-ping
   faas-cli remove img2ansi --gateway=http://remote-site.com:8080`"#)
                 .args_from_usage("[FUNCTION-NAME]
                    --tls-no-verify     'Disable TLS validation'
                    -k, --token [token]                      'Pass a JWT token to use instead of basic auth'
                    -n, --namespace  [namespace]             'Namespace of the function'
+                   --dry-run                                'Print the functions which would be deleted without deleting them'
+                   -y, --yes                                'Do not ask for confirmation before deleting the functions of the YAML config'
                 ")
         );
 
@@ -40,6 +51,10 @@ impl Remove {
             let token = r_args.value_of("token").unwrap_or_default();
             let function_namespace = r_args.value_of("namespace").unwrap_or_default();
             //let tls_no_verify = r_args.is_present("tls-no-verify");
+            let regex = r_args.value_of("regex").unwrap_or_default();
+            let filter = r_args.value_of("filter").unwrap_or_default();
+            let dry_run = r_args.is_present("dry-run");
+            let yes = r_args.is_present("yes");
             let envsubst = true;
 
             let function_name = r_args.value_of("FUNCTION-NAME").unwrap_or_default();
@@ -53,7 +68,7 @@ impl Remove {
             // var yamlGateway string
 
             let (services, yaml_gateway) = if !yaml_file.is_empty() && function_name.is_empty() {
-                let svcs = parse_yaml_file(yaml_file, "", "", envsubst).await?;
+                let svcs = parse_yaml_file(yaml_file, regex, filter, envsubst).await?;
                 let yaml_gateway = svcs.provider.gateway_url.clone();
                 (Some(svcs), yaml_gateway)
 
@@ -72,32 +87,75 @@ impl Remove {
                 openfaas_url.as_str(),
             );
 
-            let client_auth = ClientAuthE::new(token, gateway_address.as_str())?;
-            let client = client_auth.get_client(gateway_address.as_str())?;
+            // (name, namespace) of every function to delete, a stack is deleted as a whole
+            let from_stack = services
+                .as_ref()
+                .map_or(false, |services| !services.functions.is_empty());
+            let targets: Vec<(String, String)> = match services {
+                Some(services) if from_stack => {
+                    let mut targets: Vec<(String, String)> = services
+                        .functions
+                        .into_iter()
+                        .map(|(name, function)| {
+                            let namespace =
+                                get_namespace(function_namespace, function.namespace.as_str());
+                            (name, namespace)
+                        })
+                        .collect();
+                    targets.sort();
+                    targets
+                }
+                _ => {
+                    if function_name.is_empty() {
+                        return Err(State::Custom(
+                            "please provide the name of a function to delete".to_string(),
+                        ));
+                    }
+                    vec![(
+                        function_name.to_string(),
+                        get_namespace(function_namespace, ""),
+                    )]
+                }
+            };
+
+            // a dry run needs neither credentials nor the gateway
+            if dry_run {
+                for (name, namespace) in &targets {
+                    println!("Would delete: {}.{}", name, namespace);
+                }
+                return Err(State::Matched);
+            }
+
+            let client_auth = ClientAuthE::new(token, gateway_address.as_str()).await?;
+            let client = client_auth.get_client(
+                gateway_address.as_str(),
+                &get_tls_options(r_args, gateway_address.as_str()),
+            )?;
             // transport := GetDefaultCLITransport(tlsInsecure, &commandTimeout)
             // proxyclient, err := proxy.NewClient(cliAuth, gatewayAddress, transport, &commandTimeout)
 
-            if services.is_some() && !services.as_ref().unwrap().functions.is_empty() {
-                let services = services.unwrap();
-
-                for (k, mut function) in services.functions {
-                    function.namespace =
-                        get_namespace(function_namespace, function.namespace.as_str());
-                    function.name = k;
-                    colour::green!("Deleting: {}.{}\n", function.name, function.namespace);
-                    client
-                        .delete_function(function.name.as_str(), function.namespace.as_str())
-                        .await?;
+            if from_stack {
+                if !yes && !confirm_removal(&targets, gateway_address.as_str())? {
+                    println!("Aborted, no function was deleted");
+                    return Err(State::Matched);
                 }
-            } else {
-                if function_name.is_empty() {
-                    return Err(State::Custom(
-                        "please provide the name of a function to delete".to_string(),
-                    ));
+
+                let results = remove_functions(&client, &targets).await;
+                print!("{}", render_remove_summary(&results));
+
+                let failed = results.iter().filter(|(_, _, res)| res.is_err()).count();
+                if failed > 0 {
+                    return Err(State::Custom(format!(
+                        "failed to delete {} of {} function(s)",
+                        failed,
+                        results.len()
+                    )));
                 }
+            } else {
+                let (function_name, function_namespace) = &targets[0];
                 colour::green!("Deleting: {}.{}\n", function_name, function_namespace);
                 client
-                    .delete_function(function_name, function_namespace)
+                    .delete_function(function_name, function_namespace.as_str())
                     .await?;
             }
 
@@ -107,3 +165,142 @@ impl Remove {
         }
     }
 }
+
+/// asks the user to confirm the deletion of the functions, a non interactive stdin
+/// needs --yes instead
+fn confirm_removal(targets: &[(String, String)], gateway: &str) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Err(utility::Error::Custom(
+            "refusing to delete functions without confirmation, pass --yes to delete them"
+                .to_string(),
+        ));
+    }
+    for (name, namespace) in targets {
+        println!("  {}.{}", name, namespace);
+    }
+    print!(
+        "Delete {} function(s) from {}? [y/N]: ",
+        targets.len(),
+        gateway
+    );
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(is_confirmed(answer.as_str()))
+}
+
+pub(crate) fn is_confirmed(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// deletes the functions in parallel, results keep the order of the targets
+async fn remove_functions(
+    client: &Client<'_>,
+    targets: &[(String, String)],
+) -> Vec<(String, String, Result<()>)> {
+    stream::iter(targets.iter().cloned())
+        .map(|(name, namespace)| async move {
+            colour::green!("Deleting: {}.{}\n", name, namespace);
+            let res = client
+                .delete_function(name.as_str(), namespace.as_str())
+                .await;
+            (name, namespace, res)
+        })
+        .buffered(REMOVE_CONCURRENCY)
+        .collect()
+        .await
+}
+
+/// renders one line per function with its result then the totals
+pub(crate) fn render_remove_summary(results: &[(String, String, Result<()>)]) -> String {
+    let width = results
+        .iter()
+        .map(|(name, namespace, _)| name.len() + namespace.len() + 1)
+        .max()
+        .unwrap_or(0);
+
+    let mut summary = String::from("\nSummary:\n");
+    for (name, namespace, res) in results {
+        let function = format!("{}.{}", name, namespace);
+        let status = match res {
+            Ok(_) => "deleted".to_string(),
+            Err(e) => format!("failed: {}", e),
+        };
+        summary += format!("  {:<width$}  {}\n", function, status, width = width).as_str();
+    }
+    let failed = results.iter().filter(|(_, _, res)| res.is_err()).count();
+    summary += format!("{} deleted, {} failed\n", results.len() - failed, failed).as_str();
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remove::{is_confirmed, remove_functions, render_remove_summary};
+    use proxy::client::Client;
+    use proxy::utils::SYSTEM_PATH;
+    use utility::Error;
+
+    #[test]
+    fn test_is_confirmed() {
+        assert!(is_confirmed("y\n"));
+        assert!(is_confirmed(" YES "));
+        assert!(!is_confirmed("\n"));
+        assert!(!is_confirmed("no"));
+    }
+
+    #[test]
+    fn test_render_remove_summary() {
+        let results = vec![
+            ("fn1".to_string(), "openfaas-fn".to_string(), Ok(())),
+            (
+                "function-two".to_string(),
+                "dev".to_string(),
+                Err(Error::Custom("(No existing function to remove".to_string())),
+            ),
+        ];
+        let summary = render_remove_summary(&results);
+        assert_eq!(
+            summary,
+            "\nSummary:\n  fn1.openfaas-fn   deleted\n  function-two.dev  failed: (No existing function to remove\n1 deleted, 1 failed\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_functions() {
+        let _mok = mockito::mock("DELETE", SYSTEM_PATH)
+            .match_query(mockito::Matcher::UrlEncoded(
+                "namespace".into(),
+                "remove-stack".into(),
+            ))
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"functionName":"fn-missing"}"#.into(),
+            ))
+            .with_status(404)
+            .create();
+        let _ok = mockito::mock("DELETE", SYSTEM_PATH)
+            .match_query(mockito::Matcher::UrlEncoded(
+                "namespace".into(),
+                "remove-stack".into(),
+            ))
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"functionName":"fn-ok"}"#.into(),
+            ))
+            .with_status(202)
+            .create();
+        let auth = proxy::TestAuth {};
+        let add = format!("http://{}", mockito::server_address().to_string());
+        let client = Client::new(Box::new(&auth), add.as_str()).unwrap();
+
+        let targets = vec![
+            ("fn-missing".to_string(), "remove-stack".to_string()),
+            ("fn-ok".to_string(), "remove-stack".to_string()),
+        ];
+        let results = remove_functions(&client, &targets).await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "fn-missing");
+        assert!(results[0].2.is_err());
+        assert_eq!(results[1].0, "fn-ok");
+        assert!(results[1].2.is_ok());
+    }
+}
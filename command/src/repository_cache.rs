@@ -0,0 +1,568 @@
+use crate::template_lock::TEMPLATE_LOCK_FILE;
+use chrono::{DateTime, SecondsFormat, Utc};
+use config::config_file::config_dir;
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use utility::{Error, Result};
+use versioncontol::git::{get_git_commit, GIT_CHECKOUT, GIT_CLONE_FULL, GIT_FETCH};
+
+/// TemplateCacheDir is the folder of the config dir holding a clone of every template repository
+/// pulled, one per repository and ref
+pub(crate) const TEMPLATE_CACHE_DIR: &str = "template-cache";
+const ENTRY_EXTENSION: &str = "yml";
+const LOCK_EXTENSION: &str = "lock";
+const PARTIAL_SUFFIX: &str = "-partial";
+
+/// CacheEntry describes the clone of a repository at a ref, or the content of a downloaded
+/// archive or OCI artifact, it is kept next to the cached folder
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct CacheEntry {
+    pub(crate) repository: String,
+    /// branch or tag the repository is fetched at, empty for its default branch
+    #[serde(default, rename = "ref", skip_serializing_if = "String::is_empty")]
+    pub(crate) ref_name: String,
+    /// commit of the ref at the last fetch, or digest of the archive or artifact downloaded
+    pub(crate) commit: String,
+    /// RFC 3339 time of the last fetch
+    pub(crate) fetched: String,
+    /// RFC 3339 time templates were last pulled from the clone
+    pub(crate) used: String,
+}
+
+impl CacheEntry {
+    fn used_at(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(self.used.as_str())
+            .ok()
+            .map(|t| t.with_timezone(&Utc))
+    }
+}
+
+/// CacheLock an exclusive lock on the cache of a source, released when dropped
+pub(crate) struct CacheLock {
+    _file: File,
+}
+
+/// RepositoryCache the clones of the template repositories and the downloaded template sources
+/// kept under the config dir, so that pulling a repository again only fetches its new commits and
+/// works without a network
+pub(crate) struct RepositoryCache {
+    root: PathBuf,
+}
+
+impl RepositoryCache {
+    /// the cache of the config dir, see OPENFAAS_CONFIG
+    pub(crate) fn open() -> Result<RepositoryCache> {
+        let dir = config_dir()?;
+        let dir = shellexpand::tilde(dir.as_str()).into_owned();
+        Ok(RepositoryCache::new(
+            Path::new(dir.as_str()).join(TEMPLATE_CACHE_DIR),
+        ))
+    }
+
+    pub(crate) fn new(root: PathBuf) -> RepositoryCache {
+        RepositoryCache { root }
+    }
+
+    pub(crate) fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// waits for the other pulls of the source to finish and locks its cache, the lock must be
+    /// held while the cached folder of the source is written or read
+    pub(crate) fn lock(&self, source: &str, ref_name: &str) -> Result<CacheLock> {
+        self.lock_key(cache_key(source, ref_name).as_str())
+    }
+
+    fn lock_key(&self, key: &str) -> Result<CacheLock> {
+        std::fs::create_dir_all(&self.root)?;
+        let path = self.root.join(format!("{}.{}", key, LOCK_EXTENSION));
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        file.lock_exclusive()
+            .map_err(|e| Error::Custom(format!("can't lock {}: {}", path.to_string_lossy(), e)))?;
+        Ok(CacheLock { _file: file })
+    }
+
+    /// checks out the repository at the commit, or at the head of the ref when there is none, and
+    /// returns the folder of the clone. The clone is made on first use and fetched afterwards,
+    /// offline the commits fetched before are checked out instead. The repository is cloned into a
+    /// partial folder renamed once the clone is complete
+    pub(crate) fn checkout(
+        &self,
+        repository: &str,
+        ref_name: &str,
+        commit: Option<&str>,
+        offline: bool,
+    ) -> Result<PathBuf> {
+        if let Some(commit) = commit.filter(|commit| !is_commit_hash(commit)) {
+            return Err(Error::Custom(format!(
+                "invalid commit {:?} for {} in {}, expected 40 hex characters",
+                commit, repository, TEMPLATE_LOCK_FILE
+            )));
+        }
+        let key = cache_key(repository, ref_name);
+        let dir = self.root.join(key.as_str());
+        let entry_path = self.entry_path(key.as_str());
+        let entry = if dir.join(".git").is_dir() {
+            read_entry(&entry_path).ok()
+        } else {
+            None
+        };
+
+        let mut args: HashMap<String, String> = HashMap::new();
+        args.insert("dir".to_string(), dir.to_string_lossy().to_string());
+        args.insert("repo".to_string(), repository.to_string());
+
+        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        let (tip, fetched) = match entry {
+            Some(entry) if offline => (entry.commit, entry.fetched),
+            None if offline => {
+                return Err(Error::Custom(format!(
+                    "{} is not in the template cache {}, pull it once without --offline",
+                    describe_source(repository, ref_name),
+                    self.root.to_string_lossy()
+                )))
+            }
+            entry => {
+                if entry.is_none() {
+                    // leftovers of an interrupted pull
+                    let partial = self.root.join(format!("{}{}", key, PARTIAL_SUFFIX));
+                    remove_path(&partial)?;
+                    remove_path(&dir)?;
+                    std::fs::create_dir_all(&self.root)?;
+                    let mut clone_args = args.clone();
+                    clone_args.insert("dir".to_string(), partial.to_string_lossy().to_string());
+                    GIT_CLONE_FULL.invoke(".", clone_args).inspect_err(|_| {
+                        let _ = std::fs::remove_dir_all(&partial);
+                    })?;
+                    std::fs::rename(&partial, &dir)?;
+                }
+                let fetch_ref = if ref_name.is_empty() {
+                    "HEAD"
+                } else {
+                    ref_name
+                };
+                args.insert("refname".to_string(), fetch_ref.to_string());
+                GIT_FETCH.invoke(".", args.clone())?;
+                args.insert("refname".to_string(), "FETCH_HEAD".to_string());
+                GIT_CHECKOUT.invoke(".", args.clone())?;
+                (get_git_commit(dir.to_string_lossy().as_ref())?, now.clone())
+            }
+        };
+
+        let target = match commit {
+            Some(commit) => Some(commit),
+            None if offline => Some(tip.as_str()),
+            None => None,
+        };
+        if let Some(target) = target {
+            args.insert("refname".to_string(), target.to_string());
+            if GIT_CHECKOUT.invoke(".", args.clone()).is_err() {
+                if offline {
+                    return Err(Error::Custom(format!(
+                        "commit {} of {} is not in the template cache, pull it once without --offline",
+                        target, repository
+                    )));
+                }
+                // a commit the ref no longer leads to
+                GIT_FETCH.invoke(".", args.clone())?;
+                GIT_CHECKOUT.invoke(".", args)?;
+            }
+        }
+
+        write_entry(
+            &entry_path,
+            &CacheEntry {
+                repository: repository.to_string(),
+                ref_name: ref_name.to_string(),
+                commit: tip,
+                fetched,
+                used: now,
+            },
+        )?;
+        Ok(dir)
+    }
+
+    /// the folder and the revision cached for a downloaded source, when it is cached and at the
+    /// revision expected if one is given
+    pub(crate) fn cached(
+        &self,
+        source: &str,
+        ref_name: &str,
+        revision: Option<&str>,
+    ) -> Result<Option<(PathBuf, String)>> {
+        let key = cache_key(source, ref_name);
+        let dir = self.root.join(key.as_str());
+        let entry_path = self.entry_path(key.as_str());
+        let mut entry = match read_entry(&entry_path) {
+            Ok(entry) if dir.is_dir() => entry,
+            _ => return Ok(None),
+        };
+        if revision.map(|r| r != entry.commit).unwrap_or(false) {
+            return Ok(None);
+        }
+        entry.used = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        write_entry(&entry_path, &entry)?;
+        Ok(Some((dir, entry.commit)))
+    }
+
+    /// an empty folder of the cache to download a source into before storing it
+    pub(crate) fn staging_dir(&self, source: &str, ref_name: &str) -> Result<PathBuf> {
+        let dir = self
+            .root
+            .join(format!("{}{}", cache_key(source, ref_name), PARTIAL_SUFFIX));
+        remove_path(&dir)?;
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// replaces the cached content of the source with its staging folder
+    pub(crate) fn store(&self, source: &str, ref_name: &str, revision: &str) -> Result<PathBuf> {
+        let key = cache_key(source, ref_name);
+        let dir = self.root.join(key.as_str());
+        remove_path(&dir)?;
+        std::fs::rename(self.root.join(format!("{}{}", key, PARTIAL_SUFFIX)), &dir)?;
+        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        write_entry(
+            &self.entry_path(key.as_str()),
+            &CacheEntry {
+                repository: source.to_string(),
+                ref_name: ref_name.to_string(),
+                commit: revision.to_string(),
+                fetched: now.clone(),
+                used: now,
+            },
+        )?;
+        Ok(dir)
+    }
+
+    /// the entries of the cache, sorted by repository and ref
+    pub(crate) fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+        for (_, path) in self.files(ENTRY_EXTENSION)? {
+            if let Ok(entry) = read_entry(&path) {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by(|a, b| {
+            (a.repository.as_str(), a.ref_name.as_str())
+                .cmp(&(b.repository.as_str(), b.ref_name.as_str()))
+        });
+        Ok(entries)
+    }
+
+    /// removes the entries of the repository, or all of them when it is empty, that were not used
+    /// since the time given, entries without a valid time of use are kept then. Clones without an
+    /// entry are removed as well. Every entry is locked while it is removed. Returns the entries
+    /// removed
+    pub(crate) fn prune(
+        &self,
+        repository: &str,
+        unused_since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<CacheEntry>> {
+        let mut removed = Vec::new();
+        for entry in self.entries()? {
+            if !repository.is_empty() && entry.repository != repository {
+                continue;
+            }
+            if let Some(since) = unused_since {
+                match entry.used_at() {
+                    Some(used) if used < since => {}
+                    _ => continue,
+                }
+            }
+            let key = cache_key(entry.repository.as_str(), entry.ref_name.as_str());
+            let _lock = self.lock_key(key.as_str())?;
+            remove_path(&self.root.join(key.as_str()))?;
+            remove_path(&self.entry_path(key.as_str()))?;
+            removed.push(entry);
+        }
+
+        for (key, path) in self.files("")? {
+            if path.is_dir() && !self.entry_path(key.as_str()).is_file() {
+                // a pull may be writing the folder
+                let _lock = self.lock_key(key.trim_end_matches(PARTIAL_SUFFIX))?;
+                if !self.entry_path(key.as_str()).is_file() {
+                    remove_path(&path)?;
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.root.join(format!("{}.{}", key, ENTRY_EXTENSION))
+    }
+
+    /// the paths of the cache with the extension given, by file stem
+    fn files(&self, extension: &str) -> Result<Vec<(String, PathBuf)>> {
+        let dir = match std::fs::read_dir(&self.root) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::Io(e)),
+        };
+        let mut files = Vec::new();
+        for file in dir {
+            let path = file?.path();
+            let matches = path
+                .extension()
+                .map(|ext| ext == extension)
+                .unwrap_or_else(|| extension.is_empty());
+            if matches {
+                let stem = path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                files.push((stem, path));
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// tells whether the revision is a full git commit hash, anything else read from the lock file
+/// could be taken by git for an option or a ref
+fn is_commit_hash(revision: &str) -> bool {
+    revision.len() == 40 && revision.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// the folder name of a repository at a ref: its last path segment followed by the ref, with a
+/// hash of both to tell apart repositories of the same name
+pub(crate) fn cache_key(repository: &str, ref_name: &str) -> String {
+    let name = repository
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .rsplit(['/', ':', '\\'])
+        .next()
+        .unwrap_or_default();
+    let mut key: String = name
+        .chars()
+        .chain(if ref_name.is_empty() { "" } else { "-" }.chars())
+        .chain(ref_name.chars())
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let digest = Sha1::digest(format!("{}#{}", repository, ref_name).as_bytes());
+    let digest = format!("{:x}", digest);
+    if !key.is_empty() {
+        key.push('-');
+    }
+    key.push_str(&digest[..12]);
+    key
+}
+
+fn describe_source(repository: &str, ref_name: &str) -> String {
+    if ref_name.is_empty() {
+        repository.to_string()
+    } else {
+        format!("{} at {}", repository, ref_name)
+    }
+}
+
+fn read_entry(path: &Path) -> Result<CacheEntry> {
+    let content = std::fs::read_to_string(path)?;
+    serde_yaml::from_str(content.as_str())
+        .map_err(|e| Error::Custom(format!("can't read {}: {}", path.to_string_lossy(), e)))
+}
+
+fn write_entry(path: &Path, entry: &CacheEntry) -> Result<()> {
+    let content = serde_yaml::to_string(entry)
+        .map_err(|e| Error::Custom(format!("can't serialize the cache entry: {}", e)))?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    let res = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    match res {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(Error::Io(e)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .args(&["-c", "user.email=test@openfaas.com", "-c", "user.name=test"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    fn commit_template(repo: &Path, content: &str) -> String {
+        std::fs::write(repo.join("template/node/template.yml"), content).unwrap();
+        git(repo, &["add", "."]);
+        git(repo, &["commit", "-q", "-m", content]);
+        git(repo, &["rev-parse", "HEAD"])
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let key = cache_key("https://github.com/openfaas/templates.git", "");
+        assert!(key.starts_with("templates-"));
+        assert_eq!(key.len(), "templates-".len() + 12);
+        assert!(
+            cache_key("https://github.com/openfaas/templates", "1.0").starts_with("templates-1_0-")
+        );
+        assert_ne!(
+            cache_key("https://github.com/openfaas/templates", ""),
+            cache_key("https://github.com/other/templates", "")
+        );
+    }
+
+    #[test]
+    fn test_invalid_locked_commit() {
+        let dir = TempDir::new("template-cache").unwrap();
+        let cache = RepositoryCache::new(dir.path().join("cache"));
+        for commit in &[
+            "--upload-pack=touch pwned",
+            "main",
+            "abc123",
+            &"g".repeat(40),
+        ] {
+            let err = cache
+                .checkout(
+                    "https://github.com/openfaas/templates",
+                    "",
+                    Some(commit),
+                    true,
+                )
+                .unwrap_err();
+            assert!(err.to_string().contains("invalid commit"));
+        }
+        assert!(is_commit_hash("0123456789abcdefABCDEF0123456789abcdef01"));
+    }
+
+    #[test]
+    #[ignore]
+    fn test_template_cache() {
+        let dir = TempDir::new("template-cache").unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(repo.join("template/node")).unwrap();
+        git(&repo, &["init", "-q"]);
+        let first = commit_template(&repo, "first");
+        let repository = repo.to_string_lossy().to_string();
+
+        let cache = RepositoryCache::new(dir.path().join("cache"));
+        assert!(cache.checkout(repository.as_str(), "", None, true).is_err());
+
+        let clone = cache
+            .checkout(repository.as_str(), "", None, false)
+            .unwrap();
+        let template = clone.join("template/node/template.yml");
+        assert_eq!(std::fs::read_to_string(&template).unwrap(), "first");
+
+        let second = commit_template(&repo, "second");
+        cache
+            .checkout(repository.as_str(), "", Some(first.as_str()), false)
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&template).unwrap(), "first");
+        assert_eq!(cache.entries().unwrap()[0].commit, second);
+
+        // offline serves the commit of the last fetch
+        let clone = cache.checkout(repository.as_str(), "", None, true).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(clone.join("template/node/template.yml")).unwrap(),
+            "second"
+        );
+        let missing = "0".repeat(40);
+        assert!(cache
+            .checkout(repository.as_str(), "", Some(missing.as_str()), true)
+            .is_err());
+
+        std::fs::create_dir_all(cache.root().join("orphan")).unwrap();
+        assert!(cache
+            .prune("", Some(Utc::now() - chrono::Duration::days(1)))
+            .unwrap()
+            .is_empty());
+        assert!(!cache.root().join("orphan").exists());
+        assert_eq!(cache.entries().unwrap().len(), 1);
+
+        let removed = cache.prune(repository.as_str(), None).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert!(cache.entries().unwrap().is_empty());
+        assert!(!clone.exists());
+    }
+
+    #[test]
+    fn test_prune() {
+        let dir = TempDir::new("template-cache").unwrap();
+        let cache = RepositoryCache::new(dir.path().join("cache"));
+        for source in &[
+            "https://example.com/a.tar.gz",
+            "https://example.com/b.tar.gz",
+        ] {
+            cache.staging_dir(source, "").unwrap();
+            cache.store(source, "", "sha256:0").unwrap();
+        }
+        let key = cache_key("https://example.com/b.tar.gz", "");
+        let mut entry = read_entry(&cache.entry_path(key.as_str())).unwrap();
+        entry.used = "yesterday".to_string();
+        write_entry(&cache.entry_path(key.as_str()), &entry).unwrap();
+        std::fs::create_dir_all(cache.root().join(format!("{}{}", key, PARTIAL_SUFFIX))).unwrap();
+
+        // entries used after the time given or without a valid time are kept
+        let removed = cache
+            .prune("", Some(Utc::now() + chrono::Duration::days(1)))
+            .unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].repository, "https://example.com/a.tar.gz");
+        assert_eq!(cache.entries().unwrap(), vec![entry]);
+        assert!(cache.root().join(key.as_str()).is_dir());
+        assert!(!cache
+            .root()
+            .join(format!("{}{}", key, PARTIAL_SUFFIX))
+            .exists());
+
+        assert_eq!(cache.prune("", None).unwrap().len(), 1);
+        assert!(cache.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lock() {
+        let dir = TempDir::new("template-cache").unwrap();
+        let cache = RepositoryCache::new(dir.path().join("cache"));
+        let lock = cache.lock("https://example.com/a.tar.gz", "").unwrap();
+        // other sources are not locked
+        drop(cache.lock("https://example.com/b.tar.gz", "").unwrap());
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let root = cache.root().to_path_buf();
+        let waiting = std::thread::spawn(move || {
+            let cache = RepositoryCache::new(root);
+            let _lock = cache.lock("https://example.com/a.tar.gz", "").unwrap();
+            sender.send(()).unwrap();
+        });
+        assert!(receiver
+            .recv_timeout(std::time::Duration::from_millis(200))
+            .is_err());
+        drop(lock);
+        receiver
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .unwrap();
+        waiting.join().unwrap();
+    }
+}
@@ -0,0 +1,257 @@
+use crate::error::check_tls_insecure;
+use crate::faas::{check_and_set_default_yaml, DEFAULT_GATEWAY};
+use crate::priority::{get_gateway_url, get_namespace, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
+use crate::{CommandAppend, State};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use proxy::auth::ClientAuthE;
+use proxy::client::Client;
+use stack::stack::parse_yaml_file;
+use std::time::{Duration, Instant};
+use utility::{Error, Result};
+
+/// how often the gateway is asked for the function status while waiting for replicas
+const WAIT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub(crate) struct Scale;
+
+impl CommandAppend for Scale {
+    #[inline(always)]
+    fn append_subcommand(app: App<'static, 'static>) -> App<'static, 'static> {
+        let app = app.subcommand(
+            SubCommand::with_name("scale")
+                .about(r#"Scale a deployed OpenFaaS function to the given number of replicas, either by
+explicitly specifying a function name or for every function in the supplied YAML config
+using the "--yaml" flag (which may contain multiple function definitions).`,
+	Example: `  faas-cli scale figlet --replicas 3
+  faas-cli scale figlet --replicas 0 --namespace openfaas-fn
+  faas-cli scale figlet --replicas 2 --wait --timeout '2 min'
+  faas-cli scale -f ./stack.yml --replicas 2
+  faas-cli scale -f ./stack.yml --filter "*gif*" --replicas 1 --wait`"#)
+                .args_from_usage("[FUNCTION-NAME]
+                   --replicas <replicas>                     'Number of replicas to scale the function(s) to'
+                   --wait                                    'Wait until the available replicas match the requested replicas'
+                   --tls-no-verify     'Disable TLS validation'
+                   -k, --token [token]                      'Pass a JWT token to use instead of basic auth'
+                   -n, --namespace  [namespace]             'Namespace of the function'
+                ")
+                .arg(
+                    Arg::with_name("timeout")
+                        .long("timeout")
+                        .default_value("60 sec")
+                        .takes_value(true)
+                        .help("How long to wait for the replicas when --wait is set, in Duration using units defined \
+                        in 'https://www.freedesktop.org/software/systemd/man/systemd.time.html#Parsing%20Time%20Spans' \
+             Example: --timeout 30sec   or --timeout '2 min'"),
+                ),
+        );
+
+        app
+    }
+}
+
+impl Scale {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if let Some(s_args) = args.subcommand_matches("scale") {
+            let gateway = s_args.value_of("gateway").unwrap_or(DEFAULT_GATEWAY);
+            let token = s_args.value_of("token").unwrap_or_default();
+            let function_namespace = s_args.value_of("namespace").unwrap_or_default();
+            let tls_no_verify = s_args.is_present("tls-no-verify");
+            let regex = s_args.value_of("regex").unwrap_or_default();
+            let filter = s_args.value_of("filter").unwrap_or_default();
+            let wait = s_args.is_present("wait");
+            let envsubst = true;
+
+            let function_name = s_args.value_of("FUNCTION-NAME").unwrap_or_default();
+
+            let replicas = s_args.value_of("replicas").unwrap_or_default();
+            let replicas: u64 = replicas.parse().map_err(|_| {
+                State::Custom(format!(
+                    "the --replicas flag must be a non-negative integer, got: {}",
+                    replicas
+                ))
+            })?;
+
+            let timeout = s_args.value_of("timeout").unwrap_or("60 sec");
+            let timeout = parse_duration::parse(timeout).map_err(|e| {
+                State::Custom(format!(
+                    "{} , you must set timeout in duration like --timeout '60 sec'",
+                    e
+                ))
+            })?;
+
+            let yaml_file = s_args
+                .value_of("yaml")
+                .unwrap_or(check_and_set_default_yaml().unwrap_or_default());
+
+            let (services, yaml_gateway) = if !yaml_file.is_empty() && function_name.is_empty() {
+                let svcs = parse_yaml_file(yaml_file, regex, filter, envsubst).await?;
+                let yaml_gateway = svcs.provider.gateway_url.clone();
+                (Some(svcs), yaml_gateway)
+            } else {
+                (None, String::new())
+            };
+            let openfaas_url = std::env::var(OPENFAAS_URL_ENVIRONMENT).unwrap_or_default();
+            let gateway_address = get_gateway_url(
+                gateway,
+                DEFAULT_GATEWAY,
+                yaml_gateway.as_str(),
+                openfaas_url.as_str(),
+            );
+            let msg = check_tls_insecure(gateway_address.as_str(), tls_no_verify);
+            if !msg.is_empty() {
+                colour::yellow!("{}\n", msg);
+            }
+
+            let client_auth = ClientAuthE::new(token, gateway_address.as_str()).await?;
+            let client = client_auth.get_client(
+                gateway_address.as_str(),
+                &get_tls_options(s_args, gateway_address.as_str()),
+            )?;
+
+            // (name, namespace) of every function to scale
+            let targets: Vec<(String, String)> = match services {
+                Some(services) if !services.functions.is_empty() => services
+                    .functions
+                    .into_iter()
+                    .map(|(name, function)| {
+                        let namespace =
+                            get_namespace(function_namespace, function.namespace.as_str());
+                        (name, namespace)
+                    })
+                    .collect(),
+                _ => {
+                    if function_name.is_empty() {
+                        return Err(State::Custom(
+                            "please provide the name of a function to scale or a YAML file with --yaml"
+                                .to_string(),
+                        ));
+                    }
+                    vec![(
+                        function_name.to_string(),
+                        get_namespace(function_namespace, ""),
+                    )]
+                }
+            };
+
+            for (name, namespace) in &targets {
+                colour::green!(
+                    "Scaling: {}.{} to {} replica(s)\n",
+                    name,
+                    namespace,
+                    replicas
+                );
+                client
+                    .scale_function(name.as_str(), namespace.as_str(), replicas)
+                    .await?;
+            }
+
+            if wait {
+                for (name, namespace) in &targets {
+                    wait_for_replicas(
+                        &client,
+                        name.as_str(),
+                        namespace.as_str(),
+                        replicas,
+                        timeout,
+                    )
+                    .await?;
+                    colour::green!(
+                        "{}.{} has {} available replica(s)\n",
+                        name,
+                        namespace,
+                        replicas
+                    );
+                }
+            }
+
+            Err(State::Matched)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// polls the gateway until the available replicas of the function equal `replicas`
+/// or the timeout is exceeded
+pub(crate) async fn wait_for_replicas(
+    client: &Client<'_>,
+    function_name: &str,
+    namespace: &str,
+    replicas: u64,
+    timeout: Duration,
+) -> Result<()> {
+    let start = Instant::now();
+    loop {
+        let status = client.get_function_info(function_name, namespace).await?;
+        if status.available_replicas == replicas {
+            return Ok(());
+        }
+        if start.elapsed() >= timeout {
+            return Err(Error::Custom(format!(
+                "timed out after {}s waiting for {} to have {} available replica(s), currently {}",
+                timeout.as_secs(),
+                function_name,
+                replicas,
+                status.available_replicas
+            )));
+        }
+        tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scale::wait_for_replicas;
+    use proxy::client::Client;
+    use proxy::utils::FUNCTION_PATH;
+    use std::time::Duration;
+    use utility::faas::types::model::FunctionStatus;
+
+    fn function_status(name: &str, available_replicas: u64) -> String {
+        let status = FunctionStatus {
+            name: name.into(),
+            replicas: 2,
+            available_replicas,
+            ..Default::default()
+        };
+        serde_json::to_string(&status).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_replicas_ready() {
+        let _mok = mockito::mock(
+            "GET",
+            format!("{}/func-scale-ready", FUNCTION_PATH).as_str(),
+        )
+        .with_status(200)
+        .with_body(function_status("func-scale-ready", 2))
+        .create();
+        let auth = proxy::TestAuth {};
+        let add = format!("http://{}", mockito::server_address().to_string());
+        let client = Client::new(Box::new(&auth), add.as_str()).unwrap();
+
+        let res =
+            wait_for_replicas(&client, "func-scale-ready", "", 2, Duration::from_secs(1)).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_replicas_timeout() {
+        let _mok = mockito::mock(
+            "GET",
+            format!("{}/func-scale-timeout", FUNCTION_PATH).as_str(),
+        )
+        .with_status(200)
+        .with_body(function_status("func-scale-timeout", 1))
+        .create();
+        let auth = proxy::TestAuth {};
+        let add = format!("http://{}", mockito::server_address().to_string());
+        let client = Client::new(Box::new(&auth), add.as_str()).unwrap();
+
+        let res =
+            wait_for_replicas(&client, "func-scale-timeout", "", 2, Duration::from_secs(0)).await;
+        assert!(res.is_err());
+        assert!(format!("{:?}", res).contains("timed out"));
+    }
+}
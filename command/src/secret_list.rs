@@ -1,6 +1,6 @@
 use crate::error::check_tls_insecure;
 use crate::faas::DEFAULT_GATEWAY;
-use crate::priority::{get_gateway_url, OPENFAAS_URL_ENVIRONMENT};
+use crate::priority::{get_gateway_url, get_namespace, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
 use crate::{State, SubCommandAppend};
 use clap::{App, ArgMatches, SubCommand};
 use proxy::auth::ClientAuthE;
@@ -32,7 +32,8 @@ impl SecretList {
         if let Some(l_args) = args.subcommand_matches("list") {
             let gateway = l_args.value_of("gateway").unwrap_or(DEFAULT_GATEWAY);
             let token = l_args.value_of("token").unwrap_or_default();
-            let namespace = l_args.value_of("namespace").unwrap_or_default();
+            let namespace = get_namespace(l_args.value_of("namespace").unwrap_or_default(), "");
+            let namespace = namespace.as_str();
             let tls_no_verify = l_args.is_present("tls-no-verify");
 
             let openfaas_url = std::env::var(OPENFAAS_URL_ENVIRONMENT).unwrap_or_default();
@@ -43,9 +44,12 @@ impl SecretList {
             if !msg.is_empty() {
                 colour::yellow!("{}\n", msg);
             }
-            let client_auth = ClientAuthE::new(token, gateway_address.as_str())?;
+            let client_auth = ClientAuthE::new(token, gateway_address.as_str()).await?;
             //transport := GetDefaultCLITransport(tlsInsecure, &commandTimeout)
-            let client = client_auth.get_client(gateway_address.as_str())?;
+            let client = client_auth.get_client(
+                gateway_address.as_str(),
+                &get_tls_options(l_args, gateway_address.as_str()),
+            )?;
             let secrets = client.get_secret_list(namespace).await?;
 
             if secrets.is_empty() {
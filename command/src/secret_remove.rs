@@ -1,6 +1,6 @@
 use crate::error::check_tls_insecure;
 use crate::faas::DEFAULT_GATEWAY;
-use crate::priority::{get_gateway_url, OPENFAAS_URL_ENVIRONMENT};
+use crate::priority::{get_gateway_url, get_namespace, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
 use crate::{State, SubCommandAppend};
 use clap::{App, Arg, ArgMatches, SubCommand};
 use proxy::auth::ClientAuthE;
@@ -42,7 +42,9 @@ impl SecretRemove {
             let secret_name = r_args
                 .value_of("SECRET-NAME")
                 .ok_or(State::Custom("secret name is required".to_string()))?;
-            let function_namespace = r_args.value_of("namespace").unwrap_or_default();
+            let function_namespace =
+                get_namespace(r_args.value_of("namespace").unwrap_or_default(), "");
+            let function_namespace = function_namespace.as_str();
             let gateway = args.value_of("gateway").unwrap_or(DEFAULT_GATEWAY);
             let token = r_args.value_of("token").unwrap_or_default();
             let tls_no_verify = r_args.is_present("tls-no-verify");
@@ -61,9 +63,12 @@ impl SecretRemove {
             if !msg.is_empty() {
                 colour::yellow!("{}\n", msg);
             }
-            let client_auth = ClientAuthE::new(token, gateway_address.as_str())?;
+            let client_auth = ClientAuthE::new(token, gateway_address.as_str()).await?;
             //transport := GetDefaultCLITransport(tlsInsecure, &commandTimeout)
-            let client = client_auth.get_client(gateway_address.as_str())?;
+            let client = client_auth.get_client(
+                gateway_address.as_str(),
+                &get_tls_options(r_args, gateway_address.as_str()),
+            )?;
             client.remove_secret(&secret).await?;
             colour::green!("removed {}");
             Err(State::Matched)
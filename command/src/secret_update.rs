@@ -1,6 +1,6 @@
 use crate::error::check_tls_insecure;
 use crate::faas::DEFAULT_GATEWAY;
-use crate::priority::{get_gateway_url, OPENFAAS_URL_ENVIRONMENT};
+use crate::priority::{get_gateway_url, get_namespace, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
 use crate::secret_create::read_secret_from_file;
 use crate::{State, SubCommandAppend};
 use clap::{App, Arg, ArgMatches, SubCommand};
@@ -52,7 +52,9 @@ impl SecretUpdate {
                 .value_of("SECRET-NAME")
                 .ok_or(State::Custom("secret name is required".to_string()))?;
             let secret_file = c_args.value_of("from-file").unwrap_or_default();
-            let function_namespace = c_args.value_of("namespace").unwrap_or_default();
+            let function_namespace =
+                get_namespace(c_args.value_of("namespace").unwrap_or_default(), "");
+            let function_namespace = function_namespace.as_str();
             let gateway = args.value_of("gateway").unwrap_or(DEFAULT_GATEWAY);
             let token = c_args.value_of("token").unwrap_or_default();
             let tls_no_verify = c_args.is_present("tls-no-verify");
@@ -113,9 +115,12 @@ impl SecretUpdate {
             if !msg.is_empty() {
                 colour::yellow!("{}\n", msg);
             }
-            let client_auth = ClientAuthE::new(token, gateway_address.as_str())?;
+            let client_auth = ClientAuthE::new(token, gateway_address.as_str()).await?;
             //transport := GetDefaultCLITransport(tlsInsecure, &commandTimeout)
-            let client = client_auth.get_client(gateway_address.as_str())?;
+            let client = client_auth.get_client(
+                gateway_address.as_str(),
+                &get_tls_options(c_args, gateway_address.as_str()),
+            )?;
 
             colour::green!("Updating secret: {}", secret.name);
             let (_, output) = client.update_secret(&secret).await?;
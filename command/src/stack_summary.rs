@@ -0,0 +1,241 @@
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use utility::{Error, Result};
+
+/// StepStatus is what happened to one function of a stack during build, push or deploy
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum StepStatus {
+    Ok,
+    Skipped(String),
+    Failed(String),
+}
+
+/// StepResult is one row of the summary printed at the end of a stack run
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StepResult {
+    pub(crate) function: String,
+    pub(crate) image: String,
+    pub(crate) duration: Duration,
+    pub(crate) status: StepStatus,
+}
+
+impl StepResult {
+    pub(crate) fn new(function: &str, image: &str, duration: Duration, status: StepStatus) -> Self {
+        StepResult {
+            function: function.to_string(),
+            image: image.to_string(),
+            duration,
+            status,
+        }
+    }
+
+    pub(crate) fn failed(&self) -> bool {
+        matches!(self.status, StepStatus::Failed(_))
+    }
+}
+
+/// StackSummary collects the results of a build, push or deploy of a stack
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StackSummary {
+    action: &'static str,
+    results: Vec<StepResult>,
+}
+
+impl StackSummary {
+    /// action is the verb of the run, e.g. build
+    pub(crate) fn new(action: &'static str) -> Self {
+        StackSummary {
+            action,
+            results: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, result: StepResult) {
+        self.results.push(result);
+    }
+
+    /// waits for the oldest running function once queue_depth of them are running, returns true
+    /// when it failed
+    pub(crate) async fn wait_for_slot(
+        &mut self,
+        running: &mut Vec<JoinHandle<StepResult>>,
+        queue_depth: usize,
+    ) -> Result<bool> {
+        let mut failed = false;
+        while !running.is_empty() && running.len() >= queue_depth {
+            let result = running
+                .remove(0)
+                .await
+                .map_err(|e| Error::Custom(e.to_string()))?;
+            failed |= result.failed();
+            self.push(result);
+        }
+        Ok(failed)
+    }
+
+    /// names of the functions that failed
+    pub(crate) fn failed_functions(&self) -> Vec<String> {
+        self.results
+            .iter()
+            .filter(|r| r.failed())
+            .map(|r| r.function.clone())
+            .collect()
+    }
+
+    /// marks the functions that were not started because an earlier function failed
+    pub(crate) fn not_started(&mut self, functions: Vec<(String, String)>) {
+        for (function, image) in functions {
+            self.push(StepResult::new(
+                function.as_str(),
+                image.as_str(),
+                Duration::default(),
+                StepStatus::Skipped("not started after an earlier failure".to_string()),
+            ));
+        }
+    }
+
+    /// renders the summary as a table sorted by function name
+    pub(crate) fn render(&self) -> String {
+        let mut results = self.results.clone();
+        results.sort_by(|a, b| a.function.cmp(&b.function));
+
+        let function_width = results
+            .iter()
+            .map(|r| r.function.len())
+            .chain(std::iter::once("FUNCTION".len()))
+            .max()
+            .unwrap_or(0);
+        let image_width = results
+            .iter()
+            .map(|r| r.image.len())
+            .chain(std::iter::once("IMAGE".len()))
+            .max()
+            .unwrap_or(0);
+
+        let mut summary = format!(
+            "\nSummary of {}:\n{:<fw$}  {:<iw$}  {:>8}  STATUS\n",
+            self.action,
+            "FUNCTION",
+            "IMAGE",
+            "DURATION",
+            fw = function_width,
+            iw = image_width
+        );
+        for r in &results {
+            let status = match &r.status {
+                StepStatus::Ok => "ok".to_string(),
+                StepStatus::Skipped(reason) => format!("skipped: {}", reason),
+                StepStatus::Failed(reason) => {
+                    format!("failed: {}", reason.lines().next().unwrap_or_default())
+                }
+            };
+            summary += format!(
+                "{:<fw$}  {:<iw$}  {:>7.1}s  {}\n",
+                r.function,
+                r.image,
+                r.duration.as_secs_f64(),
+                status,
+                fw = function_width,
+                iw = image_width
+            )
+            .as_str();
+        }
+        let failed = results.iter().filter(|r| r.failed()).count();
+        let skipped = results
+            .iter()
+            .filter(|r| matches!(r.status, StepStatus::Skipped(_)))
+            .count();
+        summary += format!(
+            "{} ok, {} skipped, {} failed\n",
+            results.len() - failed - skipped,
+            skipped,
+            failed
+        )
+        .as_str();
+        summary
+    }
+
+    /// prints the summary and turns the failures into the error of the command
+    pub(crate) fn finish(self) -> Result<()> {
+        print!("{}", self.render());
+        let failures: Vec<String> = self
+            .results
+            .iter()
+            .filter_map(|r| match &r.status {
+                StepStatus::Failed(reason) => Some(format!("- {}: {}", r.function, reason)),
+                _ => None,
+            })
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Custom(format!(
+                "{} of {} functions failed to {}:\n{}",
+                failures.len(),
+                self.results.len(),
+                self.action,
+                failures.join("\n")
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stack_summary::{StackSummary, StepResult, StepStatus};
+    use std::time::Duration;
+
+    #[test]
+    fn test_render_stack_summary() {
+        let mut summary = StackSummary::new("build");
+        summary.push(StepResult::new(
+            "fn2",
+            "user/fn2:latest",
+            Duration::from_millis(2500),
+            StepStatus::Failed("exit code 1\nlast lines".to_string()),
+        ));
+        summary.push(StepResult::new(
+            "fn1",
+            "user/fn1:latest",
+            Duration::from_secs(12),
+            StepStatus::Ok,
+        ));
+        summary.not_started(vec![("fn3".to_string(), "user/fn3".to_string())]);
+
+        assert_eq!(
+            summary.render(),
+            "\nSummary of build:\n\
+             FUNCTION  IMAGE            DURATION  STATUS\n\
+             fn1       user/fn1:latest     12.0s  ok\n\
+             fn2       user/fn2:latest      2.5s  failed: exit code 1\n\
+             fn3       user/fn3             0.0s  skipped: not started after an earlier failure\n\
+             1 ok, 1 skipped, 1 failed\n"
+        );
+
+        assert_eq!(summary.failed_functions(), vec!["fn2".to_string()]);
+        let err = summary.finish().unwrap_err().to_string();
+        assert!(err.starts_with("1 of 3 functions failed to build:\n- fn2: exit code 1"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_slot() {
+        let result = |function: &str, status: StepStatus| {
+            StepResult::new(function, "", Duration::default(), status)
+        };
+        let mut summary = StackSummary::new("push");
+        let mut running = vec![
+            tokio::spawn(async move { result("fn1", StepStatus::Ok) }),
+            tokio::spawn(async move { result("fn2", StepStatus::Failed("denied".to_string())) }),
+        ];
+
+        // there is room for a third function
+        assert!(!summary.wait_for_slot(&mut running, 3).await.unwrap());
+        assert_eq!(running.len(), 2);
+
+        assert!(!summary.wait_for_slot(&mut running, 2).await.unwrap());
+        assert_eq!(running.len(), 1);
+        assert!(summary.wait_for_slot(&mut running, 1).await.unwrap());
+        assert!(running.is_empty());
+        assert_eq!(summary.failed_functions(), vec!["fn2".to_string()]);
+    }
+}
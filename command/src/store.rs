@@ -5,7 +5,7 @@ use crate::store_list::StoreList;
 use crate::template_store_list::MAIN_PLATFORM;
 use crate::{CommandAppend, State, SubCommandAppend};
 use clap::{App, Arg, ArgMatches, SubCommand};
-use proxy::proxy::make_http_client;
+use proxy::proxy::{make_http_client, TlsOptions};
 use reqwest::StatusCode;
 use schema::store::v2::store::{Store as V2Store, StoreFunction};
 use std::collections::HashMap;
@@ -93,7 +93,7 @@ pub(crate) async fn store_list(store: &str) -> Result<Vec<StoreFunction>> {
     let timeout = Duration::from_secs(60);
     //timeout := 60 * time.Second
     let tls_insecure = false;
-    let client = make_http_client(Some(timeout), tls_insecure)?;
+    let client = make_http_client(Some(timeout), &TlsOptions::insecure(tls_insecure))?;
 
     let req = client.get(store).build()?;
     let res = client.execute(req).await.map_err(|_e| {
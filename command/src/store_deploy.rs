@@ -1,6 +1,6 @@
 use crate::deploy::{bad_status_code, deploy_failed, deploy_image, DeployFlags};
 use crate::faas::DEFAULT_GATEWAY;
-use crate::priority::{get_gateway_url, OPENFAAS_URL_ENVIRONMENT};
+use crate::priority::{get_gateway_url, get_namespace, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
 use crate::store::{
     filter_store_list, get_target_platform, store_find_function, store_list, DEFAULT_STORE,
 };
@@ -94,7 +94,8 @@ impl StoreDeploy {
             ))?;
             let function_name = d_args.value_of("name").unwrap_or_default();
             let token = d_args.value_of("token").unwrap_or_default();
-            let namespace = d_args.value_of("namespace").unwrap_or_default();
+            let namespace = get_namespace(d_args.value_of("namespace").unwrap_or_default(), "");
+            let namespace = namespace.as_str();
 
             let replace = d_args.is_present("replace");
             let update = true; //d_args.is_present("replace");
@@ -172,8 +173,9 @@ impl StoreDeploy {
 
             let openfaas_url = std::env::var(OPENFAAS_URL_ENVIRONMENT).unwrap_or_default();
             let gateway = get_gateway_url(gateway_arg, DEFAULT_GATEWAY, "", openfaas_url.as_str());
-            let cli_auth = ClientAuthE::new(token, gateway.as_str())?;
-            let proxy_client = cli_auth.get_client(gateway.as_str())?;
+            let cli_auth = ClientAuthE::new(token, gateway.as_str()).await?;
+            let proxy_client = cli_auth
+                .get_client(gateway.as_str(), &get_tls_options(d_args, gateway.as_str()))?;
 
             let deploy_flags = DeployFlags {
                 envvar_opts: &env_var_opts,
@@ -1,5 +1,7 @@
+use crate::template_cache::TemplateCache;
 use crate::template_pull::TemplatePull;
 use crate::template_store::TemplateStore;
+use crate::template_validate::TemplateValidate;
 use crate::{CommandAppend, State, SubCommandAppend};
 use clap::{App, Arg, ArgMatches, SubCommand};
 
@@ -16,7 +18,9 @@ impl CommandAppend for Template {
   faas-cli template store list
   faas-cli template store ls
   faas-cli template store pull ruby-http
-  faas-cli template store pull openfaas-incubator/ruby-http`"#,
+  faas-cli template store pull openfaas-incubator/ruby-http
+  faas-cli template validate ./template/ruby
+  faas-cli template cache list`"#,
                 )
                 .arg(
                     Arg::with_name("overwrite")
@@ -32,7 +36,9 @@ impl CommandAppend for Template {
                 )
                 //add subcommands like store
                 .subcommand(TemplateStore::append_subcommand())
-                .subcommand(TemplatePull::append_subcommand()),
+                .subcommand(TemplatePull::append_subcommand())
+                .subcommand(TemplateValidate::append_subcommand())
+                .subcommand(TemplateCache::append_subcommand()),
         );
         app
     }
@@ -44,6 +50,8 @@ impl Template {
         if let Some(t_args) = args.subcommand_matches("template") {
             TemplateStore::dispatch_command(t_args).await?;
             TemplatePull::dispatch_command(t_args).await?;
+            TemplateValidate::dispatch_command(t_args).await?;
+            TemplateCache::dispatch_command(t_args).await?;
             let usage = t_args.usage();
 
             //println!("{}");
@@ -0,0 +1,350 @@
+use crate::repository_cache::RepositoryCache;
+use crate::template_lock::TEMPLATE_LOCK_FILE;
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use utility::{Error, Result};
+
+/// downloads the tar.gz into the template cache and returns the folder it was unpacked to with its
+/// digest. The archive must match the sha256 when one is given, or else the digest locked for it.
+/// A cached archive with the digest expected is used without downloading it again, offline the
+/// cached archive is used whatever its digest
+pub(crate) async fn fetch_archive(
+    cache: &RepositoryCache,
+    url: &str,
+    sha256: &str,
+    locked: Option<&str>,
+    offline: bool,
+) -> Result<(PathBuf, String)> {
+    let expected = if sha256.is_empty() {
+        locked.map(str::to_string)
+    } else {
+        Some(format!("sha256:{}", sha256))
+    };
+    if let Some(cached) = lookup_cache(cache, url, expected.as_deref(), offline)? {
+        return Ok(cached);
+    }
+
+    let response = reqwest::get(url)
+        .await
+        .map_err(|e| Error::Custom(format!("can't download {}: {}", url, e)))?;
+    if !response.status().is_success() {
+        return Err(Error::Custom(format!(
+            "can't download {}: the server returned {}",
+            url,
+            response.status()
+        )));
+    }
+    let archive = response
+        .bytes()
+        .await
+        .map_err(|e| Error::Custom(format!("can't download {}: {}", url, e)))?;
+
+    let digest = sha256_digest(&archive);
+    match expected {
+        Some(expected) if expected != digest && !sha256.is_empty() => {
+            return Err(Error::Custom(format!(
+                "the sha256 of {} is {} but {} was expected",
+                url,
+                digest.trim_start_matches("sha256:"),
+                sha256
+            )))
+        }
+        Some(expected) if expected != digest => {
+            return Err(Error::Custom(format!(
+                "{} changed since it was locked at {} in {}, pull it with --update to lock the new archive",
+                url, expected, TEMPLATE_LOCK_FILE
+            )))
+        }
+        _ => {}
+    }
+
+    let staging = cache.staging_dir(url, "")?;
+    unpack_tar(&archive[..], true, &staging)?;
+    Ok((cache.store(url, "", digest.as_str())?, digest))
+}
+
+/// the cached content of a downloaded source when it has the digest expected, offline the cached
+/// content must be there and its digest is printed when none is expected
+pub(crate) fn lookup_cache(
+    cache: &RepositoryCache,
+    source: &str,
+    expected: Option<&str>,
+    offline: bool,
+) -> Result<Option<(PathBuf, String)>> {
+    if !offline && expected.is_none() {
+        return Ok(None);
+    }
+    match cache.cached(source, "", expected)? {
+        Some((dir, digest)) => {
+            if expected.is_none() {
+                // nothing pins the source, tell which of its revisions is used
+                colour::yellow!(
+                    "Using {} of {} from the template cache, it may be older than the source\n",
+                    digest,
+                    source
+                );
+            }
+            Ok(Some((dir, digest)))
+        }
+        None if offline => Err(Error::Custom(format!(
+            "{}{} is not in the template cache {}, pull it once without --offline",
+            source,
+            expected.map(|e| format!(" at {}", e)).unwrap_or_default(),
+            cache.root().to_string_lossy()
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// digest of the content as sha256:<hex>
+pub(crate) fn sha256_digest(content: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content);
+    format!("sha256:{:x}", hasher.finalize())
+}
+
+/// unpacks a tar or a tar.gz into the folder, entries and links leading out of it are skipped
+pub(crate) fn unpack_tar(reader: impl Read, gzip: bool, dest: &Path) -> Result<()> {
+    let reader: Box<dyn Read> = if gzip {
+        Box::new(GzDecoder::new(reader))
+    } else {
+        Box::new(reader)
+    };
+    let unpack_error =
+        |e: std::io::Error| Error::Custom(format!("can't unpack the archive: {}", e));
+    let mut archive = tar::Archive::new(reader);
+    let mut links = Links::default();
+    for entry in archive.entries().map_err(unpack_error)? {
+        let mut entry = entry.map_err(unpack_error)?;
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            let path = entry.path().map_err(unpack_error)?.to_path_buf();
+            let stays_inside = match entry.link_name().map_err(unpack_error)? {
+                Some(target) => links.admit(&path, &target, entry_type.is_symlink()),
+                None => false,
+            };
+            if !stays_inside {
+                continue;
+            }
+        }
+        entry.unpack_in(dest).map_err(unpack_error)?;
+    }
+    Ok(())
+}
+
+/// Links keeps the symbolic links unpacked from an archive and the folders the links go through.
+/// A path going through a symbolic link may lead anywhere whatever its text says, so no link may
+/// go through another one, whichever comes first in the archive
+#[derive(Default)]
+struct Links {
+    links: HashSet<PathBuf>,
+    folders: HashSet<PathBuf>,
+}
+
+impl Links {
+    /// tells whether the link at path to target stays inside the archive, and records it if so
+    fn admit(&mut self, path: &Path, target: &Path, symlink: bool) -> bool {
+        let (path, path_folders) = match self.resolve(path) {
+            Some(resolved) => resolved,
+            None => return false,
+        };
+        // a symbolic link is relative to its folder, a hard link to the archive
+        let base = if symlink {
+            path.parent().map(Path::to_path_buf).unwrap_or_default()
+        } else {
+            PathBuf::new()
+        };
+        let (_, target_folders) = match self.resolve(&base.join(target)) {
+            Some(resolved) => resolved,
+            None => return false,
+        };
+        if self.folders.contains(&path) {
+            return false;
+        }
+        self.folders.extend(path_folders);
+        self.folders.extend(target_folders);
+        if symlink {
+            self.links.insert(path);
+        }
+        true
+    }
+
+    /// resolves a path relative to the archive without following links, with the folders it goes
+    /// through. None when it goes above the archive or through one of the links
+    fn resolve(&self, path: &Path) -> Option<(PathBuf, Vec<PathBuf>)> {
+        let mut resolved = PathBuf::new();
+        let mut folders = Vec::new();
+        for component in path.components() {
+            if self.links.contains(&resolved) {
+                return None;
+            }
+            folders.push(resolved.clone());
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::CurDir => {}
+                Component::ParentDir if resolved.pop() => {}
+                _ => return None,
+            }
+        }
+        Some((resolved, folders))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tempdir::TempDir;
+
+    /// a tar.gz holding the template folder of a template repository
+    pub(crate) fn template_archive(prefix: &str, content: &str) -> Vec<u8> {
+        let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        header.set_size(content.len() as u64);
+        tar.append_data(
+            &mut header,
+            format!("{}template/node/template.yml", prefix),
+            content.as_bytes(),
+        )
+        .unwrap();
+        tar.into_inner().unwrap().finish().unwrap()
+    }
+
+    /// appends a file whatever its path, set_path refuses ..
+    #[cfg(unix)]
+    fn append_file(tar: &mut tar::Builder<Vec<u8>>, path: &[u8], content: &str) {
+        let mut header = tar::Header::new_old();
+        header.as_old_mut().name[..path.len()].copy_from_slice(path);
+        header.set_mode(0o644);
+        header.set_size(content.len() as u64);
+        header.set_cksum();
+        tar.append(&header, content.as_bytes()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unpack_tar_stays_inside() {
+        let dir = TempDir::new("template-archive").unwrap();
+        let dest = dir.path().join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+
+        let mut tar = tar::Builder::new(Vec::new());
+        append_file(&mut tar, b"template/node/template.yml", "language: node");
+        append_file(&mut tar, b"../evil.txt", "evil");
+        append_file(&mut tar, b"template/../../evil.txt", "evil");
+        for (entry_type, path, target) in &[
+            (tar::EntryType::Symlink, "node", "template/node"),
+            (tar::EntryType::Symlink, "template/up", "../../outside"),
+            (tar::EntryType::Symlink, "etc", "/etc"),
+            (tar::EntryType::Link, "hard", "../evil.txt"),
+            (tar::EntryType::Symlink, "a/l1", ".."),
+            (tar::EntryType::Symlink, "l2", "a/l1/.."),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(*entry_type);
+            header.set_size(0);
+            tar.append_link(&mut header, path, target).unwrap();
+        }
+        append_file(&mut tar, b"template/up/escape.txt", "escape");
+        let archive = tar.into_inner().unwrap();
+
+        unpack_tar(&archive[..], false, &dest).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dest.join("node/template.yml")).unwrap(),
+            "language: node"
+        );
+        assert!(std::fs::symlink_metadata(dest.join("node"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(!dir.path().join("evil.txt").exists());
+        assert!(!dest.join("etc").exists());
+        assert!(!dest.join("hard").exists());
+        assert!(std::fs::symlink_metadata(dest.join("l2")).is_err());
+        assert!(!dir.path().join("outside").exists());
+        assert!(!std::fs::symlink_metadata(dest.join("template/up"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+    }
+
+    #[test]
+    fn test_links_resolve() {
+        let mut links = Links::default();
+        let resolved = |links: &Links, path: &str| links.resolve(Path::new(path)).map(|r| r.0);
+        assert_eq!(
+            resolved(&links, "template/node"),
+            Some(PathBuf::from("template/node"))
+        );
+        assert_eq!(
+            resolved(&links, "template/../node"),
+            Some(PathBuf::from("node"))
+        );
+        assert_eq!(resolved(&links, "./node"), Some(PathBuf::from("node")));
+        assert_eq!(resolved(&links, "../node"), None);
+        assert_eq!(resolved(&links, "template/../../node"), None);
+        assert_eq!(resolved(&links, "/etc"), None);
+
+        assert!(links.admit(Path::new("a/l1"), Path::new(".."), true));
+        // a/l1/.. is the folder above the archive, not a
+        assert!(!links.admit(Path::new("l2"), Path::new("a/l1/.."), true));
+        assert!(!links.admit(Path::new("a/l1/l3"), Path::new("."), true));
+        // a link to a link is fine
+        assert!(links.admit(Path::new("l4"), Path::new("a/l1"), true));
+
+        // b/l5 would turn the earlier target of l6 into the folder above the archive
+        assert!(links.admit(Path::new("l6"), Path::new("b/l5/.."), true));
+        assert!(!links.admit(Path::new("b/l5"), Path::new(".."), true));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_archive() {
+        let dir = TempDir::new("template-archive").unwrap();
+        let cache = RepositoryCache::new(dir.path().join("cache"));
+        let archive = template_archive("templates-main/", "language: node");
+        let digest = sha256_digest(&archive);
+        let _mok = mockito::mock("GET", "/templates/templates.tar.gz")
+            .with_status(200)
+            .with_body(&archive)
+            .expect(2)
+            .create();
+        let url = format!("{}/templates/templates.tar.gz", mockito::server_url());
+
+        let err = fetch_archive(&cache, url.as_str(), "0".repeat(64).as_str(), None, false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("but 0000"));
+
+        let sha256 = digest.trim_start_matches("sha256:");
+        let (content, fetched) = fetch_archive(&cache, url.as_str(), sha256, None, false)
+            .await
+            .unwrap();
+        assert_eq!(fetched, digest);
+        assert_eq!(
+            std::fs::read_to_string(content.join("templates-main/template/node/template.yml"))
+                .unwrap(),
+            "language: node"
+        );
+
+        // the archive locked is served from the cache, also offline
+        let (cached, _) = fetch_archive(&cache, url.as_str(), "", Some(digest.as_str()), false)
+            .await
+            .unwrap();
+        assert_eq!(cached, content);
+        assert!(fetch_archive(&cache, url.as_str(), "", None, true)
+            .await
+            .is_ok());
+        assert!(
+            fetch_archive(&cache, url.as_str(), "", Some("sha256:0"), true)
+                .await
+                .is_err()
+        );
+        _mok.assert();
+    }
+}
@@ -0,0 +1,44 @@
+use crate::template_cache_list::TemplateCacheList;
+use crate::template_cache_prune::TemplateCachePrune;
+use crate::{State, SubCommandAppend};
+use clap::{App, ArgMatches, SubCommand};
+
+pub(crate) struct TemplateCache;
+
+impl SubCommandAppend for TemplateCache {
+    #[inline(always)]
+    fn append_subcommand() -> App<'static, 'static> {
+        let app = SubCommand::with_name("cache")
+            .about(
+                r#"Manages the local clones of the template repositories pulled, kept in the
+template-cache folder of the config dir and used by --offline`,
+	Example: `  faas-cli template cache list
+  faas-cli template cache prune
+  faas-cli template cache prune --older-than 30d
+  faas-cli template cache prune https://github.com/openfaas/templates.git`"#,
+            )
+            .subcommand(TemplateCacheList::append_subcommand())
+            .subcommand(TemplateCachePrune::append_subcommand());
+        app
+    }
+}
+
+impl TemplateCache {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if let Some(c_args) = args.subcommand_matches("cache") {
+            TemplateCacheList::dispatch_command(c_args).await?;
+            TemplateCachePrune::dispatch_command(c_args).await?;
+            let usage = c_args.usage();
+            Err(State::Custom(format!(
+                "template cache must followed by sub command for example:\n\
+             {}
+  faas-cli template cache list
+  faas-cli template cache prune",
+                usage
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::repository_cache::{CacheEntry, RepositoryCache};
+use crate::{State, SubCommandAppend};
+use clap::{App, ArgMatches, SubCommand};
+
+pub(crate) struct TemplateCacheList;
+
+impl SubCommandAppend for TemplateCacheList {
+    #[inline(always)]
+    fn append_subcommand() -> App<'static, 'static> {
+        let app = SubCommand::with_name("list").alias("ls").about(
+            r#"Lists the template repositories in the cache with the commit they were last fetched at`,
+	Example: `  faas-cli template cache list
+  faas-cli template cache ls`"#,
+        );
+        app
+    }
+}
+
+impl TemplateCacheList {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if args.subcommand_matches("list").is_some() {
+            let cache = RepositoryCache::open()?;
+            let entries = cache.entries()?;
+            if entries.is_empty() {
+                println!(
+                    "No templates are cached in {}",
+                    cache.root().to_string_lossy()
+                );
+            } else {
+                print!("{}", format_cache_output(&entries));
+            }
+
+            Err(State::Matched)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn format_cache_output(entries: &[CacheEntry]) -> String {
+    let width = entries
+        .iter()
+        .map(|e| e.repository.len())
+        .max()
+        .unwrap_or_default()
+        .max("REPOSITORY".len())
+        + 3;
+    let mut output = format!(
+        "{:width$}{:15}{:17}{:24}{}\n",
+        "REPOSITORY",
+        "REF",
+        "COMMIT",
+        "FETCHED",
+        "USED",
+        width = width
+    );
+    for entry in entries {
+        output.push_str(
+            format!(
+                "{:width$}{:15}{:17}{:24}{}\n",
+                entry.repository,
+                if entry.ref_name.is_empty() {
+                    "-"
+                } else {
+                    entry.ref_name.as_str()
+                },
+                short_revision(entry.commit.as_str()),
+                entry.fetched,
+                entry.used,
+                width = width
+            )
+            .as_str(),
+        );
+    }
+    output
+}
+
+/// the first 7 characters of a commit or of the hex of a sha256: digest
+fn short_revision(revision: &str) -> String {
+    let (prefix, hex) = match revision.strip_prefix("sha256:") {
+        Some(hex) => ("sha256:", hex),
+        None => ("", revision),
+    };
+    format!("{}{}", prefix, hex.get(..7).unwrap_or(hex))
+}
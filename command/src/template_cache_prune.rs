@@ -0,0 +1,72 @@
+use crate::repository_cache::RepositoryCache;
+use crate::{State, SubCommandAppend};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use utility::Error;
+
+pub(crate) struct TemplateCachePrune;
+
+impl SubCommandAppend for TemplateCachePrune {
+    #[inline(always)]
+    fn append_subcommand() -> App<'static, 'static> {
+        let app = SubCommand::with_name("prune")
+            .about(
+                r#"Removes the template repositories from the cache, all of them unless a
+[REPOSITORY_URL] or --older-than is given`,
+	Example: `  faas-cli template cache prune
+  faas-cli template cache prune --older-than 720h
+  faas-cli template cache prune https://github.com/openfaas/templates.git`"#,
+            )
+            .arg(
+                Arg::with_name("REPOSITORY_URL")
+                    .help("repository to remove from the cache, with all of its branches and tags")
+                    .index(1),
+            )
+            .arg(
+                Arg::with_name("older-than")
+                    .long("older-than")
+                    .takes_value(true)
+                    .help("Only remove the repositories not pulled from within this duration, e.g. 720h or 30days"),
+            );
+        app
+    }
+}
+
+impl TemplateCachePrune {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if let Some(p_args) = args.subcommand_matches("prune") {
+            let repository = p_args.value_of("REPOSITORY_URL").unwrap_or_default();
+            let unused_since = match p_args.value_of("older-than") {
+                Some(older_than) => {
+                    let older_than = parse_duration::parse(older_than).map_err(|e| {
+                        Error::Custom(format!("invalid value for --older-than: {}", e))
+                    })?;
+                    let older_than = chrono::Duration::from_std(older_than).map_err(|e| {
+                        Error::Custom(format!("invalid value for --older-than: {}", e))
+                    })?;
+                    Some(chrono::Utc::now() - older_than)
+                }
+                None => None,
+            };
+
+            let cache = RepositoryCache::open()?;
+            let removed = cache.prune(repository, unused_since)?;
+            for entry in &removed {
+                if entry.ref_name.is_empty() {
+                    println!("Removed {}", entry.repository);
+                } else {
+                    println!("Removed {} at {}", entry.repository, entry.ref_name);
+                }
+            }
+            colour::green!(
+                "Pruned {} repositories from {}\n",
+                removed.len(),
+                cache.root().to_string_lossy()
+            );
+
+            Err(State::Matched)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use builder::cache::hash_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use utility::{Error, Result};
+
+/// TemplateLockFile records the commit and the content of the templates pulled into the project
+pub(crate) const TEMPLATE_LOCK_FILE: &str = "template.lock";
+
+/// LockMode tells how a pull uses the template.lock file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LockMode {
+    /// pull the locked commit of a source when there is one and lock the sources pulled
+    Locked,
+    /// pull the latest commit of the sources and lock it, as with --update
+    Update,
+    /// pull the locked commits only and fail when the templates don't match the lock, as with
+    /// --frozen
+    Frozen,
+}
+
+impl LockMode {
+    pub(crate) fn from_flags(frozen: bool, update: bool) -> Result<LockMode> {
+        match (frozen, update) {
+            (true, true) => Err(Error::Custom(
+                "the --frozen and --update flags can't be used together".to_string(),
+            )),
+            (true, false) => Ok(LockMode::Frozen),
+            (false, true) => Ok(LockMode::Update),
+            (false, false) => Ok(LockMode::Locked),
+        }
+    }
+}
+
+/// TemplateLock the content of template.lock
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct TemplateLock {
+    #[serde(default)]
+    pub(crate) sources: Vec<LockedSource>,
+}
+
+/// LockedSource a template source pinned to a commit, or to the digest of an archive or an OCI
+/// artifact, with the hash of each of its templates
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct LockedSource {
+    pub(crate) repository: String,
+    /// branch or tag the repository was pulled at, empty for its default branch
+    #[serde(default, rename = "ref", skip_serializing_if = "String::is_empty")]
+    pub(crate) ref_name: String,
+    /// empty for file:// folders, which only have their templates hashed
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) commit: String,
+    /// sha256 of the folder of each template, by template name
+    #[serde(default)]
+    pub(crate) templates: BTreeMap<String, String>,
+}
+
+impl TemplateLock {
+    /// reads the lock file, a missing file is an empty lock
+    pub(crate) fn load(path: &Path) -> Result<TemplateLock> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Default::default()),
+            Err(e) => return Err(Error::Io(e)),
+        };
+        serde_yaml::from_str(content.as_str())
+            .map_err(|e| Error::Custom(format!("can't read {}: {}", path.to_string_lossy(), e)))
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_yaml::to_string(self)
+            .map_err(|e| Error::Custom(format!("can't serialize the template lock: {}", e)))?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub(crate) fn source(&self, repository: &str, ref_name: &str) -> Option<&LockedSource> {
+        self.sources
+            .iter()
+            .find(|s| s.repository == repository && s.ref_name == ref_name)
+    }
+
+    /// adds the source or replaces its previous entry, the sources are kept sorted
+    pub(crate) fn record(&mut self, source: LockedSource) {
+        self.sources
+            .retain(|s| !(s.repository == source.repository && s.ref_name == source.ref_name));
+        self.sources.push(source);
+        self.sources.sort_by(|a, b| {
+            (a.repository.as_str(), a.ref_name.as_str())
+                .cmp(&(b.repository.as_str(), b.ref_name.as_str()))
+        });
+    }
+
+    /// returns the templates that are missing under the template folder or don't match any
+    /// source locking them, templates no source locks are reported too
+    pub(crate) fn verify(&self, template_dir: &Path, templates: &[String]) -> Result<Vec<String>> {
+        let mut mismatches = Vec::new();
+        for name in templates {
+            let hashes: Vec<&String> = self
+                .sources
+                .iter()
+                .filter_map(|s| s.templates.get(name.as_str()))
+                .collect();
+            let path = template_dir.join(name.as_str());
+            if hashes.is_empty() {
+                mismatches.push(format!("{} is not locked", name));
+            } else if !path.is_dir() {
+                mismatches.push(format!("{} is missing", name));
+            } else if !hashes.contains(&&template_hash(&path)?) {
+                mismatches.push(format!("{} was changed", name));
+            }
+        }
+        Ok(mismatches)
+    }
+}
+
+/// hash of a template folder as written in the lock
+pub(crate) fn template_hash(dir: &Path) -> Result<String> {
+    Ok(format!("sha256:{}", hash_dir(dir)?))
+}
+
+/// hashes every template of a template folder, by template name
+pub(crate) fn hash_templates(template_dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut templates = BTreeMap::new();
+    for entry in std::fs::read_dir(template_dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            templates.insert(name, template_hash(&path)?);
+        }
+    }
+    Ok(templates)
+}
+
+/// with --frozen, fails unless the templates of the languages are locked and unchanged on disk
+pub(crate) fn check_frozen_templates(
+    lock_path: &Path,
+    template_dir: &Path,
+    languages: &[String],
+) -> Result<()> {
+    let lock = TemplateLock::load(lock_path)?;
+    let mismatches = lock.verify(template_dir, languages)?;
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Custom(format!(
+            "the templates don't match {}: {}, run faas-cli template pull --update to refresh it",
+            lock_path.to_string_lossy(),
+            mismatches.join(", ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_template_lock() {
+        let dir = TempDir::new("template-lock").unwrap();
+        let template_dir = dir.path().join("template");
+        for name in &["node14", "python3"] {
+            std::fs::create_dir_all(template_dir.join(name).join("function")).unwrap();
+            std::fs::write(template_dir.join(name).join("template.yml"), name).unwrap();
+        }
+        let templates = hash_templates(&template_dir).unwrap();
+        assert_eq!(templates.len(), 2);
+        assert!(templates["node14"].starts_with("sha256:"));
+        assert_ne!(templates["node14"], templates["python3"]);
+
+        let mut lock = TemplateLock::default();
+        lock.record(LockedSource {
+            repository: "https://github.com/openfaas/templates.git".to_string(),
+            ref_name: String::new(),
+            commit: "0".repeat(40),
+            templates: templates.clone(),
+        });
+        lock.record(LockedSource {
+            repository: "https://github.com/openfaas/templates.git".to_string(),
+            ref_name: String::new(),
+            commit: "1".repeat(40),
+            templates,
+        });
+        assert_eq!(lock.sources.len(), 1);
+        assert_eq!(lock.sources[0].commit, "1".repeat(40));
+
+        let lock_path = dir.path().join(TEMPLATE_LOCK_FILE);
+        lock.save(&lock_path).unwrap();
+        assert_eq!(TemplateLock::load(&lock_path).unwrap(), lock);
+        assert_eq!(
+            TemplateLock::load(&dir.path().join("missing.lock")).unwrap(),
+            TemplateLock::default()
+        );
+
+        let languages = vec!["node14".to_string(), "python3".to_string()];
+        assert!(check_frozen_templates(&lock_path, &template_dir, &languages).is_ok());
+
+        std::fs::write(template_dir.join("node14/function/handler.js"), "").unwrap();
+        std::fs::remove_dir_all(template_dir.join("python3")).unwrap();
+        assert_eq!(
+            lock.verify(
+                &template_dir,
+                &[
+                    "node14".to_string(),
+                    "python3".to_string(),
+                    "go".to_string()
+                ]
+            )
+            .unwrap(),
+            vec![
+                "node14 was changed".to_string(),
+                "python3 is missing".to_string(),
+                "go is not locked".to_string()
+            ]
+        );
+        assert!(check_frozen_templates(&lock_path, &template_dir, &languages).is_err());
+    }
+}
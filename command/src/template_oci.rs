@@ -0,0 +1,423 @@
+use crate::repository_cache::RepositoryCache;
+use crate::template_archive::{lookup_cache, sha256_digest, unpack_tar};
+use crate::template_source::OciReference;
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::header::{ACCEPT, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use utility::{Error, Result};
+
+const MANIFEST_MEDIA_TYPES: &str =
+    "application/vnd.oci.image.manifest.v1+json, application/vnd.docker.distribution.manifest.v2+json";
+const TITLE_ANNOTATION: &str = "org.opencontainers.image.title";
+const DOCKER_HUB_REGISTRY: &str = "docker.io";
+const DOCKER_HUB_API: &str = "registry-1.docker.io";
+const DOCKER_HUB_AUTH_KEY: &str = "https://index.docker.io/v1/";
+
+lazy_static! {
+    static ref CHALLENGE_PARAM: Regex = Regex::new(r#"(\w+)="([^"]*)""#).unwrap();
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+    /// set when the reference is an image index
+    #[serde(default)]
+    manifests: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Descriptor {
+    #[serde(rename = "mediaType")]
+    media_type: String,
+    digest: String,
+    #[serde(default)]
+    annotations: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    #[serde(default)]
+    token: String,
+    #[serde(default)]
+    access_token: String,
+}
+
+#[derive(Deserialize, Default)]
+struct DockerConfig {
+    #[serde(default)]
+    auths: HashMap<String, DockerAuth>,
+}
+
+#[derive(Deserialize, Default)]
+struct DockerAuth {
+    #[serde(default)]
+    auth: String,
+}
+
+/// pulls the layers of the OCI artifact into the template cache and returns the folder they were
+/// unpacked to with the digest of the manifest. A digest reference, or else the digest locked for
+/// the source, is pulled instead of the tag and a cached artifact with that digest is used without
+/// pulling it again, offline the cached artifact is used whatever its digest
+pub(crate) async fn fetch_oci(
+    cache: &RepositoryCache,
+    source: &str,
+    reference: &OciReference,
+    locked: Option<&str>,
+    offline: bool,
+) -> Result<(PathBuf, String)> {
+    let expected = if reference.is_digest() {
+        Some(reference.reference.clone())
+    } else {
+        locked.map(str::to_string)
+    };
+    if let Some(cached) = lookup_cache(cache, source, expected.as_deref(), offline)? {
+        return Ok(cached);
+    }
+
+    let mut client = RegistryClient::new(reference);
+    let manifest_ref = expected
+        .clone()
+        .unwrap_or_else(|| reference.reference.clone());
+    let content = client
+        .get(
+            format!("manifests/{}", manifest_ref).as_str(),
+            MANIFEST_MEDIA_TYPES,
+        )
+        .await?;
+    let digest = sha256_digest(&content);
+    if let Some(expected) = &expected {
+        if *expected != digest {
+            return Err(Error::Custom(format!(
+                "the manifest of {} has the digest {} but {} was expected",
+                source, digest, expected
+            )));
+        }
+    }
+
+    let manifest: Manifest = serde_json::from_slice(&content)
+        .map_err(|e| Error::Custom(format!("can't read the manifest of {}: {}", source, e)))?;
+    if !manifest.manifests.is_empty() {
+        return Err(Error::Custom(format!(
+            "{} is an image index, reference the manifest of a single artifact instead",
+            source
+        )));
+    }
+    if manifest.layers.is_empty() {
+        return Err(Error::Custom(format!("{} has no layers", source)));
+    }
+
+    let staging = cache.staging_dir(source, "")?;
+    for layer in &manifest.layers {
+        let blob = client
+            .get(format!("blobs/{}", layer.digest).as_str(), "*/*")
+            .await?;
+        if sha256_digest(&blob) != layer.digest {
+            return Err(Error::Custom(format!(
+                "the layer {} of {} doesn't match its digest",
+                layer.digest, source
+            )));
+        }
+        unpack_layer(layer, &blob, &staging)?;
+    }
+    Ok((cache.store(source, "", digest.as_str())?, digest))
+}
+
+/// tar and tar.gz layers are unpacked, other layers are files named by their title annotation as
+/// pushed by oras
+fn unpack_layer(layer: &Descriptor, blob: &[u8], dest: &Path) -> Result<()> {
+    let media_type = layer.media_type.as_str();
+    let title = layer
+        .annotations
+        .get(TITLE_ANNOTATION)
+        .map(String::as_str)
+        .unwrap_or_default();
+    if (media_type.contains("tar") && media_type.ends_with("gzip"))
+        || title.ends_with(".tar.gz")
+        || title.ends_with(".tgz")
+    {
+        unpack_tar(blob, true, dest)
+    } else if media_type.ends_with("tar") || title.ends_with(".tar") {
+        unpack_tar(blob, false, dest)
+    } else if !title.is_empty() && !title.contains(['/', '\\']) && title != ".." && title != "." {
+        std::fs::write(dest.join(title), blob)?;
+        Ok(())
+    } else {
+        Err(Error::Custom(format!(
+            "can't unpack the layer {} of type {}, use tar or tar+gzip layers",
+            layer.digest, media_type
+        )))
+    }
+}
+
+/// registries of the loopback host, such as localhost:5000, are reached over plain http as
+/// docker does for its local registries, every other registry over https. There is no switch for
+/// other insecure registries, serve them over https or pull the templates as an archive instead
+fn registry_scheme(host: &str) -> &'static str {
+    let name = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    if name == "localhost" || name == "::1" || name.starts_with("127.") {
+        "http"
+    } else {
+        "https"
+    }
+}
+
+/// RegistryClient reads from the repository of a registry with the anonymous or basic
+/// credentials of the docker config, exchanged for a bearer token when the registry asks for one
+struct RegistryClient {
+    client: reqwest::Client,
+    base_url: String,
+    registry: String,
+    credentials: Option<(String, String)>,
+    authorization: Option<String>,
+}
+
+impl RegistryClient {
+    /// the client of the repository of the reference, see registry_scheme for the scheme used
+    fn new(reference: &OciReference) -> RegistryClient {
+        let (host, repository) = if reference.registry == DOCKER_HUB_REGISTRY {
+            let repository = if reference.repository.contains('/') {
+                reference.repository.clone()
+            } else {
+                format!("library/{}", reference.repository)
+            };
+            (DOCKER_HUB_API.to_string(), repository)
+        } else {
+            (reference.registry.clone(), reference.repository.clone())
+        };
+        RegistryClient {
+            client: reqwest::Client::new(),
+            base_url: format!(
+                "{}://{}/v2/{}",
+                registry_scheme(host.as_str()),
+                host,
+                repository
+            ),
+            registry: reference.registry.clone(),
+            credentials: registry_credentials(reference.registry.as_str()),
+            authorization: None,
+        }
+    }
+
+    async fn get(&mut self, path: &str, accept: &str) -> Result<Vec<u8>> {
+        let url = format!("{}/{}", self.base_url, path);
+        let mut response = self.send(url.as_str(), accept).await?;
+        if response.status() == StatusCode::UNAUTHORIZED && self.authorization.is_none() {
+            let challenge = response
+                .headers()
+                .get(WWW_AUTHENTICATE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            self.authorize(challenge.as_str()).await?;
+            response = self.send(url.as_str(), accept).await?;
+        }
+        if !response.status().is_success() {
+            return Err(Error::Custom(format!(
+                "can't pull {}: the registry returned {}",
+                url,
+                response.status()
+            )));
+        }
+        let content = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Custom(format!("can't pull {}: {}", url, e)))?;
+        Ok(content.to_vec())
+    }
+
+    async fn send(&self, url: &str, accept: &str) -> Result<reqwest::Response> {
+        let mut request = self.client.get(url).header(ACCEPT, accept);
+        if let Some(authorization) = &self.authorization {
+            request = request.header(reqwest::header::AUTHORIZATION, authorization.as_str());
+        }
+        request.send().await.map_err(|e| {
+            Error::Custom(format!("can't reach the registry {}: {}", self.registry, e))
+        })
+    }
+
+    /// answers the challenge of the registry, with a bearer token from its realm or basic
+    /// credentials
+    async fn authorize(&mut self, challenge: &str) -> Result<()> {
+        let params: HashMap<&str, &str> = CHALLENGE_PARAM
+            .captures_iter(challenge)
+            .filter_map(|c| Some((c.get(1)?.as_str(), c.get(2)?.as_str())))
+            .collect();
+        if challenge.to_lowercase().starts_with("bearer") {
+            let realm = params.get("realm").ok_or_else(|| {
+                Error::Custom(format!(
+                    "the registry {} asks for a token without a realm",
+                    self.registry
+                ))
+            })?;
+            let query: Vec<(&str, &str)> = ["service", "scope"]
+                .iter()
+                .filter_map(|k| params.get(k).map(|v| (*k, *v)))
+                .collect();
+            let mut request = self.client.get(*realm).query(&query);
+            if let Some((username, password)) = &self.credentials {
+                request = request.basic_auth(username, Some(password));
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|e| Error::Custom(format!("can't get a token from {}: {}", realm, e)))?;
+            if !response.status().is_success() {
+                return Err(Error::Custom(format!(
+                    "can't get a token from {}: it returned {}, log in to {} with docker login",
+                    realm,
+                    response.status(),
+                    self.registry
+                )));
+            }
+            let token = response
+                .bytes()
+                .await
+                .map_err(|e| Error::Custom(format!("can't get a token from {}: {}", realm, e)))?;
+            let token: TokenResponse = serde_json::from_slice(&token).map_err(|e| {
+                Error::Custom(format!("can't read the token from {}: {}", realm, e))
+            })?;
+            let token = if token.token.is_empty() {
+                token.access_token
+            } else {
+                token.token
+            };
+            self.authorization = Some(format!("Bearer {}", token));
+            Ok(())
+        } else {
+            let (username, password) = self.credentials.as_ref().ok_or_else(|| {
+                Error::Custom(format!(
+                    "the registry {} requires credentials, log in to it with docker login",
+                    self.registry
+                ))
+            })?;
+            self.authorization = Some(format!(
+                "Basic {}",
+                base64::encode(format!("{}:{}", username, password))
+            ));
+            Ok(())
+        }
+    }
+}
+
+/// the username and password of the registry in the docker config, see DOCKER_CONFIG
+fn registry_credentials(registry: &str) -> Option<(String, String)> {
+    let dir = std::env::var("DOCKER_CONFIG").unwrap_or_else(|_| "~/.docker".to_string());
+    let path = Path::new(shellexpand::tilde(dir.as_str()).as_ref()).join("config.json");
+    let config: DockerConfig = std::fs::read(path)
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())?;
+    let keys = if registry == DOCKER_HUB_REGISTRY {
+        vec![DOCKER_HUB_AUTH_KEY.to_string(), registry.to_string()]
+    } else {
+        vec![
+            registry.to_string(),
+            format!("https://{}", registry),
+            format!("http://{}", registry),
+        ]
+    };
+    let auth = keys.iter().find_map(|k| config.auths.get(k))?;
+    let decoded = base64::decode(auth.auth.as_str()).ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded.split_at(decoded.find(':')?);
+    Some((username.to_string(), password[1..].to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template_archive::tests::template_archive;
+    use crate::template_source::parse_oci_reference;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_registry_scheme() {
+        assert_eq!(registry_scheme("localhost:5000"), "http");
+        assert_eq!(registry_scheme("127.0.0.1:5000"), "http");
+        assert_eq!(registry_scheme("[::1]:5000"), "http");
+        assert_eq!(registry_scheme("localhost.example.com"), "https");
+        assert_eq!(registry_scheme("ghcr.io"), "https");
+        assert_eq!(registry_scheme("registry-1.docker.io"), "https");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_oci() {
+        let dir = TempDir::new("template-oci").unwrap();
+        let cache = RepositoryCache::new(dir.path().join("cache"));
+        let layer = template_archive("", "language: node");
+        let layer_digest = sha256_digest(&layer);
+        let manifest = format!(
+            r#"{{"schemaVersion":2,"mediaType":"application/vnd.oci.image.manifest.v1+json","layers":[{{"mediaType":"application/vnd.oci.image.layer.v1.tar+gzip","digest":"{}","size":{},"annotations":{{"org.opencontainers.image.title":"template"}}}}]}}"#,
+            layer_digest,
+            layer.len()
+        );
+        let manifest_digest = sha256_digest(manifest.as_bytes());
+
+        let registry = mockito::server_address().to_string();
+        let _challenge = mockito::mock("GET", "/v2/team/templates/manifests/1.0")
+            .match_header("authorization", mockito::Matcher::Missing)
+            .with_status(401)
+            .with_header(
+                "www-authenticate",
+                format!(
+                    r#"Bearer realm="http://{}/token",service="registry",scope="repository:team/templates:pull""#,
+                    registry
+                )
+                .as_str(),
+            )
+            .create();
+        let _token = mockito::mock("GET", "/token")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("service".into(), "registry".into()),
+                mockito::Matcher::UrlEncoded(
+                    "scope".into(),
+                    "repository:team/templates:pull".into(),
+                ),
+            ]))
+            .with_body(r#"{"token":"secret"}"#)
+            .create();
+        let _manifest = mockito::mock("GET", "/v2/team/templates/manifests/1.0")
+            .match_header("authorization", "Bearer secret")
+            .with_body(&manifest)
+            .expect(1)
+            .create();
+        let _blob = mockito::mock(
+            "GET",
+            format!("/v2/team/templates/blobs/{}", layer_digest).as_str(),
+        )
+        .match_header("authorization", "Bearer secret")
+        .with_body(&layer)
+        .create();
+
+        let source = format!("oci://{}/team/templates:1.0", registry);
+        let reference = parse_oci_reference(source.trim_start_matches("oci://")).unwrap();
+        let (content, digest) = fetch_oci(&cache, source.as_str(), &reference, None, false)
+            .await
+            .unwrap();
+        assert_eq!(digest, manifest_digest);
+        assert_eq!(
+            std::fs::read_to_string(content.join("template/node/template.yml")).unwrap(),
+            "language: node"
+        );
+
+        // the digest locked is served from the cache
+        let (cached, _) = fetch_oci(
+            &cache,
+            source.as_str(),
+            &reference,
+            Some(manifest_digest.as_str()),
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(cached, content);
+        _manifest.assert();
+    }
+}
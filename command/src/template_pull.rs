@@ -1,5 +1,6 @@
 use crate::fetch_template::{pull_template, DEFAULT_TEMPLATE_REPOSITORY};
 use crate::priority::{get_template_url, TEMPLATE_URL_ENVIRONMENT};
+use crate::template_lock::LockMode;
 use crate::template_pull_stack::TemplatePullStack;
 use crate::{State, SubCommandAppend};
 use clap::{App, Arg, ArgMatches, SubCommand};
@@ -16,16 +17,48 @@ impl SubCommandAppend for TemplatePull {
 directory from the root of the repo, if it exists.
 
 [REPOSITORY_URL] may specify a specific branch or tag to copy by adding a URL fragment with the branch or tag name.
+It may also be a file:// folder, the URL of a .tar.gz or .tgz archive, verified against --sha256 when it is
+given, or an oci://registry/repository:tag reference to an OCI artifact. Registries on localhost are
+reached over http and the others over https.
+
+The commit and a hash of each template pulled are recorded in template.lock, a repository already
+in template.lock is pulled at its locked commit until --update is given.
+
+Repositories are cloned once into the template cache of the config dir and fetched on later pulls,
+with --offline the templates are served from the cache without reaching the repository.
 	`,
 	Example: `
   faas-cli template pull https://github.com/openfaas/templates
   faas-cli template pull https://github.com/openfaas/templates#1.0
+  faas-cli template pull https://github.com/openfaas/templates --update
+  faas-cli template pull https://github.com/openfaas/templates --offline
+  faas-cli template pull file:///home/user/src/templates
+  faas-cli template pull https://artifacts.example.com/templates-1.0.tar.gz --sha256 <sha256>
+  faas-cli template pull oci://ghcr.io/example/templates:1.0
 `,"#)
                 .arg(
                     Arg::with_name("REPOSITORY_URL")
                         .help("repository url which specify a specific branch or tag to copy by adding a URL fragment with the branch or tag name")
                         .index(1)
                 )
+                .arg(
+                    Arg::with_name("update")
+                        .long("update")
+                        .global(true)
+                        .help("Pull the latest commit of the templates and refresh template.lock"),
+                )
+                .arg(
+                    Arg::with_name("sha256")
+                        .long("sha256")
+                        .takes_value(true)
+                        .help("The sha256 the .tar.gz archive pulled must have"),
+                )
+                .arg(
+                    Arg::with_name("offline")
+                        .long("offline")
+                        .global(true)
+                        .help("Use the template cache only, without fetching the repository"),
+                )
                 .subcommand(TemplatePullStack::append_subcommand());
         app
     }
@@ -40,7 +73,10 @@ impl TemplatePull {
             let repository = p_args.value_of("REPOSITORY_URL").unwrap_or_default();
             let overwrite = p_args.is_present("overwrite");
             let debug = p_args.is_present("debug");
-            run_template_pull(repository, overwrite, debug)?;
+            let lock_mode = LockMode::from_flags(false, p_args.is_present("update"))?;
+            let offline = p_args.is_present("offline");
+            let sha256 = p_args.value_of("sha256").unwrap_or_default();
+            run_template_pull(repository, sha256, overwrite, debug, lock_mode, offline).await?;
 
             Err(State::Matched)
         } else {
@@ -49,10 +85,25 @@ impl TemplatePull {
     }
 }
 
-pub(crate) fn run_template_pull(repository: &str, overwrite: bool, pull_debug: bool) -> Result<()> {
+pub(crate) async fn run_template_pull(
+    repository: &str,
+    sha256: &str,
+    overwrite: bool,
+    pull_debug: bool,
+    lock_mode: LockMode,
+    offline: bool,
+) -> Result<()> {
     let env_url = std::env::var(TEMPLATE_URL_ENVIRONMENT).unwrap_or_default();
     let repository = get_template_url(repository, env_url.as_str(), DEFAULT_TEMPLATE_REPOSITORY);
-    pull_template(repository.as_str(), overwrite, pull_debug)
+    pull_template(
+        repository.as_str(),
+        sha256,
+        overwrite,
+        pull_debug,
+        lock_mode,
+        offline,
+    )
+    .await
 }
 
 #[allow(dead_code)]
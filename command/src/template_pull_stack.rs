@@ -1,8 +1,9 @@
 use crate::faas::check_and_set_default_yaml;
 use crate::fetch_template::pull_template;
+use crate::template_lock::LockMode;
 use crate::template_pull::run_template_pull;
 use crate::{State, SubCommandAppend};
-use clap::{App, ArgMatches, SubCommand};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use stack::schema::{Configuration, TemplateSource};
 use std::io::ErrorKind;
 use utility::{Error, Result};
@@ -12,14 +13,25 @@ pub(crate) struct TemplatePullStack;
 impl SubCommandAppend for TemplatePullStack {
     #[inline(always)]
     fn append_subcommand() -> App<'static, 'static> {
-        let app = SubCommand::with_name("stack").about(
-            r#"Downloads templates specified in the function yaml file, in the current directory
+        let app = SubCommand::with_name("stack")
+            .about(
+                r#"Downloads templates specified in the function yaml file, in the current directory.
+With --frozen the templates are pulled at the commits of template.lock and the command fails
+when the templates on disk don't match it. The source of a template may be a git repository, a
+file:// folder, the URL of a .tar.gz archive checked against its sha256 or an oci:// reference
 	`,
 	Example: `
   faas-cli template pull stack
   faas-cli template pull stack -f myfunction.yml
+  faas-cli template pull stack --frozen
+  faas-cli template pull stack --offline
   faas-cli template pull stack -r custom_repo_name`"#,
-        );
+            )
+            .arg(
+                Arg::with_name("frozen")
+                    .long("frozen")
+                    .help("Fail when the templates don't match template.lock instead of updating it"),
+            );
         app
     }
 }
@@ -34,9 +46,22 @@ impl TemplatePullStack {
             let repository = s_args.value_of("REPOSITORY_URL").unwrap_or_default();
             let overwrite = s_args.is_present("overwrite");
             let debug = s_args.is_present("debug");
+            let lock_mode =
+                LockMode::from_flags(s_args.is_present("frozen"), s_args.is_present("update"))?;
+
+            let offline = s_args.is_present("offline");
 
             let template_config = load_template_config(yaml_file)?;
-            pull_stack_templates(template_config, repository, yaml_file, overwrite, debug)?;
+            pull_stack_templates(
+                template_config,
+                repository,
+                yaml_file,
+                overwrite,
+                debug,
+                lock_mode,
+                offline,
+            )
+            .await?;
 
             Err(State::Matched)
         } else {
@@ -45,12 +70,14 @@ impl TemplatePullStack {
     }
 }
 
-pub(crate) fn pull_stack_templates(
+pub(crate) async fn pull_stack_templates(
     template_info: Vec<TemplateSource>,
     repository: &str,
     yaml_file: &str,
     overwrite: bool,
     pull_debug: bool,
+    lock_mode: LockMode,
+    offline: bool,
 ) -> Result<()> {
     for val in template_info {
         colour::green!(
@@ -59,9 +86,17 @@ pub(crate) fn pull_stack_templates(
             yaml_file
         );
         if val.source.is_empty() {
-            run_template_pull(repository, overwrite, pull_debug)?;
+            run_template_pull(repository, "", overwrite, pull_debug, lock_mode, offline).await?;
         } else {
-            pull_template(val.source.as_str(), overwrite, pull_debug)?;
+            pull_template(
+                val.source.as_str(),
+                val.sha256.as_str(),
+                overwrite,
+                pull_debug,
+                lock_mode,
+                offline,
+            )
+            .await?;
         }
     }
     Ok(())
@@ -0,0 +1,231 @@
+use std::path::PathBuf;
+use utility::{Error, Result};
+use versioncontol::parse::{is_git_remote, is_pinned_git_remote, parse_panned_remote};
+
+pub(crate) const FILE_SCHEME: &str = "file://";
+pub(crate) const OCI_SCHEME: &str = "oci://";
+const ARCHIVE_SUFFIXES: &[&str] = &[".tar.gz", ".tgz"];
+
+/// TemplateLocation where the templates of a source are pulled from
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TemplateLocation {
+    /// a git repository, at the branch or tag of its URL fragment or at its default branch
+    Git {
+        repository: String,
+        ref_name: String,
+    },
+    /// a local folder given as file://, read in place
+    Directory { source: String, path: PathBuf },
+    /// a tar.gz downloaded over http(s), verified against the sha256 when there is one
+    Archive { url: String, sha256: String },
+    /// an OCI artifact given as oci://registry/repository:tag or @digest
+    Oci {
+        source: String,
+        reference: OciReference,
+    },
+}
+
+/// OciReference the parts of oci://registry/repository:tag and oci://registry/repository@digest
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct OciReference {
+    pub(crate) registry: String,
+    pub(crate) repository: String,
+    /// tag or digest of the artifact
+    pub(crate) reference: String,
+}
+
+impl OciReference {
+    pub(crate) fn is_digest(&self) -> bool {
+        self.reference.contains(':')
+    }
+}
+
+impl TemplateLocation {
+    /// the repository and ref template.lock and the template cache know the source by
+    pub(crate) fn key(&self) -> (&str, &str) {
+        match self {
+            TemplateLocation::Git {
+                repository,
+                ref_name,
+            } => (repository.as_str(), ref_name.as_str()),
+            TemplateLocation::Directory { source, .. } => (source.as_str(), ""),
+            TemplateLocation::Archive { url, .. } => (url.as_str(), ""),
+            TemplateLocation::Oci { source, .. } => (source.as_str(), ""),
+        }
+    }
+}
+
+/// parses a template source: file:// folders, oci:// references, http(s) URLs of .tar.gz or .tgz
+/// archives and git remotes, which may be pinned to a branch or tag with a URL fragment. The
+/// sha256 only applies to archives
+pub(crate) fn parse_template_location(source: &str, sha256: &str) -> Result<TemplateLocation> {
+    let sha256 = sha256.trim().trim_start_matches("sha256:").to_lowercase();
+    if !sha256.is_empty() && (sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return Err(Error::Custom(format!(
+            "invalid sha256 {}, it must be 64 hexadecimal characters",
+            sha256
+        )));
+    }
+
+    let location = if let Some(path) = source.strip_prefix(FILE_SCHEME) {
+        if path.is_empty() {
+            return Err(Error::Custom(format!("no folder in {}", source)));
+        }
+        TemplateLocation::Directory {
+            source: source.to_string(),
+            path: PathBuf::from(path),
+        }
+    } else if let Some(reference) = source.strip_prefix(OCI_SCHEME) {
+        TemplateLocation::Oci {
+            source: source.to_string(),
+            reference: parse_oci_reference(reference)?,
+        }
+    } else if is_archive_url(source) {
+        TemplateLocation::Archive {
+            url: source.to_string(),
+            sha256: sha256.clone(),
+        }
+    } else if std::fs::metadata(source).is_ok()
+        || is_git_remote(source)
+        || is_pinned_git_remote(source)
+    {
+        let (repository, ref_name) = parse_panned_remote(source);
+        TemplateLocation::Git {
+            repository,
+            ref_name,
+        }
+    } else {
+        return Err(Error::Custom(format!(
+            "{} is not a valid template source, use a git repository URL, a file:// folder, the URL of a .tar.gz archive or an oci:// reference",
+            source
+        )));
+    };
+
+    if !sha256.is_empty() && !matches!(location, TemplateLocation::Archive { .. }) {
+        return Err(Error::Custom(format!(
+            "a sha256 can only be given for a .tar.gz archive, not for {}",
+            source
+        )));
+    }
+    Ok(location)
+}
+
+fn is_archive_url(source: &str) -> bool {
+    let lower = source.to_lowercase();
+    let path = lower.split(['?', '#']).next().unwrap_or_default();
+    (lower.starts_with("https://") || lower.starts_with("http://"))
+        && ARCHIVE_SUFFIXES.iter().any(|suffix| path.ends_with(suffix))
+}
+
+/// parses registry/repository:tag or registry/repository@digest, the tag defaults to latest
+pub(crate) fn parse_oci_reference(reference: &str) -> Result<OciReference> {
+    let invalid = || {
+        Error::Custom(format!(
+            "invalid OCI reference {}{}, expected {}registry/repository:tag",
+            OCI_SCHEME, reference, OCI_SCHEME
+        ))
+    };
+    let (name, reference_part) = match reference.rfind('@') {
+        Some(idx) => (&reference[..idx], reference[idx + 1..].to_string()),
+        None => {
+            let slash = reference.rfind('/').unwrap_or(0);
+            match reference[slash..].rfind(':') {
+                Some(idx) => (
+                    &reference[..slash + idx],
+                    reference[slash + idx + 1..].to_string(),
+                ),
+                None => (reference, "latest".to_string()),
+            }
+        }
+    };
+    let (registry, repository) = name.split_at(name.find('/').ok_or_else(invalid)?);
+    let repository = repository.trim_start_matches('/');
+    if registry.is_empty() || repository.is_empty() || reference_part.is_empty() {
+        return Err(invalid());
+    }
+    if let Some(digest) = reference_part.strip_prefix("sha256:") {
+        if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+    } else if reference_part.contains(':') {
+        return Err(invalid());
+    }
+    Ok(OciReference {
+        registry: registry.to_string(),
+        repository: repository.to_string(),
+        reference: reference_part,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template_location() {
+        let digest = "a".repeat(64);
+        let cases = vec![
+            (
+                "https://github.com/openfaas/templates.git#1.0",
+                "",
+                TemplateLocation::Git {
+                    repository: "https://github.com/openfaas/templates.git".to_string(),
+                    ref_name: "1.0".to_string(),
+                },
+            ),
+            (
+                "file:///src/templates",
+                "",
+                TemplateLocation::Directory {
+                    source: "file:///src/templates".to_string(),
+                    path: PathBuf::from("/src/templates"),
+                },
+            ),
+            (
+                "https://artifacts.example.com/templates.tar.gz?version=2",
+                digest.as_str(),
+                TemplateLocation::Archive {
+                    url: "https://artifacts.example.com/templates.tar.gz?version=2".to_string(),
+                    sha256: digest.clone(),
+                },
+            ),
+            (
+                "oci://registry.example.com:5000/team/templates:1.2",
+                "",
+                TemplateLocation::Oci {
+                    source: "oci://registry.example.com:5000/team/templates:1.2".to_string(),
+                    reference: OciReference {
+                        registry: "registry.example.com:5000".to_string(),
+                        repository: "team/templates".to_string(),
+                        reference: "1.2".to_string(),
+                    },
+                },
+            ),
+        ];
+        for (source, sha256, expected) in cases {
+            assert_eq!(parse_template_location(source, sha256).unwrap(), expected);
+        }
+
+        let pinned =
+            parse_oci_reference(format!("ghcr.io/team/templates@sha256:{}", digest).as_str())
+                .unwrap();
+        assert!(pinned.is_digest());
+        assert_eq!(pinned.repository, "team/templates");
+        assert_eq!(
+            parse_oci_reference("localhost:5000/templates")
+                .unwrap()
+                .reference,
+            "latest"
+        );
+
+        assert!(parse_oci_reference("templates:1.0").is_err());
+        assert!(parse_oci_reference("ghcr.io/templates@sha256:abc").is_err());
+        assert!(parse_template_location("not a source", "").is_err());
+        assert!(parse_template_location("https://example.com/t.tar.gz", "abc").is_err());
+        assert!(
+            parse_template_location("https://github.com/openfaas/templates", digest.as_str())
+                .is_err()
+        );
+    }
+}
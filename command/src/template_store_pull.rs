@@ -1,5 +1,6 @@
 use crate::priority::{get_template_store_url, TEMPLATE_STORE_URL_ENVIRONMENT};
 use crate::store::DEFAULT_STORE;
+use crate::template_lock::LockMode;
 use crate::template_pull::run_template_pull;
 use crate::template_store_list::{get_template_info, DEFAULT_TEMPLATE_STORE};
 use crate::{State, SubCommandAppend};
@@ -77,12 +78,14 @@ impl TemplateStorePull {
                     format!("{}/{}", store_template.source, store_template.template_name);
 
                 if template_name == store_template.template_name || template_name == source_name {
-                    run_template_pull("", overwrite, debug).map_err(|e| {
-                        State::Custom(format!(
-                            "error while pulling template: {} : {}",
-                            store_template.template_name, e
-                        ))
-                    })?;
+                    run_template_pull("", "", overwrite, debug, LockMode::Locked, false)
+                        .await
+                        .map_err(|e| {
+                            State::Custom(format!(
+                                "error while pulling template: {} : {}",
+                                store_template.template_name, e
+                            ))
+                        })?;
                     found = true;
                     break;
                 }
@@ -0,0 +1,687 @@
+use crate::{State, SubCommandAppend};
+use builder::build::{ADDITIONAL_PACKAGE_BUILD_ARGS, DEFAULT_HANDLER_FOLDER};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use stack::language_template::parse_yaml_for_language_template;
+use stack::schema::LanguageTemplate;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::path::{Component, Path, PathBuf};
+use utility::{Error, Result};
+
+const TEMPLATE_FILE: &str = "template.yml";
+const DOCKERFILE: &str = "Dockerfile";
+
+/// instructions a Dockerfile may hold
+const DOCKERFILE_INSTRUCTIONS: [&str; 18] = [
+    "ADD",
+    "ARG",
+    "CMD",
+    "COPY",
+    "ENTRYPOINT",
+    "ENV",
+    "EXPOSE",
+    "FROM",
+    "HEALTHCHECK",
+    "LABEL",
+    "MAINTAINER",
+    "ONBUILD",
+    "RUN",
+    "SHELL",
+    "STOPSIGNAL",
+    "USER",
+    "VOLUME",
+    "WORKDIR",
+];
+
+pub(crate) struct TemplateValidate;
+
+impl SubCommandAppend for TemplateValidate {
+    #[inline(always)]
+    fn append_subcommand() -> App<'static, 'static> {
+        let app = SubCommand::with_name("validate")
+            .about(
+                r#"Checks language templates for the mistakes that break builds: a missing or invalid template.yml,
+a bad handler_folder, a Dockerfile without ARG ADDITIONAL_PACKAGE or copying files the template
+doesn't have, and build_options that can't be installed. [DIR] is a template, a folder of templates
+or a repository with a template folder. Exits with a non-zero code when an error is found.
+	`,
+	Example: `
+  faas-cli template validate
+  faas-cli template validate ./template/node14
+  faas-cli template validate ./my-templates-repo
+`,"#,
+            )
+            .arg(
+                Arg::with_name("DIR")
+                    .help("template, folder of templates or repository to validate")
+                    .default_value(".")
+                    .index(1),
+            );
+        app
+    }
+}
+
+impl TemplateValidate {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if let Some(v_args) = args.subcommand_matches("validate") {
+            let dir = v_args.value_of("DIR").unwrap_or(".");
+
+            let templates = find_templates(Path::new(dir))?;
+            let mut errors = 0;
+            let mut warnings = 0;
+            for template in &templates {
+                for finding in validate_template(template).await {
+                    match finding.severity {
+                        Severity::Error => {
+                            errors += 1;
+                            colour::red!("{}\n", finding)
+                        }
+                        Severity::Warning => {
+                            warnings += 1;
+                            colour::yellow!("{}\n", finding)
+                        }
+                    }
+                }
+            }
+            println!(
+                "{} template(s) checked: {} error(s), {} warning(s)",
+                templates.len(),
+                errors,
+                warnings
+            );
+
+            if errors > 0 {
+                return Err(State::Custom(format!(
+                    "template validation failed with {} error(s)",
+                    errors
+                )));
+            }
+            Err(State::Matched)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+}
+
+/// Finding a problem of a template, the line is 0 when it is not about a line of the file
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Finding {
+    pub(crate) severity: Severity,
+    pub(crate) file: PathBuf,
+    pub(crate) line: usize,
+    pub(crate) message: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        if self.line > 0 {
+            write!(
+                f,
+                "{}:{}: {}: {}",
+                self.file.to_string_lossy(),
+                self.line,
+                severity,
+                self.message
+            )
+        } else {
+            write!(
+                f,
+                "{}: {}: {}",
+                self.file.to_string_lossy(),
+                severity,
+                self.message
+            )
+        }
+    }
+}
+
+/// returns the templates to validate: the folder itself when it holds a template.yml, otherwise
+/// the sub folders of its template folder, or its own sub folders
+pub(crate) fn find_templates(dir: &Path) -> Result<Vec<PathBuf>> {
+    if dir.join(TEMPLATE_FILE).is_file() {
+        return Ok(vec![dir.to_path_buf()]);
+    }
+    let parent = if dir.join("template").is_dir() {
+        dir.join("template")
+    } else {
+        dir.to_path_buf()
+    };
+    let mut templates: Vec<PathBuf> = std::fs::read_dir(&parent)
+        .map_err(|e| {
+            Error::Custom(format!(
+                "can't read templates from {}: {}",
+                parent.to_string_lossy(),
+                e
+            ))
+        })?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter(|path| {
+            !path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    templates.sort();
+    if templates.is_empty() {
+        return Err(Error::Custom(format!(
+            "no templates found in {}",
+            dir.to_string_lossy()
+        )));
+    }
+    Ok(templates)
+}
+
+/// checks template.yml and the Dockerfile of a template
+pub(crate) async fn validate_template(dir: &Path) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let template_file = dir.join(TEMPLATE_FILE);
+    let error = |file: &Path, line: usize, message: String| Finding {
+        severity: Severity::Error,
+        file: file.to_path_buf(),
+        line,
+        message,
+    };
+    let warning = |file: &Path, line: usize, message: String| Finding {
+        severity: Severity::Warning,
+        file: file.to_path_buf(),
+        line,
+        message,
+    };
+
+    let content = match std::fs::read_to_string(&template_file) {
+        Ok(content) => content,
+        Err(_) => {
+            findings.push(error(
+                dir,
+                0,
+                format!("{} is missing, the template can't be used", TEMPLATE_FILE),
+            ));
+            return findings;
+        }
+    };
+    let template = match parse_yaml_for_language_template(&template_file.to_string_lossy()).await {
+        Ok(template) => template,
+        Err(e) => {
+            // the YAML error tells the line
+            let (line, message) = match serde_yaml::from_str::<LanguageTemplate>(&content) {
+                Err(yaml_error) => (
+                    yaml_error.location().map_or(0, |location| location.line()),
+                    yaml_error.to_string(),
+                ),
+                Ok(_) => (0, e.to_string()),
+            };
+            findings.push(error(
+                &template_file,
+                line,
+                format!("invalid {}: {}", TEMPLATE_FILE, message),
+            ));
+            return findings;
+        }
+    };
+
+    let name = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if template.language.is_empty() {
+        findings.push(warning(
+            &template_file,
+            0,
+            "language is not set".to_string(),
+        ));
+    } else if template.language != name {
+        findings.push(warning(
+            &template_file,
+            line_of(content.as_str(), "language:"),
+            format!(
+                "language {} differs from the name of the template folder {}",
+                template.language, name
+            ),
+        ));
+    }
+
+    // the dockerfile template builds the Dockerfile of the handler
+    if template.language == "dockerfile" || name == "dockerfile" {
+        return findings;
+    }
+
+    if template.fprocess.as_deref().unwrap_or_default().is_empty() {
+        findings.push(warning(
+            &template_file,
+            0,
+            "fprocess is not set, functions built from the template must set it".to_string(),
+        ));
+    }
+
+    let handler_folder = if template.handler_folder.is_empty() {
+        DEFAULT_HANDLER_FOLDER.to_string()
+    } else {
+        template.handler_folder.trim_end_matches('/').to_string()
+    };
+    let handler_folder_line = line_of(content.as_str(), "handler_folder:");
+    let handler_folder_valid = !handler_folder.is_empty()
+        && Path::new(handler_folder.as_str())
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !handler_folder_valid {
+        findings.push(error(
+            &template_file,
+            handler_folder_line,
+            format!(
+                "handler_folder {} must be a relative path inside the template",
+                template.handler_folder
+            ),
+        ));
+    } else if dir.join(handler_folder.as_str()).exists()
+        && !dir.join(handler_folder.as_str()).is_dir()
+    {
+        findings.push(error(
+            &template_file,
+            handler_folder_line,
+            format!("handler_folder {} is a file, not a folder", handler_folder),
+        ));
+    }
+
+    let mut option_names = HashSet::new();
+    for option in &template.build_options {
+        let line = line_of(content.as_str(), format!("name: {}", option.name).as_str());
+        if option.name.is_empty() {
+            findings.push(error(
+                &template_file,
+                line,
+                "a build option has no name".to_string(),
+            ));
+        } else if !option_names.insert(option.name.as_str()) {
+            findings.push(error(
+                &template_file,
+                line,
+                format!("build option {} is defined more than once", option.name),
+            ));
+        }
+        if option.packages.is_empty() {
+            findings.push(warning(
+                &template_file,
+                line,
+                format!("build option {} has no packages", option.name),
+            ));
+        }
+        for package in &option.packages {
+            if !is_package_name(package) {
+                findings.push(error(
+                    &template_file,
+                    line,
+                    format!(
+                        "build option {} names {:?}, which is not a package name",
+                        option.name, package
+                    ),
+                ));
+            }
+        }
+    }
+
+    let dockerfile = dir.join(DOCKERFILE);
+    match std::fs::read_to_string(&dockerfile) {
+        Ok(content) => findings.extend(validate_dockerfile(
+            dir,
+            &dockerfile,
+            content.as_str(),
+            handler_folder.as_str(),
+            &template,
+        )),
+        Err(_) => findings.push(error(
+            dir,
+            0,
+            format!("{} is missing, the template can't be built", DOCKERFILE),
+        )),
+    }
+    findings
+}
+
+/// Instruction a Dockerfile instruction with the line it starts on, continuation lines joined
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Instruction {
+    pub(crate) line: usize,
+    pub(crate) keyword: String,
+    pub(crate) arguments: String,
+}
+
+/// splits a Dockerfile in instructions, comments and empty lines are left out
+pub(crate) fn parse_dockerfile(content: &str) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if current.is_none() && (trimmed.is_empty() || trimmed.starts_with('#')) {
+            continue;
+        }
+        // comments are allowed between continuation lines
+        if current.is_some() && trimmed.starts_with('#') {
+            continue;
+        }
+        let (start, mut text) = current.take().unwrap_or((index + 1, String::new()));
+        match trimmed.strip_suffix('\\') {
+            Some(part) => {
+                text.push_str(part);
+                text.push(' ');
+                current = Some((start, text));
+            }
+            None => {
+                text.push_str(trimmed);
+                instructions.push(instruction(start, text.as_str()));
+            }
+        }
+    }
+    if let Some((start, text)) = current {
+        instructions.push(instruction(start, text.as_str()));
+    }
+    instructions
+}
+
+fn instruction(line: usize, text: &str) -> Instruction {
+    let text = text.trim();
+    let (keyword, arguments) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    Instruction {
+        line,
+        keyword: keyword.to_uppercase(),
+        arguments: arguments.trim().to_string(),
+    }
+}
+
+fn validate_dockerfile(
+    dir: &Path,
+    dockerfile: &Path,
+    content: &str,
+    handler_folder: &str,
+    template: &LanguageTemplate,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut finding = |severity: Severity, line: usize, message: String| {
+        findings.push(Finding {
+            severity,
+            file: dockerfile.to_path_buf(),
+            line,
+            message,
+        })
+    };
+
+    let instructions = parse_dockerfile(content);
+    let mut has_from = false;
+    let mut additional_package_arg = false;
+    let mut additional_package_used = false;
+    let mut copies_handler = false;
+    for instruction in &instructions {
+        let keyword = instruction.keyword.as_str();
+        if !DOCKERFILE_INSTRUCTIONS.contains(&keyword) {
+            finding(
+                Severity::Error,
+                instruction.line,
+                format!("unknown instruction {}", instruction.keyword),
+            );
+            continue;
+        }
+        if !has_from && !matches!(keyword, "FROM" | "ARG") {
+            finding(
+                Severity::Error,
+                instruction.line,
+                format!("{} comes before the first FROM", keyword),
+            );
+        }
+        match keyword {
+            "FROM" => has_from = true,
+            "ARG" => {
+                let name = instruction.arguments.split('=').next().unwrap_or_default();
+                if name.trim() == ADDITIONAL_PACKAGE_BUILD_ARGS {
+                    additional_package_arg = true;
+                }
+            }
+            "RUN"
+                if instruction
+                    .arguments
+                    .contains(format!("${}", ADDITIONAL_PACKAGE_BUILD_ARGS).as_str())
+                    || instruction
+                        .arguments
+                        .contains(format!("${{{}}}", ADDITIONAL_PACKAGE_BUILD_ARGS).as_str()) =>
+            {
+                additional_package_used = true;
+            }
+            "COPY" | "ADD" => {
+                for source in copy_sources(instruction.arguments.as_str()) {
+                    let first = Path::new(source.as_str())
+                        .components()
+                        .find(|c| matches!(c, Component::Normal(_)))
+                        .map(|c| c.as_os_str().to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    if first.is_empty() || first == handler_folder {
+                        // the whole context or the handler, copied in at build time
+                        copies_handler = true;
+                        continue;
+                    }
+                    let skipped = source.contains(['*', '?', '[', '$']) || source.contains("://");
+                    if !skipped && !dir.join(source.as_str()).exists() {
+                        finding(
+                            Severity::Error,
+                            instruction.line,
+                            format!(
+                                "{} {} is not a file or folder of the template",
+                                keyword, source
+                            ),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !has_from {
+        finding(Severity::Error, 0, "no FROM instruction".to_string());
+    }
+    if !copies_handler {
+        finding(
+            Severity::Warning,
+            0,
+            format!(
+                "the handler folder {} is never copied, the function code won't be in the image",
+                handler_folder
+            ),
+        );
+    }
+    let option_severity = if template.build_options.is_empty() {
+        Severity::Warning
+    } else {
+        Severity::Error
+    };
+    if !additional_package_arg {
+        finding(
+            option_severity,
+            0,
+            format!(
+                "ARG {} is missing, build options and packages given at build time are ignored",
+                ADDITIONAL_PACKAGE_BUILD_ARGS
+            ),
+        );
+    } else if !additional_package_used {
+        finding(
+            option_severity,
+            line_of(content, ADDITIONAL_PACKAGE_BUILD_ARGS),
+            format!(
+                "{} is never used by a RUN instruction, its packages are not installed",
+                ADDITIONAL_PACKAGE_BUILD_ARGS
+            ),
+        );
+    }
+    findings
+}
+
+/// sources of a COPY or ADD instruction, in the shell or the JSON form. Copies from another
+/// stage or image are left out
+fn copy_sources(arguments: &str) -> Vec<String> {
+    let mut parts: Vec<String> = if arguments.trim_start().starts_with('[') {
+        serde_json::from_str::<Vec<String>>(arguments).unwrap_or_default()
+    } else {
+        arguments.split_whitespace().map(String::from).collect()
+    };
+    let mut flags = Vec::new();
+    while parts.first().is_some_and(|p| p.starts_with("--")) {
+        flags.push(parts.remove(0));
+    }
+    if flags.iter().any(|f| f.starts_with("--from")) || parts.len() < 2 {
+        return Vec::new();
+    }
+    parts.pop();
+    parts
+}
+
+/// package names of apt, apk, pip or npm, without spaces or shell characters
+fn is_package_name(package: &str) -> bool {
+    !package.is_empty()
+        && package
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '@')
+        && package
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.+:=~@/".contains(c))
+}
+
+/// first line holding the text, 0 when no line does
+fn line_of(content: &str, text: &str) -> usize {
+    content
+        .lines()
+        .position(|line| line.contains(text))
+        .map_or(0, |index| index + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    const TEMPLATE_YML: &str = "language: node14
+fprocess: node index.js
+build_options:
+  - name: dev
+    packages:
+      - make
+      - \"gcc; rm -rf /\"
+";
+
+    const DOCKERFILE_CONTENT: &str = "ARG NODE_VERSION=14
+FROM node:${NODE_VERSION}-alpine as build
+
+# packages of the build options
+RUN apk add --no-cache \\
+    curl
+COPY --from=build /usr/bin/fwatchdog /usr/bin/fwatchdog
+COPY package.json index.js ./
+COPY missing.js ./
+COPY function/ ./function
+INSTALL nothing
+CMD [\"fwatchdog\"]
+";
+
+    #[test]
+    fn test_is_package_name() {
+        for package in [
+            "curl",
+            "py3-pip",
+            "@types/node",
+            "requests==2.31.0",
+            "libc6:amd64",
+        ] {
+            assert!(is_package_name(package), "{}", package);
+        }
+        for package in [
+            "",
+            "-y",
+            "curl; rm -rf /",
+            "a b",
+            "pkg>out",
+            "pkg<in",
+            "$(id)",
+        ] {
+            assert!(!is_package_name(package), "{}", package);
+        }
+    }
+
+    #[test]
+    fn test_parse_dockerfile() {
+        let instructions = parse_dockerfile(DOCKERFILE_CONTENT);
+        assert_eq!(instructions.len(), 9);
+        assert_eq!(
+            instructions[2],
+            Instruction {
+                line: 5,
+                keyword: "RUN".to_string(),
+                arguments: "apk add --no-cache  curl".to_string(),
+            }
+        );
+        assert_eq!(instructions[7].line, 11);
+        assert_eq!(instructions[7].keyword, "INSTALL");
+    }
+
+    #[tokio::test]
+    async fn test_validate_template() {
+        let dir = TempDir::new("template-validate").unwrap();
+        let template = dir.path().join("template").join("node14");
+        std::fs::create_dir_all(&template).unwrap();
+        std::fs::write(template.join(TEMPLATE_FILE), TEMPLATE_YML).unwrap();
+        std::fs::write(template.join(DOCKERFILE), DOCKERFILE_CONTENT).unwrap();
+        std::fs::write(template.join("package.json"), "{}").unwrap();
+        std::fs::write(template.join("index.js"), "").unwrap();
+        let broken = dir.path().join("template").join("python3");
+        std::fs::create_dir_all(&broken).unwrap();
+        std::fs::write(
+            broken.join(TEMPLATE_FILE),
+            "language: python3\nfprocess: [\n",
+        )
+        .unwrap();
+
+        let templates = find_templates(dir.path()).unwrap();
+        assert_eq!(templates, vec![template.clone(), broken.clone()]);
+
+        let findings: Vec<String> = validate_template(&template)
+            .await
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let template_yml = template.join(TEMPLATE_FILE).to_string_lossy().to_string();
+        let dockerfile = template.join(DOCKERFILE).to_string_lossy().to_string();
+        assert_eq!(
+            findings,
+            vec![
+                format!(
+                    "{}:4: error: build option dev names \"gcc; rm -rf /\", which is not a package name",
+                    template_yml
+                ),
+                format!(
+                    "{}:9: error: COPY missing.js is not a file or folder of the template",
+                    dockerfile
+                ),
+                format!("{}:11: error: unknown instruction INSTALL", dockerfile),
+                format!(
+                    "{}: error: ARG ADDITIONAL_PACKAGE is missing, build options and packages given at build time are ignored",
+                    dockerfile
+                ),
+            ]
+        );
+
+        let findings = validate_template(&broken).await;
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Error);
+        assert_eq!(findings[0].line, 3);
+    }
+}
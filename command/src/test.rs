@@ -0,0 +1,666 @@
+use crate::error::check_tls_insecure;
+use crate::faas::{check_and_set_default_yaml, DEFAULT_GATEWAY};
+use crate::priority::{get_gateway_url, get_namespace, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
+use crate::test_report::{junit_report, CaseResult, Outcome};
+use crate::{CommandAppend, State};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use futures_util::stream::{self, StreamExt};
+use proxy::invoke::{invoke_function_response, FunctionRequest, FunctionResponse};
+use proxy::proxy::TlsOptions;
+use reqwest::Method;
+use serde_json::Value;
+use stack::schema::{FunctionTest, TestExpectation};
+use stack::stack::{parse_tests_file, parse_yaml_file};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use utility::{Error, Result};
+
+/// file read for the tests when it is next to the YAML file and --tests is not given
+pub(crate) const DEFAULT_TESTS_FILE: &str = "tests.yml";
+/// longest part of a body quoted in a failure message
+const BODY_PREVIEW_LEN: usize = 120;
+
+pub(crate) struct Test;
+
+impl CommandAppend for Test {
+    #[inline(always)]
+    fn append_subcommand(app: App<'static, 'static>) -> App<'static, 'static> {
+        let app = app.subcommand(
+            SubCommand::with_name("test")
+                .about(r#"Send the requests listed in the tests of the functions to the deployed functions and
+check their responses, or to a function started by local-run with --local. The tests are read from the tests section of each function in the YAML
+config and from the tests file, tests.yml next to the YAML config by default. Exits with a
+non-zero code when a test fails.`,
+	Example: `  faas-cli test
+  faas-cli test -f ./stack.yml --filter "*gif*"
+  faas-cli test --tests ./e2e.yml --parallel 8
+  faas-cli test --filter figlet --local 8081
+  faas-cli test --report ./junit.xml`"#)
+                .args_from_usage("
+                   --tests [tests]                'Tests file holding the tests of the functions by function name'
+                   --report [report]              'Write a JUnit XML report of the results to this file'
+                   --local [port]                 'Send the requests to the function started by local-run on this port'
+                   --tls-no-verify                'Disable TLS validation'
+                   -n, --namespace [namespace]    'Namespace of the functions'
+                ")
+                .arg(
+                    Arg::with_name("parallel")
+                        .long("parallel")
+                        .default_value("4")
+                        .takes_value(true)
+                        .help("Number of tests running at the same time"),
+                ),
+        );
+
+        app
+    }
+}
+
+/// TestCase a test with the function it is sent to
+#[derive(Debug, Clone)]
+pub(crate) struct TestCase {
+    pub(crate) function: String,
+    pub(crate) namespace: String,
+    pub(crate) test: FunctionTest,
+    /// directory of the file the test was read from, body_file is relative to it
+    pub(crate) base_dir: PathBuf,
+}
+
+impl Test {
+    #[inline(always)]
+    pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
+        if let Some(t_args) = args.subcommand_matches("test") {
+            let gateway = t_args.value_of("gateway").unwrap_or(DEFAULT_GATEWAY);
+            let function_namespace = t_args.value_of("namespace").unwrap_or_default();
+            let tls_no_verify = t_args.is_present("tls-no-verify");
+            let regex = t_args.value_of("regex").unwrap_or_default();
+            let filter = t_args.value_of("filter").unwrap_or_default();
+            let report = t_args.value_of("report").unwrap_or_default();
+            let local_port: Option<u16> = t_args
+                .value_of("local")
+                .map(|port| {
+                    port.parse().map_err(|_| {
+                        State::Custom("the --local flag must be a port number".to_string())
+                    })
+                })
+                .transpose()?;
+            let envsubst = true;
+
+            let parallel: usize = t_args
+                .value_of("parallel")
+                .unwrap_or("4")
+                .parse()
+                .ok()
+                .filter(|p| *p > 0)
+                .ok_or_else(|| {
+                    State::Custom("--parallel must be a number greater than 0".to_string())
+                })?;
+
+            let yaml_file = t_args
+                .value_of("yaml")
+                .unwrap_or(check_and_set_default_yaml().unwrap_or_default());
+            if yaml_file.is_empty() {
+                return Err(State::Custom(
+                    "you must supply a valid YAML file with -f".to_string(),
+                ));
+            }
+            let services = parse_yaml_file(yaml_file, regex, filter, envsubst).await?;
+
+            let tests_file = match t_args.value_of("tests") {
+                Some(tests_file) => Some(tests_file.to_string()),
+                None => {
+                    let default = Path::new(yaml_file)
+                        .parent()
+                        .unwrap_or_else(|| Path::new(""))
+                        .join(DEFAULT_TESTS_FILE);
+                    if default.is_file() {
+                        Some(default.to_string_lossy().to_string())
+                    } else {
+                        None
+                    }
+                }
+            };
+            let mut file_tests = match &tests_file {
+                Some(tests_file) => parse_tests_file(tests_file.as_str())?.functions,
+                None => Default::default(),
+            };
+            let yaml_dir = parent_dir(yaml_file);
+            let tests_dir = tests_file.as_deref().map(parent_dir).unwrap_or_default();
+
+            let mut names: Vec<&String> = services.functions.keys().collect();
+            names.sort();
+            let mut cases = Vec::new();
+            for name in names {
+                let function = &services.functions[name];
+                let namespace = get_namespace(function_namespace, function.namespace.as_str());
+                let tests = function
+                    .tests
+                    .iter()
+                    .map(|test| (test.clone(), &yaml_dir))
+                    .chain(
+                        file_tests
+                            .remove(name.as_str())
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|test| (test, &tests_dir)),
+                    );
+                for (test, base_dir) in tests {
+                    cases.push(TestCase {
+                        function: name.clone(),
+                        namespace: namespace.clone(),
+                        test,
+                        base_dir: base_dir.clone(),
+                    });
+                }
+            }
+            // with --filter or --regex the functions left out are expected to have tests
+            if regex.is_empty() && filter.is_empty() {
+                let mut unknown: Vec<String> = file_tests.into_keys().collect();
+                unknown.sort();
+                for name in unknown {
+                    colour::yellow!(
+                        "Skipping the tests of {}, it is not a function of {}\n",
+                        name,
+                        yaml_file
+                    );
+                }
+            }
+            if cases.is_empty() {
+                return Err(State::Custom(format!(
+                    "no tests found for the functions of {}",
+                    yaml_file
+                )));
+            }
+
+            let (gateway_address, local) = match local_port {
+                Some(port) => {
+                    // local-run serves a single function, its tests are the only ones sent
+                    let mut functions: Vec<&str> =
+                        cases.iter().map(|case| case.function.as_str()).collect();
+                    functions.dedup();
+                    if functions.len() > 1 {
+                        return Err(State::Custom(format!(
+                            "--local sends the tests of a single function, select one of {} with --filter",
+                            functions.join(", ")
+                        )));
+                    }
+                    (format!("http://127.0.0.1:{}", port), true)
+                }
+                None => {
+                    let openfaas_url = std::env::var(OPENFAAS_URL_ENVIRONMENT).unwrap_or_default();
+                    let gateway_address = get_gateway_url(
+                        gateway,
+                        DEFAULT_GATEWAY,
+                        services.provider.gateway_url.as_str(),
+                        openfaas_url.as_str(),
+                    );
+                    let msg = check_tls_insecure(gateway_address.as_str(), tls_no_verify);
+                    if !msg.is_empty() {
+                        colour::yellow!("{}\n", msg);
+                    }
+                    (gateway_address, false)
+                }
+            };
+            let tls = get_tls_options(t_args, gateway_address.as_str());
+
+            let results = run_cases(gateway_address.as_str(), local, &tls, cases, parallel).await;
+
+            let failed = results
+                .iter()
+                .filter(|r| r.outcome != Outcome::Passed)
+                .count();
+            println!(
+                "\n{} passed, {} failed, {} total",
+                results.len() - failed,
+                failed,
+                results.len()
+            );
+
+            if !report.is_empty() {
+                std::fs::write(report, junit_report(&results)).map_err(|e| {
+                    State::Custom(format!("can't write the report {}: {}", report, e))
+                })?;
+                println!("Wrote the JUnit report to {}", report);
+            }
+
+            if failed > 0 {
+                return Err(State::Custom(format!(
+                    "{} of {} tests failed",
+                    failed,
+                    results.len()
+                )));
+            }
+            Err(State::Matched)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// directory holding the file, the current directory for a bare file name
+fn parent_dir(file: &str) -> PathBuf {
+    Path::new(file)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf()
+}
+
+/// runs up to `parallel` tests at the same time, the results are printed and returned in the
+/// order of the cases
+async fn run_cases(
+    gateway: &str,
+    local: bool,
+    tls: &TlsOptions,
+    cases: Vec<TestCase>,
+    parallel: usize,
+) -> Vec<CaseResult> {
+    let mut running = stream::iter(cases)
+        .map(|case| async move { run_case(gateway, local, tls, case).await })
+        .buffered(parallel);
+
+    let mut results = Vec::new();
+    while let Some(result) = running.next().await {
+        let millis = result.duration.as_millis();
+        match &result.outcome {
+            Outcome::Passed => {
+                colour::green!("PASS {}: {} ({}ms)\n", result.function, result.name, millis)
+            }
+            Outcome::Failed(messages) => {
+                colour::red!("FAIL {}: {} ({}ms)\n", result.function, result.name, millis);
+                for message in messages {
+                    println!("    {}", message);
+                }
+            }
+            Outcome::Error(message) => {
+                colour::red!(
+                    "ERROR {}: {} ({}ms)\n",
+                    result.function,
+                    result.name,
+                    millis
+                );
+                println!("    {}", message);
+            }
+        }
+        results.push(result);
+    }
+    results
+}
+
+async fn run_case(gateway: &str, local: bool, tls: &TlsOptions, case: TestCase) -> CaseResult {
+    let started = Instant::now();
+    let outcome = match send_request(gateway, local, tls, &case).await {
+        Ok(response) => {
+            let failures = check_expectation(&case.test.expect, &response);
+            if failures.is_empty() {
+                Outcome::Passed
+            } else {
+                Outcome::Failed(failures)
+            }
+        }
+        Err(e) => Outcome::Error(e.to_string()),
+    };
+    CaseResult {
+        function: case.function,
+        name: case.test.name,
+        duration: started.elapsed(),
+        outcome,
+    }
+}
+
+async fn send_request(
+    gateway: &str,
+    local: bool,
+    tls: &TlsOptions,
+    case: &TestCase,
+) -> Result<FunctionResponse> {
+    let request = &case.test.request;
+    let body = match (&request.body, request.body_file.is_empty()) {
+        (Some(_), false) => {
+            return Err(Error::Custom(
+                "set either body or body_file in the request, not both".to_string(),
+            ))
+        }
+        (Some(body), true) => body.as_bytes().to_vec(),
+        (None, false) => {
+            let body_file = case.base_dir.join(request.body_file.as_str());
+            std::fs::read(&body_file).map_err(|e| {
+                Error::Custom(format!(
+                    "can't read body_file {}: {}",
+                    body_file.display(),
+                    e
+                ))
+            })?
+        }
+        (None, true) => Vec::new(),
+    };
+
+    let method = match request.method.as_str() {
+        "" if body.is_empty() => "GET".to_string(),
+        "" => "POST".to_string(),
+        method => method.to_uppercase(),
+    };
+    let method = Method::from_bytes(method.as_bytes())
+        .map_err(|_e| Error::Custom(format!("invalid method {}", method)))?;
+
+    let query: Vec<(String, String)> = request
+        .query
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+
+    let mut content_type = "text/plain";
+    let mut headers = Vec::new();
+    for (key, value) in &request.headers {
+        if key.eq_ignore_ascii_case("content-type") {
+            content_type = value.as_str();
+        } else {
+            headers.push(format!("{}={}", key, value));
+        }
+    }
+    let headers: Vec<&str> = headers.iter().map(String::as_str).collect();
+
+    invoke_function_response(
+        gateway,
+        &FunctionRequest {
+            name: case.function.as_str(),
+            namespace: case.namespace.as_str(),
+            path: request.path.as_str(),
+            method,
+            query: &query,
+            headers: &headers,
+            content_type,
+            body: &body,
+            is_async: false,
+            local,
+        },
+        tls,
+    )
+    .await
+}
+
+/// returns the expectations the response does not meet, a test without a status expects a 2xx
+pub(crate) fn check_expectation(
+    expect: &TestExpectation,
+    response: &FunctionResponse,
+) -> Vec<String> {
+    let mut failures = Vec::new();
+    let status = response.status.as_u16();
+    match expect.status {
+        Some(expected) if expected != status => {
+            failures.push(format!("expected status {} but got {}", expected, status))
+        }
+        None if !response.status.is_success() => {
+            failures.push(format!("expected a 2xx status but got {}", status))
+        }
+        _ => {}
+    }
+
+    for (name, expected) in &expect.headers {
+        match response.headers.get(&name.to_lowercase()) {
+            Some(value) if value == expected => {}
+            Some(value) => failures.push(format!(
+                "expected header {} to be {:?} but got {:?}",
+                name, expected, value
+            )),
+            None => failures.push(format!("expected header {} but it is missing", name)),
+        }
+    }
+
+    if let Some(expected) = &expect.body {
+        if *expected != response.body {
+            failures.push(format!(
+                "expected body {:?} but got {:?}",
+                preview(expected),
+                preview(response.body.as_str())
+            ));
+        }
+    }
+
+    if !expect.regex.is_empty() {
+        match regex::Regex::new(expect.regex.as_str()) {
+            Ok(regex) if regex.is_match(response.body.as_str()) => {}
+            Ok(_) => failures.push(format!(
+                "expected body to match {:?} but got {:?}",
+                expect.regex,
+                preview(response.body.as_str())
+            )),
+            Err(e) => failures.push(format!("invalid regex {:?}: {}", expect.regex, e)),
+        }
+    }
+
+    if !expect.json_path.is_empty() {
+        match serde_json::from_str::<Value>(response.body.as_str()) {
+            Ok(json) => {
+                for (path, expected) in &expect.json_path {
+                    if let Err(failure) = check_json_path(&json, path.as_str(), expected) {
+                        failures.push(failure);
+                    }
+                }
+            }
+            Err(e) => failures.push(format!(
+                "expected a JSON body but got {:?}: {}",
+                preview(response.body.as_str()),
+                e
+            )),
+        }
+    }
+    failures
+}
+
+fn check_json_path(
+    json: &Value,
+    path: &str,
+    expected: &serde_yaml::Value,
+) -> std::result::Result<(), String> {
+    let expected = serde_json::to_value(expected)
+        .map_err(|e| format!("can't compare {} to its expected value: {}", path, e))?;
+    let segments = parse_json_path(path)?;
+    match select_json_path(json, &segments) {
+        Some(value) if *value == expected => Ok(()),
+        Some(value) => Err(format!(
+            "expected {} to be {} but got {}",
+            path, expected, value
+        )),
+        None => Err(format!(
+            "expected {} to be {} but it is missing",
+            path, expected
+        )),
+    }
+}
+
+/// a step of a JSON path: the field of an object or the element of an array
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// parses the JSON paths of the form $.items[0].name or $['content-type'], the leading $ can be
+/// left out
+pub(crate) fn parse_json_path(path: &str) -> std::result::Result<Vec<PathSegment>, String> {
+    let invalid = || format!("invalid JSON path {}", path);
+    let normalized = match path.trim() {
+        p if p.starts_with('$') => p.to_string(),
+        p if p.starts_with('[') => format!("${}", p),
+        p => format!("$.{}", p),
+    };
+
+    let mut segments = Vec::new();
+    let mut rest = &normalized[1..];
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            segments.push(PathSegment::Key(after[..end].to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            let inner = after[..end].trim();
+            let quoted = inner.len() >= 2
+                && ((inner.starts_with('\'') && inner.ends_with('\''))
+                    || (inner.starts_with('"') && inner.ends_with('"')));
+            if quoted {
+                segments.push(PathSegment::Key(inner[1..inner.len() - 1].to_string()));
+            } else {
+                segments.push(PathSegment::Index(inner.parse().map_err(|_e| invalid())?));
+            }
+            rest = &after[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(segments)
+}
+
+pub(crate) fn select_json_path<'v>(json: &'v Value, segments: &[PathSegment]) -> Option<&'v Value> {
+    segments
+        .iter()
+        .try_fold(json, |value, segment| match segment {
+            PathSegment::Key(key) => value.get(key.as_str()),
+            PathSegment::Index(index) => value.get(*index),
+        })
+}
+
+fn preview(body: &str) -> String {
+    match body.char_indices().nth(BODY_PREVIEW_LEN) {
+        Some((end, _)) => format!("{}...", &body[..end]),
+        None => body.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+    use std::collections::HashMap;
+
+    fn response(status: u16, body: &str) -> FunctionResponse {
+        let mut headers = HashMap::new();
+        headers.insert("content-type".to_string(), "application/json".to_string());
+        FunctionResponse {
+            status: StatusCode::from_u16(status).unwrap(),
+            headers,
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_json_path() {
+        assert_eq!(
+            parse_json_path("$.items[0]['first-name']").unwrap(),
+            vec![
+                PathSegment::Key("items".to_string()),
+                PathSegment::Index(0),
+                PathSegment::Key("first-name".to_string())
+            ]
+        );
+        assert_eq!(
+            parse_json_path("status").unwrap(),
+            vec![PathSegment::Key("status".to_string())]
+        );
+        assert_eq!(parse_json_path("$").unwrap(), vec![]);
+        assert!(parse_json_path("$..items").is_err());
+        assert!(parse_json_path("$.items[first]").is_err());
+        assert!(parse_json_path("$.items[0").is_err());
+    }
+
+    #[test]
+    fn test_check_expectation() {
+        let body = r#"{"status":"ok","items":[{"id":1,"tags":["a"]}]}"#;
+        let mut expect = TestExpectation {
+            status: Some(200),
+            regex: "\"status\":\"ok\"".to_string(),
+            ..Default::default()
+        };
+        expect
+            .headers
+            .insert("Content-Type".to_string(), "application/json".to_string());
+        expect
+            .json_path
+            .insert("$.items[0].id".to_string(), serde_yaml::Value::from(1));
+        expect.json_path.insert(
+            "$.items[0].tags".to_string(),
+            serde_yaml::from_str("[a]").unwrap(),
+        );
+        assert!(check_expectation(&expect, &response(200, body)).is_empty());
+
+        expect.body = Some("ok".to_string());
+        expect.headers.insert("X-Id".to_string(), "1".to_string());
+        expect
+            .json_path
+            .insert("$.status".to_string(), serde_yaml::Value::from("failed"));
+        expect
+            .json_path
+            .insert("$.items[1].id".to_string(), serde_yaml::Value::from(2));
+        assert_eq!(
+            check_expectation(&expect, &response(500, body)),
+            vec![
+                "expected status 200 but got 500".to_string(),
+                "expected header X-Id but it is missing".to_string(),
+                format!("expected body \"ok\" but got {:?}", body),
+                "expected $.items[1].id to be 2 but it is missing".to_string(),
+                "expected $.status to be \"failed\" but got \"ok\"".to_string(),
+            ]
+        );
+
+        // without a status any 2xx passes
+        let expect = TestExpectation::default();
+        assert!(check_expectation(&expect, &response(204, "")).is_empty());
+        assert_eq!(
+            check_expectation(&expect, &response(404, "")),
+            vec!["expected a 2xx status but got 404".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_request() {
+        let base_dir = std::env::temp_dir().join("faas-cli-test-send-request");
+        std::fs::create_dir_all(&base_dir).unwrap();
+        std::fs::write(base_dir.join("body.json"), "{\"name\":\"a\"}").unwrap();
+
+        let _mok = mockito::mock("POST", "/users")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("q".into(), "a b".into()),
+                mockito::Matcher::UrlEncoded("empty".into(), "".into()),
+            ]))
+            .match_header("content-type", "application/json")
+            .match_body("{\"name\":\"a\"}")
+            .with_status(201)
+            .create();
+
+        let mut test = FunctionTest::default();
+        test.request.path = "users".to_string();
+        test.request.body_file = "body.json".to_string();
+        test.request
+            .query
+            .insert("q".to_string(), "a b".to_string());
+        test.request
+            .query
+            .insert("empty".to_string(), String::new());
+        test.request
+            .headers
+            .insert("Content-Type".to_string(), "application/json".to_string());
+        let case = TestCase {
+            function: "users".to_string(),
+            namespace: String::new(),
+            test,
+            base_dir: base_dir.clone(),
+        };
+        let gateway = format!("http://{}", mockito::server_address());
+        let response = send_request(gateway.as_str(), true, &TlsOptions::default(), &case)
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&base_dir).unwrap();
+
+        assert_eq!(response.status, StatusCode::CREATED);
+    }
+
+    #[test]
+    fn test_parent_dir() {
+        assert_eq!(parent_dir("stack.yml"), PathBuf::from("."));
+        assert_eq!(parent_dir("fns/stack.yml"), PathBuf::from("fns"));
+    }
+}
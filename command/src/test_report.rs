@@ -0,0 +1,171 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Outcome of a test: the expectations that were not met, or the error that kept the request
+/// from being answered
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Outcome {
+    Passed,
+    Failed(Vec<String>),
+    Error(String),
+}
+
+/// CaseResult the outcome of one test of a function
+#[derive(Debug, Clone)]
+pub(crate) struct CaseResult {
+    pub(crate) function: String,
+    pub(crate) name: String,
+    pub(crate) duration: Duration,
+    pub(crate) outcome: Outcome,
+}
+
+/// renders the results as a JUnit XML report, with one test suite per function in the order the
+/// functions first appear in the results
+pub(crate) fn junit_report(results: &[CaseResult]) -> String {
+    let mut suites: Vec<(&str, Vec<&CaseResult>)> = Vec::new();
+    for result in results {
+        match suites
+            .iter_mut()
+            .find(|(function, _)| *function == result.function)
+        {
+            Some((_, cases)) => cases.push(result),
+            None => suites.push((result.function.as_str(), vec![result])),
+        }
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let all: Vec<&CaseResult> = results.iter().collect();
+    let _ = writeln!(xml, "<testsuites name=\"faas-cli test\" {}>", counts(&all));
+    for (function, cases) in &suites {
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" {}>",
+            escape_xml(function),
+            counts(cases)
+        );
+        for case in cases {
+            let _ = write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\"",
+                escape_xml(case.name.as_str()),
+                escape_xml(case.function.as_str()),
+                seconds(case.duration)
+            );
+            match &case.outcome {
+                Outcome::Passed => xml.push_str("/>\n"),
+                Outcome::Failed(messages) => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <failure message=\"{}\" type=\"assertion\">{}</failure>\n    </testcase>",
+                        escape_xml(messages.first().map(String::as_str).unwrap_or_default()),
+                        escape_xml(messages.join("\n").as_str())
+                    );
+                }
+                Outcome::Error(message) => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <error message=\"{}\" type=\"invocation\"/>\n    </testcase>",
+                        escape_xml(message)
+                    );
+                }
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn counts(cases: &[&CaseResult]) -> String {
+    let failures = cases
+        .iter()
+        .filter(|c| matches!(c.outcome, Outcome::Failed(_)))
+        .count();
+    let errors = cases
+        .iter()
+        .filter(|c| matches!(c.outcome, Outcome::Error(_)))
+        .count();
+    let duration = cases.iter().map(|c| c.duration).sum();
+    format!(
+        "tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{}\"",
+        cases.len(),
+        failures,
+        errors,
+        seconds(duration)
+    )
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64())
+}
+
+/// escapes the markup characters, control characters XML can't hold are dropped
+pub(crate) fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_junit_report() {
+        let case = |function: &str, name: &str, millis: u64, outcome: Outcome| CaseResult {
+            function: function.to_string(),
+            name: name.to_string(),
+            duration: Duration::from_millis(millis),
+            outcome,
+        };
+        let results = vec![
+            case("echo", "says <hello>", 12, Outcome::Passed),
+            case(
+                "env",
+                "reads the header",
+                250,
+                Outcome::Error("cannot connect to OpenFaaS on URL: http://gw".to_string()),
+            ),
+            case(
+                "echo",
+                "status & body",
+                1500,
+                Outcome::Failed(vec![
+                    "expected status 200 but got 500".to_string(),
+                    "expected body \"ok\" but got \"\u{1b}fail\"".to_string(),
+                ]),
+            ),
+        ];
+
+        assert_eq!(
+            junit_report(&results),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="faas-cli test" tests="3" failures="1" errors="1" time="1.762">
+  <testsuite name="echo" tests="2" failures="1" errors="0" time="1.512">
+    <testcase name="says &lt;hello&gt;" classname="echo" time="0.012"/>
+    <testcase name="status &amp; body" classname="echo" time="1.500">
+      <failure message="expected status 200 but got 500" type="assertion">expected status 200 but got 500
+expected body &quot;ok&quot; but got &quot;fail&quot;</failure>
+    </testcase>
+  </testsuite>
+  <testsuite name="env" tests="1" failures="0" errors="1" time="0.250">
+    <testcase name="reads the header" classname="env" time="0.250">
+      <error message="cannot connect to OpenFaaS on URL: http://gw" type="invocation"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
@@ -1,8 +1,21 @@
 use crate::build::{build_from_args, generate_build_args};
+use crate::cmds::build_cli;
 use crate::deploy::deploy_from_args;
+use crate::faas::check_and_set_default_yaml;
+use crate::logs::Logs;
 use crate::push::push_from_args;
+use crate::ready::Ready;
+use crate::watch::{args_for_functions, functions_regex, Watcher, DEBOUNCE, POLL_INTERVAL};
 use crate::{CommandAppend, State};
+use builder::ignore_file::project_root;
 use clap::{App, Arg, ArgMatches, SubCommand};
+use stack::stack::parse_yaml_file;
+use std::future::Future;
+use std::pin::Pin;
+use utility::{Error, Result};
+
+/// number of recent log lines printed when up --watch starts tailing the logs of a deployment
+const WATCH_LOG_LINES: usize = 20;
 
 pub(crate) struct Up;
 
@@ -18,15 +31,21 @@ definitions), or directly via flags.
 The push step may be skipped by setting the --skip-push flag
 and the deploy step with --skip-deploy.
 
+With --watch the handlers and extra paths of the functions are watched, and the
+functions whose files change are built, pushed and deployed again.
+
 Note: All flags from the build, push and deploy flags are valid and can be combined,
 see the --help text for those commands for details.`,
 	Example: `  faas-cli up -f myfn.yaml
-faas-cli up --filter "*gif*" --secret dockerhuborg`"#,
+faas-cli up --filter "*gif*" --secret dockerhuborg
+faas-cli up --watch --logs`"#,
             )
             .args_from_usage(
                 "
         --skip-push                   'Skip pushing function to remote registry'
         --skip-deploy                 'Skip function deployment'
+        --watch                       'Watch the handlers and rebuild, push and deploy the functions that change'
+        --logs                        'With --watch, tail the logs of the functions after they are deployed'
 
          --network [network]               'Name of the network'
           -n ,--namespace [namespace]       'Namespace of the function'
@@ -90,21 +109,248 @@ impl Up {
     #[inline(always)]
     pub(crate) async fn dispatch_command(args: &ArgMatches<'_>) -> crate::Result {
         if let Some(u_args) = args.subcommand_matches("up") {
-            build_from_args(u_args).await?;
-            println!();
-
-            if !u_args.is_present("skip-push") {
-                push_from_args(u_args).await?;
-                println!();
+            if u_args.is_present("logs") && !u_args.is_present("watch") {
+                return Err(State::Custom(
+                    "the --logs flag requires --watch".to_string(),
+                ));
             }
-            if !u_args.is_present("skip-deploy") {
-                deploy_from_args(u_args).await?;
-                println!();
+            if !u_args.is_present("watch") {
+                up(u_args).await?;
+                return Err(State::Matched);
             }
 
+            // a broken function must not end the inner dev loop
+            if let Err(e) = up(u_args).await {
+                colour::red!("{}\n", e);
+            }
+            watch(u_args).await?;
             Err(State::Matched)
         } else {
             Ok(())
         }
     }
 }
+
+async fn up(u_args: &ArgMatches<'_>) -> Result<()> {
+    let keep_going = u_args.is_present("keep-going");
+    // with --keep-going the functions failing a step are left out of the next steps and the
+    // errors are returned at the end
+    let mut failed = Vec::new();
+    let mut errors = Vec::new();
+    let built = build_from_args(u_args, &mut failed).await;
+    keep_error(built, keep_going && !failed.is_empty(), &mut errors)?;
+    println!();
+
+    if !u_args.is_present("skip-push") {
+        let failed_before = failed.len();
+        let pushed = push_from_args(u_args, &mut failed).await;
+        keep_error(
+            pushed,
+            keep_going && failed.len() > failed_before,
+            &mut errors,
+        )?;
+        println!();
+    }
+    if !u_args.is_present("skip-deploy") {
+        let deployed = deploy_from_args(u_args, &failed).await;
+        keep_error(deployed, keep_going, &mut errors)?;
+        println!();
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Custom(errors.join("\n")))
+    }
+}
+
+/// returns the error of a step, or keeps it for the end when keep is set
+fn keep_error(result: Result<()>, keep: bool, errors: &mut Vec<String>) -> Result<()> {
+    match result {
+        Err(e) if keep => {
+            errors.push(e.to_string());
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// watches the functions of the stack and runs up again for the ones whose files change, until
+/// the CLI is interrupted
+async fn watch(u_args: &ArgMatches<'_>) -> Result<()> {
+    let yaml_file = u_args
+        .value_of("yaml")
+        .unwrap_or(check_and_set_default_yaml().unwrap_or_default());
+    if yaml_file.is_empty() {
+        return Err(Error::Custom(
+            "the --watch flag requires a YAML file, use --yaml (-f) YAML_FILE".to_string(),
+        ));
+    }
+    let regex = u_args.value_of("regex").unwrap_or_default();
+    let filter = u_args.value_of("filter").unwrap_or_default();
+    let services = parse_yaml_file(yaml_file, regex, filter, true).await?;
+
+    let mut extra_paths = services.stack_configuration.copy_extra_paths.clone();
+    extra_paths.extend(
+        u_args
+            .values_of("copy-extra")
+            .unwrap_or_default()
+            .map(|p| p.to_string()),
+    );
+    let functions: Vec<(String, String, Vec<String>)> = services
+        .functions
+        .into_iter()
+        .filter(|(_, function)| !function.skip_build)
+        .map(|(name, function)| (name, function.handler, extra_paths.clone()))
+        .collect();
+    let mut watcher = Watcher::new(functions, project_root(yaml_file).as_str())?;
+
+    let command_line: Vec<String> = std::env::args().collect();
+    let tail_logs = u_args.is_present("logs") && !u_args.is_present("skip-deploy");
+    let mut logs: Option<Pin<Box<dyn Future<Output = ()>>>> = None;
+    loop {
+        colour::blue!("Watching for changes, press Control+C to stop.\n");
+        let changed = loop {
+            match logs.as_mut() {
+                Some(tail) => {
+                    tokio::select! {
+                        changed = watcher.wait_for_changes(POLL_INTERVAL, DEBOUNCE) => break changed,
+                        _ = tail => logs = None,
+                    }
+                }
+                None => break watcher.wait_for_changes(POLL_INTERVAL, DEBOUNCE).await,
+            }
+        };
+        // stops tailing the logs of the previous deployment
+        logs = None;
+        colour::blue!("Changes in: {}\n", changed.join(", "));
+
+        let matches = build_cli()
+            .get_matches_from_safe(args_for_functions(&command_line, &changed))
+            .map_err(|e| Error::Custom(e.to_string()))?;
+        let rerun_args = matches.subcommand_matches("up").ok_or_else(|| {
+            Error::Custom("can't read the up command to run it again".to_string())
+        })?;
+        if let Err(e) = up(rerun_args).await {
+            colour::red!("{}\n", e);
+            continue;
+        }
+        if u_args.is_present("skip-deploy") {
+            continue;
+        }
+
+        let ready_args = function_command_args("ready", u_args, yaml_file, &changed);
+        if let Err(e) = run_command(ready_args, |m| Box::pin(Ready::dispatch_command(m))).await {
+            colour::red!("{}\n", e);
+        }
+        if tail_logs {
+            let mut logs_args = function_command_args("logs", u_args, yaml_file, &changed);
+            logs_args.push("--tail".to_string());
+            // the recent lines of the deployment, not every line the functions ever logged
+            logs_args.push("--lines".to_string());
+            logs_args.push(WATCH_LOG_LINES.to_string());
+            logs = Some(Box::pin(async move {
+                if let Err(e) =
+                    run_command(logs_args, |m| Box::pin(Logs::dispatch_command(m))).await
+                {
+                    colour::red!("{}\n", e);
+                }
+            }));
+        }
+    }
+}
+
+/// the command line of another command for the changed functions, with the gateway, namespace,
+/// credentials and TLS settings of up
+fn function_command_args(
+    command: &str,
+    u_args: &ArgMatches,
+    yaml_file: &str,
+    functions: &[String],
+) -> Vec<String> {
+    let mut args = vec![
+        "faas".to_string(),
+        command.to_string(),
+        "--yaml".to_string(),
+        yaml_file.to_string(),
+        "--regex".to_string(),
+        functions_regex(functions),
+    ];
+    for (name, flag) in &[
+        ("gateway", "--gateway"),
+        ("namespace", "--namespace"),
+        ("token", "--token"),
+        ("ca-file", "--ca-file"),
+        ("tls-cert", "--tls-cert"),
+        ("tls-key", "--tls-key"),
+    ] {
+        if let Some(value) = u_args.value_of(name) {
+            args.push(flag.to_string());
+            args.push(value.to_string());
+        }
+    }
+    for flag in &["tls-no-verify", "envsubst"] {
+        if u_args.is_present(flag) {
+            args.push(format!("--{}", flag));
+        }
+    }
+    args
+}
+
+/// parses the command line and dispatches it to the command
+async fn run_command<F>(args: Vec<String>, dispatch: F) -> Result<()>
+where
+    F: for<'a> FnOnce(&'a ArgMatches<'static>) -> Pin<Box<dyn Future<Output = crate::Result> + 'a>>,
+{
+    let matches = build_cli()
+        .get_matches_from_safe(args)
+        .map_err(|e| Error::Custom(e.to_string()))?;
+    match dispatch(&matches).await {
+        Ok(()) | Err(State::Matched) => Ok(()),
+        Err(State::Error(e)) => Err(e),
+        Err(state) => Err(Error::Custom(state.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keep_error() {
+        let mut errors = Vec::new();
+        assert!(keep_error(Ok(()), true, &mut errors).is_ok());
+        assert!(keep_error(Err(Error::Custom("build".to_string())), false, &mut errors).is_err());
+        assert!(errors.is_empty());
+
+        // with --keep-going the next steps run and the error is returned at the end
+        assert!(keep_error(Err(Error::Custom("build".to_string())), true, &mut errors).is_ok());
+        assert_eq!(errors, vec!["build".to_string()]);
+    }
+
+    #[test]
+    fn test_function_command_args_forwards_tls() {
+        let matches = build_cli().get_matches_from(vec![
+            "faas",
+            "up",
+            "--watch",
+            "--ca-file",
+            "ca.pem",
+            "--tls-cert",
+            "cert.pem",
+            "--tls-key",
+            "key.pem",
+            "--envsubst",
+        ]);
+        let u_args = matches.subcommand_matches("up").unwrap();
+        let args = function_command_args("ready", u_args, "stack.yml", &["fn1".to_string()]);
+        for flag in &["--ca-file", "--tls-cert", "--tls-key", "--envsubst"] {
+            assert!(args.iter().any(|a| a == flag), "{} is not forwarded", flag);
+        }
+        // the forwarded command line parses as the command
+        let ready = build_cli().get_matches_from_safe(args).unwrap();
+        let r_args = ready.subcommand_matches("ready").unwrap();
+        assert_eq!(r_args.value_of("ca-file"), Some("ca.pem"));
+        assert_eq!(r_args.value_of("tls-key"), Some("key.pem"));
+        assert!(r_args.is_present("envsubst"));
+    }
+}
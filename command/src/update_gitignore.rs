@@ -2,21 +2,10 @@ use std::io::{Read, Write};
 use utility::Result;
 
 pub(crate) fn update_gitignore() -> Result<()> {
-    let mut file;
     // update .gitignore file if it already present othewise creates it
-    #[cfg(target_os = "windows")]
-    {
-        let mut options = std::fs::File::options();
-        options.write(true).read(true);
-        file = options.open(".gitignore")?;
-    }
-    //todo fix this
-    #[cfg(target_os = "unix")]
-    {
-        let mut options = std::fs::File::options();
-        options.write(true).read(true);
-        file = options.open(".gitignore")?;
-    }
+    let mut options = std::fs::File::options();
+    options.write(true).read(true);
+    let mut file = options.open(".gitignore")?;
 
     // f, err := os.OpenFile(".gitignore", os.O_RDWR|os.O_CREATE, 0644)
     let mut content = String::new();
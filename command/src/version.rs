@@ -1,6 +1,6 @@
 use crate::cloud::find_release;
 use crate::faas::{check_and_set_default_yaml, DEFAULT_GATEWAY};
-use crate::priority::{get_gateway_url, OPENFAAS_URL_ENVIRONMENT};
+use crate::priority::{get_gateway_url, get_tls_options, OPENFAAS_URL_ENVIRONMENT};
 use crate::{CommandAppend, State};
 use clap::{App, ArgMatches, SubCommand};
 use proxy::auth::ClientAuthE;
@@ -66,7 +66,8 @@ impl Version {
                     get_git_commit()?,
                     build_version()?
                 );
-                print_server_versions(gateway, yaml_file, token, regex, filter, envsubst).await?;
+                print_server_versions(gateway, yaml_file, token, regex, filter, envsubst, v_args)
+                    .await?;
             }
 
             if warn_update {
@@ -96,6 +97,7 @@ async fn print_server_versions(
     regex: &str,
     filter: &str,
     envsubst: bool,
+    args: &ArgMatches<'_>,
 ) -> Result<()> {
     let services = if !yaml_file.is_empty() {
         parse_yaml_file(yaml_file, regex, filter, envsubst)
@@ -112,12 +114,13 @@ async fn print_server_versions(
         openfaas_url.as_str(),
     );
 
-    let cli_auth = ClientAuthE::new(token, gateway_address.as_str())?;
+    let cli_auth = ClientAuthE::new(token, gateway_address.as_str()).await?;
 
     // versionTimeout := 5 * time.Second
     // transport := GetDefaultCLITransport(tlsInsecure, &versionTimeout)
 
-    let client = cli_auth.get_client(gateway_address.as_str())?;
+    let tls = get_tls_options(args, gateway_address.as_str());
+    let client = cli_auth.get_client(gateway_address.as_str(), &tls)?;
     let gateway_info = client.get_system_info().await?;
 
     print_gateway_details(
@@ -0,0 +1,222 @@
+use builder::ignore_file::IgnoreRules;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use utility::Result;
+
+/// how often the watched files are looked at
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// how long the files have to stay untouched after a change before the functions are rebuilt
+pub(crate) const DEBOUNCE: Duration = Duration::from_millis(1000);
+
+/// folders never looked at, the build contexts and templates are written by the build itself
+const SKIPPED_FOLDERS: [&str; 3] = [".git", "build", "template"];
+
+/// modification time and size of every file under the watched paths of a function
+type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+struct WatchedFunction {
+    name: String,
+    paths: Vec<PathBuf>,
+    rules: IgnoreRules,
+    snapshot: Snapshot,
+}
+
+/// Watcher polls the handler and extra paths of functions and tells which of them changed
+pub(crate) struct Watcher {
+    functions: Vec<WatchedFunction>,
+}
+
+impl Watcher {
+    /// functions are given with the paths they are built from, files ignored by the
+    /// .dockerignore of the handler or the .faasignore of the project root are not watched
+    pub(crate) fn new(
+        functions: Vec<(String, String, Vec<String>)>,
+        project_root: &str,
+    ) -> Result<Watcher> {
+        let mut watched = Vec::new();
+        for (name, handler, extra_paths) in functions {
+            let rules = IgnoreRules::load(handler.as_str(), project_root)?;
+            let mut paths = vec![PathBuf::from(handler)];
+            paths.extend(extra_paths.into_iter().map(PathBuf::from));
+            let snapshot = snapshot(&paths, &rules);
+            watched.push(WatchedFunction {
+                name,
+                paths,
+                rules,
+                snapshot,
+            });
+        }
+        Ok(Watcher { functions: watched })
+    }
+
+    /// returns the functions whose files changed since the last call, sorted by name
+    pub(crate) fn changed(&mut self) -> Vec<String> {
+        let mut changed = Vec::new();
+        for function in &mut self.functions {
+            let current = snapshot(&function.paths, &function.rules);
+            if current != function.snapshot {
+                function.snapshot = current;
+                changed.push(function.name.clone());
+            }
+        }
+        changed.sort();
+        changed
+    }
+
+    /// waits for files to change and then for them to settle for the debounce time, returns the
+    /// functions changed in the meantime
+    pub(crate) async fn wait_for_changes(
+        &mut self,
+        interval: Duration,
+        debounce: Duration,
+    ) -> Vec<String> {
+        let mut changed = BTreeSet::new();
+        let mut last_change: Option<Instant> = None;
+        loop {
+            tokio::time::sleep(interval).await;
+            let now_changed = self.changed();
+            if !now_changed.is_empty() {
+                changed.extend(now_changed);
+                last_change = Some(Instant::now());
+            } else if matches!(last_change, Some(at) if at.elapsed() >= debounce) {
+                return changed.into_iter().collect();
+            }
+        }
+    }
+}
+
+fn snapshot(paths: &[PathBuf], rules: &IgnoreRules) -> Snapshot {
+    let mut files = Snapshot::new();
+    for path in paths {
+        collect(path, path, rules, &mut files);
+    }
+    files
+}
+
+fn collect(root: &Path, path: &Path, rules: &IgnoreRules, files: &mut Snapshot) {
+    if rules.is_ignored(path) {
+        return;
+    }
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        // removed while looking at it, the next snapshot tells
+        Err(_) => return,
+    };
+    if metadata.is_file() {
+        files.insert(
+            path.to_path_buf(),
+            (metadata.modified().ok(), metadata.len()),
+        );
+        return;
+    }
+    if let Ok(entries) = std::fs::read_dir(path) {
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if SKIPPED_FOLDERS.contains(&file_name.as_str())
+                && (path == root || file_name == ".git")
+            {
+                continue;
+            }
+            collect(root, &entry.path(), rules, files);
+        }
+    }
+}
+
+/// returns the command line with its --filter and --regex replaced by a regex matching exactly
+/// the given functions, to run a command again for them only
+pub(crate) fn args_for_functions(args: &[String], functions: &[String]) -> Vec<String> {
+    let mut filtered = Vec::new();
+    let mut skip_value = false;
+    for arg in args {
+        if skip_value {
+            skip_value = false;
+        } else if arg == "--filter" || arg == "--regex" {
+            skip_value = true;
+        } else if !arg.starts_with("--filter=") && !arg.starts_with("--regex=") {
+            filtered.push(arg.clone());
+        }
+    }
+    filtered.push("--regex".to_string());
+    filtered.push(functions_regex(functions));
+    filtered
+}
+
+/// regex matching exactly the names of the functions
+pub(crate) fn functions_regex(functions: &[String]) -> String {
+    let names: Vec<String> = functions.iter().map(|f| regex::escape(f)).collect();
+    format!("^({})$", names.join("|"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_args_for_functions() {
+        let functions = args(&["fn1", "fn.2"]);
+        assert_eq!(
+            args_for_functions(
+                &args(&[
+                    "faas",
+                    "up",
+                    "-f",
+                    "stack.yml",
+                    "--filter",
+                    "fn*",
+                    "--watch"
+                ]),
+                &functions
+            ),
+            args(&[
+                "faas",
+                "up",
+                "-f",
+                "stack.yml",
+                "--watch",
+                "--regex",
+                "^(fn1|fn\\.2)$"
+            ])
+        );
+        assert_eq!(
+            args_for_functions(&args(&["faas", "up", "--regex=^fn"]), &functions[..1]),
+            args(&["faas", "up", "--regex", "^(fn1)$"])
+        );
+    }
+
+    #[test]
+    fn test_watcher_changed() {
+        let dir = TempDir::new("watch").unwrap();
+        let root = dir.path();
+        for handler in &["fn1", "fn2", "shared"] {
+            std::fs::create_dir_all(root.join(handler).join("build")).unwrap();
+            std::fs::write(root.join(handler).join("handler.py"), "v1").unwrap();
+        }
+        let path = |p: &str| root.join(p).to_string_lossy().to_string();
+        let mut watcher = Watcher::new(
+            vec![
+                ("fn1".to_string(), path("fn1"), vec![path("shared")]),
+                ("fn2".to_string(), path("fn2"), vec![]),
+            ],
+            ".",
+        )
+        .unwrap();
+        assert!(watcher.changed().is_empty());
+
+        // the build folder of a handler is not watched
+        std::fs::write(root.join("fn2/build/out.txt"), "ignored").unwrap();
+        assert!(watcher.changed().is_empty());
+
+        std::fs::write(root.join("fn2/handler.py"), "version 2").unwrap();
+        assert_eq!(watcher.changed(), vec!["fn2".to_string()]);
+        assert!(watcher.changed().is_empty());
+
+        std::fs::write(root.join("shared/lib.py"), "new").unwrap();
+        assert_eq!(watcher.changed(), vec!["fn1".to_string()]);
+    }
+}
@@ -1,5 +1,7 @@
+use crate::credential_helper::{CredentialHelper, Credentials};
 use path_clean::PathClean;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env::VarError;
 use std::io::Write;
 use std::path::PathBuf;
@@ -20,14 +22,14 @@ pub const CONFIG_LOCATION_ENV: &str = "OPENFAAS_CONFIG";
 
 pub const DEFAULT_DIR: &str = "~/.openfaas";
 pub const DEFAULT_FILE: &str = "config.yml";
-pub const DEFAULT_PERMISSION: usize = 0700;
+pub const DEFAULT_PERMISSION: u32 = 0o700;
 
 /// DEFAULT_CI_DIR creates the 'openfaas' directory in the current directory
 /// if running in a CI environment.
 pub const DEFAULT_CI_DIR: &str = "./openfaas";
 /// DEFAULT_CI_PERMISSION creates the config file with elevated permissions
 /// for it to be read by multiple users when running in a CI environment.
-pub const DEFAULT_CI_PERMISSION: usize = 0744;
+pub const DEFAULT_CI_PERMISSION: u32 = 0o744;
 
 /// ConfigFile for OpenFaaS CLI exclusively.
 #[derive(Serialize, Deserialize, Default, Clone, Debug)]
@@ -35,6 +37,28 @@ pub struct ConfigFile<'s> {
     #[serde(default)]
     #[serde(rename = "auths")]
     auth_configs: Vec<AuthConfig>, //`yaml:"auths"`
+    #[serde(default)]
+    #[serde(rename = "current-context")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    current_context: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    contexts: Vec<Context>,
+    /// credential helper used for the gateways without their own one
+    #[serde(default)]
+    #[serde(rename = "credentials-store")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    credentials_store: String,
+    /// credential helper of each gateway
+    #[serde(default)]
+    #[serde(rename = "credential-helpers")]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    credential_helpers: HashMap<String, String>,
+    /// container build engine used when --engine is not set
+    #[serde(default)]
+    #[serde(rename = "build-engine")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    build_engine: String,
     #[serde(skip_deserializing)]
     #[serde(skip_serializing)]
     file_path: &'s str, //`yaml:"-"`
@@ -47,6 +71,70 @@ pub struct AuthConfig {
     pub auth: AuthType, //`yaml:"auth,omitempty"`
     #[serde(default)]
     pub token: String, //`yaml:"token,omitempty"`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oauth2: Option<OAuth2Config>,
+}
+
+/// OAuth2Config keeps what is needed to renew an oauth2 token before it expires
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct OAuth2Config {
+    /// unix time in seconds when the token expires, 0 when the IdP did not tell
+    #[serde(default)]
+    pub expiry: i64,
+    #[serde(default)]
+    #[serde(rename = "refresh-token")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub refresh_token: String,
+    #[serde(default)]
+    #[serde(rename = "token-url")]
+    pub token_url: String,
+    /// grant used to get the token, a client_credentials token is renewed by running it again
+    #[serde(default)]
+    pub grant: String,
+    #[serde(default)]
+    #[serde(rename = "client-id")]
+    pub client_id: String,
+    /// only kept by a credential helper, never written to the config file, without a helper
+    /// the secret of a client_credentials grant is read from OPENFAAS_CLIENT_SECRET to renew it
+    #[serde(default)]
+    #[serde(rename = "client-secret")]
+    #[serde(skip_serializing)]
+    pub client_secret: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub audience: String,
+}
+
+/// Context bundles the settings used to talk to one gateway, the auth entry of a context is the
+/// `auths` item with the same gateway
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Context {
+    pub name: String,
+    pub gateway: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub namespace: String,
+    #[serde(default)]
+    #[serde(rename = "tls-insecure")]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub tls_insecure: bool,
+    #[serde(default)]
+    #[serde(rename = "ca-file")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub ca_file: String,
+    #[serde(default)]
+    #[serde(rename = "tls-cert")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub cert_file: String,
+    #[serde(default)]
+    #[serde(rename = "tls-key")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub key_file: String,
+    #[serde(default)]
+    #[serde(rename = "template-store-url")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub template_store_url: String,
 }
 
 impl<'s> ConfigFile<'s> {
@@ -59,6 +147,11 @@ impl<'s> ConfigFile<'s> {
         } else {
             Ok(ConfigFile {
                 auth_configs: vec![],
+                current_context: String::new(),
+                contexts: vec![],
+                credentials_store: String::new(),
+                credential_helpers: HashMap::new(),
+                build_engine: String::new(),
                 file_path,
             })
         };
@@ -121,11 +214,10 @@ pub fn ensure_file() -> Result<String> {
         .parent()
         .map_or_else(|| file_path.clone(), |v| v.to_owned());
 
-    #[cfg(target_os = "unix")]
+    #[cfg(unix)]
     {
         use std::fs::DirBuilder;
-        use std::os::unix::fs::DirBuilderExt;
-        std::os::unix::fs::PermissionsExt;
+        use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
 
         let permission = if is_running_in_cli() {
             DEFAULT_CI_PERMISSION
@@ -133,20 +225,20 @@ pub fn ensure_file() -> Result<String> {
             DEFAULT_PERMISSION
         };
         let mut builder = DirBuilder::new();
-        builder
-            .mode(permission)
-            .recursive(true)
-            .create(dir)
-            .map_error(|e| Error::Custom(e.to_string()))?;
+        builder.mode(permission).recursive(true).create(dir)?;
         if let Err(err) = std::fs::metadata(&file_path) {
             if format!("{:?}", err).contains("kind: NotFound") {
-                let mut file = std::fs::File::create(&file_path)?;
-                let permissions = Permissions::from_mode(0600);
-                file.set_permissions(permissions);
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(&file_path)?;
+            } else {
+                return Err(Error::Io(err));
             }
         }
     }
-    #[cfg(target_os = "windows")]
+    #[cfg(not(unix))]
     {
         use std::fs::DirBuilder;
         let mut builder = DirBuilder::new();
@@ -186,15 +278,21 @@ impl<'s> ConfigFile<'s> {
     // Save writes the config to disk
     fn save(&self) -> Result<()> {
         let mut file;
-        #[cfg(target_os = "unix")]
+        #[cfg(unix)]
         {
-            std::os::unix::fs::PermissionsExt;
-
-            file = std::fs::File::create(&file_path)?;
-            let permissions = Permissions::from_mode(0600);
-            file.set_permissions(permissions);
+            use std::fs::Permissions;
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+            file = std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(self.file_path)?;
+            // the mode only applies to new files, so tighten one left by older versions
+            file.set_permissions(Permissions::from_mode(0o600))?;
         }
-        #[cfg(target_os = "windows")]
+        #[cfg(not(unix))]
         {
             file = std::fs::File::create(&self.file_path)?;
         }
@@ -219,7 +317,6 @@ impl<'s> ConfigFile<'s> {
             }
         }
         let data = std::fs::read_to_string(&self.file_path)?;
-        println!("data: {}", data);
         let conf: ConfigFile = if data.is_empty() {
             ConfigFile::default()
         } else {
@@ -229,8 +326,91 @@ impl<'s> ConfigFile<'s> {
         if !conf.auth_configs.is_empty() {
             self.auth_configs = conf.auth_configs;
         }
+        if !conf.contexts.is_empty() {
+            self.contexts = conf.contexts;
+        }
+        self.current_context = conf.current_context;
+        self.credentials_store = conf.credentials_store;
+        self.credential_helpers = conf.credential_helpers;
+        self.build_engine = conf.build_engine;
         Ok(())
     }
+
+    /// returns the credential helper of the gateway, None when its credentials are kept in
+    /// the config file
+    fn credential_helper(&self, gateway: &str) -> Option<CredentialHelper> {
+        let name = self
+            .credential_helpers
+            .get(gateway)
+            .unwrap_or(&self.credentials_store);
+        if name.is_empty() {
+            None
+        } else {
+            Some(CredentialHelper::new(name))
+        }
+    }
+}
+
+/// secret part of an oauth2 auth config kept by a credential helper
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct OAuth2Secret {
+    #[serde(default)]
+    token: String,
+    #[serde(default)]
+    #[serde(rename = "refresh-token")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    refresh_token: String,
+    #[serde(default)]
+    #[serde(rename = "client-secret")]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    client_secret: String,
+}
+
+/// moves the secrets of the auth config to helper credentials, the returned auth config only
+/// keeps what can be written in plain text
+fn split_secrets(mut auth: AuthConfig) -> Result<(AuthConfig, Credentials)> {
+    let (username, secret) = if auth.auth == BASIC_AUTH_TYPE {
+        decode_auth(auth.token.as_str())?
+    } else {
+        let mut secret = OAuth2Secret {
+            token: auth.token.clone(),
+            ..Default::default()
+        };
+        if let Some(oauth2) = auth.oauth2.as_mut() {
+            secret.refresh_token = std::mem::take(&mut oauth2.refresh_token);
+            secret.client_secret = std::mem::take(&mut oauth2.client_secret);
+        }
+        let secret = serde_json::to_string(&secret)
+            .map_err(|e| Error::Custom(format!("can't serialize the token: {}", e)))?;
+        (auth.auth.clone(), secret)
+    };
+    auth.token = String::new();
+
+    let credentials = Credentials {
+        server_url: auth.gateway.clone(),
+        username,
+        secret,
+    };
+    Ok((auth, credentials))
+}
+
+/// puts the secrets kept by a credential helper back into the auth config
+fn join_secrets(auth: &mut AuthConfig, credentials: Credentials) {
+    if auth.auth == BASIC_AUTH_TYPE {
+        auth.token = encode_auth(credentials.username.as_str(), credentials.secret.as_str());
+        return;
+    }
+    match serde_json::from_str::<OAuth2Secret>(credentials.secret.as_str()) {
+        Ok(secret) => {
+            auth.token = secret.token;
+            if let Some(oauth2) = auth.oauth2.as_mut() {
+                oauth2.refresh_token = secret.refresh_token;
+                oauth2.client_secret = secret.client_secret;
+            }
+        }
+        // a raw token stored by another tool
+        Err(_) => auth.token = credentials.secret,
+    }
 }
 
 /// encodes the username and password strings to base64
@@ -261,7 +441,17 @@ pub fn decode_auth(encoded: &str) -> Result<(String, String)> {
 }
 /// creates or updates the username and password for a given gateway
 pub fn update_auth_config(gateway: &str, token: &str, auth_type: AuthType) -> Result<()> {
-    if gateway.is_empty() || url::Url::parse(gateway).is_err() {
+    save_auth_config(AuthConfig {
+        gateway: gateway.to_string(),
+        auth: auth_type,
+        token: token.to_string(),
+        oauth2: None,
+    })
+}
+
+/// creates or updates the auth config of its gateway
+pub fn save_auth_config(auth: AuthConfig) -> Result<()> {
+    if auth.gateway.is_empty() || url::Url::parse(auth.gateway.as_str()).is_err() {
         return Err(Error::Custom(format!("invalid gateway")));
     }
 
@@ -271,15 +461,18 @@ pub fn update_auth_config(gateway: &str, token: &str, auth_type: AuthType) -> Re
 
     cfg.load()?;
 
-    let auth = AuthConfig {
-        gateway: gateway.to_string(),
-        auth: auth_type,
-        token: token.to_string(),
+    let auth = match cfg.credential_helper(auth.gateway.as_str()) {
+        Some(helper) => {
+            let (auth, credentials) = split_secrets(auth)?;
+            helper.store(&credentials)?;
+            auth
+        }
+        None => auth,
     };
 
     let mut index = -1;
     for (i, v) in cfg.auth_configs.iter().enumerate() {
-        if gateway == v.gateway {
+        if auth.gateway == v.gateway {
             index = i as i32;
             break;
         }
@@ -306,8 +499,21 @@ pub fn lookup_auth_config(gateway: &str) -> Result<AuthConfig> {
     cfg.load()?;
     //println!("{:#?}", cfg);
 
-    for v in cfg.auth_configs {
+    let helper = cfg.credential_helper(gateway);
+    for mut v in cfg.auth_configs {
         if gateway == v.gateway {
+            // a token left in the file, i.e. saved before the helper was set, is the fallback
+            if let Some(helper) = helper {
+                match helper.get(gateway) {
+                    Ok(Some(credentials)) => join_secrets(&mut v, credentials),
+                    Ok(None) => {}
+                    // a helper that is missing or locked doesn't stop the file token from being used
+                    Err(e) => eprintln!(
+                        "can't read the credentials of {} from the credential helper, using the config file: {}",
+                        gateway, e
+                    ),
+                }
+            }
             return Ok(v);
         }
     }
@@ -339,6 +545,9 @@ pub fn remove_auth_config(gateway: &str) -> Result<()> {
     }
 
     if index > -1 {
+        if let Some(helper) = cfg.credential_helper(gateway) {
+            helper.erase(gateway)?;
+        }
         //cfg.AuthConfigs = remove_auth_by_index(cfg.auth_configs, index);
         cfg.auth_configs.remove(index as usize);
         cfg.save()
@@ -349,3 +558,142 @@ pub fn remove_auth_config(gateway: &str) -> Result<()> {
         )))
     }
 }
+
+/// sets the credential helper keeping the credentials of the gateway, an empty helper
+/// keeps them in the config file again
+pub fn set_credential_helper(gateway: &str, helper: &str) -> Result<()> {
+    if gateway.is_empty() || url::Url::parse(gateway).is_err() {
+        return Err(Error::Custom(format!("invalid gateway")));
+    }
+
+    let config_path = ensure_file()?;
+
+    let mut cfg = ConfigFile::new(config_path.as_str())?;
+    cfg.load()?;
+
+    if helper.is_empty() {
+        cfg.credential_helpers.remove(gateway);
+    } else {
+        cfg.credential_helpers
+            .insert(gateway.to_string(), helper.to_string());
+    }
+    cfg.save()
+}
+
+/// sets the credential helper keeping the credentials of the gateways without their own one,
+/// an empty helper keeps them in the config file again
+pub fn set_credentials_store(helper: &str) -> Result<()> {
+    let config_path = ensure_file()?;
+
+    let mut cfg = ConfigFile::new(config_path.as_str())?;
+    cfg.load()?;
+
+    cfg.credentials_store = helper.to_string();
+    cfg.save()
+}
+
+/// creates or updates the context with the same name
+pub fn update_context(context: Context) -> Result<()> {
+    if context.name.is_empty() {
+        return Err(Error::Custom(format!("context name can't be empty")));
+    }
+    if context.gateway.is_empty() || url::Url::parse(context.gateway.as_str()).is_err() {
+        return Err(Error::Custom(format!("invalid gateway")));
+    }
+
+    let config_path = ensure_file()?;
+
+    let mut cfg = ConfigFile::new(config_path.as_str())?;
+    cfg.load()?;
+
+    match cfg.contexts.iter_mut().find(|c| c.name == context.name) {
+        Some(existing) => *existing = context,
+        None => cfg.contexts.push(context),
+    }
+
+    cfg.save()
+}
+
+///makes the named context the current one
+pub fn use_context(name: &str) -> Result<()> {
+    if !file_exists()? {
+        return Err(Error::Custom("config file is not found".to_string()));
+    }
+
+    let config_path = ensure_file()?;
+
+    let mut cfg = ConfigFile::new(config_path.as_str())?;
+    cfg.load()?;
+
+    if !cfg.contexts.iter().any(|c| c.name == name) {
+        return Err(Error::Custom(format!(
+            "context {} not found in config",
+            name
+        )));
+    }
+    cfg.current_context = name.to_string();
+    cfg.save()
+}
+
+///deletes the named context, the current context is unset when it is the deleted one
+pub fn remove_context(name: &str) -> Result<()> {
+    if !file_exists()? {
+        return Err(Error::Custom("config file is not found".to_string()));
+    }
+
+    let config_path = ensure_file()?;
+
+    let mut cfg = ConfigFile::new(config_path.as_str())?;
+    cfg.load()?;
+
+    let index = cfg
+        .contexts
+        .iter()
+        .position(|c| c.name == name)
+        .ok_or(Error::Custom(format!(
+            "context {} not found in config",
+            name
+        )))?;
+    cfg.contexts.remove(index);
+    if cfg.current_context == name {
+        cfg.current_context = String::new();
+    }
+    cfg.save()
+}
+
+///returns all the contexts and the name of the current one
+pub fn list_contexts() -> Result<(Vec<Context>, String)> {
+    if !file_exists()? {
+        return Ok((vec![], String::new()));
+    }
+
+    let config_path = ensure_file()?;
+
+    let mut cfg = ConfigFile::new(config_path.as_str())?;
+    cfg.load()?;
+
+    Ok((cfg.contexts, cfg.current_context))
+}
+
+///returns the build engine set in the config file, empty when none is set
+pub fn lookup_build_engine() -> Result<String> {
+    if !file_exists()? {
+        return Ok(String::new());
+    }
+
+    let config_path = ensure_file()?;
+
+    let mut cfg = ConfigFile::new(config_path.as_str())?;
+    cfg.load()?;
+
+    Ok(cfg.build_engine)
+}
+
+///returns the current context, None when no context is in use
+pub fn lookup_current_context() -> Result<Option<Context>> {
+    let (contexts, current) = list_contexts()?;
+    if current.is_empty() {
+        return Ok(None);
+    }
+    Ok(contexts.into_iter().find(|c| c.name == current))
+}
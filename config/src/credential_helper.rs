@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use utility::{Error, Result};
+
+/// prefix of the binaries implementing the docker credential helper protocol
+pub const CREDENTIAL_HELPER_PREFIX: &str = "docker-credential-";
+/// message printed by the helpers when nothing is stored for a server URL
+const CREDENTIALS_NOT_FOUND: &str = "credentials not found";
+
+/// Credentials as exchanged with a credential helper
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Credentials {
+    #[serde(rename = "ServerURL")]
+    #[serde(default)]
+    pub server_url: String, //`json:"ServerURL"`
+    #[serde(rename = "Username")]
+    #[serde(default)]
+    pub username: String, //`json:"Username"`
+    #[serde(rename = "Secret")]
+    #[serde(default)]
+    pub secret: String, //`json:"Secret"`
+}
+
+/// CredentialHelper runs a `docker-credential-*` binary with its `store`, `get` and `erase`
+/// actions
+#[derive(Debug, Clone, PartialEq)]
+pub struct CredentialHelper {
+    program: String,
+}
+
+impl CredentialHelper {
+    /// new returns the helper for a name like `pass` (runs `docker-credential-pass`),
+    /// a path is used as it is
+    pub fn new(name: &str) -> CredentialHelper {
+        let program = if name.contains(std::path::MAIN_SEPARATOR)
+            || name.starts_with(CREDENTIAL_HELPER_PREFIX)
+        {
+            name.to_string()
+        } else {
+            format!("{}{}", CREDENTIAL_HELPER_PREFIX, name)
+        };
+        CredentialHelper { program }
+    }
+
+    /// saves the credentials of their server URL
+    pub fn store(&self, credentials: &Credentials) -> Result<()> {
+        let input = serde_json::to_string(credentials)
+            .map_err(|e| Error::Custom(format!("can't serialize credentials: {}", e)))?;
+        self.run("store", input.as_str())?;
+        Ok(())
+    }
+
+    /// returns the credentials of the server URL, None when the helper has none
+    pub fn get(&self, server_url: &str) -> Result<Option<Credentials>> {
+        match self.run("get", server_url) {
+            Ok(out) => {
+                let credentials = serde_json::from_str(out.as_str()).map_err(|e| {
+                    Error::Custom(format!(
+                        "can't parse the output of {} get: {}",
+                        self.program, e
+                    ))
+                })?;
+                Ok(Some(credentials))
+            }
+            Err(e) if e.to_string().contains(CREDENTIALS_NOT_FOUND) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// deletes the credentials of the server URL, missing credentials are not an error
+    pub fn erase(&self, server_url: &str) -> Result<()> {
+        match self.run("erase", server_url) {
+            Err(e) if !e.to_string().contains(CREDENTIALS_NOT_FOUND) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn run(&self, action: &str, input: &str) -> Result<String> {
+        let mut child = Command::new(self.program.as_str())
+            .arg(action)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                Error::Custom(format!(
+                    "can't run credential helper {}: {}",
+                    self.program, e
+                ))
+            })?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::Custom(format!(
+                "credential helper {} {} failed: {} {}",
+                self.program,
+                action,
+                stdout,
+                stderr.trim()
+            )));
+        }
+        Ok(stdout)
+    }
+}
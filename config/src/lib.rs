@@ -1,4 +1,5 @@
 pub mod config_file;
+pub mod credential_helper;
 
 #[cfg(test)]
 mod tests {
@@ -283,3 +283,133 @@ fn test_config_dir() {
         }
     }
 }
+
+#[test]
+fn test_update_context_invalid() {
+    let res = update_context(Context {
+        name: "dev".into(),
+        gateway: "http//dev.test".into(),
+        ..Default::default()
+    });
+    assert!(res.is_err());
+    assert!(format!("{:?}", res).contains("invalid gateway"));
+
+    let res = update_context(Context {
+        gateway: "http://dev.test".into(),
+        ..Default::default()
+    });
+    assert!(res.is_err());
+    assert!(format!("{:?}", res).contains("context name can't be empty"));
+}
+
+#[test]
+#[ignore]
+///ignored as it fails in running all test due to temp files creation
+fn test_use_and_remove_context() {
+    let config_dir = tempdir::TempDir::new("faas-cli-file-test");
+    assert!(config_dir.is_ok());
+
+    let config_dir = config_dir.unwrap().path().to_string_lossy().into_owned();
+    std::env::set_var(CONFIG_LOCATION_ENV, config_dir);
+
+    let dev = Context {
+        name: "dev".into(),
+        gateway: "http://dev.test:8080".into(),
+        namespace: "dev-fn".into(),
+        ..Default::default()
+    };
+    let prod = Context {
+        name: "prod".into(),
+        gateway: "https://prod.test".into(),
+        ca_file: "/etc/ssl/prod-ca.pem".into(),
+        ..Default::default()
+    };
+    assert!(update_context(dev.clone()).is_ok());
+    assert!(update_context(prod.clone()).is_ok());
+    assert_eq!(lookup_current_context().unwrap(), None);
+
+    let res = use_context("staging");
+    assert!(format!("{:?}", res).contains("context staging not found in config"));
+
+    assert!(use_context("prod").is_ok());
+    assert_eq!(lookup_current_context().unwrap(), Some(prod));
+
+    assert!(remove_context("prod").is_ok());
+    let (contexts, current) = list_contexts().unwrap();
+    assert_eq!(contexts, vec![dev]);
+    assert!(current.is_empty());
+}
+
+#[test]
+#[ignore]
+///ignored as it fails in running all test due to temp files creation
+fn test_save_auth_config_keeps_oauth2() {
+    let config_dir = tempdir::TempDir::new("faas-cli-file-test");
+    assert!(config_dir.is_ok());
+
+    let config_dir = config_dir.unwrap().path().to_string_lossy().into_owned();
+    std::env::set_var(CONFIG_LOCATION_ENV, config_dir);
+
+    let gateway = "http://openfaas.test";
+    let oauth2 = OAuth2Config {
+        expiry: 1_700_000_000,
+        refresh_token: "refresh".into(),
+        token_url: "http://idp.test/oauth/token".into(),
+        grant: "client_credentials".into(),
+        client_id: "client".into(),
+        ..Default::default()
+    };
+    let res = save_auth_config(AuthConfig {
+        gateway: gateway.into(),
+        auth: OAUTH_2AUTH_TYPE.into(),
+        token: "access".into(),
+        oauth2: Some(oauth2.clone()),
+    });
+    assert!(res.is_ok());
+
+    let auth_config = lookup_auth_config(gateway).unwrap();
+    assert_eq!(auth_config.token, "access");
+    assert_eq!(auth_config.oauth2, Some(oauth2));
+
+    // a plain update replaces the stored oauth2 details
+    assert!(update_auth_config(gateway, "other", OAUTH_2AUTH_TYPE.into()).is_ok());
+    let auth_config = lookup_auth_config(gateway).unwrap();
+    assert_eq!(auth_config.token, "other");
+    assert_eq!(auth_config.oauth2, None);
+}
+
+#[test]
+fn test_oauth2_client_secret_not_written() {
+    let oauth2 = OAuth2Config {
+        token_url: "http://idp.test/oauth/token".into(),
+        client_id: "client".into(),
+        client_secret: "secret".into(),
+        ..Default::default()
+    };
+    let data = serde_yaml::to_string(&oauth2).unwrap();
+    assert!(!data.contains("secret"));
+
+    // a secret written by an earlier version is still read
+    let oauth2: OAuth2Config = serde_yaml::from_str("client-secret: secret").unwrap();
+    assert_eq!(oauth2.client_secret, "secret");
+}
+
+#[test]
+#[ignore]
+///ignored as it fails in running all test due to temp files creation
+fn test_lookup_auth_config_helper_error_falls_back_to_file() {
+    let config_dir = tempdir::TempDir::new("faas-cli-file-test").unwrap();
+    std::env::set_var(
+        CONFIG_LOCATION_ENV,
+        config_dir.path().to_string_lossy().into_owned(),
+    );
+
+    let gateway = "http://openfaas.test";
+    let token = encode_auth("admin", "pass");
+    assert!(update_auth_config(gateway, token.as_str(), BASIC_AUTH_TYPE.into()).is_ok());
+
+    // the helper set afterwards can't be run, the token saved before is still used
+    assert!(set_credentials_store("faas-cli-missing-helper").is_ok());
+    let auth_config = lookup_auth_config(gateway).unwrap();
+    assert_eq!(auth_config.token, token);
+}
@@ -6,6 +6,8 @@ mod tests {
     }
 }
 
+pub mod stream;
+
 use utility::{Error, Result};
 /// Command run a system command and return output
 pub fn command_exec(
@@ -0,0 +1,193 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc::{channel, Sender};
+use utility::{Error, Result};
+
+/// number of output lines kept to explain a failed command
+pub const DEFAULT_TAIL_LINES: usize = 20;
+
+/// ANSI colours given to the prefixes, picked from the prefix so a function keeps its colour
+const PREFIX_COLOURS: [&str; 6] = [
+    "\x1b[36m", "\x1b[33m", "\x1b[35m", "\x1b[32m", "\x1b[34m", "\x1b[91m",
+];
+const RESET_COLOUR: &str = "\x1b[0m";
+
+/// StreamOptions controls how the output of a streamed command is shown
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamOptions<'s> {
+    /// printed as `[prefix]` in front of every line
+    pub prefix: &'s str,
+    /// keeps the output for the tail but prints nothing
+    pub quiet: bool,
+    /// number of last lines kept in the StreamOutput
+    pub tail_lines: usize,
+}
+
+impl<'s> StreamOptions<'s> {
+    pub fn new(prefix: &'s str, quiet: bool) -> StreamOptions<'s> {
+        StreamOptions {
+            prefix,
+            quiet,
+            tail_lines: DEFAULT_TAIL_LINES,
+        }
+    }
+}
+
+/// StreamOutput is the exit status and the last lines of stdout and stderr of a command
+#[derive(Debug)]
+pub struct StreamOutput {
+    pub status: ExitStatus,
+    pub tail: Vec<String>,
+}
+
+impl StreamOutput {
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+}
+
+/// runs the command in the directory and prints its stdout and stderr line by line as they come,
+/// prefixed with the coloured `[prefix]` of the options
+pub fn command_stream(
+    temp_path: &str,
+    cmd: &str,
+    args: &[&str],
+    options: &StreamOptions,
+) -> Result<StreamOutput> {
+    let mut child = Command::new(cmd)
+        .args(args)
+        .current_dir(temp_path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            Error::Custom(format!(
+                "ERROR - Could not execute command: {} {:?}\n{:?}",
+                cmd, args, e
+            ))
+        })?;
+
+    let (sender, receiver) = channel();
+    let readers = vec![
+        child
+            .stdout
+            .take()
+            .map(|out| forward_lines(out, sender.clone())),
+        child
+            .stderr
+            .take()
+            .map(|err| forward_lines(err, sender.clone())),
+    ];
+    drop(sender);
+
+    let prefix = coloured_prefix(options.prefix);
+    let mut tail = VecDeque::with_capacity(options.tail_lines);
+    // the receiver ends once both readers are done with their pipe
+    for line in receiver {
+        if !options.quiet {
+            let stdout = std::io::stdout();
+            let mut handle = stdout.lock();
+            let _ = writeln!(handle, "{} {}", prefix, line);
+        }
+        if options.tail_lines > 0 {
+            if tail.len() == options.tail_lines {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+    }
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+
+    let status = child.wait()?;
+    Ok(StreamOutput {
+        status,
+        tail: tail.into_iter().collect(),
+    })
+}
+
+fn forward_lines<R: Read + Send + 'static>(
+    source: R,
+    sender: Sender<String>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in BufReader::new(source).split(b'\n') {
+            match line {
+                Ok(line) => {
+                    let line = String::from_utf8_lossy(&line);
+                    if sender
+                        .send(line.trim_end_matches('\r').to_string())
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    })
+}
+
+/// returns `[prefix]`, coloured when stdout is a terminal and NO_COLOR is not set
+fn coloured_prefix(prefix: &str) -> String {
+    colour_by_key(prefix, format!("[{}]", prefix).as_str())
+}
+
+/// returns the text in the colour picked for the key, so the output of a function always has the
+/// same colour, when stdout is a terminal and NO_COLOR is not set
+pub fn colour_by_key(key: &str, text: &str) -> String {
+    if std::env::var_os("NO_COLOR").is_some() || !std::io::stdout().is_terminal() {
+        text.to_string()
+    } else {
+        format!("{}{}{}", prefix_colour(key), text, RESET_COLOUR)
+    }
+}
+
+/// picks the colour from the FNV-1a hash of the key, names differing by a swapped or shifted
+/// character get different colours unlike with a sum of the bytes
+fn prefix_colour(key: &str) -> &'static str {
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    PREFIX_COLOURS[(hash % PREFIX_COLOURS.len() as u64) as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_colour_is_stable() {
+        assert_eq!(prefix_colour("figlet"), prefix_colour("figlet"));
+        assert!(PREFIX_COLOURS.contains(&prefix_colour("")));
+        assert_ne!(prefix_colour("api-ab"), prefix_colour("api-ba"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_stream_keeps_tail() {
+        let options = StreamOptions {
+            prefix: "fn",
+            quiet: true,
+            tail_lines: 2,
+        };
+        let out = command_stream(
+            "./",
+            "sh",
+            &["-c", "echo one; echo two; echo three; exit 3"],
+            &options,
+        )
+        .unwrap();
+
+        assert!(!out.success());
+        assert_eq!(out.status.code(), Some(3));
+        assert_eq!(out.tail, vec!["two".to_string(), "three".to_string()]);
+
+        let options = StreamOptions::new("fn", true);
+        let out = command_stream("./", "sh", &["-c", "echo failed >&2"], &options).unwrap();
+        assert!(out.success());
+        assert_eq!(out.tail, vec!["failed".to_string()]);
+    }
+}
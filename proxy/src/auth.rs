@@ -1,6 +1,12 @@
 use crate::client::{Client, ClientAuth};
-use config::config_file::{decode_auth, BASIC_AUTH_TYPE};
+use crate::oauth2::{can_renew, is_expiring, now_unix, renew_auth_config};
+use crate::proxy::TlsOptions;
+use async_trait::async_trait;
+use config::config_file::{
+    decode_auth, save_auth_config, AuthConfig, BASIC_AUTH_TYPE, OAUTH_2AUTH_TYPE,
+};
 use reqwest::RequestBuilder;
+use std::sync::Mutex;
 use utility::{Error, Result};
 
 ///auth struct for the CLI
@@ -18,7 +24,10 @@ pub struct BasicAuth {
 
 ///bearer token
 pub struct BearerToken {
-    token: String,
+    token: Mutex<String>,
+    ///the stored oauth2 config of the token, renewed when the token expires or the gateway
+    ///refuses it. None for a token given with --token
+    oauth2: Option<Mutex<AuthConfig>>,
 }
 
 impl<'s> ClientAuth for BasicAuth {
@@ -34,14 +43,62 @@ impl<'s> ClientAuth for BasicAuth {
     //     return nil
     // }
 }
+#[async_trait(?Send)]
 impl<'s> ClientAuth for BearerToken {
     fn set(&self, req: &mut RequestBuilder) -> Result<()> {
         let req2 = req
             .try_clone()
             .ok_or(Error::Custom(format!("can't clone request :{:?}", req)))?;
-        *req = req2.header("Authorization", "Bearer ".to_string() + self.token.as_str());
+        let token = self.token.lock().map(|t| t.clone()).unwrap_or_default();
+        *req = req2.header("Authorization", "Bearer ".to_string() + token.as_str());
         Ok(())
     }
+
+    async fn refresh(&self, refused: bool) -> Result<bool> {
+        let config = match &self.oauth2 {
+            Some(config) => config.lock().map(|c| c.clone()).unwrap_or_default(),
+            None => return Ok(false),
+        };
+        let renewable = config.oauth2.as_ref().map(can_renew).unwrap_or(false);
+        let renewed = if refused && renewable {
+            renew_and_save(config).await?
+        } else {
+            renew_if_expiring(config).await?
+        };
+
+        let mut token = self
+            .token
+            .lock()
+            .map_err(|_| Error::Custom("the token lock is poisoned".to_string()))?;
+        if *token == renewed.token {
+            return Ok(false);
+        }
+        *token = renewed.token.clone();
+        if let Some(config) = &self.oauth2 {
+            if let Ok(mut config) = config.lock() {
+                *config = renewed;
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl BearerToken {
+    ///a token sent as it is
+    pub fn new(token: &str) -> BearerToken {
+        BearerToken {
+            token: Mutex::new(token.to_string()),
+            oauth2: None,
+        }
+    }
+
+    ///the token of a stored oauth2 config, renewed and saved again when it expires
+    pub fn renewable(config: AuthConfig) -> BearerToken {
+        BearerToken {
+            token: Mutex::new(config.token.clone()),
+            oauth2: Some(Mutex::new(config)),
+        }
+    }
 }
 
 pub enum ClientAuthE {
@@ -49,23 +106,24 @@ pub enum ClientAuthE {
     BearerToken(BearerToken),
 }
 impl ClientAuthE {
-    ///returns a new CLI Auth
-    pub fn new(token: &str, gateway: &str) -> Result<ClientAuthE> {
-        let config = config::config_file::lookup_auth_config(gateway)?;
+    ///returns a new CLI Auth, a stored oauth2 token which expired or is about to expire
+    ///is renewed and saved first
+    pub async fn new(token: &str, gateway: &str) -> Result<ClientAuthE> {
+        let mut config = config::config_file::lookup_auth_config(gateway)?;
+        if token.is_empty() {
+            config = renew_if_expiring(config).await?;
+        }
         if config.auth == BASIC_AUTH_TYPE {
             let (username, password) = decode_auth(config.token.as_str())?;
 
             Ok(ClientAuthE::BasicAuth(BasicAuth { username, password }))
-        } else {
+        } else if !token.is_empty() {
             // User specified token gets priority
-            let bearer_token = if !token.is_empty() {
-                token.to_string()
-            } else {
-                config.token
-            };
-            Ok(ClientAuthE::BearerToken(BearerToken {
-                token: bearer_token,
-            }))
+            Ok(ClientAuthE::BearerToken(BearerToken::new(token)))
+        } else if config.auth == OAUTH_2AUTH_TYPE && config.oauth2.is_some() {
+            Ok(ClientAuthE::BearerToken(BearerToken::renewable(config)))
+        } else {
+            Ok(ClientAuthE::BearerToken(BearerToken::new(config.token.as_str())))
         }
     }
 
@@ -79,16 +137,49 @@ impl ClientAuthE {
     pub fn get_client(
         &self,
         gateway: &str, /*,basic:&mut BasicAuth,bearer:&mut BearerToken)*/
-    ) -> Result<Client> {
+        tls: &TlsOptions,
+    ) -> Result<Client<'_>> {
         match self {
             ClientAuthE::BasicAuth(basic) => {
                 // basic = b;
-                Client::new(Box::new(basic), gateway)
+                Client::new_with_tls(Box::new(basic), gateway, tls)
             }
             ClientAuthE::BearerToken(bearer) => {
                 // bearer = b;
-                Client::new(Box::new(bearer), gateway)
+                Client::new_with_tls(Box::new(bearer), gateway, tls)
             }
         }
     }
 }
+
+///renews an expiring oauth2 token with its refresh token or by running the
+///client_credentials grant again, other auth configs are returned unchanged
+pub async fn renew_if_expiring(config: AuthConfig) -> Result<AuthConfig> {
+    if config.auth != OAUTH_2AUTH_TYPE {
+        return Ok(config);
+    }
+    let oauth2 = match &config.oauth2 {
+        Some(oauth2) if is_expiring(oauth2, now_unix()) => oauth2,
+        _ => return Ok(config),
+    };
+    if !can_renew(oauth2) {
+        return Err(Error::Custom(format!(
+            "the token for {} has expired, run \"faas-cli auth\" again",
+            config.gateway
+        )));
+    }
+
+    renew_and_save(config).await
+}
+
+///gets a new oauth2 token and saves it
+async fn renew_and_save(config: AuthConfig) -> Result<AuthConfig> {
+    let renewed = renew_auth_config(&config).await.map_err(|e| {
+        Error::Custom(format!(
+            "the token for {} has expired and can't be renewed, run \"faas-cli auth\" again\nreason: {}",
+            config.gateway, e
+        ))
+    })?;
+    save_auth_config(renewed.clone())?;
+    Ok(renewed)
+}
@@ -1,4 +1,6 @@
-use reqwest::Url;
+use crate::proxy::{make_http_client, TlsOptions};
+use async_trait::async_trait;
+use reqwest::{StatusCode, Url};
 use std::collections::HashMap;
 use url::ParseError;
 use utility::{Error, Result};
@@ -16,18 +18,34 @@ pub struct Client<'s> {
 
 ///ClientAuth an interface for client authentication.
 // to add authentication to the client implement this interface
+#[async_trait(?Send)]
 pub trait ClientAuth {
     fn set(&self, req: &mut reqwest::RequestBuilder) -> Result<()>;
+
+    ///renews the credentials when they are about to expire, or when the gateway refused them
+    ///if refused is set. Returns true when they changed and requests must be authorized again
+    async fn refresh(&self, _refused: bool) -> Result<bool> {
+        Ok(false)
+    }
 }
 
 impl<'s> Client<'s> {
     /// initializes a new API client
     pub fn new(auth: Box<&'s dyn ClientAuth>, gateway: &str) -> Result<Client<'s>> {
+        Client::new_with_tls(auth, gateway, &TlsOptions::default())
+    }
+
+    /// initializes a new API client using the given TLS settings
+    pub fn new_with_tls(
+        auth: Box<&'s dyn ClientAuth>,
+        gateway: &str,
+        tls: &TlsOptions,
+    ) -> Result<Client<'s>> {
         let url = gateway.trim_end_matches('/');
         let url = Url::parse(url).map_err(|e| Error::Custom(format!("{:?}", e)))?;
 
         Ok(Client {
-            http_client: reqwest::Client::new(),
+            http_client: make_http_client(None, tls)?,
             client_auth: auth,
             gateway: url,
             user_agent: None,
@@ -97,9 +115,31 @@ impl<'s> Client<'s> {
         //     fmt.Println(string(dump))
         // }
 
+        // the token of a long running command may expire between two requests
+        let req = if self.client_auth.refresh(false).await? {
+            self.authorize(req)?
+        } else {
+            req
+        };
+        let retry = req.try_clone();
         let resp = self.http_client.execute(req).await?;
+        if resp.status() == StatusCode::UNAUTHORIZED {
+            if let Some(retry) = retry {
+                if self.client_auth.refresh(true).await? {
+                    return Ok(self.http_client.execute(self.authorize(retry)?).await?);
+                }
+            }
+        }
         Ok(resp)
     }
+
+    ///sets the renewed credentials on a request built before they changed
+    fn authorize(&self, mut req: reqwest::Request) -> Result<reqwest::Request> {
+        req.headers_mut().remove(reqwest::header::AUTHORIZATION);
+        let mut builder = reqwest::RequestBuilder::from_parts(self.http_client.clone(), req);
+        self.client_auth.set(&mut builder)?;
+        Ok(builder.build()?)
+    }
 }
 
 pub(crate) fn add_query_params(url: &str, params: &HashMap<&str, &str>) -> Result<String> {
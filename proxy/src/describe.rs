@@ -59,14 +59,14 @@ impl<'s> Client<'s> {
 
             },
             StatusCode::UNAUTHORIZED=>{
-                 Err( Error::Custom(format!("unauthorized access, run \"faas-cli login\" to setup authentication for this server") ))
+                 Err( Error::Status(StatusCode::UNAUTHORIZED.as_u16(), format!("unauthorized access, run \"faas-cli login\" to setup authentication for this server") ))
             },
             StatusCode::NOT_FOUND=>{
-                 Err( Error::Custom(format!("No such function: {}", function_name)))
+                 Err( Error::Status(StatusCode::NOT_FOUND.as_u16(), format!("No such function: {}", function_name)))
             },
             status=>{
                 let err= res.text().await
-                    .map(|body| Error::Custom(format!("Server returned unexpected status code {} and body {}", status, body)))?;
+                    .map(|body| Error::Status(status.as_u16(), format!("Server returned unexpected status code {} and body {}", status, body)))?;
                 Err(err)
             }
         }
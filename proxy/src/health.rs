@@ -0,0 +1,56 @@
+use crate::client::Client;
+use crate::utils::HEALTHZ_PATH;
+use reqwest::{Method, StatusCode};
+use utility::{Error, Result};
+
+impl<'s> Client<'s> {
+    /// GetHealth checks the gateway healthz endpoint, returns Ok when the gateway is healthy
+    pub async fn get_health(&self) -> Result<()> {
+        let req = self
+            .new_request(Method::GET, HEALTHZ_PATH, "".into())
+            .map_err(|e| {
+                Error::Custom(format!(
+                    "can't check health of OpenFaaS on URL {}\nand debug reason {:?}",
+                    self.gateway.as_str(),
+                    e
+                ))
+            })?
+            .build()
+            .map_err(|e| {
+                Error::Custom(format!(
+                    "can't check health of OpenFaaS on URL {}\nand debug reason {:?}",
+                    self.gateway.as_str(),
+                    e
+                ))
+            })?;
+
+        let res = self.do_request(req).await.map_err(|e| {
+            Error::Custom(format!(
+                "can't check health of OpenFaaS on URL {}\nand debug reason {:?}",
+                self.gateway.as_str(),
+                e
+            ))
+        })?;
+
+        match res.status() {
+            StatusCode::OK => Ok(()),
+            StatusCode::UNAUTHORIZED => Err(Error::Status(
+                StatusCode::UNAUTHORIZED.as_u16(),
+                "unauthorized access, run \"faas-cli login\" to setup authentication for this server"
+                    .to_string(),
+            )),
+            status => {
+                let err = res.text().await.map(|body| {
+                    Error::Status(
+                        status.as_u16(),
+                        format!(
+                            "Server returned unexpected status code {} and body {}",
+                            status, body
+                        ),
+                    )
+                })?;
+                Err(err)
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::proxy::make_http_client;
+use crate::proxy::{make_http_client, TlsOptions};
 use reqwest::{Method, StatusCode, Url};
 use std::collections::HashMap;
 use std::io::Write;
@@ -14,38 +14,116 @@ pub async fn invoke_function(
     headers: &Vec<&str>,
     is_async: bool,
     http_method: Method,
-    tls_insecure: bool,
+    tls: &TlsOptions,
     namespace: &str,
 ) -> Result<String> {
+    let query = parse_query(query)?;
+    let request = FunctionRequest {
+        name,
+        namespace,
+        path: "",
+        method: http_method,
+        query: &query,
+        headers,
+        content_type,
+        body: bytes_in,
+        is_async,
+        local: false,
+    };
+    let res = invoke_function_response(gateway, &request, tls).await?;
+
+    match res.status {
+        StatusCode::ACCEPTED => {
+            std::io::stderr()
+                .write(b"Function submitted asynchronously.\n")
+                .map_err(|e| Error::Custom(e.to_string()))?;
+            Ok(String::new())
+        }
+        StatusCode::OK => Ok(res.body),
+        StatusCode::UNAUTHORIZED => Err(Error::Custom(format!(
+            "unauthorized access, run \"faas-cli login\" to setup authentication for this server"
+        ))),
+        status => Err(Error::Custom(format!(
+            "Server returned unexpected status code {} and body {}",
+            status, res.body
+        ))),
+    }
+}
+
+/// FunctionRequest is a request sent to a function through the gateway
+#[derive(Debug, Clone)]
+pub struct FunctionRequest<'a> {
+    pub name: &'a str,
+    pub namespace: &'a str,
+    /// path after the function name, such as /users/1
+    pub path: &'a str,
+    pub method: Method,
+    /// key and value of each query string parameter, they are encoded when sent
+    pub query: &'a [(String, String)],
+    /// key=value pairs of the request headers
+    pub headers: &'a Vec<&'a str>,
+    pub content_type: &'a str,
+    pub body: &'a [u8],
+    pub is_async: bool,
+    /// the gateway is the address of a function started by local-run, the path is sent to it
+    /// without the /function/<name> prefix
+    pub local: bool,
+}
+
+/// FunctionResponse is what a function answered, whatever its status code
+#[derive(Debug, Clone)]
+pub struct FunctionResponse {
+    pub status: StatusCode,
+    /// header names are lower case
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+/// invokes a function and returns its response, a status code other than 2xx is not an error
+pub async fn invoke_function_response(
+    gateway: &str,
+    request: &FunctionRequest<'_>,
+    tls: &TlsOptions,
+) -> Result<FunctionResponse> {
     let gateway = gateway.trim_end_matches('/');
 
     let disable_function_timeout = None;
-    let client = make_http_client(disable_function_timeout, tls_insecure)?;
+    let client = make_http_client(disable_function_timeout, tls)?;
 
-    let qs = build_query_string(query)?;
+    let header_map = parse_headers(request.headers)?;
 
-    let header_map = parse_headers(headers)?;
-
-    let function_endpoint = if is_async {
+    let function_endpoint = if request.is_async {
         "/async-function/".to_string()
     } else {
         "/function/".to_string()
     };
-    validate_http_method(http_method.clone())?;
-    let mut gateway_url = format!("{}{}{}", gateway, function_endpoint, name);
+    validate_http_method(request.method.clone())?;
+    let mut gateway_url = if request.local {
+        gateway.to_string()
+    } else {
+        format!("{}{}{}", gateway, function_endpoint, request.name)
+    };
 
-    if !namespace.is_empty() {
+    if !request.local && !request.namespace.is_empty() {
         gateway_url.push('.');
-        gateway_url.push_str(namespace);
+        gateway_url.push_str(request.namespace);
+    }
+    if !request.path.is_empty() {
+        if !request.path.starts_with('/') {
+            gateway_url.push('/');
+        }
+        gateway_url.push_str(request.path);
     }
-    gateway_url.push_str(qs.as_str());
     let url = Url::parse(gateway_url.as_str()).map_err(|e| Error::Custom(e.to_string()))?;
 
     let mut req = client
-        .request(http_method.clone(), url)
-        .header("Content-type", content_type);
-    if !bytes_in.is_empty() {
-        req = req.body(bytes_in.to_owned())
+        .request(request.method.clone(), url)
+        .header("Content-type", request.content_type);
+    if !request.query.is_empty() {
+        req = req.query(request.query);
+    }
+    if !request.body.is_empty() {
+        req = req.body(request.body.to_owned())
     }
 
     // Add additional headers to request
@@ -63,59 +141,46 @@ pub async fn invoke_function(
         .await
         .map_err(|_e| Error::Custom(format!("cannot connect to OpenFaaS on URL: {}", gateway)))?;
 
-    match res.status() {
-        StatusCode::ACCEPTED => {
-            std::io::stderr()
-                .write(b"Function submitted asynchronously.\n")
-                .map_err(|e| Error::Custom(e.to_string()))?;
-            Ok(String::new())
-        }
-        StatusCode::OK => {
-            let body = res.text().await.map_err(|e| {
-                Error::Custom(format!(
-                    "cannot read result from OpenFaaS on URL: {} {}",
-                    gateway, e
-                ))
-            })?;
-            Ok(body)
-        }
-        StatusCode::UNAUTHORIZED => Err(Error::Custom(format!(
-            "unauthorized access, run \"faas-cli login\" to setup authentication for this server"
-        ))),
-        status => {
-            let err = res.text().await.map(|body| {
-                Error::Custom(format!(
-                    "Server returned unexpected status code {} and body {}",
-                    status, body
-                ))
-            })?;
-            Err(err)
-        }
-    }
+    let status = res.status();
+    let headers = res
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.as_str().to_lowercase(),
+                String::from_utf8_lossy(value.as_bytes()).to_string(),
+            )
+        })
+        .collect();
+    let body = res.text().await.map_err(|e| {
+        Error::Custom(format!(
+            "cannot read result from OpenFaaS on URL: {} {}",
+            gateway, e
+        ))
+    })?;
+    Ok(FunctionResponse {
+        status,
+        headers,
+        body,
+    })
 }
 
-fn build_query_string(query: &Vec<&str>) -> Result<String> {
-    if !query.is_empty() {
-        let mut qs = "?".to_string();
-        for q in query {
-            qs.push_str(q);
-            qs.push('&');
-            if !q.contains('=') {
-                return Err(Error::Custom(
-                    "the --query flags must take the form of key=value (= not found)".to_string(),
-                ));
+/// splits the key=value flags of the query string into keys and values
+fn parse_query(query: &Vec<&str>) -> Result<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+    for q in query {
+        match q.split_once('=') {
+            Some((key, value)) if !value.is_empty() => {
+                pairs.push((key.to_string(), value.to_string()))
             }
-            if q.ends_with('=') {
+            _ => {
                 return Err(Error::Custom(
                     "the --query flags must take the form of key=value (= not found)".to_string(),
-                ));
+                ))
             }
         }
-        qs = qs.trim_end_matches('&').to_string();
-        Ok(qs)
-    } else {
-        Ok(String::new())
     }
+    Ok(pairs)
 }
 
 fn parse_headers(headers: &Vec<&str>) -> Result<HashMap<String, String>> {
@@ -6,10 +6,12 @@ pub mod delete;
 pub mod deploy;
 pub mod describe;
 pub mod function_store;
+pub mod health;
 pub mod invoke;
 pub mod list;
 pub mod logs;
 pub mod namespace;
+pub mod oauth2;
 pub mod proxy;
 pub mod scale;
 pub mod secret;
@@ -1,13 +1,58 @@
 use crate::client::Client;
 use crate::utils::LOGS_PATH;
+use chrono::{DateTime, FixedOffset};
+use futures_util::stream::{self, Stream, StreamExt};
 use reqwest::{Method, StatusCode};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
 use utility::faas_provider::logs::{Message, Request};
 use utility::{Error, Result};
 
+/// how many times in a row the log stream is reopened after a transient disconnect
+pub const LOGS_MAX_RECONNECTS: u32 = 5;
+/// how long to wait before reopening the log stream
+const LOGS_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
 impl<'s> Client<'s> {
-    ///return stream for the logs
+    ///return all the logs of the request, use get_logs_stream to handle them as they arrive
     pub async fn get_logs(&self, params: Request<'s>) -> Result<Vec<Message>> {
+        let mut messages = Vec::new();
+        let logs = self.get_logs_stream(params);
+        futures_util::pin_mut!(logs);
+        while let Some(msg) = logs.next().await {
+            messages.push(msg?);
+        }
+        Ok(messages)
+    }
+
+    ///return a stream of the logs, the newline-delimited JSON body is decoded line by line as it
+    /// arrives and the request is reopened from the last seen timestamp after a transient disconnect
+    pub fn get_logs_stream<'c>(
+        &'c self,
+        params: Request<'c>,
+    ) -> impl Stream<Item = Result<Message>> + 'c {
+        let state = LogStream {
+            client: self,
+            params,
+            response: None,
+            buffer: Vec::new(),
+            pending: VecDeque::new(),
+            array_body: None,
+            last_timestamp: None,
+            seen_at_last: Vec::new(),
+            resume_after: None,
+            resume_seen: Vec::new(),
+            reconnecting: false,
+            reconnects: 0,
+            done: false,
+        };
+        stream::unfold(state, |mut state| async move {
+            let item = state.next_message().await;
+            item.map(|item| (item, state))
+        })
+    }
+
+    async fn open_logs(&self, params: &Request<'_>) -> Result<reqwest::Response> {
         let url = format!(
             "{}/{}",
             self.gateway.as_str().trim_end_matches('/'),
@@ -16,7 +61,7 @@ impl<'s> Client<'s> {
         let mut url = url::Url::parse(url.as_str())?;
         {
             let mut url_query = url.query_pairs_mut();
-            let params = req_as_query_values(&params);
+            let params = req_as_query_values(params);
             url_query.extend_pairs(params);
         }
         let log_request = self
@@ -39,18 +84,9 @@ impl<'s> Client<'s> {
 
         let res = self.do_request(log_request).await?;
         match res.status() {
-            StatusCode::OK => {
-                let body = res.text().await?;
-                if !body.is_empty() {
-                    let status: Vec<Message> = serde_json::from_str(body.as_str())
-                        .map_err(|e| Error::Custom( format!("can't read logs from OpenFaaS on URL {}\nand debug reason {:?}", self.gateway.as_str(), e)))?;
-                    Ok(status)
-                }else {
-                    Ok(vec![])
-                }
-            },
+            StatusCode::OK => Ok(res),
             StatusCode::UNAUTHORIZED => {
-                Err(Error::Custom(format!("unauthorized access, run \"faas-cli login\" to setup authentication for this server")))
+                Err(Error::Custom("unauthorized access, run \"faas-cli login\" to setup authentication for this server".to_string()))
             },
             status => {
                 let err = res.text().await
@@ -61,6 +97,196 @@ impl<'s> Client<'s> {
     }
 }
 
+/// state of a log stream between two polled messages
+struct LogStream<'c, 's> {
+    client: &'c Client<'s>,
+    params: Request<'c>,
+    response: Option<reqwest::Response>,
+    /// bytes received after the last complete line
+    buffer: Vec<u8>,
+    /// decoded messages not returned yet
+    pending: VecDeque<Message>,
+    /// whether the body is a single JSON array instead of newline-delimited JSON
+    array_body: Option<bool>,
+    last_timestamp: Option<DateTime<FixedOffset>>,
+    /// the messages returned at last_timestamp
+    seen_at_last: Vec<MessageKey>,
+    /// messages before this timestamp were already returned before the reconnect
+    resume_after: Option<DateTime<FixedOffset>>,
+    /// the messages at resume_after returned before the reconnect, since has a one second
+    /// resolution so the reopened stream repeats them among new ones of the same timestamp
+    resume_seen: Vec<MessageKey>,
+    reconnecting: bool,
+    reconnects: u32,
+    done: bool,
+}
+
+impl<'c, 's> LogStream<'c, 's> {
+    async fn next_message(&mut self) -> Option<Result<Message>> {
+        loop {
+            if let Some(msg) = self.pending.pop_front() {
+                if self.is_duplicate(&msg) {
+                    continue;
+                }
+                self.reconnects = 0;
+                if let Ok(timestamp) = DateTime::parse_from_rfc3339(msg.timestamp.as_str()) {
+                    if self.last_timestamp != Some(timestamp) {
+                        self.last_timestamp = Some(timestamp);
+                        self.seen_at_last.clear();
+                    }
+                    self.seen_at_last.push(message_key(&msg));
+                }
+                return Some(Ok(msg));
+            }
+            if self.done {
+                return None;
+            }
+            if self.array_body.is_none() {
+                self.array_body = self
+                    .buffer
+                    .iter()
+                    .find(|b| !b.is_ascii_whitespace())
+                    .map(|b| *b == b'[');
+            }
+            let pos = match self.array_body {
+                // a JSON array body is only decoded once it is complete
+                Some(true) => None,
+                _ => self.buffer.iter().position(|b| *b == b'\n'),
+            };
+            if let Some(pos) = pos {
+                let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+                if let Err(e) = self.decode_line(&line) {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                continue;
+            }
+
+            let response = match self.response.as_mut() {
+                Some(response) => response,
+                None => match self.client.open_logs(&self.params).await {
+                    Ok(response) => self.response.insert(response),
+                    Err(Error::Reqwest(e)) if self.reconnecting => {
+                        if let Some(err) = self.retry(e).await {
+                            return Some(Err(err));
+                        }
+                        continue;
+                    }
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                },
+            };
+
+            match response.chunk().await {
+                Ok(Some(chunk)) => self.buffer.extend_from_slice(&chunk),
+                Ok(None) => {
+                    // the body may end without a trailing newline
+                    self.response = None;
+                    let line: Vec<u8> = self.buffer.drain(..).collect();
+                    if let Err(e) = self.decode_line(&line) {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                    if !self.params.follow {
+                        self.done = true;
+                    } else if let Some(err) = self.retry("the server closed the stream").await {
+                        // a followed stream only ends when the client stops reading it
+                        return Some(Err(err));
+                    }
+                }
+                Err(e) => {
+                    if let Some(err) = self.retry(e).await {
+                        return Some(Err(err));
+                    }
+                }
+            }
+        }
+    }
+
+    /// prepares the request to resume after the last seen message, returns the error when the
+    /// reconnects are exhausted
+    async fn retry(&mut self, e: impl std::fmt::Debug) -> Option<Error> {
+        self.response = None;
+        self.buffer.clear();
+        self.array_body = None;
+        if self.reconnects >= LOGS_MAX_RECONNECTS {
+            self.done = true;
+            return Some(Error::Custom(format!(
+                "log stream from OpenFaaS on URL {} disconnected {} times\nand debug reason {:?}",
+                self.client.gateway.as_str(),
+                self.reconnects,
+                e
+            )));
+        }
+        self.reconnects += 1;
+        self.reconnecting = true;
+        if let Some(last) = self.last_timestamp {
+            self.params.since = Some(last.timestamp());
+            self.params.tail = 0;
+            self.resume_after = Some(last);
+            self.resume_seen = self.seen_at_last.clone();
+        }
+        tokio::time::sleep(LOGS_RECONNECT_DELAY).await;
+        None
+    }
+
+    /// decodes one line of the body, or the whole body when it is a JSON array of messages
+    fn decode_line(&mut self, line: &[u8]) -> Result<()> {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(());
+        }
+        let map_err = |e: serde_json::Error| {
+            Error::Custom(format!(
+                "can't read logs from OpenFaaS on URL {}\nand debug reason {:?}",
+                self.client.gateway.as_str(),
+                e
+            ))
+        };
+        if line.starts_with('[') {
+            let messages: Vec<Message> = serde_json::from_str(line).map_err(map_err)?;
+            self.pending.extend(messages);
+        } else {
+            let message: Message = serde_json::from_str(line).map_err(map_err)?;
+            self.pending.push_back(message);
+        }
+        Ok(())
+    }
+
+    /// whether the message was already returned before the stream was reopened
+    fn is_duplicate(&mut self, msg: &Message) -> bool {
+        let (resume_after, timestamp) = match (
+            self.resume_after,
+            DateTime::parse_from_rfc3339(msg.timestamp.as_str()),
+        ) {
+            (Some(resume_after), Ok(timestamp)) => (resume_after, timestamp),
+            _ => return false,
+        };
+        if timestamp != resume_after {
+            return timestamp < resume_after;
+        }
+        // each message seen at the last timestamp is repeated once, the others are new
+        let key = message_key(msg);
+        match self.resume_seen.iter().position(|seen| *seen == key) {
+            Some(i) => {
+                self.resume_seen.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// (name, instance, text) telling apart the messages of a timestamp
+type MessageKey = (String, String, String);
+
+fn message_key(msg: &Message) -> MessageKey {
+    (msg.name.clone(), msg.instance.clone(), msg.text.clone())
+}
+
 fn req_as_query_values(r: &Request) -> HashMap<String, String> {
     let mut query = HashMap::new();
     query.insert("name".to_string(), r.name.to_string());
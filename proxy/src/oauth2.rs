@@ -0,0 +1,386 @@
+use config::config_file::{AuthConfig, OAuth2Config};
+use rand::Rng;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, Instant};
+use utility::{Error, Result};
+
+///CLIENT_CREDENTIALS_GRANT oauth2 grant which can be run again when its token expires
+pub const CLIENT_CREDENTIALS_GRANT: &str = "client_credentials";
+///REFRESH_TOKEN_GRANT oauth2 grant exchanging a refresh token for a new access token
+pub const REFRESH_TOKEN_GRANT: &str = "refresh_token";
+///AUTHORIZATION_CODE_GRANT oauth2 grant exchanging the code received by the redirect URL
+pub const AUTHORIZATION_CODE_GRANT: &str = "authorization_code";
+///DEVICE_CODE_GRANT oauth2 device authorization grant (RFC 8628)
+pub const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+///how often the token endpoint is polled when the IdP does not set an interval
+pub const DEFAULT_DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+///client secret used to renew a client_credentials token when no credential helper keeps it
+pub const CLIENT_SECRET_ENVIRONMENT: &str = "OPENFAAS_CLIENT_SECRET";
+///a token is renewed when it expires in less than this many seconds
+pub const TOKEN_EXPIRY_LEEWAY: i64 = 60;
+
+#[derive(Serialize, Debug)]
+struct ClientCredentialsReq<'s> {
+    pub client_id: &'s str,     // `json:"client_id"`
+    pub client_secret: &'s str, // `json:"client_secret"`
+    pub audience: &'s str,      // `json:"audience"`
+    pub grant_type: &'s str,    // `json:"grant_type"`
+}
+
+///token returned by the token endpoint of an IdP
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct TokenResponse {
+    #[serde(default)]
+    pub access_token: String, // `json:"access_token"`
+    #[serde(default)]
+    pub id_token: String, // `json:"id_token"`
+    #[serde(default)]
+    pub refresh_token: String, // `json:"refresh_token"`
+    #[serde(default)]
+    pub scope: String, // `json:"scope"`
+    pub expires_in: Option<i64>, //`json:"expires_in"`
+    #[serde(default)]
+    pub token_type: String, // `json:"token_type"`
+}
+
+impl TokenResponse {
+    ///unix time in seconds when the token expires, 0 when the IdP did not return expires_in
+    pub fn expiry(&self, now: i64) -> i64 {
+        match self.expires_in {
+            Some(expires_in) if expires_in > 0 => now + expires_in,
+            _ => 0,
+        }
+    }
+}
+
+///returns the current unix time in seconds
+pub fn now_unix() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+///returns true when the oauth2 token expires within TOKEN_EXPIRY_LEEWAY seconds of now,
+///a token with an unknown expiry is never considered expired
+pub fn is_expiring(oauth2: &OAuth2Config, now: i64) -> bool {
+    oauth2.expiry > 0 && oauth2.expiry - TOKEN_EXPIRY_LEEWAY <= now
+}
+
+///returns true when the stored details allow getting a new token without the user
+pub fn can_renew(oauth2: &OAuth2Config) -> bool {
+    !oauth2.token_url.is_empty()
+        && (!oauth2.refresh_token.is_empty() || oauth2.grant == CLIENT_CREDENTIALS_GRANT)
+}
+
+///runs the client_credentials grant against the token endpoint
+pub async fn client_credentials_token(
+    token_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    audience: &str,
+) -> Result<TokenResponse> {
+    let body = ClientCredentialsReq {
+        client_id,
+        client_secret,
+        audience,
+        grant_type: CLIENT_CREDENTIALS_GRANT,
+    };
+    let body_bytes = serde_json::to_string(&body)
+        .map_err(|e| Error::Custom(format!("unable to serialize {:?} ,with error {}", body, e)))?;
+    let url = reqwest::Url::parse(token_url).map_err(|e| Error::Custom(e.to_string()))?;
+
+    let client = reqwest::Client::new();
+    let req = client
+        .request(Method::POST, url)
+        .header("Content-Type", "application/json")
+        .body(body_bytes);
+
+    send_token_request(req, token_url).await
+}
+
+///exchanges a refresh token for a new access token
+pub async fn refresh_token(
+    token_url: &str,
+    refresh_token: &str,
+    client_id: &str,
+    client_secret: &str,
+) -> Result<TokenResponse> {
+    let url = reqwest::Url::parse(token_url).map_err(|e| Error::Custom(e.to_string()))?;
+    let mut form = vec![
+        ("grant_type", REFRESH_TOKEN_GRANT),
+        ("refresh_token", refresh_token),
+        ("client_id", client_id),
+    ];
+    if !client_secret.is_empty() {
+        form.push(("client_secret", client_secret));
+    }
+
+    let client = reqwest::Client::new();
+    let req = client.request(Method::POST, url).form(&form);
+
+    send_token_request(req, token_url).await
+}
+
+///PKCE verifier and its S256 challenge (RFC 7636)
+#[derive(Debug, Clone)]
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Pkce {
+    ///returns a random verifier with its challenge
+    pub fn new() -> Pkce {
+        let verifier = random_string(64);
+        let challenge = pkce_challenge(verifier.as_str());
+        Pkce {
+            verifier,
+            challenge,
+        }
+    }
+}
+
+///returns a random string of unreserved URL characters, used for PKCE verifiers and states
+pub fn random_string(len: usize) -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-._~";
+    let mut rng = rand::thread_rng();
+    (0..len)
+        .map(|_| CHARS[rng.gen_range(0, CHARS.len())] as char)
+        .collect()
+}
+
+///returns the S256 challenge of a PKCE verifier
+pub fn pkce_challenge(verifier: &str) -> String {
+    let digest = Sha256::digest(verifier.as_bytes());
+    base64::encode_config(digest, base64::URL_SAFE_NO_PAD)
+}
+
+///exchanges the code of the authorization code grant for a token
+pub async fn exchange_code(
+    token_url: &str,
+    code: &str,
+    redirect_uri: &str,
+    client_id: &str,
+    client_secret: &str,
+    code_verifier: &str,
+) -> Result<TokenResponse> {
+    let url = reqwest::Url::parse(token_url).map_err(|e| Error::Custom(e.to_string()))?;
+    let mut form = vec![
+        ("grant_type", AUTHORIZATION_CODE_GRANT),
+        ("code", code),
+        ("redirect_uri", redirect_uri),
+        ("client_id", client_id),
+        ("code_verifier", code_verifier),
+    ];
+    if !client_secret.is_empty() {
+        form.push(("client_secret", client_secret));
+    }
+
+    let client = reqwest::Client::new();
+    let req = client.request(Method::POST, url).form(&form);
+
+    send_token_request(req, token_url).await
+}
+
+///response of the device authorization endpoint
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct DeviceAuthorization {
+    pub device_code: String, // `json:"device_code"`
+    pub user_code: String,   // `json:"user_code"`
+    #[serde(default)]
+    pub verification_uri: String, // `json:"verification_uri"`
+    #[serde(default)]
+    pub verification_uri_complete: String, // `json:"verification_uri_complete"`
+    #[serde(default)]
+    pub expires_in: u64, // `json:"expires_in"`
+    pub interval: Option<u64>, // `json:"interval"`
+}
+
+impl DeviceAuthorization {
+    ///how often the token endpoint may be polled
+    pub fn poll_interval(&self) -> Duration {
+        self.interval
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_DEVICE_POLL_INTERVAL)
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct TokenError {
+    #[serde(default)]
+    pub error: String, // `json:"error"`
+    #[serde(default)]
+    pub error_description: String, // `json:"error_description"`
+}
+
+///starts the device authorization grant, the user then approves the returned user code
+pub async fn request_device_code(
+    device_url: &str,
+    client_id: &str,
+    scope: &str,
+    audience: &str,
+) -> Result<DeviceAuthorization> {
+    let url = reqwest::Url::parse(device_url).map_err(|e| Error::Custom(e.to_string()))?;
+    let mut form = vec![("client_id", client_id), ("scope", scope)];
+    if !audience.is_empty() {
+        form.push(("audience", audience));
+    }
+
+    let client = reqwest::Client::new();
+    let (status_code, body) =
+        post_token_request(client.request(Method::POST, url).form(&form), device_url).await?;
+    if status_code != StatusCode::OK {
+        return Err(Error::Custom(format!(
+            "cannot start device authorization, code: {}.\nResponse: {}",
+            status_code, body
+        )));
+    }
+    serde_json::from_str(body.as_str()).map_err(|e| {
+        Error::Custom(format!(
+            "can't deserialize {} to DeviceAuthorization and the error {}",
+            body, e
+        ))
+    })
+}
+
+///polls the token endpoint until the user approves the device code, it is denied or it expires
+pub async fn poll_device_token(
+    token_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    device: &DeviceAuthorization,
+    interval: Duration,
+) -> Result<TokenResponse> {
+    let url = reqwest::Url::parse(token_url).map_err(|e| Error::Custom(e.to_string()))?;
+    let mut form = vec![
+        ("grant_type", DEVICE_CODE_GRANT),
+        ("device_code", device.device_code.as_str()),
+        ("client_id", client_id),
+    ];
+    // confidential clients authenticate every poll of the token endpoint
+    if !client_secret.is_empty() {
+        form.push(("client_secret", client_secret));
+    }
+    let deadline = if device.expires_in > 0 {
+        Some(Instant::now() + Duration::from_secs(device.expires_in))
+    } else {
+        None
+    };
+
+    let client = reqwest::Client::new();
+    let mut interval = interval;
+    loop {
+        if deadline.map_or(false, |d| Instant::now() >= d) {
+            return Err(Error::Custom(
+                "the device code expired before it was approved".to_string(),
+            ));
+        }
+        tokio::time::sleep(interval).await;
+
+        let req = client.request(Method::POST, url.clone()).form(&form);
+        let (status_code, body) = post_token_request(req, token_url).await?;
+        if status_code == StatusCode::OK {
+            return parse_token(body, token_url);
+        }
+
+        let token_error: TokenError = serde_json::from_str(body.as_str()).unwrap_or_default();
+        match token_error.error.as_str() {
+            "authorization_pending" => {}
+            "slow_down" => interval += Duration::from_secs(5),
+            _ => {
+                return Err(Error::Custom(format!(
+                    "device authorization failed, code: {}.\nResponse: {} {}",
+                    status_code, token_error.error, token_error.error_description
+                )))
+            }
+        }
+    }
+}
+
+async fn post_token_request(
+    req: reqwest::RequestBuilder,
+    token_url: &str,
+) -> Result<(StatusCode, String)> {
+    let res = req
+        .send()
+        .await
+        .map_err(|e| Error::Custom(format!("cannot POST to {} and the error {}", token_url, e)))?;
+
+    let status_code = res.status();
+    let token_data = res
+        .text()
+        .await
+        .map_err(|e| Error::Custom(format!("cannot read body and the error {}", e)))?;
+    Ok((status_code, token_data))
+}
+
+async fn send_token_request(
+    req: reqwest::RequestBuilder,
+    token_url: &str,
+) -> Result<TokenResponse> {
+    let (status_code, token_data) = post_token_request(req, token_url).await?;
+    if status_code != StatusCode::OK {
+        return Err(Error::Custom(format!(
+            "cannot authenticate, code: {}.\nResponse: {}",
+            status_code, token_data
+        )));
+    }
+    parse_token(token_data, token_url)
+}
+
+fn parse_token(token_data: String, token_url: &str) -> Result<TokenResponse> {
+    let token: TokenResponse = serde_json::from_str(token_data.as_str()).map_err(|e| {
+        Error::Custom(format!(
+            "can't deserialize {} to TokenResponse and the error {}",
+            token_data, e
+        ))
+    })?;
+    if token.access_token.is_empty() {
+        return Err(Error::Custom(format!(
+            "no access_token returned by {}",
+            token_url
+        )));
+    }
+    Ok(token)
+}
+
+///gets a new token for an expired auth config, the returned config keeps the previous
+///refresh token when the IdP does not rotate it
+pub async fn renew_auth_config(auth: &AuthConfig) -> Result<AuthConfig> {
+    let mut oauth2 = auth.oauth2.clone().unwrap_or_default();
+    if oauth2.client_secret.is_empty() {
+        oauth2.client_secret = std::env::var(CLIENT_SECRET_ENVIRONMENT).unwrap_or_default();
+    }
+    let token = if !oauth2.refresh_token.is_empty() {
+        refresh_token(
+            oauth2.token_url.as_str(),
+            oauth2.refresh_token.as_str(),
+            oauth2.client_id.as_str(),
+            oauth2.client_secret.as_str(),
+        )
+        .await?
+    } else if oauth2.grant == CLIENT_CREDENTIALS_GRANT {
+        client_credentials_token(
+            oauth2.token_url.as_str(),
+            oauth2.client_id.as_str(),
+            oauth2.client_secret.as_str(),
+            oauth2.audience.as_str(),
+        )
+        .await?
+    } else {
+        return Err(Error::Custom(format!(
+            "the token for {} expired and can't be renewed",
+            auth.gateway
+        )));
+    };
+
+    let mut renewed = auth.clone();
+    oauth2.client_secret = auth.oauth2.clone().unwrap_or_default().client_secret;
+    oauth2.expiry = token.expiry(now_unix());
+    if !token.refresh_token.is_empty() {
+        oauth2.refresh_token = token.refresh_token;
+    }
+    renewed.token = token.access_token;
+    renewed.oauth2 = Some(oauth2);
+    Ok(renewed)
+}
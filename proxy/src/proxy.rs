@@ -1,44 +1,102 @@
-use reqwest::Client;
+use reqwest::{Certificate, Client, ClientBuilder, Identity};
 use std::option::Option::Some;
 use std::time::Duration;
-use utility::Result;
+use utility::{Error, Result};
+
+///TLSOptions TLS settings of the HTTP clients talking to the gateway
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TlsOptions {
+    ///skip the validation of the server certificate
+    pub insecure: bool,
+    ///path of a PEM bundle with extra certificate authorities to trust
+    pub ca_file: String,
+    ///path of a PEM client certificate for gateways requiring mutual TLS
+    pub cert_file: String,
+    ///path of the PEM (PKCS#8) private key of the client certificate
+    pub key_file: String,
+}
+
+impl TlsOptions {
+    ///options that only set whether the server certificate is validated
+    pub fn insecure(insecure: bool) -> TlsOptions {
+        TlsOptions {
+            insecure,
+            ..Default::default()
+        }
+    }
+}
 
 //makes a HTTP client with good defaults for timeouts.
-pub fn make_http_client(timeout: Option<Duration>, _tls_insecure: bool) -> Result<Client> {
-    return make_http_client_with_string(timeout, _tls_insecure, false);
+pub fn make_http_client(timeout: Option<Duration>, tls: &TlsOptions) -> Result<Client> {
+    return make_http_client_with_string(timeout, tls, false);
 }
 
 // make_http_client_with_String makes a HTTP client with good defaults for timeouts.
 fn make_http_client_with_string(
     timeout: Option<Duration>,
-    tls_insecure: bool,
+    tls: &TlsOptions,
     _disable_keep_alive: bool,
 ) -> Result<Client> {
     let mut client = reqwest::ClientBuilder::new();
 
-    if timeout.is_some() || tls_insecure {
-        // tr := &http.Transport{
-        //     Proxy:             http.ProxyFromEnvironment,
-        //     disable_keep_alive: disable_keep_alive,
-        // }
-
-        if let Some(time) = timeout {
-            client = client.timeout(time);
-            // tr.DialContext = (&net.Dialer{
-            //     Timeout: *timeout,
-            // }).DialContext
-            // tr.IdleConnTimeout = 120 * time.Millisecond
-            // tr.ExpectContinueTimeout = 1500 * time.Millisecond
+    if let Some(time) = timeout {
+        client = client.timeout(time);
+        // tr.IdleConnTimeout = 120 * time.Millisecond
+        // tr.ExpectContinueTimeout = 1500 * time.Millisecond
+    }
+    let client = with_tls_options(client, tls)?;
+
+    Ok(client.build()?)
+}
+
+fn with_tls_options(mut client: ClientBuilder, tls: &TlsOptions) -> Result<ClientBuilder> {
+    if tls.insecure {
+        client = client.danger_accept_invalid_certs(true);
+    }
+
+    if !tls.ca_file.is_empty() {
+        let pem = std::fs::read(tls.ca_file.as_str())
+            .map_err(|e| Error::Custom(format!("can't read CA file {} : {}", tls.ca_file, e)))?;
+        let certs = Certificate::from_pem_bundle(pem.as_slice())
+            .map_err(|e| Error::Custom(format!("can't parse CA file {} : {}", tls.ca_file, e)))?;
+        if certs.is_empty() {
+            return Err(Error::Custom(format!(
+                "no PEM certificate found in CA file {}",
+                tls.ca_file
+            )));
+        }
+        for cert in certs {
+            client = client.add_root_certificate(cert);
         }
+    }
 
-        // if tlsInsecure {
-        //     tr.TLSClientConfig = &tls.Config{InsecureSkipVerify: tlsInsecure}
-        // }
-        //
-        // tr.disable_keep_alive = disable_keep_alive
-        //
-        // client.Transport = tr
+    match (tls.cert_file.is_empty(), tls.key_file.is_empty()) {
+        (true, true) => {}
+        (false, false) => {
+            let cert = std::fs::read(tls.cert_file.as_str()).map_err(|e| {
+                Error::Custom(format!(
+                    "can't read client certificate {} : {}",
+                    tls.cert_file, e
+                ))
+            })?;
+            let key = std::fs::read(tls.key_file.as_str()).map_err(|e| {
+                Error::Custom(format!("can't read client key {} : {}", tls.key_file, e))
+            })?;
+            let identity =
+                Identity::from_pkcs8_pem(cert.as_slice(), key.as_slice()).map_err(|e| {
+                    Error::Custom(format!(
+                        "can't load client certificate {} with key {} : {}",
+                        tls.cert_file, tls.key_file, e
+                    ))
+                })?;
+            client = client.identity(identity);
+        }
+        _ => {
+            return Err(Error::Custom(
+                "a client certificate and its key must be set together".to_string(),
+            ))
+        }
     }
 
-    Ok(client.build()?)
+    Ok(client)
 }
@@ -9,6 +9,7 @@ pub const SCALE_PATH: &str = "/system/scale-function";
 pub const LOGS_PATH: &str = "/system/logs";
 pub const SECRET_ENDPOINT: &str = "/system/secrets";
 pub const INFO_ENDPOINT: &str = "/system/info";
+pub const HEALTHZ_PATH: &str = "/healthz";
 
 pub fn create_system_endpoint(gateway: &str, namespace: &str) -> Result<String> {
     let mut url = url::Url::parse(gateway).map_err(|e| Error::Custom(format!("{:?}", e)))?;
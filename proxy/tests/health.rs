@@ -0,0 +1,21 @@
+use proxy::client::Client;
+use proxy::utils::HEALTHZ_PATH;
+
+#[tokio::test]
+async fn test_get_health() {
+    let add = format!("http://{}", mockito::server_address().to_string());
+    let auth = proxy::TestAuth {};
+    let client = Client::new(Box::new(&auth), add.as_str()).unwrap();
+
+    let _mok = mockito::mock("GET", HEALTHZ_PATH).with_status(200).create();
+    assert!(client.get_health().await.is_ok());
+    drop(_mok);
+
+    let _mok = mockito::mock("GET", HEALTHZ_PATH)
+        .with_status(503)
+        .with_body("not ready")
+        .create();
+    let res = client.get_health().await;
+    assert!(res.is_err());
+    assert!(format!("{:?}", res).contains("503"));
+}
@@ -0,0 +1,75 @@
+use mockito::Matcher;
+use proxy::invoke::{invoke_function_response, FunctionRequest};
+use proxy::proxy::TlsOptions;
+use reqwest::{Method, StatusCode};
+
+#[tokio::test]
+async fn test_invoke_function_response() {
+    let gateway = format!("http://{}", mockito::server_address());
+    let _mok = mockito::mock("GET", "/function/echo.dev/users/1")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("verbose".into(), "1".into()),
+            Matcher::UrlEncoded("q".into(), "a b&c".into()),
+            Matcher::UrlEncoded("empty".into(), "".into()),
+        ]))
+        .match_header("x-test", "yes")
+        .with_status(404)
+        .with_header("X-Request-Id", "42")
+        .with_body("not found")
+        .create();
+
+    let query = vec![
+        ("verbose".to_string(), "1".to_string()),
+        ("q".to_string(), "a b&c".to_string()),
+        ("empty".to_string(), String::new()),
+    ];
+    let headers = vec!["X-Test=yes"];
+    let request = FunctionRequest {
+        name: "echo",
+        namespace: "dev",
+        path: "users/1",
+        method: Method::GET,
+        query: &query,
+        headers: &headers,
+        content_type: "text/plain",
+        body: &[],
+        is_async: false,
+        local: false,
+    };
+    let response = invoke_function_response(gateway.as_str(), &request, &TlsOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert_eq!(response.headers["x-request-id"], "42");
+    assert_eq!(response.body, "not found");
+}
+
+#[tokio::test]
+async fn test_invoke_local_function_response() {
+    let gateway = format!("http://{}", mockito::server_address());
+    let _mok = mockito::mock("POST", "/local/users")
+        .match_body("hi")
+        .with_status(201)
+        .with_body("created")
+        .create();
+
+    let request = FunctionRequest {
+        name: "echo",
+        namespace: "dev",
+        path: "/local/users",
+        method: Method::POST,
+        query: &[],
+        headers: &vec![],
+        content_type: "text/plain",
+        body: b"hi",
+        is_async: false,
+        local: true,
+    };
+    let response = invoke_function_response(gateway.as_str(), &request, &TlsOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert_eq!(response.body, "created");
+}
@@ -1,3 +1,4 @@
+use futures_util::StreamExt;
 use mockito::Matcher;
 use proxy::client::{Client, ClientAuth};
 use proxy::utils::LOGS_PATH;
@@ -146,3 +147,165 @@ async fn test_get_logs_bad_request() {
     assert!(res.is_err());
     assert!(format!("{:?}", res).contains("Server returned unexpected status code"));
 }
+
+#[tokio::test]
+async fn test_get_logs_stream_ndjson() {
+    let expected = vec![
+        Message {
+            name: "stream1".into(),
+            timestamp: "2021-06-01T10:00:00.000000001Z".into(),
+            text: "first".into(),
+            ..Default::default()
+        },
+        Message {
+            name: "stream1".into(),
+            timestamp: "2021-06-01T10:00:01.000000001Z".into(),
+            text: "second".into(),
+            ..Default::default()
+        },
+    ];
+    // newline-delimited JSON, the last line has no trailing newline
+    let body = expected
+        .iter()
+        .map(|msg| serde_json::to_string(msg).unwrap())
+        .collect::<Vec<String>>()
+        .join("\n");
+    let params = Request {
+        name: "streamFunc",
+        ..Default::default()
+    };
+
+    let add = format!("http://{}", mockito::server_address().to_string());
+    let _mok = mockito::mock("GET", LOGS_PATH)
+        .match_query(Matcher::UrlEncoded("name".into(), params.name.into()))
+        .with_status(200)
+        .with_body(body)
+        .create();
+    let auth = proxy::TestAuth {};
+    let client = Client::new(Box::new(&auth), add.as_str()).unwrap();
+
+    let logs = client.get_logs_stream(params);
+    futures_util::pin_mut!(logs);
+    let mut messages = Vec::new();
+    while let Some(msg) = logs.next().await {
+        messages.push(msg.unwrap());
+    }
+    assert_eq!(messages, expected);
+}
+
+#[tokio::test]
+async fn test_get_logs_stream_follow_reconnects() {
+    let message = |timestamp: &str, text: &str| Message {
+        name: "followFunc".into(),
+        timestamp: timestamp.into(),
+        text: text.into(),
+        ..Default::default()
+    };
+    let first = message("2021-06-01T10:00:00Z", "first");
+    let second = message("2021-06-01T10:00:01Z", "second");
+    let params = Request {
+        name: "followFunc",
+        follow: true,
+        ..Default::default()
+    };
+
+    let add = format!("http://{}", mockito::server_address().to_string());
+    // the gateway closes the followed stream, it's reopened from the last timestamp. The mock of
+    // the reopened request is created first to be matched before the one of the first request
+    let _resumed = mockito::mock("GET", LOGS_PATH)
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("name".into(), params.name.into()),
+            Matcher::UrlEncoded("since".into(), "1622541600".into()),
+        ]))
+        .with_status(200)
+        .with_body(format!(
+            "{}\n{}\n",
+            serde_json::to_string(&first).unwrap(),
+            serde_json::to_string(&second).unwrap()
+        ))
+        .create();
+    let _mok = mockito::mock("GET", LOGS_PATH)
+        .match_query(Matcher::UrlEncoded("name".into(), params.name.into()))
+        .with_status(200)
+        .with_body(format!("{}\n", serde_json::to_string(&first).unwrap()))
+        .create();
+    let auth = proxy::TestAuth {};
+    let client = Client::new(Box::new(&auth), add.as_str()).unwrap();
+
+    let logs = client.get_logs_stream(params);
+    futures_util::pin_mut!(logs);
+    assert_eq!(logs.next().await.unwrap().unwrap(), first);
+    assert_eq!(logs.next().await.unwrap().unwrap(), second);
+    _mok.assert();
+    _resumed.assert();
+}
+
+#[tokio::test]
+async fn test_get_logs_stream_reconnect_same_timestamp() {
+    let message = |timestamp: &str, text: &str| Message {
+        name: "sameSecondFunc".into(),
+        timestamp: timestamp.into(),
+        text: text.into(),
+        ..Default::default()
+    };
+    let line = |msg: &Message| format!("{}\n", serde_json::to_string(msg).unwrap());
+    let first = message("2021-06-01T10:00:00Z", "first");
+    let second = message("2021-06-01T10:00:00Z", "second");
+    let third = message("2021-06-01T10:00:00Z", "third");
+    let fourth = message("2021-06-01T10:00:01Z", "fourth");
+    let params = Request {
+        name: "sameSecondFunc",
+        follow: true,
+        ..Default::default()
+    };
+
+    let add = format!("http://{}", mockito::server_address().to_string());
+    // the reopened stream repeats the lines of the last timestamp, a new line sharing it is kept
+    let _resumed = mockito::mock("GET", LOGS_PATH)
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("name".into(), params.name.into()),
+            Matcher::UrlEncoded("since".into(), "1622541600".into()),
+        ]))
+        .with_status(200)
+        .with_body(
+            line(&first) + line(&second).as_str() + line(&third).as_str() + line(&fourth).as_str(),
+        )
+        .create();
+    let _mok = mockito::mock("GET", LOGS_PATH)
+        .match_query(Matcher::UrlEncoded("name".into(), params.name.into()))
+        .with_status(200)
+        .with_body(line(&first) + line(&second).as_str())
+        .create();
+    let auth = proxy::TestAuth {};
+    let client = Client::new(Box::new(&auth), add.as_str()).unwrap();
+
+    let logs = client.get_logs_stream(params);
+    futures_util::pin_mut!(logs);
+    assert_eq!(logs.next().await.unwrap().unwrap(), first);
+    assert_eq!(logs.next().await.unwrap().unwrap(), second);
+    assert_eq!(logs.next().await.unwrap().unwrap(), third);
+    assert_eq!(logs.next().await.unwrap().unwrap(), fourth);
+}
+
+#[tokio::test]
+async fn test_get_logs_stream_bad_line() {
+    let params = Request {
+        name: "streamBadFunc",
+        ..Default::default()
+    };
+
+    let add = format!("http://{}", mockito::server_address().to_string());
+    let _mok = mockito::mock("GET", LOGS_PATH)
+        .match_query(Matcher::UrlEncoded("name".into(), params.name.into()))
+        .with_status(200)
+        .with_body("{\"name\":\"func\"}\nnot json\n")
+        .create();
+    let auth = proxy::TestAuth {};
+    let client = Client::new(Box::new(&auth), add.as_str()).unwrap();
+
+    let logs = client.get_logs_stream(params);
+    futures_util::pin_mut!(logs);
+    assert!(logs.next().await.unwrap().is_ok());
+    assert!(logs.next().await.unwrap().is_err());
+    assert!(logs.next().await.is_none());
+}
@@ -0,0 +1,300 @@
+use config::config_file::{AuthConfig, OAuth2Config, CONFIG_LOCATION_ENV, OAUTH_2AUTH_TYPE};
+use mockito::Matcher;
+use proxy::auth::BearerToken;
+use proxy::client::Client;
+use proxy::oauth2::{
+    can_renew, client_credentials_token, exchange_code, is_expiring, pkce_challenge,
+    poll_device_token, refresh_token, renew_auth_config, request_device_code, Pkce,
+    CLIENT_CREDENTIALS_GRANT, CLIENT_SECRET_ENVIRONMENT, DEVICE_CODE_GRANT,
+};
+use proxy::utils::INFO_ENDPOINT;
+use std::time::Duration;
+
+#[test]
+fn test_is_expiring() {
+    let mut oauth2 = OAuth2Config::default();
+    // unknown expiry is never renewed
+    assert!(!is_expiring(&oauth2, 1_000));
+
+    oauth2.expiry = 1_000;
+    assert!(is_expiring(&oauth2, 1_000));
+    assert!(is_expiring(&oauth2, 950));
+    assert!(!is_expiring(&oauth2, 900));
+}
+
+#[test]
+fn test_can_renew() {
+    let mut oauth2 = OAuth2Config {
+        token_url: "http://idp.test/oauth/token".into(),
+        ..Default::default()
+    };
+    assert!(!can_renew(&oauth2));
+
+    oauth2.grant = CLIENT_CREDENTIALS_GRANT.into();
+    assert!(can_renew(&oauth2));
+
+    oauth2.grant = "implicit".into();
+    oauth2.refresh_token = "refresh".into();
+    assert!(can_renew(&oauth2));
+
+    oauth2.token_url = String::new();
+    assert!(!can_renew(&oauth2));
+}
+
+#[tokio::test]
+async fn test_refresh_token() {
+    let _mok = mockito::mock("POST", "/oauth2-refresh/token")
+        .match_body(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("grant_type".into(), "refresh_token".into()),
+            Matcher::UrlEncoded("refresh_token".into(), "old-refresh".into()),
+            Matcher::UrlEncoded("client_id".into(), "client".into()),
+        ]))
+        .with_status(200)
+        .with_body(
+            r#"{"access_token":"new-access","refresh_token":"new-refresh","expires_in":3600}"#,
+        )
+        .create();
+    let url = format!("{}/oauth2-refresh/token", mockito::server_url());
+
+    let token = refresh_token(url.as_str(), "old-refresh", "client", "")
+        .await
+        .unwrap();
+    assert_eq!(token.access_token, "new-access");
+    assert_eq!(token.refresh_token, "new-refresh");
+    assert_eq!(token.expiry(100), 3700);
+}
+
+#[tokio::test]
+async fn test_client_credentials_token_error() {
+    let _mok = mockito::mock("POST", "/oauth2-cc-error/token")
+        .with_status(401)
+        .with_body("invalid_client")
+        .create();
+    let url = format!("{}/oauth2-cc-error/token", mockito::server_url());
+
+    let res = client_credentials_token(url.as_str(), "client", "secret", "").await;
+    assert!(res.is_err());
+    assert!(format!("{:?}", res).contains("invalid_client"));
+}
+
+#[tokio::test]
+async fn test_renew_auth_config_with_client_credentials() {
+    let _mok = mockito::mock("POST", "/oauth2-renew/token")
+        .match_body(Matcher::PartialJsonString(
+            r#"{"client_id":"client","client_secret":"secret","grant_type":"client_credentials"}"#
+                .into(),
+        ))
+        .with_status(200)
+        .with_body(r#"{"access_token":"renewed","expires_in":60}"#)
+        .create();
+    let token_url = format!("{}/oauth2-renew/token", mockito::server_url());
+
+    let auth = AuthConfig {
+        gateway: "http://openfaas.test".into(),
+        auth: OAUTH_2AUTH_TYPE.into(),
+        token: "expired".into(),
+        oauth2: Some(OAuth2Config {
+            expiry: 1,
+            token_url,
+            grant: CLIENT_CREDENTIALS_GRANT.into(),
+            client_id: "client".into(),
+            client_secret: "secret".into(),
+            ..Default::default()
+        }),
+    };
+    let renewed = renew_auth_config(&auth).await.unwrap();
+    assert_eq!(renewed.token, "renewed");
+    let oauth2 = renewed.oauth2.unwrap();
+    assert!(oauth2.expiry > 1);
+    assert_eq!(oauth2.client_secret, "secret");
+}
+
+#[tokio::test]
+async fn test_renew_auth_config_with_environment_secret() {
+    let _mok = mockito::mock("POST", "/oauth2-renew-env/token")
+        .match_body(Matcher::PartialJsonString(
+            r#"{"client_id":"client","client_secret":"from-env"}"#.into(),
+        ))
+        .with_status(200)
+        .with_body(r#"{"access_token":"renewed"}"#)
+        .create();
+    std::env::set_var(CLIENT_SECRET_ENVIRONMENT, "from-env");
+
+    // the secret isn't in the config file when no credential helper keeps it
+    let auth = AuthConfig {
+        gateway: "http://openfaas.test".into(),
+        auth: OAUTH_2AUTH_TYPE.into(),
+        token: "expired".into(),
+        oauth2: Some(OAuth2Config {
+            expiry: 1,
+            token_url: format!("{}/oauth2-renew-env/token", mockito::server_url()),
+            grant: CLIENT_CREDENTIALS_GRANT.into(),
+            client_id: "client".into(),
+            ..Default::default()
+        }),
+    };
+    let renewed = renew_auth_config(&auth).await.unwrap();
+    assert_eq!(renewed.token, "renewed");
+    // the secret of the environment isn't saved with the token
+    assert!(renewed.oauth2.unwrap().client_secret.is_empty());
+}
+
+#[tokio::test]
+#[ignore]
+///ignored as it fails in running all test due to temp files creation
+async fn test_bearer_token_renewed_on_unauthorized() {
+    let config_dir = std::env::temp_dir().join("faas-oauth2-unauthorized");
+    std::env::set_var(CONFIG_LOCATION_ENV, config_dir.to_string_lossy().to_string());
+
+    let _token = mockito::mock("POST", "/oauth2-unauthorized/token")
+        .match_body(Matcher::Regex("refresh_token=refresh".into()))
+        .with_status(200)
+        .with_body(r#"{"access_token":"renewed","expires_in":3600}"#)
+        .create();
+    // the gateway refuses the token before it was expected to expire
+    let _refused = mockito::mock("GET", INFO_ENDPOINT)
+        .match_header("Authorization", "Bearer revoked")
+        .with_status(401)
+        .create();
+    let _accepted = mockito::mock("GET", INFO_ENDPOINT)
+        .match_header("Authorization", "Bearer renewed")
+        .with_status(200)
+        .with_body("{}")
+        .expect(1)
+        .create();
+
+    let gateway = format!("http://{}", mockito::server_address());
+    let auth = BearerToken::renewable(AuthConfig {
+        gateway: gateway.clone(),
+        auth: OAUTH_2AUTH_TYPE.into(),
+        token: "revoked".into(),
+        oauth2: Some(OAuth2Config {
+            expiry: chrono::Utc::now().timestamp() + 3600,
+            refresh_token: "refresh".into(),
+            token_url: format!("{}/oauth2-unauthorized/token", mockito::server_url()),
+            client_id: "client".into(),
+            ..Default::default()
+        }),
+    });
+    let client = Client::new(Box::new(&auth), gateway.as_str()).unwrap();
+    assert!(client.get_system_info().await.is_ok());
+    _accepted.assert();
+    std::fs::remove_dir_all(config_dir).unwrap();
+}
+
+#[test]
+fn test_pkce_challenge() {
+    // base64url without padding of the sha256 digest of the verifier
+    assert_eq!(
+        pkce_challenge("faas-cli-pkce-verifier-0123456789-abcdefghijk"),
+        "W15jlgmAanQiFMjfwjKwpnSaHsDeunp2JCvIJLJRF2Y"
+    );
+
+    let pkce = Pkce::new();
+    assert!(pkce.verifier.len() >= 43 && pkce.verifier.len() <= 128);
+    assert_eq!(pkce.challenge, pkce_challenge(pkce.verifier.as_str()));
+}
+
+#[tokio::test]
+async fn test_exchange_code() {
+    let _mok = mockito::mock("POST", "/oauth2-code/token")
+        .match_body(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("grant_type".into(), "authorization_code".into()),
+            Matcher::UrlEncoded("code".into(), "the-code".into()),
+            Matcher::UrlEncoded(
+                "redirect_uri".into(),
+                "http://127.0.0.1:31111/oauth/callback".into(),
+            ),
+            Matcher::UrlEncoded("code_verifier".into(), "verifier".into()),
+        ]))
+        .with_status(200)
+        .with_body(r#"{"access_token":"code-access","refresh_token":"code-refresh"}"#)
+        .create();
+    let url = format!("{}/oauth2-code/token", mockito::server_url());
+
+    let token = exchange_code(
+        url.as_str(),
+        "the-code",
+        "http://127.0.0.1:31111/oauth/callback",
+        "client",
+        "",
+        "verifier",
+    )
+    .await
+    .unwrap();
+    assert_eq!(token.access_token, "code-access");
+    assert_eq!(token.refresh_token, "code-refresh");
+    assert_eq!(token.expiry(100), 0);
+}
+
+#[tokio::test]
+async fn test_device_code_flow() {
+    let _device = mockito::mock("POST", "/oauth2-device/device")
+        .match_body(Matcher::UrlEncoded("client_id".into(), "client".into()))
+        .with_status(200)
+        .with_body(
+            r#"{"device_code":"dev-code","user_code":"ABCD-EFGH","verification_uri":"http://idp.test/device","expires_in":600,"interval":1}"#,
+        )
+        .create();
+    let device_code_body = Matcher::AllOf(vec![
+        Matcher::UrlEncoded("grant_type".into(), DEVICE_CODE_GRANT.into()),
+        Matcher::UrlEncoded("device_code".into(), "dev-code".into()),
+        Matcher::UrlEncoded("client_secret".into(), "secret".into()),
+    ]);
+    // the first poll is pending, the second one is approved
+    let _pending = mockito::mock("POST", "/oauth2-device/token")
+        .match_body(device_code_body.clone())
+        .with_status(400)
+        .with_body(r#"{"error":"authorization_pending"}"#)
+        .expect(1)
+        .create();
+    let _approved = mockito::mock("POST", "/oauth2-device/token")
+        .match_body(device_code_body)
+        .with_status(200)
+        .with_body(r#"{"access_token":"device-access","expires_in":3600}"#)
+        .create();
+    let device_url = format!("{}/oauth2-device/device", mockito::server_url());
+    let token_url = format!("{}/oauth2-device/token", mockito::server_url());
+
+    let device = request_device_code(device_url.as_str(), "client", "openid", "")
+        .await
+        .unwrap();
+    assert_eq!(device.user_code, "ABCD-EFGH");
+    assert_eq!(device.poll_interval(), Duration::from_secs(1));
+
+    let token = poll_device_token(
+        token_url.as_str(),
+        "client",
+        "secret",
+        &device,
+        Duration::from_millis(10),
+    )
+    .await
+    .unwrap();
+    assert_eq!(token.access_token, "device-access");
+    _pending.assert();
+}
+
+#[tokio::test]
+async fn test_device_code_denied() {
+    let _mok = mockito::mock("POST", "/oauth2-device-denied/token")
+        .with_status(400)
+        .with_body(r#"{"error":"access_denied","error_description":"user denied"}"#)
+        .create();
+    let token_url = format!("{}/oauth2-device-denied/token", mockito::server_url());
+    let device = proxy::oauth2::DeviceAuthorization {
+        device_code: "dev-code".into(),
+        ..Default::default()
+    };
+
+    let res = poll_device_token(
+        token_url.as_str(),
+        "client",
+        "",
+        &device,
+        Duration::from_millis(10),
+    )
+    .await;
+    assert!(res.is_err());
+    assert!(format!("{:?}", res).contains("access_denied user denied"));
+}
@@ -65,3 +65,34 @@
 // func durationPtr(duration time.Duration) *time.Duration {
 // return &duration
 // }
+
+use proxy::proxy::{make_http_client, TlsOptions};
+use std::time::Duration;
+
+#[test]
+fn test_make_http_client_tls_options() {
+    assert!(make_http_client(None, &TlsOptions::default()).is_ok());
+    assert!(make_http_client(Some(Duration::from_secs(30)), &TlsOptions::insecure(true)).is_ok());
+}
+
+#[test]
+fn test_make_http_client_missing_ca_file() {
+    let tls = TlsOptions {
+        ca_file: "./testdata/does-not-exist.pem".to_string(),
+        ..Default::default()
+    };
+    let res = make_http_client(None, &tls);
+    assert!(res.is_err());
+    assert!(format!("{:?}", res).contains("can't read CA file"));
+}
+
+#[test]
+fn test_make_http_client_cert_without_key() {
+    let tls = TlsOptions {
+        cert_file: "./client.pem".to_string(),
+        ..Default::default()
+    };
+    let res = make_http_client(None, &tls);
+    assert!(res.is_err());
+    assert!(format!("{:?}", res).contains("must be set together"));
+}
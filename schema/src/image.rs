@@ -19,6 +19,15 @@ pub const BRANCH_AND_SHA_FORMAT: BuildFormat = 2;
 /// DescribeFormat uses the git-describe output as the docker tag
 pub const DESCRIBE_FORMAT: BuildFormat = 3;
 
+/// TemplateFormat renders the docker tag from a tag template like `{branch}-{sha:7}`
+pub const TEMPLATE_FORMAT: BuildFormat = 4;
+
+/// prefix of a --tag value holding a tag template, e.g. `template:{branch}-{sha:7}`
+pub const TAG_TEMPLATE_PREFIX: &str = "template:";
+
+/// maximum length of a tag in the OCI distribution spec
+const MAX_TAG_LENGTH: usize = 128;
+
 /// Type implements pflag.Value
 pub trait TBuildFormat {
     fn _type(&self) -> String;
@@ -42,6 +51,9 @@ impl TBuildFormat for Option<BuildFormat> {
                 "sha" => *self = Some(SHA_FORMAT),
                 "branch" => *self = Some(BRANCH_AND_SHA_FORMAT),
                 "describe" => *self = Some(DESCRIBE_FORMAT),
+                template if template.starts_with(TAG_TEMPLATE_PREFIX) => {
+                    *self = Some(TEMPLATE_FORMAT)
+                }
                 _ => {
                     return Err(utility::Error::Custom(format!(
                         "unknown image tag format: '{}'",
@@ -61,6 +73,7 @@ impl TBuildFormat for Option<BuildFormat> {
                 SHA_FORMAT => "sha",
                 BRANCH_AND_SHA_FORMAT => "branch",
                 DESCRIBE_FORMAT => "describe",
+                TEMPLATE_FORMAT => "template",
                 _ => "latest",
             },
         }
@@ -76,7 +89,117 @@ pub fn build_image_name(format: BuildFormat, image: &str, version: &str, branch:
     }
     match format {
         SHA_FORMAT | DESCRIBE_FORMAT => image + "-" + "version",
-        BRANCH_AND_SHA_FORMAT => image + "-" + branch + "-" + version,
+        BRANCH_AND_SHA_FORMAT => image + "-" + &tag_safe(branch) + "-" + version,
         _ => image,
     }
 }
+
+/// returns the tag template of a --tag value, None when it is a tag format like `sha`
+pub fn tag_template_of(tag: &str) -> Option<&str> {
+    tag.strip_prefix(TAG_TEMPLATE_PREFIX)
+}
+
+/// returns the tag template used for a function, a --tag flag overrides the stack.yml
+/// `tag_template` and an explicit tag format like `--tag sha` disables it
+pub fn select_tag_template<'s>(flag_tag: &'s str, function_template: &'s str) -> &'s str {
+    match tag_template_of(flag_tag) {
+        Some(template) => template,
+        None if !flag_tag.is_empty() => "",
+        None => function_template,
+    }
+}
+
+/// renders a tag template, `{name}` is replaced by `lookup(name, None)`, `{name:arg}` by
+/// `lookup(name, Some(arg))` and a numeric last part like `{sha:7}` or `{env:BUILD_ID:8}` keeps
+/// only that many characters of the value. The result must be a valid OCI tag
+pub fn render_tag_template<F>(template: &str, mut lookup: F) -> Result<String, utility::Error>
+where
+    F: FnMut(&str, Option<&str>) -> Result<String, utility::Error>,
+{
+    let mut tag = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        tag.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| {
+            utility::Error::Custom(format!(
+                "unclosed placeholder in tag template: {}",
+                template
+            ))
+        })? + start;
+        let placeholder = &rest[start + 1..end];
+
+        let mut parts: Vec<&str> = placeholder.split(':').collect();
+        let width = match parts.last() {
+            Some(last) if parts.len() > 1 && last.chars().all(|c| c.is_ascii_digit()) => {
+                let width = last.parse::<usize>().map_err(|e| {
+                    utility::Error::Custom(format!("invalid width in {{{}}}: {}", placeholder, e))
+                })?;
+                parts.pop();
+                Some(width)
+            }
+            _ => None,
+        };
+        if parts.len() > 2 || parts[0].is_empty() {
+            return Err(utility::Error::Custom(format!(
+                "invalid placeholder {{{}}} in tag template: {}",
+                placeholder, template
+            )));
+        }
+
+        let value = lookup(parts[0], parts.get(1).copied())?;
+        match width {
+            Some(width) => tag.extend(value.chars().take(width)),
+            None => tag.push_str(value.as_str()),
+        }
+        rest = &rest[end + 1..];
+    }
+    tag.push_str(rest);
+
+    validate_tag(tag.as_str())?;
+    Ok(tag)
+}
+
+/// replaces the characters not allowed in a tag, i.e. the slash of a branch like feature/login
+pub fn tag_safe(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// checks the tag against the OCI grammar `[a-zA-Z0-9_][a-zA-Z0-9._-]{0,127}`
+pub fn validate_tag(tag: &str) -> Result<(), utility::Error> {
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-';
+    let valid = match tag.chars().next() {
+        Some(first) => {
+            (first.is_ascii_alphanumeric() || first == '_')
+                && tag.chars().all(valid_char)
+                && tag.len() <= MAX_TAG_LENGTH
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(utility::Error::Custom(format!(
+            "invalid image tag '{}', a tag must match [a-zA-Z0-9_][a-zA-Z0-9._-]{{0,127}}",
+            tag
+        )))
+    }
+}
+
+/// replaces the tag of the image, or adds it when the image has none
+pub fn image_with_tag(image: &str, tag: &str) -> String {
+    let name_start = image.rfind('/').map(|i| i + 1).unwrap_or(0);
+    let repository = match image[name_start..].find(':') {
+        Some(colon) => &image[..name_start + colon],
+        None => image,
+    };
+    format!("{}:{}", repository, tag)
+}
@@ -6,3 +6,71 @@ fn test_build_image_name_default_format() {
 
     assert_eq!(want, got.as_str());
 }
+
+#[test]
+fn test_build_image_name_branch_format() {
+    let got = schema::image::build_image_name(
+        schema::image::BRANCH_AND_SHA_FORMAT,
+        "img",
+        "ef384",
+        "feature/login",
+    );
+
+    assert_eq!("img:latest-feature-login-ef384", got.as_str());
+}
+
+#[test]
+fn test_render_tag_template() {
+    let got = schema::image::render_tag_template("{branch}-{sha:7}-{env:BUILD_ID}", |name, arg| {
+        match (name, arg) {
+            ("branch", None) => Ok("main".to_string()),
+            ("sha", None) => Ok("3f8a1c9d2e".to_string()),
+            ("env", Some("BUILD_ID")) => Ok("42".to_string()),
+            _ => Err(utility::Error::Custom(format!("unknown {}", name))),
+        }
+    })
+    .unwrap();
+
+    assert_eq!("main-3f8a1c9-42", got.as_str());
+}
+
+#[test]
+fn test_render_tag_template_errors() {
+    let lookup = |_: &str, _: Option<&str>| Ok("feature/login".to_string());
+
+    assert!(schema::image::render_tag_template("{branch}", lookup).is_err());
+    assert!(schema::image::render_tag_template("v1-{sha", lookup).is_err());
+    assert!(schema::image::render_tag_template("{}", lookup).is_err());
+}
+
+#[test]
+fn test_validate_tag() {
+    assert!(schema::image::validate_tag("1.2.3-rc_1").is_ok());
+    assert!(schema::image::validate_tag("_latest").is_ok());
+    assert!(schema::image::validate_tag("").is_err());
+    assert!(schema::image::validate_tag("-latest").is_err());
+    assert!(schema::image::validate_tag("1.0+build").is_err());
+    assert!(schema::image::validate_tag(&"a".repeat(129)).is_err());
+}
+
+#[test]
+fn test_image_with_tag() {
+    assert_eq!("img:v1", schema::image::image_with_tag("img", "v1"));
+    assert_eq!(
+        "user/img:v1",
+        schema::image::image_with_tag("user/img:latest", "v1")
+    );
+    assert_eq!(
+        "localhost:5000/user/img:v1",
+        schema::image::image_with_tag("localhost:5000/user/img", "v1")
+    );
+}
+
+#[test]
+fn test_select_tag_template() {
+    use schema::image::select_tag_template;
+
+    assert_eq!("{sha}", select_tag_template("template:{sha}", "{branch}"));
+    assert_eq!("", select_tag_template("sha", "{branch}"));
+    assert_eq!("{branch}", select_tag_template("", "{branch}"));
+}
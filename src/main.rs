@@ -1,6 +1,7 @@
 use command::exec;
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() {
-    exec().await;
+async fn main() -> ExitCode {
+    exec().await
 }
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use utility::faas::types::model::*;
 
 /// Provider for the FaaS set of functions.
@@ -82,6 +82,69 @@ pub struct Function {
     // Platforms for use with buildx and faas-cli publish
     #[serde(default)]
     pub platforms: String, //`yaml:"platforms,omitempty"`
+
+    // TagTemplate renders the image tag, e.g. {branch}-{sha:7}-{timestamp}
+    #[serde(default)]
+    pub tag_template: String, //`yaml:"tag_template,omitempty"`
+
+    // Tests sent to the deployed function by faas-cli test
+    #[serde(default)]
+    pub tests: Vec<FunctionTest>, //`yaml:"tests,omitempty"`
+}
+
+/// FunctionTest a request sent to a deployed function and what it must answer
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FunctionTest {
+    pub name: String, //`yaml:"name"`
+    #[serde(default)]
+    pub request: TestRequest, //`yaml:"request,omitempty"`
+    #[serde(default)]
+    pub expect: TestExpectation, //`yaml:"expect,omitempty"`
+}
+
+/// TestRequest the HTTP request of a test, the body is given inline or read from body_file
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TestRequest {
+    /// defaults to POST when the request has a body and to GET otherwise
+    #[serde(default)]
+    pub method: String, //`yaml:"method,omitempty"`
+    /// path after the function name, such as /users/1
+    #[serde(default)]
+    pub path: String, //`yaml:"path,omitempty"`
+    #[serde(default)]
+    pub query: BTreeMap<String, String>, //`yaml:"query,omitempty"`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>, //`yaml:"headers,omitempty"`
+    #[serde(default)]
+    pub body: Option<String>, //`yaml:"body,omitempty"`
+    /// relative to the directory of the file holding the test
+    #[serde(default)]
+    pub body_file: String, //`yaml:"body_file,omitempty"`
+}
+
+/// TestExpectation what the response of a test must match, a missing status means any 2xx
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TestExpectation {
+    #[serde(default)]
+    pub status: Option<u16>, //`yaml:"status,omitempty"`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>, //`yaml:"headers,omitempty"`
+    /// the exact body
+    #[serde(default)]
+    pub body: Option<String>, //`yaml:"body,omitempty"`
+    /// a regular expression the body must match
+    #[serde(default)]
+    pub regex: String, //`yaml:"regex,omitempty"`
+    /// values the JSON body must hold at the given paths, such as $.items[0].name
+    #[serde(default)]
+    pub json_path: BTreeMap<String, serde_yaml::Value>, //`yaml:"json_path,omitempty"`
+}
+
+/// TestsFile the tests.yml file, tests of the functions of a stack kept out of the stack file
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TestsFile {
+    #[serde(default)]
+    pub functions: HashMap<String, Vec<FunctionTest>>, //`yaml:"functions,omitempty"`
 }
 
 /// Configuration for the tests.yml file
@@ -113,6 +176,9 @@ pub struct TemplateSource {
     pub name: String, //`yaml:"name"`
     #[serde(default)]
     pub source: String, //`yaml:"source,omitempty"`
+    /// sha256 the archive of the source must have, for .tar.gz sources
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sha256: String,
 }
 
 // FunctionResources Memory and CPU
@@ -157,6 +223,9 @@ pub struct LanguageTemplate {
     /// HandlerFolder to copy the function code into
     #[serde(default)]
     pub handler_folder: String, //`yaml:"handler_folder,omitempty"`
+    /// Version of the template, added to the provenance labels of the images built from it
+    #[serde(default)]
+    pub version: String, //`yaml:"version,omitempty"`
 }
 
 // BuildOption a named build option for one or more packages
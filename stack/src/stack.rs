@@ -1,4 +1,4 @@
-use crate::schema::Services;
+use crate::schema::{Services, TestsFile};
 use lazy_static::lazy_static;
 use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
@@ -137,3 +137,16 @@ pub fn parse_yaml_data(data: &str, regex: &str, filter: &str, envsubsts: bool) -
 
     Ok(services)
 }
+
+/// ParseTestsFile parse a tests.yml file, environment variables are substituted as in the stack file
+pub fn parse_tests_file(tests_file: &str) -> Result<TestsFile> {
+    let data = std::fs::read_to_string(tests_file)
+        .map_err(|e| Error::Custom(format!("can't read tests file {}: {}", tests_file, e)))?;
+    parse_tests_data(data.as_str())
+}
+
+/// ParseTestsData parse the tests of the functions of a stack
+pub fn parse_tests_data(data: &str) -> Result<TestsFile> {
+    let data = substitute_vars(data)?;
+    serde_yaml::from_str(data.as_str()).map_err(|e| Error::Custom(format!("{:?}", e)))
+}
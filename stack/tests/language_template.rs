@@ -12,6 +12,7 @@ fn test_parse_yaml_data_for_language_template() {
             input: "
             language: python
             fprocess: python index.py
+            version: 1.2.0
             ",
             expected: LanguageTemplate {
                 language: "python".into(),
@@ -19,6 +20,7 @@ fn test_parse_yaml_data_for_language_template() {
                 build_options: vec![],
                 welcome_message: "".to_string(),
                 handler_folder: "".to_string(),
+                version: "1.2.0".to_string(),
             },
         },
         LangTemplateTest {
@@ -31,6 +33,7 @@ fn test_parse_yaml_data_for_language_template() {
                 build_options: vec![],
                 welcome_message: "".to_string(),
                 handler_folder: "".to_string(),
+                version: "".to_string(),
             },
         },
         LangTemplateTest {
@@ -43,6 +46,7 @@ fn test_parse_yaml_data_for_language_template() {
                 build_options: vec![],
                 welcome_message: "".to_string(),
                 handler_folder: "".to_string(),
+                version: "".to_string(),
             },
         },
     ];
@@ -409,6 +409,73 @@ provider:
         std::env::remove_var("USER");
     }
 
+    #[test]
+    fn test_parse_yaml_data_function_tests() {
+        let data = r#"version: 1.0
+provider:
+  name: openfaas
+  gateway: http://127.0.0.1:8080
+
+functions:
+  echo:
+    lang: node
+    handler: ./echo
+    image: echo:latest
+    tests:
+      - name: echoes the body
+        request:
+          method: POST
+          path: /echo
+          query:
+            verbose: "1"
+          body: hello
+        expect:
+          status: 200
+          regex: "^hello"
+          json_path:
+            $.items[0].id: 1
+"#;
+        let services = parse_yaml_data(data, "", "", true).unwrap();
+        let tests = &services.functions["echo"].tests;
+        assert_eq!(tests.len(), 1);
+        assert_eq!(tests[0].name, "echoes the body");
+        assert_eq!(tests[0].request.method, "POST");
+        assert_eq!(tests[0].request.path, "/echo");
+        assert_eq!(tests[0].request.query["verbose"], "1");
+        assert_eq!(tests[0].request.body.as_deref(), Some("hello"));
+        assert_eq!(tests[0].expect.status, Some(200));
+        assert_eq!(tests[0].expect.regex, "^hello");
+        assert_eq!(
+            tests[0].expect.json_path["$.items[0].id"],
+            serde_yaml::Value::from(1)
+        );
+    }
+
+    #[test]
+    fn test_parse_tests_data() {
+        let data = r#"functions:
+  echo:
+    - name: empty body
+      expect:
+        body: ""
+  env:
+    - name: reads the header
+      request:
+        headers:
+          X-Test: "yes"
+        body_file: ./testdata/body.json
+"#;
+        let tests = parse_tests_data(data).unwrap();
+        assert_eq!(tests.functions.len(), 2);
+        assert_eq!(tests.functions["echo"][0].expect.body.as_deref(), Some(""));
+        assert_eq!(tests.functions["echo"][0].expect.status, None);
+        assert_eq!(tests.functions["env"][0].request.headers["X-Test"], "yes");
+        assert_eq!(
+            tests.functions["env"][0].request.body_file,
+            "./testdata/body.json"
+        );
+    }
+
     //  #[tokio::test]
     // async fn test_parse_yaml_file() {
     //     let res= parse_yaml_file("C:/Users/AbdelmonemMohamed/CLionProjects/faas/stack.yml","","",true).await;
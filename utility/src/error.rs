@@ -10,6 +10,9 @@ pub enum Error {
     Reqwest(#[from] reqwest::Error),
     #[error("{0}")]
     Parse(#[from] url::ParseError),
+    /// the gateway answered with a status code the request can't go on with
+    #[error("{1}")]
+    Status(u16, String),
 }
 
 pub type Result<T> = std::result::Result<T, crate::Error>;
@@ -108,7 +108,7 @@ fn replace_vars(map: &HashMap<String, String>, s: &str) -> String {
     for (k, val) in map {
         s = s
             .as_str()
-            .replace(format!("{{+{}+}}", k).as_str(), val.as_str());
+            .replace(format!("{{{}}}", k).as_str(), val.as_str());
     }
     s
 }
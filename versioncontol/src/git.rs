@@ -17,6 +17,22 @@ pub const GIT_CLONE_DEFAULT: VcsCmd = VcsCmd {
     scheme: &["git", "https", "http", "git+ssh", "ssh"],
 };
 
+/// GitCloneFull defines the command to clone a repo with its history, to check out any commit
+pub const GIT_CLONE_FULL: VcsCmd = VcsCmd {
+    name: "Git",
+    cmd: "git",
+    cmds: &["clone {repo} {dir} --config core.autocrlf=false"],
+    scheme: &["git", "https", "http", "git+ssh", "ssh"],
+};
+
+/// GitFetch defines the command to fetch a REF of the origin of a clone into its FETCH_HEAD
+pub const GIT_FETCH: VcsCmd = VcsCmd {
+    name: "Git",
+    cmd: "git",
+    cmds: &["-C {dir} fetch --quiet origin {refname}"],
+    scheme: &["git", "https", "http", "git+ssh", "ssh"],
+};
+
 /// GitCheckout defines the command to clone a specific REF of repo into a directory
 pub const GIT_CHECKOUT: VcsCmd = VcsCmd {
     name: "Git",
@@ -80,8 +96,26 @@ pub fn get_git_sha() -> Result<String> {
     }
 }
 
+// get_git_full_sha returns the full Git commit SHA from local repo
+pub fn get_git_full_sha() -> Result<String> {
+    let get_sha_command = vec!["git", "rev-parse", "HEAD"];
+    let sha: String = command_with_output(get_sha_command, true)?;
+    if sha.contains("Not a git repository") {
+        Err(Error::Custom("Not a git repository".to_string()))
+    } else {
+        let sha = sha.trim_end_matches("\n");
+        Ok(sha.into())
+    }
+}
+
 pub fn get_git_branch() -> Result<String> {
-    let get_branch_command = vec!["git", "rev-parse", "--short", "HEAD"];
+    let get_branch_command = vec![
+        "git",
+        "rev-parse",
+        "--symbolic-full-name",
+        "--abbrev-ref",
+        "HEAD",
+    ];
     let branch: String = command_with_output(get_branch_command, true)?;
     if branch.contains("Not a git repository") {
         Err(Error::Custom("Not a git repository".to_string()))
@@ -90,3 +124,32 @@ pub fn get_git_branch() -> Result<String> {
         Ok(branch.into())
     }
 }
+
+// get_git_remote_url returns the URL of the origin remote of the local repo
+pub fn get_git_remote_url() -> Result<String> {
+    let get_remote_command = vec!["git", "config", "--get", "remote.origin.url"];
+    let url: String = command_with_output(get_remote_command, true)?;
+    let url = url.trim_end_matches("\n");
+    if url.is_empty() {
+        Err(Error::Custom(
+            "no origin remote in the Git repository".to_string(),
+        ))
+    } else {
+        Ok(url.into())
+    }
+}
+
+// get_git_commit returns the full SHA of the commit checked out in the repo at dir
+pub fn get_git_commit(dir: &str) -> Result<String> {
+    let get_commit_command = vec!["git", "-C", dir, "rev-parse", "HEAD"];
+    let sha: String = command_with_output(get_commit_command, true)?;
+    let sha = sha.trim_end_matches("\n");
+    if sha.len() != 40 || !sha.chars().all(|c| c.is_ascii_hexdigit()) {
+        Err(Error::Custom(format!(
+            "can't resolve the commit of {}",
+            dir
+        )))
+    } else {
+        Ok(sha.into())
+    }
+}
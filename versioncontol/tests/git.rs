@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use versioncontol::git::{GIT_CHECKOUT_REF_NAME, GIT_CLONE};
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?}", args);
+}
+
+#[test]
+fn test_check_ref_name() {
+    let args = |ref_name: &str| -> HashMap<String, String> {
+        [("refname".to_string(), ref_name.to_string())]
+            .iter()
+            .cloned()
+            .collect()
+    };
+    // the ref name is checked in the current folder, the key must match the {refname} placeholder
+    assert!(GIT_CHECKOUT_REF_NAME.invoke(".", args("v1.0.0")).is_ok());
+    assert!(GIT_CHECKOUT_REF_NAME.invoke(".", args("feature/a")).is_ok());
+    assert!(GIT_CHECKOUT_REF_NAME
+        .invoke(".", args("bad..name"))
+        .is_err());
+    assert!(GIT_CHECKOUT_REF_NAME.invoke(".", args("bad name")).is_err());
+}
+
+#[test]
+fn test_clone_ref_name() {
+    let root = std::env::temp_dir().join(format!("faas-cli-git-clone-{}", std::process::id()));
+    let repo = root.join("repo");
+    let dest = root.join("clone");
+    std::fs::create_dir_all(&repo).unwrap();
+    git(&repo, &["init", "--quiet"]);
+    git(&repo, &["config", "user.email", "test@example.com"]);
+    git(&repo, &["config", "user.name", "test"]);
+    std::fs::write(repo.join("README.md"), "main").unwrap();
+    git(&repo, &["add", "README.md"]);
+    git(&repo, &["commit", "--quiet", "-m", "main"]);
+    git(&repo, &["checkout", "--quiet", "-b", "stable"]);
+    std::fs::write(repo.join("README.md"), "stable").unwrap();
+    git(&repo, &["commit", "--quiet", "-am", "stable"]);
+    git(&repo, &["checkout", "--quiet", "-"]);
+
+    let args: HashMap<String, String> = [
+        (
+            "repo".to_string(),
+            format!("file://{}", repo.to_string_lossy()),
+        ),
+        ("dir".to_string(), dest.to_string_lossy().to_string()),
+        ("refname".to_string(), "stable".to_string()),
+    ]
+    .iter()
+    .cloned()
+    .collect();
+    let result = GIT_CLONE.invoke(root.to_string_lossy().as_ref(), args);
+    let readme = std::fs::read_to_string(dest.join("README.md"));
+    std::fs::remove_dir_all(&root).unwrap();
+
+    result.unwrap();
+    assert_eq!(readme.unwrap(), "stable");
+}